@@ -194,6 +194,22 @@ mod benchmarks {
         });
     }
 
+    // Test the cost of a swap on its own, with idle readers to repoint but
+    // without taking the write lock or waiting on readers.
+    #[cfg(all(feature = "unchecked-swap", not(feature = "safe-impl")))]
+    #[bench]
+    fn swap_tables_unchecked_lockless(b: &mut test::bench::Bencher) {
+        let table = AsLockHandle::<i32>::from_identical(1, 1);
+        let _readers: Vec<_> = (0..10).map(|_| table.clone()).collect();
+        let mut wg = table.write();
+
+        b.iter(|| {
+            wg.update_tables(AddOne {});
+            // Safety: none of the readers are reading.
+            unsafe { wg.swap_tables_now_unchecked() };
+        });
+    }
+
     // Test the speed of acquiring the AsLockReadGuard when the writer never takes a guard
     // are there are no other readers.
     #[bench]
@@ -0,0 +1,311 @@
+//! Soak test for running the collections on your own hardware.
+//!
+//! Each collection is run in both flavors. Readers randomly clone and drop
+//! handles, and check that every table they read is consistent. Writers apply
+//! random updates, which are mirrored onto a plain std collection, and at the
+//! end the tables are checked against it and against each other.
+//!
+//!     $ cargo run --release --example stress -- --readers 8 --writers 2 --seconds 60
+//!
+//! Options (defaults in parentheses):
+//!     --readers N    reader threads per table (4)
+//!     --writers N    writer threads per table (2)
+//!     --seconds N    how long to run each table for (5)
+//!     --seed N       seed for the first thread; each thread adds its index (0)
+//!
+//! Every published table keeps the invariant that its first entry (index 0 or
+//! key 0) holds the wrapping sum of the rest, so a reader seeing a partially
+//! applied write, or a table which diverged, fails the check.
+
+use active_standby::lockless::collections::{AsBTreeMapHandle, AsHashMapHandle, AsVecHandle};
+use active_standby::sync::collections::{AsBTreeMap, AsHashMap, AsVec};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const NUM_KEYS: u64 = 64;
+const MAX_HANDLES: usize = 8;
+
+#[derive(Debug, Clone)]
+struct Config {
+    readers: usize,
+    writers: usize,
+    duration: Duration,
+    seed: u64,
+}
+
+fn parse_args() -> Config {
+    let mut config = Config {
+        readers: 4,
+        writers: 2,
+        duration: Duration::from_secs(5),
+        seed: 0,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value: u64 = args
+            .next()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| panic!("{} expects a number", flag));
+        match flag.as_str() {
+            "--readers" => config.readers = value as usize,
+            "--writers" => config.writers = value as usize,
+            "--seconds" => config.duration = Duration::from_secs(value),
+            "--seed" => config.seed = value,
+            _ => panic!("unknown flag {}", flag),
+        }
+    }
+    config
+}
+
+/// Small deterministic PRNG (splitmix64).
+struct Prng(u64);
+
+impl Prng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// A table under test. Handles are cloned for each thread; for the sync
+/// flavor this is an `Arc`.
+trait Table: Clone + Send + 'static {
+    type Model: PartialEq + std::fmt::Debug + Send;
+
+    fn model(&self) -> Self::Model;
+
+    /// Check the invariant on the active table.
+    fn check_read(&self);
+
+    /// Apply a random update to both the table and the model, within a single
+    /// write guard.
+    fn write_random(&self, model: &mut Self::Model, prng: &mut Prng);
+
+    /// Check that the tables are identical and match the model.
+    fn check_synced(&self, model: &Self::Model);
+}
+
+fn vec_checksum(vec: &[u64]) -> u64 {
+    vec[1..]
+        .iter()
+        .fold(0, |sum, value| sum.wrapping_add(*value))
+}
+
+fn map_checksum<'a>(values: impl Iterator<Item = (&'a u64, &'a u64)>) -> u64 {
+    values
+        .filter(|(key, _)| **key != 0)
+        .fold(0, |sum, (_, value)| sum.wrapping_add(*value))
+}
+
+// The collections have the same interface in both flavors, so the lockless
+// and sync tables share an implementation.
+macro_rules! impl_vec_table {
+    ($Table:ty) => {
+        impl Table for $Table {
+            type Model = Vec<u64>;
+
+            fn model(&self) -> Vec<u64> {
+                self.read().clone()
+            }
+
+            fn check_read(&self) {
+                let table = self.read();
+                assert_eq!(table[0], vec_checksum(&table), "{:?}", *table);
+            }
+
+            fn write_random(&self, model: &mut Vec<u64>, prng: &mut Prng) {
+                let mut wg = self.write();
+                let value = prng.next_u64();
+                // Index 0 holds the checksum, so it is never moved.
+                match prng.below(6) {
+                    0 => {
+                        wg.push(value);
+                        model.push(value);
+                    }
+                    1 => {
+                        let index = 1 + prng.below(model.len() as u64) as usize;
+                        wg.insert(index, value);
+                        model.insert(index, value);
+                    }
+                    2 if model.len() > 1 => {
+                        let index = 1 + prng.below(model.len() as u64 - 1) as usize;
+                        assert_eq!(wg.remove(index), model.remove(index));
+                    }
+                    3 if model.len() > 1 => {
+                        let index = 1 + prng.below(model.len() as u64 - 1) as usize;
+                        assert_eq!(wg.swap_remove(index), model.swap_remove(index));
+                    }
+                    4 => {
+                        let len = 1 + prng.below(model.len() as u64) as usize;
+                        wg.truncate(len);
+                        model.truncate(len);
+                    }
+                    _ => {
+                        let start = 1 + prng.below(model.len() as u64) as usize;
+                        let drained: Vec<_> = wg.drain(start..).collect();
+                        assert_eq!(drained, model.drain(start..).collect::<Vec<_>>());
+                    }
+                }
+                wg.update_tables_closure(|table| table[0] = vec_checksum(table));
+                model[0] = vec_checksum(model);
+                assert_eq!(*wg, *model);
+            }
+
+            fn check_synced(&self, model: &Vec<u64>) {
+                let mut wg = self.write();
+                wg.assert_synced();
+                assert_eq!(*wg, *model);
+            }
+        }
+    };
+}
+
+macro_rules! impl_map_table {
+    ($Table:ty, $Model:ty) => {
+        impl Table for $Table {
+            type Model = $Model;
+
+            fn model(&self) -> $Model {
+                self.read().clone()
+            }
+
+            fn check_read(&self) {
+                let table = self.read();
+                assert_eq!(table[&0], map_checksum(table.iter()), "{:?}", *table);
+            }
+
+            fn write_random(&self, model: &mut $Model, prng: &mut Prng) {
+                let mut wg = self.write();
+                let key = 1 + prng.below(NUM_KEYS - 1);
+                let value = prng.next_u64();
+                // Key 0 holds the checksum, so it is never removed.
+                match prng.below(5) {
+                    0 => assert_eq!(wg.insert(key, value), model.insert(key, value)),
+                    1 => {
+                        let value = prng.below(4);
+                        let different = model.get(&key) != Some(&value);
+                        assert_eq!(wg.insert_if_different(key, value), different);
+                        model.insert(key, value);
+                    }
+                    2 => assert_eq!(wg.remove(key), model.remove(&key)),
+                    3 => {
+                        let modulus = 2 + prng.below(4);
+                        wg.retain(move |key, _| *key == 0 || *key % modulus != 0);
+                        model.retain(|key, _| *key == 0 || *key % modulus != 0);
+                    }
+                    _ => {
+                        let checksum = model[&0];
+                        wg.clear();
+                        model.clear();
+                        wg.insert(0, checksum);
+                        model.insert(0, checksum);
+                    }
+                }
+                let checksum = map_checksum(model.iter());
+                wg.insert(0, checksum);
+                model.insert(0, checksum);
+                assert_eq!(*wg, *model);
+            }
+
+            fn check_synced(&self, model: &$Model) {
+                let mut wg = self.write();
+                wg.assert_synced();
+                assert_eq!(*wg, *model);
+            }
+        }
+    };
+}
+
+impl_vec_table!(AsVecHandle<u64>);
+impl_vec_table!(Arc<AsVec<u64>>);
+impl_map_table!(AsHashMapHandle<u64, u64>, HashMap<u64, u64>);
+impl_map_table!(Arc<AsHashMap<u64, u64>>, HashMap<u64, u64>);
+impl_map_table!(AsBTreeMapHandle<u64, u64>, BTreeMap<u64, u64>);
+impl_map_table!(Arc<AsBTreeMap<u64, u64>>, BTreeMap<u64, u64>);
+
+fn soak<T: Table>(name: &str, config: &Config, table: T) {
+    let deadline = Instant::now() + config.duration;
+    let model = Mutex::new(table.model());
+    let num_reads = AtomicU64::new(0);
+    let num_writes = AtomicU64::new(0);
+
+    std::thread::scope(|scope| {
+        for index in 0..config.readers {
+            let mut prng = Prng(config.seed.wrapping_add(index as u64));
+            let mut handles = vec![table.clone()];
+            let num_reads = &num_reads;
+            scope.spawn(move || {
+                while Instant::now() < deadline {
+                    match prng.below(10) {
+                        0 if handles.len() < MAX_HANDLES => {
+                            let handle = handles[prng.below(handles.len() as u64) as usize].clone();
+                            handles.push(handle);
+                        }
+                        1 if handles.len() > 1 => {
+                            handles.swap_remove(prng.below(handles.len() as u64) as usize);
+                        }
+                        _ => {
+                            handles[prng.below(handles.len() as u64) as usize].check_read();
+                            num_reads.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            });
+        }
+
+        for index in 0..config.writers {
+            let mut prng = Prng(config.seed.wrapping_add((config.readers + index) as u64));
+            let table = table.clone();
+            let (model, num_writes) = (&model, &num_writes);
+            scope.spawn(move || {
+                while Instant::now() < deadline {
+                    // Hold the model for the duration of the write guard, so
+                    // that the updates are applied to it in the same order.
+                    let mut model = model.lock().unwrap();
+                    table.write_random(&mut model, &mut prng);
+                    num_writes.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    table.check_synced(&model.lock().unwrap());
+    println!(
+        "{:<24} reads: {:>12} writes: {:>10}",
+        name,
+        num_reads.load(Ordering::Relaxed),
+        num_writes.load(Ordering::Relaxed),
+    );
+}
+
+fn main() {
+    let config = parse_args();
+    println!("{:?}", config);
+
+    soak("lockless::AsVecHandle", &config, AsVecHandle::new(vec![0]));
+    soak("sync::AsVec", &config, Arc::new(AsVec::new(vec![0])));
+    let map = HashMap::from([(0, 0)]);
+    soak(
+        "lockless::AsHashMapHandle",
+        &config,
+        AsHashMapHandle::new(map.clone()),
+    );
+    soak("sync::AsHashMap", &config, Arc::new(AsHashMap::new(map)));
+    let map = BTreeMap::from([(0, 0)]);
+    soak(
+        "lockless::AsBTreeMapHandle",
+        &config,
+        AsBTreeMapHandle::new(map.clone()),
+    );
+    soak("sync::AsBTreeMap", &config, Arc::new(AsBTreeMap::new(map)));
+}
@@ -0,0 +1,80 @@
+//! Applies the ops decoded from the input to a lockless and a sync hash map,
+//! and to a plain `HashMap`, checking that they agree every time a write guard
+//! is dropped.
+//!
+//!     $ cargo +nightly fuzz run hashmap
+#![no_main]
+
+use active_standby::lockless::collections::AsHashMapHandle;
+use active_standby::sync::collections::AsHashMap;
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+
+fuzz_target!(|data: &[u8]| {
+    let mut handles = vec![AsHashMapHandle::<u8, u8>::default()];
+    let aslock = AsHashMap::<u8, u8>::default();
+    let mut model = HashMap::<u8, u8>::new();
+
+    // Each write guard covers the ops until the next `0` op.
+    for batch in data
+        .chunks(3)
+        .collect::<Vec<_>>()
+        .split(|op| op[0] % 6 == 0)
+    {
+        {
+            let mut lockless = handles[0].write();
+            let mut sync = aslock.write();
+            for op in batch {
+                let key = op.get(1).copied().unwrap_or_default() % 32;
+                let value = op.get(2).copied().unwrap_or_default();
+                match op[0] % 6 {
+                    1 => {
+                        let old = model.insert(key, value);
+                        assert_eq!(lockless.insert(key, value), old);
+                        assert_eq!(sync.insert(key, value), old);
+                    }
+                    2 => {
+                        let different = model.get(&key) != Some(&value);
+                        model.insert(key, value);
+                        assert_eq!(lockless.insert_if_different(key, value), different);
+                        assert_eq!(sync.insert_if_different(key, value), different);
+                    }
+                    3 => {
+                        let removed = model.remove(&key);
+                        assert_eq!(lockless.remove(key), removed);
+                        assert_eq!(sync.remove(key), removed);
+                    }
+                    4 => {
+                        lockless.retain(move |_, v| *v > value);
+                        sync.retain(move |_, v| *v > value);
+                        model.retain(|_, v| *v > value);
+                    }
+                    _ => {
+                        lockless.clear();
+                        sync.clear();
+                        model.clear();
+                    }
+                }
+                assert_eq!(*lockless, model);
+                assert_eq!(*sync, model);
+            }
+        }
+
+        // Readers must see the update once the write guard is dropped,
+        // regardless of which handle they read through.
+        for handle in &handles {
+            assert_eq!(*handle.read(), model);
+        }
+        assert_eq!(*aslock.read(), model);
+
+        // Grow and shrink the set of readers between write guards.
+        match batch.first().map(|op| op[0] / 6 % 4) {
+            Some(0) if handles.len() < 8 => handles.push(handles[0].clone()),
+            Some(1) if handles.len() > 1 => drop(handles.pop()),
+            _ => (),
+        }
+    }
+
+    handles[0].write().assert_synced();
+    aslock.write().assert_synced();
+});
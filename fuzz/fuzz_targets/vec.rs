@@ -0,0 +1,88 @@
+//! Applies the ops decoded from the input to a lockless and a sync vector,
+//! and to a plain `Vec`, checking that they agree every time a write guard is
+//! dropped.
+//!
+//!     $ cargo +nightly fuzz run vec
+#![no_main]
+
+use active_standby::lockless::collections::AsVecHandle;
+use active_standby::sync::collections::AsVec;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut handles = vec![AsVecHandle::<u8>::default()];
+    let aslock = AsVec::<u8>::default();
+    let mut model = Vec::<u8>::new();
+
+    // Each write guard covers the ops until the next `0` op.
+    for batch in data
+        .chunks(2)
+        .collect::<Vec<_>>()
+        .split(|op| op[0] % 8 == 0)
+    {
+        {
+            let mut lockless = handles[0].write();
+            let mut sync = aslock.write();
+            for op in batch {
+                let arg = op.get(1).copied().unwrap_or_default();
+                let index = arg as usize % (model.len() + 1);
+                match op[0] % 8 {
+                    1 => {
+                        lockless.push(arg);
+                        sync.push(arg);
+                        model.push(arg);
+                    }
+                    2 => {
+                        lockless.insert(index, arg);
+                        sync.insert(index, arg);
+                        model.insert(index, arg);
+                    }
+                    3 if index < model.len() => {
+                        let removed = model.remove(index);
+                        assert_eq!(lockless.remove(index), removed);
+                        assert_eq!(sync.remove(index), removed);
+                    }
+                    4 => {
+                        let popped = model.pop();
+                        assert_eq!(lockless.pop(), popped);
+                        assert_eq!(sync.pop(), popped);
+                    }
+                    5 => {
+                        lockless.truncate(index);
+                        sync.truncate(index);
+                        model.truncate(index);
+                    }
+                    6 => {
+                        lockless.retain(move |value| value % 3 != arg % 3);
+                        sync.retain(move |value| value % 3 != arg % 3);
+                        model.retain(|value| value % 3 != arg % 3);
+                    }
+                    _ => {
+                        lockless.dedup();
+                        sync.dedup();
+                        model.dedup();
+                    }
+                }
+                assert_eq!(*lockless, model);
+                assert_eq!(*sync, model);
+            }
+        }
+
+        // Readers must see the update once the write guard is dropped,
+        // regardless of which handle they read through.
+        for handle in &handles {
+            assert_eq!(*handle.read(), model);
+        }
+        assert_eq!(*aslock.read(), model);
+
+        // Grow and shrink the set of readers between write guards.
+        match batch.first().map(|op| op[0] / 8 % 4) {
+            Some(0) if handles.len() < 8 => handles.push(handles[0].clone()),
+            Some(1) if handles.len() > 1 => drop(handles.pop()),
+            _ => (),
+        }
+    }
+
+    handles[0].write().assert_synced();
+    aslock.write().assert_synced();
+});
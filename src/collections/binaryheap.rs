@@ -0,0 +1,480 @@
+use crate::UpdateTables;
+use std::collections::BinaryHeap;
+
+// Both tables receive the same pushes and pops in the same order, so their
+// heaps have the same layout, and `pop` removes the same element from each,
+// even among elements which compare equal.
+
+struct Push<T> {
+    value: T,
+}
+
+impl<'a, T> UpdateTables<'a, BinaryHeap<T>, ()> for Push<T>
+where
+    T: Clone + Ord,
+{
+    fn apply_first(&mut self, table: &'a mut BinaryHeap<T>) {
+        table.push(self.value.clone())
+    }
+    fn apply_second(self, table: &mut BinaryHeap<T>) {
+        table.push(self.value); // Move the value instead of cloning.
+    }
+}
+
+struct Append<T> {
+    values: BinaryHeap<T>,
+}
+
+impl<'a, T> UpdateTables<'a, BinaryHeap<T>, ()> for Append<T>
+where
+    T: Clone + Ord,
+{
+    fn apply_first(&mut self, table: &'a mut BinaryHeap<T>) {
+        table.append(&mut self.values.clone())
+    }
+    fn apply_second(mut self, table: &mut BinaryHeap<T>) {
+        table.append(&mut self.values);
+    }
+}
+
+struct Retain<F> {
+    f: F,
+}
+
+impl<'a, T, F> UpdateTables<'a, BinaryHeap<T>, ()> for Retain<F>
+where
+    F: 'static + Clone + FnMut(&T) -> bool,
+    T: Ord,
+{
+    fn apply_first(&mut self, table: &'a mut BinaryHeap<T>) {
+        table.retain(self.f.clone())
+    }
+    fn apply_second(self, table: &mut BinaryHeap<T>) {
+        table.retain(self.f)
+    }
+}
+
+struct Drain {}
+
+impl<'a, T> UpdateTables<'a, BinaryHeap<T>, std::collections::binary_heap::Drain<'a, T>> for Drain {
+    fn apply_first(
+        &mut self,
+        table: &'a mut BinaryHeap<T>,
+    ) -> std::collections::binary_heap::Drain<'a, T> {
+        table.drain()
+    }
+    fn apply_second(self, table: &mut BinaryHeap<T>) {
+        table.clear();
+    }
+}
+
+/// Implementation of BinaryHeap for use in the active_standby model.
+/// `lockless::AsLockHandle<T>`, should function similarly to
+/// `Arc<RwLock<BinaryHeap<T>>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(BinaryHeap<T>);
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send + Ord,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn append(&mut self, other: &mut BinaryHeap<T>) {
+            self.guard.update_tables(Append {
+                values: std::mem::take(other),
+            })
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: Ord,
+    {
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        /// Pop up to `n` of the greatest values, in descending order.
+        pub fn pop_n(&mut self, n: usize) -> Vec<T> {
+            self.guard.update_tables_closure(move |table| {
+                std::iter::from_fn(|| table.pop()).take(n).collect()
+            })
+        }
+
+        /// Empty the table, returning its values in ascending order. See
+        /// `BinaryHeap::into_sorted_vec`.
+        pub fn into_sorted_vec(&mut self) -> Vec<T> {
+            self.guard
+                .update_tables_closure(|table| std::mem::take(table).into_sorted_vec())
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+    }
+
+    impl<'w, 'a, T> AsLockWriteGuard<'w, T> {
+        /// Removes all of the values, in arbitrary order.
+        pub fn drain(&'a mut self) -> std::collections::binary_heap::Drain<'a, T> {
+            self.guard.update_tables(Drain {})
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send + Ord,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// Implementation of BinaryHeap for use in the active_standby model.
+/// `sync::AsLock<T>`, should function similarly to `RwLock<BinaryHeap<T>>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(BinaryHeap<T>);
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send + Ord,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn append(&mut self, other: &mut BinaryHeap<T>) {
+            self.guard.update_tables(Append {
+                values: std::mem::take(other),
+            })
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: Ord,
+    {
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        /// Pop up to `n` of the greatest values, in descending order.
+        pub fn pop_n(&mut self, n: usize) -> Vec<T> {
+            self.guard.update_tables_closure(move |table| {
+                std::iter::from_fn(|| table.pop()).take(n).collect()
+            })
+        }
+
+        /// Empty the table, returning its values in ascending order. See
+        /// `BinaryHeap::into_sorted_vec`.
+        pub fn into_sorted_vec(&mut self) -> Vec<T> {
+            self.guard
+                .update_tables_closure(|table| std::mem::take(table).into_sorted_vec())
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+    }
+
+    impl<'w, 'a, T> AsLockWriteGuard<'w, T> {
+        /// Removes all of the values, in arbitrary order.
+        pub fn drain(&'a mut self) -> std::collections::binary_heap::Drain<'a, T> {
+            self.guard.update_tables(Drain {})
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send + Ord,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(BinaryHeap<T>);
+
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send + Ord,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn append(&mut self, other: &mut BinaryHeap<T>) {
+            self.guard.update_tables(Append {
+                values: std::mem::take(other),
+            })
+        }
+    }
+
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
+    where
+        T: Ord,
+    {
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        /// Pop up to `n` of the greatest values, in descending order.
+        pub fn pop_n(&mut self, n: usize) -> Vec<T> {
+            self.guard.update_tables_closure(move |table| {
+                std::iter::from_fn(|| table.pop()).take(n).collect()
+            })
+        }
+
+        /// Empty the table, returning its values in ascending order. See
+        /// `BinaryHeap::into_sorted_vec`.
+        pub fn into_sorted_vec(&mut self) -> Vec<T> {
+            self.guard
+                .update_tables_closure(|table| std::mem::take(table).into_sorted_vec())
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+    }
+
+    impl<'w, T> AsArcLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+    }
+
+    impl<'w, 'a, T> AsArcLockWriteGuard<'w, T> {
+        /// Removes all of the values, in arbitrary order.
+        pub fn drain(&'a mut self) -> std::collections::binary_heap::Drain<'a, T> {
+            self.guard.update_tables(Drain {})
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send + Ord,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+// BinaryHeap isn't PartialEq, so the tables are compared as sorted vectors.
+#[cfg(test)]
+macro_rules! assert_heap_eq {
+    ($table:expr, $expected:expr) => {
+        assert_eq!($table.read().clone().into_sorted_vec(), $expected);
+        assert_eq!($table.write().clone().into_sorted_vec(), $expected);
+        assert_eq!($table.read().clone().into_sorted_vec(), $expected);
+    };
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let table = lockless::AsLockHandle::<i32>::default();
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            wg.extend([3, 1, 4, 1, 5]);
+            wg.push(9);
+            assert_eq!(wg.peek(), Some(&9));
+            assert_eq!(wg.pop(), Some(9));
+            assert!(table2.read().is_empty());
+        }
+        assert_eq!(table2.read().peek(), Some(&5));
+        assert_heap_eq!(table, vec![1, 1, 3, 4, 5]);
+
+        assert_eq!(table.write().pop_n(2), vec![5, 4]);
+        assert_heap_eq!(table, vec![1, 1, 3]);
+    }
+
+    #[test]
+    fn ties_pop_the_same_value() {
+        // Ordered by priority only, so the tables must agree on which of the
+        // equal priorities is popped.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        struct Task(u32, &'static str);
+        impl PartialOrd for Task {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Task {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let table = lockless::AsLockHandle::<Task>::default();
+        table
+            .write()
+            .extend([Task(1, "a"), Task(1, "b"), Task(1, "c")]);
+        let popped = table.write().pop().unwrap();
+        let mut rest = table.read().clone().into_vec();
+        // The second table replays the pop when the next guard is taken.
+        let mut rest2 = table.write().clone().into_vec();
+        rest.sort_by_key(|task| task.1);
+        rest2.sort_by_key(|task| task.1);
+        assert_eq!(rest, rest2);
+        assert!(!rest.contains(&popped));
+    }
+
+    #[test]
+    fn into_sorted_vec() {
+        let table = lockless::AsLockHandle::new(BinaryHeap::from([2, 7, 1]));
+        {
+            let mut wg = table.write();
+            wg.append(&mut BinaryHeap::from([5]));
+            assert_eq!(wg.into_sorted_vec(), vec![1, 2, 5, 7]);
+            wg.push(3);
+        }
+        assert_heap_eq!(table, vec![3]);
+
+        table.write().retain(|x| *x != 3);
+        assert_heap_eq!(table, Vec::<i32>::new());
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let table = sync::AsLock::<i32>::default();
+        {
+            let mut wg = table.write();
+            wg.extend([3, 1, 4, 1, 5]);
+            wg.push(9);
+            assert_eq!(wg.peek(), Some(&9));
+            assert_eq!(wg.pop(), Some(9));
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(table.read().peek(), Some(&5));
+        assert_heap_eq!(table, vec![1, 1, 3, 4, 5]);
+
+        assert_eq!(table.write().pop_n(2), vec![5, 4]);
+        assert_heap_eq!(table, vec![1, 1, 3]);
+    }
+
+    #[test]
+    fn into_sorted_vec() {
+        let table = sync::AsLock::new(BinaryHeap::from([2, 7, 1]));
+        {
+            let mut wg = table.write();
+            wg.append(&mut BinaryHeap::from([5]));
+            assert_eq!(wg.into_sorted_vec(), vec![1, 2, 5, 7]);
+            wg.push(3);
+        }
+        assert_heap_eq!(table, vec![3]);
+
+        assert_eq!(table.write().drain().collect::<Vec<_>>(), vec![3]);
+        assert_heap_eq!(table, Vec::<i32>::new());
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let table = arc::AsArcLock::<i32>::default();
+        {
+            let mut wg = table.write();
+            wg.extend([3, 1, 4]);
+            assert_eq!(wg.pop(), Some(4));
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(table.read().peek(), Some(&3));
+        assert_eq!(table.write().clone().into_sorted_vec(), vec![1, 3]);
+        assert_eq!(BinaryHeap::clone(&table.read()).into_sorted_vec(), vec![1, 3]);
+    }
+}
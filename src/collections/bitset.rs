@@ -0,0 +1,392 @@
+use std::fmt;
+use std::ops::Range;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Growable set of bits. Bits which have never been set read as unset, so the
+/// set grows only when a bit past its end is set.
+#[derive(Clone, Default)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> BitSet {
+        BitSet::default()
+    }
+
+    /// Create a `BitSet` with space for `num_bits` bits.
+    pub fn with_capacity(num_bits: usize) -> BitSet {
+        BitSet {
+            words: Vec::with_capacity(num_bits.div_ceil(WORD_BITS)),
+        }
+    }
+
+    pub fn get(&self, bit: usize) -> bool {
+        self.words
+            .get(bit / WORD_BITS)
+            .is_some_and(|word| word & (1 << (bit % WORD_BITS)) != 0)
+    }
+
+    /// The number of bits which are set.
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Iterate over the indices of the bits which are set, in increasing order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(index, &word)| {
+            (0..WORD_BITS)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| index * WORD_BITS + bit)
+        })
+    }
+
+    /// Set `bit` to `value`, returning its previous value.
+    pub fn set(&mut self, bit: usize, value: bool) -> bool {
+        let index = bit / WORD_BITS;
+        let mask = 1 << (bit % WORD_BITS);
+        if index >= self.words.len() {
+            if !value {
+                return false;
+            }
+            self.words.resize(index + 1, 0);
+        }
+
+        let word = &mut self.words[index];
+        let old_value = *word & mask != 0;
+        if value {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+        old_value
+    }
+
+    /// Set all of the bits in `range` to `value`.
+    pub fn set_range(&mut self, range: Range<usize>, value: bool) {
+        let end = if value {
+            let num_words = range.end.div_ceil(WORD_BITS);
+            if num_words > self.words.len() {
+                self.words.resize(num_words, 0);
+            }
+            range.end
+        } else {
+            // Bits past the end are already unset.
+            range.end.min(self.words.len() * WORD_BITS)
+        };
+        if range.start >= end {
+            return;
+        }
+
+        for index in range.start / WORD_BITS..=(end - 1) / WORD_BITS {
+            let word_start = index * WORD_BITS;
+            let start = range.start.max(word_start) - word_start;
+            let len = end.min(word_start + WORD_BITS) - word_start - start;
+            let mask = if len == WORD_BITS {
+                u64::MAX
+            } else {
+                ((1 << len) - 1) << start
+            };
+            if value {
+                self.words[index] |= mask;
+            } else {
+                self.words[index] &= !mask;
+            }
+        }
+    }
+
+    /// Unset all bits.
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+}
+
+/// Equality is based on which bits are set, regardless of how far the sets
+/// have grown.
+impl PartialEq for BitSet {
+    fn eq(&self, other: &BitSet) -> bool {
+        let (shorter, longer) = if self.words.len() <= other.words.len() {
+            (&self.words, &other.words)
+        } else {
+            (&other.words, &self.words)
+        };
+        let (overlap, rest) = longer.split_at(shorter.len());
+        shorter == overlap && rest.iter().all(|word| *word == 0)
+    }
+}
+
+impl Eq for BitSet {}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter_ones()).finish()
+    }
+}
+
+impl crate::WithCapacity for BitSet {
+    fn with_capacity(num_bits: usize) -> BitSet {
+        BitSet::with_capacity(num_bits)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl crate::ParallelClone for BitSet {
+    fn par_clone(&self) -> BitSet {
+        BitSet {
+            words: self.words.par_clone(),
+        }
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(bits: I) -> BitSet {
+        let mut bitset = BitSet::new();
+        for bit in bits {
+            bitset.set(bit, true);
+        }
+        bitset
+    }
+}
+
+/// Implementation of BitSet for use in the active_standby model.
+/// `lockless::AsLockHandle`, should function similarly to
+/// `Arc<RwLock<BitSet>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(BitSet);
+
+    impl<'w> AsLockWriteGuard<'w> {
+        pub fn set(&mut self, bit: usize, value: bool) -> bool {
+            self.guard
+                .update_tables_closure(move |table| table.set(bit, value))
+        }
+
+        pub fn set_range(&mut self, range: Range<usize>, value: bool) {
+            self.guard
+                .update_tables_closure(move |table| table.set_range(range.clone(), value))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// Implementation of BitSet for use in the active_standby model.
+/// `sync::AsLock`, should function similarly to `RwLock<BitSet>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(BitSet);
+
+    impl<'w> AsLockWriteGuard<'w> {
+        pub fn set(&mut self, bit: usize, value: bool) -> bool {
+            self.guard
+                .update_tables_closure(move |table| table.set(bit, value))
+        }
+
+        pub fn set_range(&mut self, range: Range<usize>, value: bool) {
+            self.guard
+                .update_tables_closure(move |table| table.set_range(range.clone(), value))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(BitSet);
+
+    impl<'w> AsArcLockWriteGuard<'w> {
+        pub fn set(&mut self, bit: usize, value: bool) -> bool {
+            self.guard
+                .update_tables_closure(move |table| table.set(bit, value))
+        }
+
+        pub fn set_range(&mut self, range: Range<usize>, value: bool) {
+            self.guard
+                .update_tables_closure(move |table| table.set_range(range.clone(), value))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+#[cfg(test)]
+mod bitset_test {
+    use super::*;
+
+    #[test]
+    fn set_and_get() {
+        let mut bitset = BitSet::new();
+        assert!(!bitset.get(3));
+        assert!(!bitset.set(3, true));
+        assert!(bitset.set(3, true));
+        assert!(bitset.get(3));
+        assert!(!bitset.get(200));
+
+        // Unsetting past the end doesn't grow the set.
+        assert!(!bitset.set(200, false));
+        assert_eq!(bitset.words.len(), 1);
+
+        assert!(!bitset.set(200, true));
+        assert_eq!(bitset.iter_ones().collect::<Vec<_>>(), vec![3, 200]);
+        assert_eq!(bitset.count_ones(), 2);
+    }
+
+    #[test]
+    fn set_range() {
+        let mut bitset = BitSet::new();
+        bitset.set_range(60..130, true);
+        assert_eq!(
+            bitset.iter_ones().collect::<Vec<_>>(),
+            (60..130).collect::<Vec<_>>()
+        );
+
+        bitset.set_range(64..128, false);
+        assert_eq!(
+            bitset.iter_ones().collect::<Vec<_>>(),
+            vec![60, 61, 62, 63, 128, 129]
+        );
+
+        // Unsetting past the end doesn't grow the set.
+        bitset.set_range(100..1000, false);
+        assert_eq!(bitset.iter_ones().collect::<Vec<_>>(), vec![60, 61, 62, 63]);
+        assert_eq!(bitset.words.len(), 3);
+
+        bitset.set_range(5..5, true);
+        assert_eq!(bitset.count_ones(), 4);
+    }
+
+    #[test]
+    fn eq_ignores_growth() {
+        let mut bitset: BitSet = [1, 1000].into_iter().collect();
+        bitset.set(1000, false);
+        assert_eq!(bitset, [1].into_iter().collect());
+        assert_ne!(bitset, BitSet::new());
+
+        bitset.clear();
+        assert!(bitset.is_empty());
+        assert_eq!(bitset, BitSet::new());
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn set() {
+        let table = lockless::AsLockHandle::default();
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            assert!(!wg.set(3, true));
+            assert!(wg.set(3, true));
+            wg.set(70, true);
+            assert!(wg.get(70));
+            assert!(!table2.read().get(70));
+        }
+        assert!(table2.read().get(70));
+        assert_tables_eq!(table, [3, 70].into_iter().collect::<BitSet>());
+    }
+
+    #[test]
+    fn set_range() {
+        let table = lockless::AsLockHandle::default();
+        table.write().set_range(0..100, true);
+        table.write().set_range(10..90, false);
+        assert_tables_eq!(table, (0..10).chain(90..100).collect::<BitSet>());
+    }
+
+    #[test]
+    fn clear() {
+        let table = lockless::AsLockHandle::new([1, 2, 3].into_iter().collect());
+        table.write().clear();
+        assert_tables_eq!(table, BitSet::new());
+    }
+
+    #[test]
+    fn debug_str() {
+        let table = lockless::AsLockHandle::default();
+        table.write().set(12, true);
+        assert_eq!(format!("{:?}", table.read()), "{12}");
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn set() {
+        let table = std::sync::Arc::new(sync::AsLock::default());
+        {
+            let mut wg = table.write();
+            assert!(!wg.set(3, true));
+            assert!(wg.set(3, true));
+            wg.set(70, true);
+            assert!(wg.get(70));
+        }
+        assert!(table.read().get(70));
+        assert_tables_eq!(table, [3, 70].into_iter().collect::<BitSet>());
+    }
+
+    #[test]
+    fn set_range() {
+        let table = sync::AsLock::default();
+        table.write().set_range(0..100, true);
+        table.write().set_range(10..90, false);
+        assert_tables_eq!(table, (0..10).chain(90..100).collect::<BitSet>());
+    }
+
+    #[test]
+    fn clear() {
+        let table = sync::AsLock::new([1, 2, 3].into_iter().collect());
+        table.write().clear();
+        assert_tables_eq!(table, BitSet::new());
+    }
+
+    #[test]
+    fn with_capacity() {
+        let table = sync::AsLock::with_capacity(1000);
+        table.write().set(999, true);
+        assert_tables_eq!(table, [999].into_iter().collect::<BitSet>());
+    }
+
+    #[test]
+    fn debug_str() {
+        let table = sync::AsLock::default();
+        table.write().set(12, true);
+        assert_eq!(format!("{:?}", table.read()), "{12}");
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn set() {
+        let table = arc::AsArcLock::default();
+        assert!(!table.write().set(3, true));
+        assert!(table.read().get(3));
+        assert_eq!(*table.write(), [3].into_iter().collect::<BitSet>());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::collections::{OccupiedError, Prng};
 use crate::UpdateTables;
 use std::collections::BTreeMap;
 
@@ -58,6 +59,139 @@ where
     }
 }
 
+struct ExtractIf<F> {
+    f: F,
+}
+
+impl<'a, K, V, F> UpdateTables<'a, BTreeMap<K, V>, Vec<(K, V)>> for ExtractIf<F>
+where
+    K: Ord,
+    F: Clone + FnMut(&K, &mut V) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut BTreeMap<K, V>) -> Vec<(K, V)> {
+        table.extract_if(.., self.f.clone()).collect()
+    }
+    fn apply_second(mut self, table: &mut BTreeMap<K, V>) {
+        table.retain(|k, v| !(self.f)(k, v));
+    }
+}
+
+struct RandomEvict {
+    n: usize,
+    seed: u64,
+}
+
+impl<'a, K, V> UpdateTables<'a, BTreeMap<K, V>, Vec<(K, V)>> for RandomEvict
+where
+    K: Ord,
+{
+    fn apply_first(&mut self, table: &'a mut BTreeMap<K, V>) -> Vec<(K, V)> {
+        let mut evict = Prng::new(self.seed).sample(table.len(), self.n);
+        table.extract_if(.., |_, _| evict()).collect()
+    }
+    fn apply_second(self, table: &mut BTreeMap<K, V>) {
+        // The entries are in the same order in both tables, so the same seed
+        // picks the same entries.
+        let mut evict = Prng::new(self.seed).sample(table.len(), self.n);
+        table.retain(|_, _| !evict());
+    }
+}
+
+struct GetMutWith<Q, F> {
+    key_like: Q,
+    f: F,
+}
+
+impl<'a, K, V, Q, F> UpdateTables<'a, BTreeMap<K, V>, Option<&'a V>> for GetMutWith<Q, F>
+where
+    K: Ord + std::borrow::Borrow<Q>,
+    Q: Ord,
+    F: Clone + FnMut(&mut V),
+{
+    fn apply_first(&mut self, table: &'a mut BTreeMap<K, V>) -> Option<&'a V> {
+        let value = table.get_mut(&self.key_like)?;
+        (self.f.clone())(value);
+        Some(value)
+    }
+    fn apply_second(mut self, table: &mut BTreeMap<K, V>) {
+        if let Some(value) = table.get_mut(&self.key_like) {
+            (self.f)(value);
+        }
+    }
+}
+
+struct Upsert<K, V, F> {
+    key: K,
+    modify: Option<F>,
+    // Only `None` if the key is known to be in the table.
+    default: Option<V>,
+}
+
+impl<'a, K, V, F> UpdateTables<'a, BTreeMap<K, V>, &'a V> for Upsert<K, V, F>
+where
+    K: Ord + Clone,
+    V: Clone,
+    F: Clone + FnMut(&mut V),
+{
+    fn apply_first(&mut self, table: &'a mut BTreeMap<K, V>) -> &'a V {
+        upsert(table, self.key.clone(), self.modify.clone(), || {
+            self.default.clone()
+        })
+    }
+    fn apply_second(self, table: &mut BTreeMap<K, V>) {
+        // Move the value instead of cloning.
+        upsert(table, self.key, self.modify, || self.default);
+    }
+}
+
+fn upsert<K: Ord, V, F: FnMut(&mut V)>(
+    table: &mut BTreeMap<K, V>,
+    key: K,
+    modify: Option<F>,
+    default: impl FnOnce() -> Option<V>,
+) -> &V {
+    use std::collections::btree_map::Entry;
+    match table.entry(key) {
+        Entry::Occupied(entry) => {
+            let value = entry.into_mut();
+            if let Some(mut modify) = modify {
+                modify(value);
+            }
+            value
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(default().expect("the key was checked to be in the table"))
+        }
+    }
+}
+
+/// Shared by lockless & sync `read_chunks`.
+fn read_chunks<G, K, V>(read: impl Fn() -> G, chunk_size: usize, mut f: impl FnMut(&[(&K, &V)]))
+where
+    G: std::ops::Deref<Target = BTreeMap<K, V>>,
+    K: Ord + Clone,
+{
+    use std::ops::Bound::{Excluded, Unbounded};
+
+    assert_ne!(chunk_size, 0, "chunk_size must be positive");
+    let mut last_key: Option<K> = None;
+    loop {
+        let table = read();
+        let chunk: Vec<_> = match &last_key {
+            None => table.iter().take(chunk_size).collect(),
+            Some(last_key) => table
+                .range((Excluded(last_key), Unbounded))
+                .take(chunk_size)
+                .collect(),
+        };
+        match chunk.last() {
+            None => return,
+            Some((key, _)) => last_key = Some(K::clone(key)),
+        }
+        f(&chunk);
+    }
+}
+
 /// Implementation of BtreeeMap for use in the active_standby model.
 /// `lockless::AsLockHandle<K, V>`, should function similarly to
 /// `Arc<RwLock<BTreeMap<K, V>>>`.
@@ -65,6 +199,67 @@ pub mod lockless {
     use super::*;
     crate::generate_lockless_aslockhandle!(BTreeMap<K, V>);
 
+    impl<K, V> AsLockHandle<K, V> {
+        /// Call `f` on consecutive chunks of at most `chunk_size` entries, taking a
+        /// new read guard for each chunk. This bounds how long a full scan
+        /// holds onto a single guard, so that it never blocks the writer for
+        /// the duration of the scan.
+        ///
+        /// Each chunk continues from the last key of the previous chunk, so
+        /// every entry present for the whole scan is seen exactly once, in
+        /// order. Entries inserted or removed between chunks are only seen if
+        /// they come after the current position.
+        pub fn read_chunks(&self, chunk_size: usize, f: impl FnMut(&[(&K, &V)]))
+        where
+            K: Ord + Clone,
+        {
+            read_chunks(move || self.read(), chunk_size, f)
+        }
+
+        /// Clone the entries in `range` out of the active table. The read
+        /// guard is only held while cloning, so the result can be processed
+        /// without holding up the writer.
+        pub fn range_cloned<Q, R>(&self, range: R) -> Vec<(K, V)>
+        where
+            K: Ord + Clone + std::borrow::Borrow<Q>,
+            V: Clone,
+            Q: Ord + ?Sized,
+            R: std::ops::RangeBounds<Q>,
+        {
+            self.read()
+                .range(range)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        }
+
+        /// Like `range_cloned`, but only clones the keys.
+        pub fn range_keys_cloned<Q, R>(&self, range: R) -> Vec<K>
+        where
+            K: Ord + Clone + std::borrow::Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: std::ops::RangeBounds<Q>,
+        {
+            self.read().range(range).map(|(k, _)| k.clone()).collect()
+        }
+
+        /// Clone the values of `keys` out of the active table, all under a
+        /// single read guard, so they are consistent with each other.
+        pub fn get_many_cloned<'k, Q>(
+            &self,
+            keys: impl IntoIterator<Item = &'k Q>,
+        ) -> Vec<Option<V>>
+        where
+            K: Ord + std::borrow::Borrow<Q>,
+            V: Clone,
+            Q: 'k + Ord + ?Sized,
+        {
+            let table = self.read();
+            keys.into_iter()
+                .map(|key| table.get(key).cloned())
+                .collect()
+        }
+    }
+
     impl<'w, K, V> AsLockWriteGuard<'w, K, V>
     where
         K: 'static + Ord + Clone + Send,
@@ -74,6 +269,73 @@ pub mod lockless {
             self.guard.update_tables(Insert { key, value })
         }
 
+        /// Insert `value` for `key`, unless `key` already maps to an equal
+        /// value, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        pub fn insert_if_different(&mut self, key: K, value: V) -> bool
+        where
+            V: PartialEq,
+        {
+            if self.get(&key) == Some(&value) {
+                return false;
+            }
+            self.insert(key, value);
+            true
+        }
+
+        /// Insert `value` for `key` only if `key` isn't in the table yet.
+        /// Otherwise no update is recorded, and the key and value are handed
+        /// back in the error.
+        pub fn try_insert(&mut self, key: K, value: V) -> Result<(), OccupiedError<K, V>> {
+            if self.contains_key(&key) {
+                return Err(OccupiedError { key, value });
+            }
+            self.insert(key, value);
+            Ok(())
+        }
+
+        /// Returns the entry for `key`, to update it in place like
+        /// `BTreeMap::entry`.
+        pub fn entry(&mut self, key: K) -> Entry<'_, 'w, K, V> {
+            Entry {
+                guard: self,
+                key,
+                modify: None,
+            }
+        }
+
+        /// Returns the value for `key`, inserting the value returned by `f`
+        /// if `key` isn't in the table yet. Like
+        /// `entry(key).or_insert_with(f)`, but no update is recorded if `key`
+        /// is already present.
+        pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+            if self.contains_key(&key) {
+                return self.get(&key).unwrap();
+            }
+            self.guard.update_tables(Upsert {
+                key,
+                modify: None::<fn(&mut V)>,
+                default: Some(f()),
+            })
+        }
+
+        /// Call `f` on the value for `key_like` and return the updated value,
+        /// or `None`, without recording an update, if the key isn't in the
+        /// table. `f` is called on both tables, so it must make the same change
+        /// each time.
+        pub fn get_mut_with<Q, F>(&mut self, key_like: Q, f: F) -> Option<&V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+            F: 'static + Send + Clone + FnMut(&mut V),
+        {
+            if !self.contains_key(&key_like) {
+                return None;
+            }
+            self.guard.update_tables(GetMutWith { key_like, f })
+        }
+
         pub fn clear(&mut self) {
             self.guard.update_tables_closure(move |table| table.clear())
         }
@@ -100,12 +362,126 @@ pub mod lockless {
             self.guard.update_tables(Append { other })
         }
 
+        /// A no-op, for parity with the hash map guards. A `BTreeMap` frees
+        /// its nodes as entries are removed, so there is no spare capacity to
+        /// release, and no update is recorded.
+        pub fn shrink_to_fit(&mut self) {}
+
         pub fn retain<F>(&mut self, f: F)
         where
             F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
         {
             self.guard.update_tables(Retain { f })
         }
+
+        /// Removes the entries for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same entries
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed entries instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.extract_if(move |k, v| !f(k, v))
+        }
+
+        /// Removes `n` entries picked at random, or all of them if the table is
+        /// smaller, and returns them. The entries are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the entries picked for a given seed are the same for equal tables.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<(K, V)> {
+            self.guard.update_tables(RandomEvict { n, seed })
+        }
+    }
+
+    /// A view into a single entry of the table, built by
+    /// `AsLockWriteGuard::entry`. Only `or_insert`, `or_insert_with` and
+    /// `or_default` update the table. They record a single update, which
+    /// applies the closures passed to `and_modify` identically on both tables.
+    #[must_use = "the table is only updated by or_insert, or_insert_with or or_default"]
+    pub struct Entry<'g, 'w, K, V, F = fn(&mut V)> {
+        guard: &'g mut AsLockWriteGuard<'w, K, V>,
+        key: K,
+        modify: Option<F>,
+    }
+
+    impl<'g, 'w, K, V, F> Entry<'g, 'w, K, V, F>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+        F: 'static + Send + Clone + FnMut(&mut V),
+    {
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Modify the value if the key is in the table. `f` is called on both
+        /// tables, so it must make the same change each time.
+        pub fn and_modify<G>(
+            self,
+            mut f: G,
+        ) -> Entry<'g, 'w, K, V, impl 'static + Send + Clone + FnMut(&mut V)>
+        where
+            G: 'static + Send + Clone + FnMut(&mut V),
+        {
+            let mut modify = self.modify;
+            Entry {
+                guard: self.guard,
+                key: self.key,
+                modify: Some(move |value: &mut V| {
+                    if let Some(modify) = &mut modify {
+                        modify(value);
+                    }
+                    f(value);
+                }),
+            }
+        }
+
+        /// Insert `default` if the key isn't in the table, and return the
+        /// value in the table.
+        pub fn or_insert(self, default: V) -> &'g V {
+            self.or_insert_with(move || default)
+        }
+
+        /// Like `or_insert`, but `default` is only called if the key isn't in
+        /// the table. It is called once, and the value is cloned into the
+        /// first table.
+        pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'g V {
+            let default = match self.guard.contains_key(&self.key) {
+                true => None,
+                false => Some(default()),
+            };
+            self.guard.update_tables(Upsert {
+                key: self.key,
+                modify: self.modify,
+                default,
+            })
+        }
+
+        pub fn or_default(self) -> &'g V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+    }
+
+    impl<'w, K, V> Extend<(K, V)> for AsLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+            self.append(iter.into_iter().collect())
+        }
     }
 }
 
@@ -116,6 +492,67 @@ pub mod sync {
     use super::*;
     crate::generate_sync_aslock!(BTreeMap<K, V>);
 
+    impl<K, V> AsLock<K, V> {
+        /// Call `f` on consecutive chunks of at most `chunk_size` entries, taking a
+        /// new read guard for each chunk. This bounds how long a full scan
+        /// holds onto a single guard, so that it never blocks the writer for
+        /// the duration of the scan.
+        ///
+        /// Each chunk continues from the last key of the previous chunk, so
+        /// every entry present for the whole scan is seen exactly once, in
+        /// order. Entries inserted or removed between chunks are only seen if
+        /// they come after the current position.
+        pub fn read_chunks(&self, chunk_size: usize, f: impl FnMut(&[(&K, &V)]))
+        where
+            K: Ord + Clone,
+        {
+            read_chunks(move || self.read(), chunk_size, f)
+        }
+
+        /// Clone the entries in `range` out of the active table. The read
+        /// guard is only held while cloning, so the result can be processed
+        /// without holding up the writer.
+        pub fn range_cloned<Q, R>(&self, range: R) -> Vec<(K, V)>
+        where
+            K: Ord + Clone + std::borrow::Borrow<Q>,
+            V: Clone,
+            Q: Ord + ?Sized,
+            R: std::ops::RangeBounds<Q>,
+        {
+            self.read()
+                .range(range)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        }
+
+        /// Like `range_cloned`, but only clones the keys.
+        pub fn range_keys_cloned<Q, R>(&self, range: R) -> Vec<K>
+        where
+            K: Ord + Clone + std::borrow::Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: std::ops::RangeBounds<Q>,
+        {
+            self.read().range(range).map(|(k, _)| k.clone()).collect()
+        }
+
+        /// Clone the values of `keys` out of the active table, all under a
+        /// single read guard, so they are consistent with each other.
+        pub fn get_many_cloned<'k, Q>(
+            &self,
+            keys: impl IntoIterator<Item = &'k Q>,
+        ) -> Vec<Option<V>>
+        where
+            K: Ord + std::borrow::Borrow<Q>,
+            V: Clone,
+            Q: 'k + Ord + ?Sized,
+        {
+            let table = self.read();
+            keys.into_iter()
+                .map(|key| table.get(key).cloned())
+                .collect()
+        }
+    }
+
     impl<'w, K, V> AsLockWriteGuard<'w, K, V>
     where
         K: 'static + Ord + Clone + Send,
@@ -125,6 +562,73 @@ pub mod sync {
             self.guard.update_tables(Insert { key, value })
         }
 
+        /// Insert `value` for `key`, unless `key` already maps to an equal
+        /// value, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        pub fn insert_if_different(&mut self, key: K, value: V) -> bool
+        where
+            V: PartialEq,
+        {
+            if self.get(&key) == Some(&value) {
+                return false;
+            }
+            self.insert(key, value);
+            true
+        }
+
+        /// Insert `value` for `key` only if `key` isn't in the table yet.
+        /// Otherwise no update is recorded, and the key and value are handed
+        /// back in the error.
+        pub fn try_insert(&mut self, key: K, value: V) -> Result<(), OccupiedError<K, V>> {
+            if self.contains_key(&key) {
+                return Err(OccupiedError { key, value });
+            }
+            self.insert(key, value);
+            Ok(())
+        }
+
+        /// Returns the entry for `key`, to update it in place like
+        /// `BTreeMap::entry`.
+        pub fn entry(&mut self, key: K) -> Entry<'_, 'w, K, V> {
+            Entry {
+                guard: self,
+                key,
+                modify: None,
+            }
+        }
+
+        /// Returns the value for `key`, inserting the value returned by `f`
+        /// if `key` isn't in the table yet. Like
+        /// `entry(key).or_insert_with(f)`, but no update is recorded if `key`
+        /// is already present.
+        pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+            if self.contains_key(&key) {
+                return self.get(&key).unwrap();
+            }
+            self.guard.update_tables(Upsert {
+                key,
+                modify: None::<fn(&mut V)>,
+                default: Some(f()),
+            })
+        }
+
+        /// Call `f` on the value for `key_like` and return the updated value,
+        /// or `None`, without recording an update, if the key isn't in the
+        /// table. `f` is called on both tables, so it must make the same change
+        /// each time.
+        pub fn get_mut_with<Q, F>(&mut self, key_like: Q, f: F) -> Option<&V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+            F: 'static + Send + Clone + FnMut(&mut V),
+        {
+            if !self.contains_key(&key_like) {
+                return None;
+            }
+            self.guard.update_tables(GetMutWith { key_like, f })
+        }
+
         pub fn clear(&mut self) {
             self.guard.update_tables_closure(move |table| table.clear())
         }
@@ -151,12 +655,357 @@ pub mod sync {
             self.guard.update_tables(Append { other })
         }
 
+        /// A no-op, for parity with the hash map guards. A `BTreeMap` frees
+        /// its nodes as entries are removed, so there is no spare capacity to
+        /// release, and no update is recorded.
+        pub fn shrink_to_fit(&mut self) {}
+
         pub fn retain<F>(&mut self, f: F)
         where
             F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
         {
             self.guard.update_tables(Retain { f })
         }
+
+        /// Removes the entries for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same entries
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed entries instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.extract_if(move |k, v| !f(k, v))
+        }
+
+        /// Removes `n` entries picked at random, or all of them if the table is
+        /// smaller, and returns them. The entries are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the entries picked for a given seed are the same for equal tables.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<(K, V)> {
+            self.guard.update_tables(RandomEvict { n, seed })
+        }
+    }
+
+    /// A view into a single entry of the table, built by
+    /// `AsLockWriteGuard::entry`. Only `or_insert`, `or_insert_with` and
+    /// `or_default` update the table. They record a single update, which
+    /// applies the closures passed to `and_modify` identically on both tables.
+    #[must_use = "the table is only updated by or_insert, or_insert_with or or_default"]
+    pub struct Entry<'g, 'w, K, V, F = fn(&mut V)> {
+        guard: &'g mut AsLockWriteGuard<'w, K, V>,
+        key: K,
+        modify: Option<F>,
+    }
+
+    impl<'g, 'w, K, V, F> Entry<'g, 'w, K, V, F>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+        F: 'static + Send + Clone + FnMut(&mut V),
+    {
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Modify the value if the key is in the table. `f` is called on both
+        /// tables, so it must make the same change each time.
+        pub fn and_modify<G>(
+            self,
+            mut f: G,
+        ) -> Entry<'g, 'w, K, V, impl 'static + Send + Clone + FnMut(&mut V)>
+        where
+            G: 'static + Send + Clone + FnMut(&mut V),
+        {
+            let mut modify = self.modify;
+            Entry {
+                guard: self.guard,
+                key: self.key,
+                modify: Some(move |value: &mut V| {
+                    if let Some(modify) = &mut modify {
+                        modify(value);
+                    }
+                    f(value);
+                }),
+            }
+        }
+
+        /// Insert `default` if the key isn't in the table, and return the
+        /// value in the table.
+        pub fn or_insert(self, default: V) -> &'g V {
+            self.or_insert_with(move || default)
+        }
+
+        /// Like `or_insert`, but `default` is only called if the key isn't in
+        /// the table. It is called once, and the value is cloned into the
+        /// first table.
+        pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'g V {
+            let default = match self.guard.contains_key(&self.key) {
+                true => None,
+                false => Some(default()),
+            };
+            self.guard.update_tables(Upsert {
+                key: self.key,
+                modify: self.modify,
+                default,
+            })
+        }
+
+        pub fn or_default(self) -> &'g V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+    }
+
+    impl<'w, K, V> Extend<(K, V)> for AsLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+            self.append(iter.into_iter().collect())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(BTreeMap<K, V>);
+
+    impl<'w, K, V> AsArcLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+            self.guard.update_tables(Insert { key, value })
+        }
+
+        /// Insert `value` for `key`, unless `key` already maps to an equal
+        /// value, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        pub fn insert_if_different(&mut self, key: K, value: V) -> bool
+        where
+            V: PartialEq,
+        {
+            if self.get(&key) == Some(&value) {
+                return false;
+            }
+            self.insert(key, value);
+            true
+        }
+
+        /// Insert `value` for `key` only if `key` isn't in the table yet.
+        /// Otherwise no update is recorded, and the key and value are handed
+        /// back in the error.
+        pub fn try_insert(&mut self, key: K, value: V) -> Result<(), OccupiedError<K, V>> {
+            if self.contains_key(&key) {
+                return Err(OccupiedError { key, value });
+            }
+            self.insert(key, value);
+            Ok(())
+        }
+
+        /// Returns the entry for `key`, to update it in place like
+        /// `BTreeMap::entry`.
+        pub fn entry(&mut self, key: K) -> Entry<'_, 'w, K, V> {
+            Entry {
+                guard: self,
+                key,
+                modify: None,
+            }
+        }
+
+        /// Returns the value for `key`, inserting the value returned by `f`
+        /// if `key` isn't in the table yet. Like
+        /// `entry(key).or_insert_with(f)`, but no update is recorded if `key`
+        /// is already present.
+        pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+            if self.contains_key(&key) {
+                return self.get(&key).unwrap();
+            }
+            self.guard.update_tables(Upsert {
+                key,
+                modify: None::<fn(&mut V)>,
+                default: Some(f()),
+            })
+        }
+
+        /// Call `f` on the value for `key_like` and return the updated value,
+        /// or `None`, without recording an update, if the key isn't in the
+        /// table. `f` is called on both tables, so it must make the same change
+        /// each time.
+        pub fn get_mut_with<Q, F>(&mut self, key_like: Q, f: F) -> Option<&V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+            F: 'static + Send + Clone + FnMut(&mut V),
+        {
+            if !self.contains_key(&key_like) {
+                return None;
+            }
+            self.guard.update_tables(GetMutWith { key_like, f })
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(move |table| table.clear())
+        }
+
+        pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
+        where
+            K: Ord + std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&key_like))
+        }
+
+        pub fn remove_entry<Q>(&mut self, key_like: Q) -> Option<(K, V)>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove_entry(&key_like))
+        }
+
+        pub fn append(&mut self, other: BTreeMap<K, V>) {
+            self.guard.update_tables(Append { other })
+        }
+
+        /// A no-op, for parity with the hash map guards. A `BTreeMap` frees
+        /// its nodes as entries are removed, so there is no spare capacity to
+        /// release, and no update is recorded.
+        pub fn shrink_to_fit(&mut self) {}
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        /// Removes the entries for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same entries
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed entries instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.extract_if(move |k, v| !f(k, v))
+        }
+
+        /// Removes `n` entries picked at random, or all of them if the table is
+        /// smaller, and returns them. The entries are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the entries picked for a given seed are the same for equal tables.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<(K, V)> {
+            self.guard.update_tables(RandomEvict { n, seed })
+        }
+    }
+
+    /// A view into a single entry of the table, built by
+    /// `AsArcLockWriteGuard::entry`. Only `or_insert`, `or_insert_with` and
+    /// `or_default` update the table. They record a single update, which
+    /// applies the closures passed to `and_modify` identically on both tables.
+    #[must_use = "the table is only updated by or_insert, or_insert_with or or_default"]
+    pub struct Entry<'g, 'w, K, V, F = fn(&mut V)> {
+        guard: &'g mut AsArcLockWriteGuard<'w, K, V>,
+        key: K,
+        modify: Option<F>,
+    }
+
+    impl<'g, 'w, K, V, F> Entry<'g, 'w, K, V, F>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+        F: 'static + Send + Clone + FnMut(&mut V),
+    {
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Modify the value if the key is in the table. `f` is called on both
+        /// tables, so it must make the same change each time.
+        pub fn and_modify<G>(
+            self,
+            mut f: G,
+        ) -> Entry<'g, 'w, K, V, impl 'static + Send + Clone + FnMut(&mut V)>
+        where
+            G: 'static + Send + Clone + FnMut(&mut V),
+        {
+            let mut modify = self.modify;
+            Entry {
+                guard: self.guard,
+                key: self.key,
+                modify: Some(move |value: &mut V| {
+                    if let Some(modify) = &mut modify {
+                        modify(value);
+                    }
+                    f(value);
+                }),
+            }
+        }
+
+        /// Insert `default` if the key isn't in the table, and return the
+        /// value in the table.
+        pub fn or_insert(self, default: V) -> &'g V {
+            self.or_insert_with(move || default)
+        }
+
+        /// Like `or_insert`, but `default` is only called if the key isn't in
+        /// the table. It is called once, and the value is cloned into the
+        /// first table.
+        pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'g V {
+            let default = match self.guard.contains_key(&self.key) {
+                true => None,
+                false => Some(default()),
+            };
+            self.guard.update_tables(Upsert {
+                key: self.key,
+                modify: self.modify,
+                default,
+            })
+        }
+
+        pub fn or_default(self) -> &'g V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+    }
+
+    impl<'w, K, V> Extend<(K, V)> for AsArcLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Ord + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+            self.append(iter.into_iter().collect())
+        }
     }
 }
 
@@ -166,6 +1015,44 @@ mod lockless_test {
     use crate::assert_tables_eq;
     use maplit::*;
 
+    #[test]
+    fn read_chunks() {
+        let table = lockless::AsLockHandle::new(btreemap! { 1 => 'a', 3 => 'c', 5 => 'e' });
+        let mut chunks = vec![];
+        table.read_chunks(2, |chunk| {
+            if chunks.is_empty() {
+                // Inserted before the current position, so not seen.
+                let mut wg = table.write();
+                wg.insert(2, 'b');
+                wg.insert(6, 'f');
+            }
+            chunks.push(chunk.iter().map(|(k, v)| (**k, **v)).collect::<Vec<_>>());
+        });
+        assert_eq!(
+            chunks,
+            vec![vec![(1, 'a'), (3, 'c')], vec![(5, 'e'), (6, 'f')]]
+        );
+    }
+
+    #[test]
+    fn get_many_cloned() {
+        let table = lockless::AsLockHandle::new(btreemap! { 1 => 'a', 3 => 'c' });
+        assert_eq!(
+            table.get_many_cloned(&[3, 2, 1]),
+            vec![Some('c'), None, Some('a')]
+        );
+    }
+
+    #[test]
+    fn range_cloned() {
+        let table =
+            lockless::AsLockHandle::new(btreemap! { 1 => 'a', 3 => 'c', 5 => 'e', 7 => 'g' });
+        assert_eq!(table.range_cloned(2..6), vec![(3, 'c'), (5, 'e')]);
+        assert_eq!(table.range_cloned(..=1), vec![(1, 'a')]);
+        assert_eq!(table.range_keys_cloned(3..), vec![3, 5, 7]);
+        assert!(table.range_keys_cloned(8..).is_empty());
+    }
+
     #[test]
     fn insert() {
         let expected = btreemap! {
@@ -183,6 +1070,86 @@ mod lockless_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[test]
+    fn try_insert() {
+        let table = lockless::AsLockHandle::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(
+                wg.try_insert("hello", 2),
+                Err(OccupiedError {
+                    key: "hello",
+                    value: 2
+                })
+            );
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert_eq!(wg.try_insert("world", 3), Ok(()));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => 1, "world" => 3 });
+    }
+
+    #[test]
+    fn entry() {
+        let table = lockless::AsLockHandle::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.entry("hello").and_modify(|v| *v += 1).or_insert(10), 2);
+            assert_eq!(*wg.entry("world").and_modify(|v| *v += 1).or_insert(10), 10);
+            assert_eq!(
+                *wg.entry("hello")
+                    .and_modify(|v| *v *= 3)
+                    .and_modify(|v| *v += 1)
+                    .or_insert_with(|| unreachable!()),
+                7
+            );
+            assert_eq!(*wg.entry("default").or_default(), 0);
+            assert_eq!(wg.num_pending_ops(), 4);
+        }
+        assert_tables_eq!(
+            table,
+            btreemap! { "hello" => 7, "world" => 10, "default" => 0 }
+        );
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let table = lockless::AsLockHandle::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.get_or_insert_with("hello", || unreachable!()), 1);
+            assert_eq!(*wg.get_or_insert_with("world", || 2), 2);
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => 1, "world" => 2 });
+    }
+
+    #[test]
+    fn get_mut_with() {
+        let table = lockless::AsLockHandle::new(btreemap! { "hello" => vec![1] });
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.get_mut_with("hello", |v| v.push(2)), Some(&vec![1, 2]));
+            assert_eq!(wg.get_mut_with("world", |v| v.push(2)), None);
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => vec![1, 2] });
+    }
+
+    #[test]
+    fn insert_if_different() {
+        let table = lockless::AsLockHandle::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert!(!wg.insert_if_different("hello", 1));
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert!(wg.insert_if_different("hello", 2));
+            assert!(wg.insert_if_different("world", 3));
+            assert_eq!(wg.num_pending_ops(), 2);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => 2, "world" => 3 });
+    }
+
     #[test]
     fn clear() {
         let table = lockless::AsLockHandle::<&str, i32>::default();
@@ -281,6 +1248,40 @@ mod lockless_test {
         );
     }
 
+    #[test]
+    fn extract_if() {
+        let table = lockless::AsLockHandle::new(btreemap! {
+            "hello" => 1,
+            "world" => 0,
+            "my" => 2,
+            "name" => -1,
+        });
+        assert_eq!(
+            table.write().extract_if(|_, &mut v| v % 2 != 0),
+            vec![("hello", 1), ("name", -1)]
+        );
+        assert_tables_eq!(table, btreemap! { "world" => 0, "my" => 2 });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = lockless::AsLockHandle::new((0..10).map(|i| (i, i * 10)).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for (key, _) in &evicted {
+            assert!(!table.read().contains_key(key));
+        }
+
+        // The same seed evicts the same entries from equal tables.
+        let other = lockless::AsLockHandle::new((0..10).map(|i| (i, i * 10)).collect());
+        assert_eq!(other.write().random_evict_with_seed(3, 11), evicted);
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, BTreeMap::new());
+    }
+
     #[test]
     fn debug_str() {
         let table = lockless::AsLockHandle::<i32, i32>::default();
@@ -307,6 +1308,43 @@ mod sync_test {
     use maplit::*;
     use std::sync::Arc;
 
+    #[test]
+    fn read_chunks() {
+        let table = sync::AsLock::new(btreemap! { 1 => 'a', 3 => 'c', 5 => 'e' });
+        let mut chunks = vec![];
+        table.read_chunks(2, |chunk| {
+            if chunks.is_empty() {
+                // Inserted before the current position, so not seen.
+                let mut wg = table.write();
+                wg.insert(2, 'b');
+                wg.insert(6, 'f');
+            }
+            chunks.push(chunk.iter().map(|(k, v)| (**k, **v)).collect::<Vec<_>>());
+        });
+        assert_eq!(
+            chunks,
+            vec![vec![(1, 'a'), (3, 'c')], vec![(5, 'e'), (6, 'f')]]
+        );
+    }
+
+    #[test]
+    fn get_many_cloned() {
+        let table = sync::AsLock::new(btreemap! { 1 => 'a', 3 => 'c' });
+        assert_eq!(
+            table.get_many_cloned(&[3, 2, 1]),
+            vec![Some('c'), None, Some('a')]
+        );
+    }
+
+    #[test]
+    fn range_cloned() {
+        let table = sync::AsLock::new(btreemap! { 1 => 'a', 3 => 'c', 5 => 'e', 7 => 'g' });
+        assert_eq!(table.range_cloned(2..6), vec![(3, 'c'), (5, 'e')]);
+        assert_eq!(table.range_cloned(..=1), vec![(1, 'a')]);
+        assert_eq!(table.range_keys_cloned(3..), vec![3, 5, 7]);
+        assert!(table.range_keys_cloned(8..).is_empty());
+    }
+
     #[test]
     fn insert() {
         let expected = btreemap! {
@@ -325,6 +1363,86 @@ mod sync_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[test]
+    fn try_insert() {
+        let table = sync::AsLock::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(
+                wg.try_insert("hello", 2),
+                Err(OccupiedError {
+                    key: "hello",
+                    value: 2
+                })
+            );
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert_eq!(wg.try_insert("world", 3), Ok(()));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => 1, "world" => 3 });
+    }
+
+    #[test]
+    fn entry() {
+        let table = sync::AsLock::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.entry("hello").and_modify(|v| *v += 1).or_insert(10), 2);
+            assert_eq!(*wg.entry("world").and_modify(|v| *v += 1).or_insert(10), 10);
+            assert_eq!(
+                *wg.entry("hello")
+                    .and_modify(|v| *v *= 3)
+                    .and_modify(|v| *v += 1)
+                    .or_insert_with(|| unreachable!()),
+                7
+            );
+            assert_eq!(*wg.entry("default").or_default(), 0);
+            assert_eq!(wg.num_pending_ops(), 4);
+        }
+        assert_tables_eq!(
+            table,
+            btreemap! { "hello" => 7, "world" => 10, "default" => 0 }
+        );
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let table = sync::AsLock::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.get_or_insert_with("hello", || unreachable!()), 1);
+            assert_eq!(*wg.get_or_insert_with("world", || 2), 2);
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => 1, "world" => 2 });
+    }
+
+    #[test]
+    fn get_mut_with() {
+        let table = sync::AsLock::new(btreemap! { "hello" => vec![1] });
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.get_mut_with("hello", |v| v.push(2)), Some(&vec![1, 2]));
+            assert_eq!(wg.get_mut_with("world", |v| v.push(2)), None);
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => vec![1, 2] });
+    }
+
+    #[test]
+    fn insert_if_different() {
+        let table = sync::AsLock::new(btreemap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert!(!wg.insert_if_different("hello", 1));
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert!(wg.insert_if_different("hello", 2));
+            assert!(wg.insert_if_different("world", 3));
+            assert_eq!(wg.num_pending_ops(), 2);
+        }
+        assert_tables_eq!(table, btreemap! { "hello" => 2, "world" => 3 });
+    }
+
     #[test]
     fn clear() {
         let table = Arc::new(sync::AsLock::<&str, i32>::default());
@@ -423,6 +1541,40 @@ mod sync_test {
         );
     }
 
+    #[test]
+    fn extract_if() {
+        let table = sync::AsLock::new(btreemap! {
+            "hello" => 1,
+            "world" => 0,
+            "my" => 2,
+            "name" => -1,
+        });
+        assert_eq!(
+            table.write().extract_if(|_, &mut v| v % 2 != 0),
+            vec![("hello", 1), ("name", -1)]
+        );
+        assert_tables_eq!(table, btreemap! { "world" => 0, "my" => 2 });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = sync::AsLock::new((0..10).map(|i| (i, i * 10)).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for (key, _) in &evicted {
+            assert!(!table.read().contains_key(key));
+        }
+
+        // The same seed evicts the same entries from equal tables.
+        let other = sync::AsLock::new((0..10).map(|i| (i, i * 10)).collect());
+        assert_eq!(other.write().random_evict_with_seed(3, 11), evicted);
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, BTreeMap::new());
+    }
+
     #[test]
     fn debug_str() {
         let table = Arc::new(sync::AsLock::<i32, i32>::default());
@@ -441,3 +1593,24 @@ mod sync_test {
         assert_eq!(format!("{:?}", table.read()), "{12: -1}",);
     }
 }
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+    use maplit::*;
+
+    #[test]
+    fn insert_and_entry() {
+        let table = arc::AsArcLock::<i32, char>::default();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.insert(1, 'a'), None);
+            assert_eq!(*wg.entry(2).or_insert('b'), 'b');
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(**table.read(), btreemap! { 1 => 'a', 2 => 'b' });
+        assert_eq!(table.write().remove(1), Some('a'));
+        assert_eq!(*table.write(), btreemap! { 2 => 'b' });
+        assert_eq!(**table.read(), btreemap! { 2 => 'b' });
+    }
+}
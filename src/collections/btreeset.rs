@@ -1,3 +1,5 @@
+use crate::collections::Prng;
+use crate::types::Replace;
 use crate::UpdateTables;
 use std::collections::BTreeSet;
 
@@ -18,10 +20,6 @@ where
     }
 }
 
-struct Replace<T> {
-    value: T,
-}
-
 impl<'a, T> UpdateTables<'a, BTreeSet<T>, Option<T>> for Replace<T>
 where
     T: Ord + Clone,
@@ -70,6 +68,61 @@ where
     }
 }
 
+struct ExtractIf<F> {
+    f: F,
+}
+
+impl<'a, T, F> UpdateTables<'a, BTreeSet<T>, Vec<T>> for ExtractIf<F>
+where
+    T: Ord,
+    F: Clone + FnMut(&T) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut BTreeSet<T>) -> Vec<T> {
+        table.extract_if(.., self.f.clone()).collect()
+    }
+    fn apply_second(mut self, table: &mut BTreeSet<T>) {
+        table.retain(|t| !(self.f)(t));
+    }
+}
+
+struct RandomEvict {
+    n: usize,
+    seed: u64,
+}
+
+impl<'a, T> UpdateTables<'a, BTreeSet<T>, Vec<T>> for RandomEvict
+where
+    T: Ord,
+{
+    fn apply_first(&mut self, table: &'a mut BTreeSet<T>) -> Vec<T> {
+        let mut evict = Prng::new(self.seed).sample(table.len(), self.n);
+        table.extract_if(.., |_| evict()).collect()
+    }
+    fn apply_second(self, table: &mut BTreeSet<T>) {
+        // The values are in the same order in both tables, so the same seed
+        // picks the same values.
+        let mut evict = Prng::new(self.seed).sample(table.len(), self.n);
+        table.retain(|_| !evict());
+    }
+}
+
+struct TakeRange<R> {
+    range: R,
+}
+
+impl<'a, T, R> UpdateTables<'a, BTreeSet<T>, Vec<T>> for TakeRange<R>
+where
+    T: Ord,
+    R: Clone + std::ops::RangeBounds<T>,
+{
+    fn apply_first(&mut self, table: &'a mut BTreeSet<T>) -> Vec<T> {
+        table.extract_if(self.range.clone(), |_| true).collect()
+    }
+    fn apply_second(self, table: &mut BTreeSet<T>) {
+        table.extract_if(self.range, |_| true).for_each(drop);
+    }
+}
+
 /// Implementation of BTreeSet for use in the active_standby model.
 /// `lockless::AsLockHandle<T>`, should function similarly to
 /// `Arc<RwLock<BTreeSet<T>>>`.
@@ -121,6 +174,59 @@ pub mod lockless {
         {
             self.guard.update_tables(Retain { f })
         }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        /// Removes `n` values picked at random, or all of them if the table is
+        /// smaller, and returns them. The values are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the values picked for a given seed are the same for equal tables.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<T> {
+            self.guard.update_tables(RandomEvict { n, seed })
+        }
+
+        pub fn pop_first(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_first())
+        }
+
+        pub fn pop_last(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_last())
+        }
+
+        /// Removes the values in `range` and returns them in order. Useful
+        /// for pruning everything before a cutoff from time ordered sets.
+        pub fn take_range<R>(&mut self, range: R) -> Vec<T>
+        where
+            R: 'static + Send + Clone + std::ops::RangeBounds<T>,
+        {
+            self.guard.update_tables(TakeRange { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Ord + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.append(iter.into_iter().collect())
+        }
     }
 }
 
@@ -174,6 +280,165 @@ pub mod sync {
         {
             self.guard.update_tables(Retain { f })
         }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        /// Removes `n` values picked at random, or all of them if the table is
+        /// smaller, and returns them. The values are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the values picked for a given seed are the same for equal tables.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<T> {
+            self.guard.update_tables(RandomEvict { n, seed })
+        }
+
+        pub fn pop_first(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_first())
+        }
+
+        pub fn pop_last(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_last())
+        }
+
+        /// Removes the values in `range` and returns them in order. Useful
+        /// for pruning everything before a cutoff from time ordered sets.
+        pub fn take_range<R>(&mut self, range: R) -> Vec<T>
+        where
+            R: 'static + Send + Clone + std::ops::RangeBounds<T>,
+        {
+            self.guard.update_tables(TakeRange { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Ord + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.append(iter.into_iter().collect())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(BTreeSet<T>);
+
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Ord + Clone + Send,
+    {
+        pub fn insert(&mut self, value: T) -> bool {
+            self.guard.update_tables(Insert { value })
+        }
+
+        pub fn replace(&mut self, value: T) -> Option<T> {
+            self.guard.update_tables(Replace { value })
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(move |table| table.clear())
+        }
+
+        pub fn remove<Q>(&mut self, value_like: Q) -> bool
+        where
+            T: std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&value_like))
+        }
+
+        pub fn take<Q>(&mut self, value_like: Q) -> Option<T>
+        where
+            T: std::borrow::Borrow<Q>,
+            Q: 'static + Ord + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.take(&value_like))
+        }
+
+        pub fn append(&mut self, other: BTreeSet<T>) {
+            self.guard.update_tables(Append { other })
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        /// Removes `n` values picked at random, or all of them if the table is
+        /// smaller, and returns them. The values are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the values picked for a given seed are the same for equal tables.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<T> {
+            self.guard.update_tables(RandomEvict { n, seed })
+        }
+
+        pub fn pop_first(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_first())
+        }
+
+        pub fn pop_last(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_last())
+        }
+
+        /// Removes the values in `range` and returns them in order. Useful
+        /// for pruning everything before a cutoff from time ordered sets.
+        pub fn take_range<R>(&mut self, range: R) -> Vec<T>
+        where
+            R: 'static + Send + Clone + std::ops::RangeBounds<T>,
+        {
+            self.guard.update_tables(TakeRange { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Ord + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.append(iter.into_iter().collect())
+        }
     }
 }
 
@@ -278,6 +543,61 @@ mod lockless_test {
         );
     }
 
+    #[test]
+    fn extract_if() {
+        let table = lockless::AsLockHandle::new(btreeset! {
+            "hello",
+            "world",
+            "name's",
+            "joe",
+        });
+        assert_eq!(
+            table.write().extract_if(|t| t.len() > 4),
+            vec!["hello", "name's", "world"]
+        );
+        assert_tables_eq!(table, btreeset! { "joe" });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = lockless::AsLockHandle::new((0..10).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for key in &evicted {
+            assert!(!table.read().contains(key));
+        }
+
+        // The same seed evicts the same entries from equal tables.
+        let other = lockless::AsLockHandle::new((0..10).collect());
+        assert_eq!(other.write().random_evict_with_seed(3, 11), evicted);
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, BTreeSet::new());
+    }
+
+    #[test]
+    fn pop_first_last() {
+        let table = lockless::AsLockHandle::new(btreeset! { 1, 2, 3 });
+        assert_eq!(table.write().pop_first(), Some(1));
+        assert_eq!(table.write().pop_last(), Some(3));
+        assert_tables_eq!(table, btreeset! { 2 });
+
+        table.write().clear();
+        assert_eq!(table.write().pop_first(), None);
+        assert_eq!(table.write().pop_last(), None);
+    }
+
+    #[test]
+    fn take_range() {
+        let table = lockless::AsLockHandle::new((0..10).collect());
+        assert_eq!(table.write().take_range(..3), vec![0, 1, 2]);
+        assert_eq!(table.write().take_range(5..=6), vec![5, 6]);
+        assert_eq!(table.write().take_range(20..), Vec::<i32>::new());
+        assert_tables_eq!(table, btreeset! { 3, 4, 7, 8, 9 });
+    }
+
     #[test]
     fn debug_str() {
         let table = lockless::AsLockHandle::<i32>::default();
@@ -399,6 +719,61 @@ mod sync_test {
         );
     }
 
+    #[test]
+    fn extract_if() {
+        let table = sync::AsLock::new(btreeset! {
+            "hello",
+            "world",
+            "name's",
+            "joe",
+        });
+        assert_eq!(
+            table.write().extract_if(|t| t.len() > 4),
+            vec!["hello", "name's", "world"]
+        );
+        assert_tables_eq!(table, btreeset! { "joe" });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = sync::AsLock::new((0..10).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for key in &evicted {
+            assert!(!table.read().contains(key));
+        }
+
+        // The same seed evicts the same entries from equal tables.
+        let other = sync::AsLock::new((0..10).collect());
+        assert_eq!(other.write().random_evict_with_seed(3, 11), evicted);
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, BTreeSet::new());
+    }
+
+    #[test]
+    fn pop_first_last() {
+        let table = sync::AsLock::new(btreeset! { 1, 2, 3 });
+        assert_eq!(table.write().pop_first(), Some(1));
+        assert_eq!(table.write().pop_last(), Some(3));
+        assert_tables_eq!(table, btreeset! { 2 });
+
+        table.write().clear();
+        assert_eq!(table.write().pop_first(), None);
+        assert_eq!(table.write().pop_last(), None);
+    }
+
+    #[test]
+    fn take_range() {
+        let table = sync::AsLock::new((0..10).collect());
+        assert_eq!(table.write().take_range(..3), vec![0, 1, 2]);
+        assert_eq!(table.write().take_range(5..=6), vec![5, 6]);
+        assert_eq!(table.write().take_range(20..), Vec::<i32>::new());
+        assert_tables_eq!(table, btreeset! { 3, 4, 7, 8, 9 });
+    }
+
     #[test]
     fn debug_str() {
         let table = sync::AsLock::<i32>::default();
@@ -417,3 +792,22 @@ mod sync_test {
         assert_eq!(format!("{:?}", table.read()), "{12}",);
     }
 }
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+    use maplit::*;
+
+    #[test]
+    fn insert() {
+        let table = arc::AsArcLock::<&str>::default();
+        {
+            let mut wg = table.write();
+            assert!(wg.insert("hello"));
+            assert!(wg.insert("world"));
+        }
+        assert_eq!(**table.read(), btreeset! { "hello", "world" });
+        assert!(table.write().remove("hello"));
+        assert_eq!(*table.write(), btreeset! { "world" });
+    }
+}
@@ -0,0 +1,472 @@
+use crate::UpdateTables;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+
+/// Map with a budget on the total cost of its values, for caches which are
+/// bounded by memory rather than by their number of entries. The cost of each
+/// value is given by `cost`, e.g. its size in bytes.
+///
+/// Inserting evicts the oldest entries until the total cost is back within
+/// the budget. Entries are ordered by when they were last inserted, so the
+/// eviction order only depends on the updates made to the map. This means that
+/// both tables evict the same entries.
+pub struct BudgetMap<K, V> {
+    entries: HashMap<K, Entry<V>>,
+
+    // Keys in eviction order, by when they were last inserted.
+    order: BTreeMap<u64, K>,
+    next_order: u64,
+
+    cost: fn(&V) -> usize,
+    total_cost: usize,
+    budget: usize,
+}
+
+#[derive(Clone)]
+struct Entry<V> {
+    value: V,
+    cost: usize,
+    order: u64,
+}
+
+impl<K, V> BudgetMap<K, V>
+where
+    K: Hash + Eq + Clone,
+{
+    pub fn new(budget: usize, cost: fn(&V) -> usize) -> BudgetMap<K, V> {
+        BudgetMap {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_order: 0,
+            cost,
+            total_cost: 0,
+            budget,
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sum of the costs of the values in the map.
+    pub fn total_cost(&self) -> usize {
+        self.total_cost
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Iterate over the entries in eviction order, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.order
+            .values()
+            .map(|key| (key, &self.entries[key].value))
+    }
+
+    /// Insert `value` as the newest entry, replacing any value already held
+    /// under `key`. Returns the entries evicted to get back within the budget,
+    /// oldest first. If `value` alone costs more than the budget, it is
+    /// evicted as well.
+    pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+        self.remove(&key);
+
+        let cost = (self.cost)(&value);
+        let order = self.next_order;
+        self.next_order += 1;
+        self.order.insert(order, key.clone());
+        self.entries.insert(key, Entry { value, cost, order });
+        self.total_cost += cost;
+        self.evict()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.order);
+        self.total_cost -= entry.cost;
+        Some(entry.value)
+    }
+
+    /// Change the budget, returning the entries evicted to get within it.
+    pub fn set_budget(&mut self, budget: usize) -> Vec<(K, V)> {
+        self.budget = budget;
+        self.evict()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.total_cost = 0;
+    }
+
+    fn evict(&mut self) -> Vec<(K, V)> {
+        let mut evicted = vec![];
+        while self.total_cost > self.budget {
+            let (_, key) = self
+                .order
+                .pop_first()
+                .expect("cost is only held by entries");
+            let entry = self.entries.remove(&key).unwrap();
+            self.total_cost -= entry.cost;
+            evicted.push((key, entry.value));
+        }
+        evicted
+    }
+}
+
+impl<K, V> Clone for BudgetMap<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> BudgetMap<K, V> {
+        BudgetMap {
+            entries: self.entries.clone(),
+            order: self.order.clone(),
+            next_order: self.next_order,
+            cost: self.cost,
+            total_cost: self.total_cost,
+            budget: self.budget,
+        }
+    }
+}
+
+/// Maps are equal if they have the same budget and hold the same entries in
+/// the same eviction order.
+impl<K, V> PartialEq for BudgetMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: PartialEq,
+{
+    fn eq(&self, other: &BudgetMap<K, V>) -> bool {
+        self.budget == other.budget && self.iter().eq(other.iter())
+    }
+}
+
+impl<K, V> fmt::Debug for BudgetMap<K, V>
+where
+    K: Hash + Eq + Clone + fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+struct Insert<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<'a, K, V> UpdateTables<'a, BudgetMap<K, V>, Vec<(K, V)>> for Insert<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut BudgetMap<K, V>) -> Vec<(K, V)> {
+        table.insert(self.key.clone(), self.value.clone())
+    }
+    fn apply_second(self, table: &mut BudgetMap<K, V>) {
+        // Move the values instead of cloning.
+        table.insert(self.key, self.value);
+    }
+}
+
+/// Implementation of BudgetMap for use in the active_standby model.
+/// `lockless::AsLockHandle`, should function similarly to
+/// `Arc<RwLock<BudgetMap<K, V>>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(BudgetMap<K, V>);
+
+    impl<'w, K, V> AsLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Hash + Eq + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        /// See `BudgetMap::insert`.
+        pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+            self.guard.update_tables(Insert { key, value })
+        }
+
+        pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&key_like))
+        }
+
+        /// See `BudgetMap::set_budget`.
+        pub fn set_budget(&mut self, budget: usize) -> Vec<(K, V)> {
+            self.guard
+                .update_tables_closure(move |table| table.set_budget(budget))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// Implementation of BudgetMap for use in the active_standby model.
+/// `sync::AsLock`, should function similarly to `RwLock<BudgetMap<K, V>>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(BudgetMap<K, V>);
+
+    impl<'w, K, V> AsLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Hash + Eq + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        /// See `BudgetMap::insert`.
+        pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+            self.guard.update_tables(Insert { key, value })
+        }
+
+        pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&key_like))
+        }
+
+        /// See `BudgetMap::set_budget`.
+        pub fn set_budget(&mut self, budget: usize) -> Vec<(K, V)> {
+            self.guard
+                .update_tables_closure(move |table| table.set_budget(budget))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(BudgetMap<K, V>);
+
+    impl<'w, K, V> AsArcLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Hash + Eq + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        /// See `BudgetMap::insert`.
+        pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+            self.guard.update_tables(Insert { key, value })
+        }
+
+        pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&key_like))
+        }
+
+        /// See `BudgetMap::set_budget`.
+        pub fn set_budget(&mut self, budget: usize) -> Vec<(K, V)> {
+            self.guard
+                .update_tables_closure(move |table| table.set_budget(budget))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+#[cfg(test)]
+mod budgetmap_test {
+    use super::*;
+
+    #[test]
+    fn evicts_oldest() {
+        let mut map = BudgetMap::new(10, String::len);
+        assert!(map.insert(1, "aaaa".to_string()).is_empty());
+        assert!(map.insert(2, "bbbb".to_string()).is_empty());
+        assert_eq!(map.total_cost(), 8);
+
+        // Reinserting makes the entry the newest.
+        assert!(map.insert(1, "aa".to_string()).is_empty());
+        assert_eq!(map.insert(3, "ccc".to_string()), vec![]);
+        assert_eq!(map.total_cost(), 9);
+        assert_eq!(
+            map.insert(4, "dddd".to_string()),
+            vec![(2, "bbbb".to_string())]
+        );
+        assert_eq!(
+            map.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            vec![1, 3, 4]
+        );
+        assert_eq!(map.total_cost(), 9);
+
+        // A value over the budget evicts everything, itself included.
+        assert_eq!(map.insert(5, "e".repeat(11)).len(), 4);
+        assert!(map.is_empty());
+        assert_eq!(map.total_cost(), 0);
+    }
+
+    #[test]
+    fn remove_and_set_budget() {
+        let mut map = BudgetMap::new(10, String::len);
+        map.insert("a", "aaa".to_string());
+        map.insert("b", "bbb".to_string());
+        map.insert("c", "ccc".to_string());
+        assert_eq!(map.remove("b"), Some("bbb".to_string()));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.total_cost(), 6);
+
+        assert_eq!(map.set_budget(3), vec![("a", "aaa".to_string())]);
+        assert_eq!(map.get("c"), Some(&"ccc".to_string()));
+        assert_eq!(map.budget(), 3);
+    }
+
+    #[test]
+    fn debug_str() {
+        let mut map = BudgetMap::new(10, |value: &i32| *value as usize);
+        map.insert("b", 2);
+        map.insert("a", 1);
+        assert_eq!(format!("{:?}", map), r#"{"b": 2, "a": 1}"#);
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn insert() {
+        let table = lockless::AsLockHandle::new(BudgetMap::new(6, String::len));
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            assert!(wg.insert(1, "aaa".to_string()).is_empty());
+            assert!(wg.insert(2, "bbb".to_string()).is_empty());
+            assert_eq!(wg.insert(3, "c".to_string()), vec![(1, "aaa".to_string())]);
+            assert!(table2.read().is_empty());
+        }
+
+        let mut expected = BudgetMap::new(6, String::len);
+        expected.insert(2, "bbb".to_string());
+        expected.insert(3, "c".to_string());
+        assert_tables_eq!(table, expected);
+    }
+
+    #[test]
+    fn remove_and_set_budget() {
+        let table = lockless::AsLockHandle::new(BudgetMap::new(10, String::len));
+        {
+            let mut wg = table.write();
+            wg.insert("a", "aaa".to_string());
+            wg.insert("b", "bbb".to_string());
+            wg.insert("c", "ccc".to_string());
+            assert_eq!(wg.remove("b"), Some("bbb".to_string()));
+            assert_eq!(wg.set_budget(3), vec![("a", "aaa".to_string())]);
+        }
+
+        let mut expected = BudgetMap::new(3, String::len);
+        expected.insert("c", "ccc".to_string());
+        assert_tables_eq!(table, expected);
+
+        table.write().clear();
+        assert_tables_eq!(table, BudgetMap::new(3, String::len));
+        assert_eq!(table.read().total_cost(), 0);
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn insert() {
+        let table = std::sync::Arc::new(sync::AsLock::new(BudgetMap::new(6, String::len)));
+        {
+            let mut wg = table.write();
+            assert!(wg.insert(1, "aaa".to_string()).is_empty());
+            assert!(wg.insert(2, "bbb".to_string()).is_empty());
+            assert_eq!(wg.insert(3, "c".to_string()), vec![(1, "aaa".to_string())]);
+        }
+
+        let mut expected = BudgetMap::new(6, String::len);
+        expected.insert(2, "bbb".to_string());
+        expected.insert(3, "c".to_string());
+        assert_tables_eq!(table, expected);
+    }
+
+    #[test]
+    fn remove_and_set_budget() {
+        let table = sync::AsLock::new(BudgetMap::new(10, String::len));
+        {
+            let mut wg = table.write();
+            wg.insert("a", "aaa".to_string());
+            wg.insert("b", "bbb".to_string());
+            wg.insert("c", "ccc".to_string());
+            assert_eq!(wg.remove("b"), Some("bbb".to_string()));
+            assert_eq!(wg.set_budget(3), vec![("a", "aaa".to_string())]);
+        }
+
+        let mut expected = BudgetMap::new(3, String::len);
+        expected.insert("c", "ccc".to_string());
+        assert_tables_eq!(table, expected);
+
+        table.write().clear();
+        assert_tables_eq!(table, BudgetMap::new(3, String::len));
+        assert_eq!(table.read().total_cost(), 0);
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let table = arc::AsArcLock::new(BudgetMap::new(6, String::len));
+        {
+            let mut wg = table.write();
+            assert!(wg.insert(1, "aaa".to_string()).is_empty());
+            assert!(wg.insert(2, "bbb".to_string()).is_empty());
+            assert_eq!(wg.insert(3, "c".to_string()), vec![(1, "aaa".to_string())]);
+        }
+        assert_eq!(table.read().total_cost(), 4);
+        assert_eq!(table.write().total_cost(), 4);
+    }
+}
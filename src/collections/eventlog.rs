@@ -0,0 +1,352 @@
+use crate::UpdateTables;
+use std::collections::VecDeque;
+
+/// Append only log of events, each numbered by a sequence number. Sequence
+/// numbers start at 0 and increase by 1 with each event pushed, and are never
+/// reused, even once the event has been dropped from the log.
+///
+/// Consumers keep track of the next sequence number they haven't seen, and
+/// poll for the events from it on with `events_since`.
+///
+/// The log can be bounded with `with_max_len`, in which case pushing drops the
+/// oldest events beyond it, or trimmed explicitly with `retain_since`. The
+/// sequence numbers are assigned by the log itself, so both tables number the
+/// events identically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLog<E> {
+    events: VecDeque<E>,
+
+    // Sequence number of the front of `events`.
+    first_seq: u64,
+
+    max_len: Option<usize>,
+}
+
+impl<E> EventLog<E> {
+    pub fn new() -> EventLog<E> {
+        EventLog {
+            events: VecDeque::new(),
+            first_seq: 0,
+            max_len: None,
+        }
+    }
+
+    /// Keep at most `max_len` events, dropping the oldest ones on `push`.
+    pub fn with_max_len(mut self, max_len: usize) -> EventLog<E> {
+        self.max_len = Some(max_len);
+        self.trim();
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Sequence number of the oldest event in the log. If the log is empty,
+    /// this is `next_seq`.
+    pub fn first_seq(&self) -> u64 {
+        self.first_seq
+    }
+
+    /// Sequence number which the next event pushed will get.
+    pub fn next_seq(&self) -> u64 {
+        self.first_seq + self.events.len() as u64
+    }
+
+    /// The event numbered `seq`, unless it was dropped or not pushed yet.
+    pub fn get(&self, seq: u64) -> Option<&E> {
+        let index = seq.checked_sub(self.first_seq)?;
+        self.events.get(usize::try_from(index).ok()?)
+    }
+
+    /// The events from `seq` on, paired with their sequence numbers. Starts at
+    /// the oldest event if those from `seq` have already been dropped.
+    pub fn iter_since(&self, seq: u64) -> impl Iterator<Item = (u64, &E)> {
+        let skip = seq.saturating_sub(self.first_seq);
+        let skip = usize::try_from(skip).unwrap_or(usize::MAX);
+        (self.first_seq..).zip(self.events.iter()).skip(skip)
+    }
+
+    /// Clones of the events from `seq` on. Starts at the oldest event if
+    /// those from `seq` have already been dropped, which consumers can detect
+    /// with `first_seq`.
+    pub fn events_since(&self, seq: u64) -> Vec<E>
+    where
+        E: Clone,
+    {
+        self.iter_since(seq)
+            .map(|(_, event)| event.clone())
+            .collect()
+    }
+
+    /// Append `event`, returning its sequence number.
+    pub fn push(&mut self, event: E) -> u64 {
+        let seq = self.next_seq();
+        self.events.push_back(event);
+        self.trim();
+        seq
+    }
+
+    /// Drop the events before `seq`.
+    pub fn retain_since(&mut self, seq: u64) {
+        let seq = seq.min(self.next_seq());
+        while self.first_seq < seq {
+            self.events.pop_front();
+            self.first_seq += 1;
+        }
+    }
+
+    /// Drop all of the events. Sequence numbers keep counting from where they
+    /// were.
+    pub fn clear(&mut self) {
+        self.retain_since(self.next_seq());
+    }
+
+    fn trim(&mut self) {
+        if let Some(max_len) = self.max_len {
+            let excess = self.events.len().saturating_sub(max_len);
+            self.retain_since(self.first_seq + excess as u64);
+        }
+    }
+}
+
+impl<E> Default for EventLog<E> {
+    fn default() -> EventLog<E> {
+        EventLog::new()
+    }
+}
+
+struct Push<E> {
+    event: E,
+}
+
+impl<'a, E> UpdateTables<'a, EventLog<E>, u64> for Push<E>
+where
+    E: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut EventLog<E>) -> u64 {
+        table.push(self.event.clone())
+    }
+    fn apply_second(self, table: &mut EventLog<E>) {
+        // Move the event instead of cloning.
+        table.push(self.event);
+    }
+}
+
+/// Implementation of EventLog for use in the active_standby model.
+/// `lockless::AsLockHandle`, should function similarly to
+/// `Arc<RwLock<EventLog<E>>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(EventLog<E>);
+
+    impl<'w, E> AsLockWriteGuard<'w, E>
+    where
+        E: 'static + Clone + Send,
+    {
+        /// See `EventLog::push`.
+        pub fn push(&mut self, event: E) -> u64 {
+            self.guard.update_tables(Push { event })
+        }
+
+        /// See `EventLog::retain_since`.
+        pub fn retain_since(&mut self, seq: u64) {
+            self.guard
+                .update_tables_closure(move |table| table.retain_since(seq))
+        }
+
+        /// See `EventLog::clear`.
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// Implementation of EventLog for use in the active_standby model.
+/// `sync::AsLock`, should function similarly to `RwLock<EventLog<E>>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(EventLog<E>);
+
+    impl<'w, E> AsLockWriteGuard<'w, E>
+    where
+        E: 'static + Clone + Send,
+    {
+        /// See `EventLog::push`.
+        pub fn push(&mut self, event: E) -> u64 {
+            self.guard.update_tables(Push { event })
+        }
+
+        /// See `EventLog::retain_since`.
+        pub fn retain_since(&mut self, seq: u64) {
+            self.guard
+                .update_tables_closure(move |table| table.retain_since(seq))
+        }
+
+        /// See `EventLog::clear`.
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(EventLog<E>);
+
+    impl<'w, E> AsArcLockWriteGuard<'w, E>
+    where
+        E: 'static + Clone + Send,
+    {
+        /// See `EventLog::push`.
+        pub fn push(&mut self, event: E) -> u64 {
+            self.guard.update_tables(Push { event })
+        }
+
+        /// See `EventLog::retain_since`.
+        pub fn retain_since(&mut self, seq: u64) {
+            self.guard
+                .update_tables_closure(move |table| table.retain_since(seq))
+        }
+
+        /// See `EventLog::clear`.
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+#[cfg(test)]
+mod eventlog_test {
+    use super::*;
+
+    #[test]
+    fn sequence_numbers() {
+        let mut log = EventLog::new();
+        assert_eq!(log.push("a"), 0);
+        assert_eq!(log.push("b"), 1);
+        assert_eq!(log.push("c"), 2);
+        assert_eq!(log.get(1), Some(&"b"));
+        assert_eq!(log.events_since(1), ["b", "c"]);
+        assert_eq!(log.events_since(3), Vec::<&str>::new());
+
+        log.retain_since(2);
+        assert_eq!((log.first_seq(), log.next_seq()), (2, 3));
+        assert_eq!(log.get(1), None);
+        // Dropped events are skipped.
+        assert_eq!(log.events_since(0), ["c"]);
+
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(log.push("d"), 3);
+        assert_eq!(log.iter_since(0).collect::<Vec<_>>(), [(3, &"d")]);
+    }
+
+    #[test]
+    fn max_len() {
+        let mut log = EventLog::new().with_max_len(2);
+        for event in 0..5 {
+            log.push(event);
+        }
+        assert_eq!(log.first_seq(), 3);
+        assert_eq!(log.events_since(0), [3, 4]);
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn push_and_retain() {
+        let table = lockless::AsLockHandle::new(EventLog::new().with_max_len(3));
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.push("a"), 0);
+            assert_eq!(wg.push("b"), 1);
+            assert!(table2.read().is_empty());
+        }
+        assert_eq!(table2.read().events_since(1), ["b"]);
+
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.push("c"), 2);
+            assert_eq!(wg.push("d"), 3);
+            wg.retain_since(2);
+        }
+        let mut expected = EventLog::new().with_max_len(3);
+        for event in ["a", "b", "c", "d"] {
+            expected.push(event);
+        }
+        expected.retain_since(2);
+        assert_tables_eq!(table, expected);
+
+        table.write().clear();
+        expected.clear();
+        assert_tables_eq!(table, expected);
+        assert_eq!(table.read().next_seq(), 4);
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn push_and_retain() {
+        let table = sync::AsLock::new(EventLog::new().with_max_len(3));
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.push("a"), 0);
+            assert_eq!(wg.push("b"), 1);
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(table.read().events_since(1), ["b"]);
+
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.push("c"), 2);
+            assert_eq!(wg.push("d"), 3);
+            wg.retain_since(2);
+        }
+        let mut expected = EventLog::new().with_max_len(3);
+        for event in ["a", "b", "c", "d"] {
+            expected.push(event);
+        }
+        expected.retain_since(2);
+        assert_tables_eq!(table, expected);
+
+        table.write().clear();
+        expected.clear();
+        assert_tables_eq!(table, expected);
+        assert_eq!(table.read().next_seq(), 4);
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn push() {
+        let table = arc::AsArcLock::new(EventLog::new());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.push("a"), 0);
+            assert_eq!(wg.push("b"), 1);
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(table.read().events_since(1), ["b"]);
+        assert_eq!(table.write().push("c"), 2);
+        assert_eq!(table.read().events_since(1), ["b", "c"]);
+    }
+}
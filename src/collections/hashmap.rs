@@ -1,4 +1,6 @@
+use crate::collections::{OccupiedError, Prng};
 use crate::UpdateTables;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
 use std::hash::Hash;
 
@@ -21,6 +23,113 @@ where
     }
 }
 
+struct InsertAll<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+impl<'a, K, V> UpdateTables<'a, HashMap<K, V>, ()> for InsertAll<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut HashMap<K, V>) {
+        table.extend(self.entries.iter().cloned());
+    }
+    fn apply_second(self, table: &mut HashMap<K, V>) {
+        // Move the entries instead of cloning.
+        table.extend(self.entries);
+    }
+}
+
+struct GetOrInsert<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<'a, K, V> UpdateTables<'a, HashMap<K, V>, &'a V> for GetOrInsert<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut HashMap<K, V>) -> &'a V {
+        table
+            .entry(self.key.clone())
+            .or_insert_with(|| self.value.clone())
+    }
+    fn apply_second(self, table: &mut HashMap<K, V>) {
+        // Move the value instead of cloning.
+        table.entry(self.key).or_insert(self.value);
+    }
+}
+
+struct GetMutWith<Q, F> {
+    key_like: Q,
+    f: F,
+}
+
+impl<'a, K, V, Q, F> UpdateTables<'a, HashMap<K, V>, Option<&'a V>> for GetMutWith<Q, F>
+where
+    K: Eq + Hash + std::borrow::Borrow<Q>,
+    Q: Eq + Hash,
+    F: Clone + FnMut(&mut V),
+{
+    fn apply_first(&mut self, table: &'a mut HashMap<K, V>) -> Option<&'a V> {
+        let value = table.get_mut(&self.key_like)?;
+        (self.f.clone())(value);
+        Some(value)
+    }
+    fn apply_second(mut self, table: &mut HashMap<K, V>) {
+        if let Some(value) = table.get_mut(&self.key_like) {
+            (self.f)(value);
+        }
+    }
+}
+
+struct Upsert<K, V, F> {
+    key: K,
+    modify: Option<F>,
+    // Only `None` if the key is known to be in the table.
+    default: Option<V>,
+}
+
+impl<'a, K, V, F> UpdateTables<'a, HashMap<K, V>, &'a V> for Upsert<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Clone + FnMut(&mut V),
+{
+    fn apply_first(&mut self, table: &'a mut HashMap<K, V>) -> &'a V {
+        upsert(table, self.key.clone(), self.modify.clone(), || {
+            self.default.clone()
+        })
+    }
+    fn apply_second(self, table: &mut HashMap<K, V>) {
+        // Move the value instead of cloning.
+        upsert(table, self.key, self.modify, || self.default);
+    }
+}
+
+fn upsert<K: Eq + Hash, V, F: FnMut(&mut V)>(
+    table: &mut HashMap<K, V>,
+    key: K,
+    modify: Option<F>,
+    default: impl FnOnce() -> Option<V>,
+) -> &V {
+    use std::collections::hash_map::Entry;
+    match table.entry(key) {
+        Entry::Occupied(entry) => {
+            let value = entry.into_mut();
+            if let Some(mut modify) = modify {
+                modify(value);
+            }
+            value
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(default().expect("the key was checked to be in the table"))
+        }
+    }
+}
+
 struct Drain {}
 
 impl<'a, K, V> UpdateTables<'a, HashMap<K, V>, std::collections::hash_map::Drain<'a, K, V>>
@@ -44,6 +153,8 @@ where
     F: 'static + Clone + FnMut(&K, &mut V) -> bool,
 {
     f: F,
+    // Whether to also shrink the table, for `retain_and_shrink`.
+    shrink: bool,
     _compile_k_v: std::marker::PhantomData<(K, V)>,
 }
 impl<'a, K, V, F> UpdateTables<'a, HashMap<K, V>, ()> for Retain<K, V, F>
@@ -52,10 +163,80 @@ where
     F: 'static + Clone + FnMut(&K, &mut V) -> bool,
 {
     fn apply_first(&mut self, table: &'a mut HashMap<K, V>) {
-        table.retain(self.f.clone())
+        table.retain(self.f.clone());
+        if self.shrink {
+            table.shrink_to_fit();
+        }
+    }
+    fn apply_second(self, table: &mut HashMap<K, V>) {
+        table.retain(self.f);
+        if self.shrink {
+            table.shrink_to_fit();
+        }
+    }
+}
+
+struct ExtractIf<F> {
+    f: F,
+}
+
+impl<'a, K, V, F> UpdateTables<'a, HashMap<K, V>, Vec<(K, V)>> for ExtractIf<F>
+where
+    K: Eq + Hash,
+    F: Clone + FnMut(&K, &mut V) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut HashMap<K, V>) -> Vec<(K, V)> {
+        table.extract_if(self.f.clone()).collect()
+    }
+    fn apply_second(mut self, table: &mut HashMap<K, V>) {
+        table.retain(|k, v| !(self.f)(k, v))
+    }
+}
+
+struct RandomEvict<K> {
+    n: usize,
+    seed: u64,
+    evicted: Vec<K>,
+}
+
+impl<'a, K, V> UpdateTables<'a, HashMap<K, V>, Vec<(K, V)>> for RandomEvict<K>
+where
+    K: Eq + Hash + Clone,
+{
+    fn apply_first(&mut self, table: &'a mut HashMap<K, V>) -> Vec<(K, V)> {
+        let mut evict = Prng::new(self.seed).sample(table.len(), self.n);
+        let evicted: Vec<(K, V)> = table.extract_if(|_, _| evict()).collect();
+        self.evicted = evicted.iter().map(|(key, _)| key.clone()).collect();
+        evicted
     }
     fn apply_second(self, table: &mut HashMap<K, V>) {
-        table.retain(self.f)
+        // The tables don't necessarily iterate in the same order, so remove
+        // the keys picked from the first table, rather than sampling again.
+        for key in &self.evicted {
+            table.remove(key);
+        }
+    }
+}
+
+/// Shared by lockless & sync `read_chunks`.
+fn read_chunks<G, K, V>(read: impl Fn() -> G, chunk_size: usize, mut f: impl FnMut(&[(&K, &V)]))
+where
+    G: std::ops::Deref<Target = HashMap<K, V>>,
+    K: Eq + Hash + Clone,
+{
+    assert_ne!(chunk_size, 0, "chunk_size must be positive");
+    // HashMap iteration can't be resumed across tables, so snapshot the keys,
+    // and look each chunk of them up in the current table.
+    let keys: Vec<K> = read().keys().cloned().collect();
+    for keys in keys.chunks(chunk_size) {
+        let table = read();
+        let chunk: Vec<_> = keys
+            .iter()
+            .filter_map(|key| table.get_key_value(key))
+            .collect();
+        if !chunk.is_empty() {
+            f(&chunk);
+        }
     }
 }
 
@@ -66,6 +247,53 @@ pub mod lockless {
     use super::*;
     crate::generate_lockless_aslockhandle!(HashMap<K, V>);
 
+    impl<K, V> AsLockHandle<K, V> {
+        /// Create an `AsLockHandle` where both tables have space for `capacity`
+        /// entries. Both tables use a clone of `hasher`, so they also iterate in
+        /// the same order.
+        pub fn with_capacity_and_hasher(
+            capacity: usize,
+            hasher: RandomState,
+        ) -> AsLockHandle<K, V> {
+            AsLockHandle::from_identical(
+                HashMap::with_capacity_and_hasher(capacity, hasher.clone()),
+                HashMap::with_capacity_and_hasher(capacity, hasher),
+            )
+        }
+
+        /// Call `f` on consecutive chunks of at most `chunk_size` entries, taking
+        /// a new read guard for each chunk. This bounds how long a full scan
+        /// holds onto a single guard, so that it never blocks the writer for
+        /// the duration of the scan.
+        ///
+        /// The keys are collected when the scan starts, and each chunk shows
+        /// their latest values. Entries removed between chunks are skipped,
+        /// and entries inserted after the scan starts are not seen.
+        pub fn read_chunks(&self, chunk_size: usize, f: impl FnMut(&[(&K, &V)]))
+        where
+            K: Eq + Hash + Clone,
+        {
+            read_chunks(move || self.read(), chunk_size, f)
+        }
+
+        /// Clone the values of `keys` out of the active table, all under a
+        /// single read guard, so they are consistent with each other.
+        pub fn get_many_cloned<'k, Q>(
+            &self,
+            keys: impl IntoIterator<Item = &'k Q>,
+        ) -> Vec<Option<V>>
+        where
+            K: Eq + Hash + std::borrow::Borrow<Q>,
+            V: Clone,
+            Q: 'k + Eq + Hash + ?Sized,
+        {
+            let table = self.read();
+            keys.into_iter()
+                .map(|key| table.get(key).cloned())
+                .collect()
+        }
+    }
+
     impl<'w, 'a, K, V> AsLockWriteGuard<'w, K, V>
     where
         K: 'static + Eq + Hash + Clone + Send,
@@ -75,10 +303,55 @@ pub mod lockless {
             self.guard.update_tables(Insert { key, value })
         }
 
+        /// Insert `value` for `key`, unless `key` already maps to an equal
+        /// value, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        pub fn insert_if_different(&mut self, key: K, value: V) -> bool
+        where
+            V: PartialEq,
+        {
+            if self.get(&key) == Some(&value) {
+                return false;
+            }
+            self.insert(key, value);
+            true
+        }
+
+        /// Insert `value` for `key` only if `key` isn't in the table yet.
+        /// Otherwise no update is recorded, and the key and value are handed
+        /// back in the error.
+        pub fn try_insert(&mut self, key: K, value: V) -> Result<(), OccupiedError<K, V>> {
+            if self.contains_key(&key) {
+                return Err(OccupiedError { key, value });
+            }
+            self.insert(key, value);
+            Ok(())
+        }
+
+        /// Returns the entry for `key`, to update it in place like
+        /// `HashMap::entry`.
+        pub fn entry(&mut self, key: K) -> Entry<'_, 'w, K, V> {
+            Entry {
+                guard: self,
+                key,
+                modify: None,
+            }
+        }
+
         pub fn clear(&mut self) {
             self.guard.update_tables_closure(move |table| table.clear())
         }
 
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
         pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
         where
             K: std::borrow::Borrow<Q>,
@@ -107,6 +380,38 @@ pub mod lockless {
                 .update_tables_closure(move |table| table.shrink_to_fit())
         }
 
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        /// Returns the value for `key`, inserting the value returned by `f`
+        /// if `key` isn't in the table yet. Like
+        /// `entry(key).or_insert_with(f)`, but `f` is only called once, and
+        /// no update is recorded if `key` is already present.
+        pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+            if self.contains_key(&key) {
+                return self.get(&key).unwrap();
+            }
+            self.guard.update_tables(GetOrInsert { key, value: f() })
+        }
+
+        /// Call `f` on the value for `key_like` and return the updated value,
+        /// or `None`, without recording an update, if the key isn't in the
+        /// table. `f` is called on both tables, so it must make the same change
+        /// each time.
+        pub fn get_mut_with<Q, F>(&mut self, key_like: Q, f: F) -> Option<&V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+            F: 'static + Send + Clone + FnMut(&mut V),
+        {
+            if !self.contains_key(&key_like) {
+                return None;
+            }
+            self.guard.update_tables(GetMutWith { key_like, f })
+        }
+
         pub fn drain(&'a mut self) -> std::collections::hash_map::Drain<'a, K, V> {
             self.guard.update_tables(Drain {})
         }
@@ -117,9 +422,139 @@ pub mod lockless {
         {
             self.guard.update_tables(Retain {
                 f,
+                shrink: false,
                 _compile_k_v: std::marker::PhantomData,
             })
         }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: true,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Removes the entries for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same entries
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed entries instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.extract_if(move |k, v| !f(k, v))
+        }
+
+        /// Removes `n` entries picked at random, or all of them if the table is
+        /// smaller, and returns them. The entries are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the entries picked for a given seed depend on the order the table
+        /// iterates in.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<(K, V)> {
+            self.guard.update_tables(RandomEvict {
+                n,
+                seed,
+                evicted: vec![],
+            })
+        }
+    }
+
+    /// A view into a single entry of the table, built by
+    /// `AsLockWriteGuard::entry`. Only `or_insert`, `or_insert_with` and
+    /// `or_default` update the table. They record a single update, which
+    /// applies the closures passed to `and_modify` identically on both tables.
+    #[must_use = "the table is only updated by or_insert, or_insert_with or or_default"]
+    pub struct Entry<'g, 'w, K, V, F = fn(&mut V)> {
+        guard: &'g mut AsLockWriteGuard<'w, K, V>,
+        key: K,
+        modify: Option<F>,
+    }
+
+    impl<'g, 'w, K, V, F> Entry<'g, 'w, K, V, F>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+        F: 'static + Send + Clone + FnMut(&mut V),
+    {
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Modify the value if the key is in the table. `f` is called on both
+        /// tables, so it must make the same change each time.
+        pub fn and_modify<G>(
+            self,
+            mut f: G,
+        ) -> Entry<'g, 'w, K, V, impl 'static + Send + Clone + FnMut(&mut V)>
+        where
+            G: 'static + Send + Clone + FnMut(&mut V),
+        {
+            let mut modify = self.modify;
+            Entry {
+                guard: self.guard,
+                key: self.key,
+                modify: Some(move |value: &mut V| {
+                    if let Some(modify) = &mut modify {
+                        modify(value);
+                    }
+                    f(value);
+                }),
+            }
+        }
+
+        /// Insert `default` if the key isn't in the table, and return the
+        /// value in the table.
+        pub fn or_insert(self, default: V) -> &'g V {
+            self.or_insert_with(move || default)
+        }
+
+        /// Like `or_insert`, but `default` is only called if the key isn't in
+        /// the table. It is called once, and the value is cloned into the
+        /// first table.
+        pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'g V {
+            let default = match self.guard.contains_key(&self.key) {
+                true => None,
+                false => Some(default()),
+            };
+            self.guard.update_tables(Upsert {
+                key: self.key,
+                modify: self.modify,
+                default,
+            })
+        }
+
+        pub fn or_default(self) -> &'g V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+    }
+
+    impl<'w, K, V> Extend<(K, V)> for AsLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+            self.guard.update_tables(InsertAll {
+                entries: iter.into_iter().collect(),
+            })
+        }
     }
 }
 
@@ -130,6 +565,50 @@ pub mod sync {
     use super::*;
     crate::generate_sync_aslock!(HashMap<K, V>);
 
+    impl<K, V> AsLock<K, V> {
+        /// Create an `AsLock` where both tables have space for `capacity`
+        /// entries. Both tables use a clone of `hasher`, so they also iterate in
+        /// the same order.
+        pub fn with_capacity_and_hasher(capacity: usize, hasher: RandomState) -> AsLock<K, V> {
+            AsLock::from_identical(
+                HashMap::with_capacity_and_hasher(capacity, hasher.clone()),
+                HashMap::with_capacity_and_hasher(capacity, hasher),
+            )
+        }
+
+        /// Call `f` on consecutive chunks of at most `chunk_size` entries, taking
+        /// a new read guard for each chunk. This bounds how long a full scan
+        /// holds onto a single guard, so that it never blocks the writer for
+        /// the duration of the scan.
+        ///
+        /// The keys are collected when the scan starts, and each chunk shows
+        /// their latest values. Entries removed between chunks are skipped,
+        /// and entries inserted after the scan starts are not seen.
+        pub fn read_chunks(&self, chunk_size: usize, f: impl FnMut(&[(&K, &V)]))
+        where
+            K: Eq + Hash + Clone,
+        {
+            read_chunks(move || self.read(), chunk_size, f)
+        }
+
+        /// Clone the values of `keys` out of the active table, all under a
+        /// single read guard, so they are consistent with each other.
+        pub fn get_many_cloned<'k, Q>(
+            &self,
+            keys: impl IntoIterator<Item = &'k Q>,
+        ) -> Vec<Option<V>>
+        where
+            K: Eq + Hash + std::borrow::Borrow<Q>,
+            V: Clone,
+            Q: 'k + Eq + Hash + ?Sized,
+        {
+            let table = self.read();
+            keys.into_iter()
+                .map(|key| table.get(key).cloned())
+                .collect()
+        }
+    }
+
     impl<'w, 'a, K, V> AsLockWriteGuard<'w, K, V>
     where
         K: 'static + Eq + Hash + Clone + Send,
@@ -139,10 +618,325 @@ pub mod sync {
             self.guard.update_tables(Insert { key, value })
         }
 
+        /// Insert `value` for `key`, unless `key` already maps to an equal
+        /// value, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        pub fn insert_if_different(&mut self, key: K, value: V) -> bool
+        where
+            V: PartialEq,
+        {
+            if self.get(&key) == Some(&value) {
+                return false;
+            }
+            self.insert(key, value);
+            true
+        }
+
+        /// Insert `value` for `key` only if `key` isn't in the table yet.
+        /// Otherwise no update is recorded, and the key and value are handed
+        /// back in the error.
+        pub fn try_insert(&mut self, key: K, value: V) -> Result<(), OccupiedError<K, V>> {
+            if self.contains_key(&key) {
+                return Err(OccupiedError { key, value });
+            }
+            self.insert(key, value);
+            Ok(())
+        }
+
+        /// Returns the entry for `key`, to update it in place like
+        /// `HashMap::entry`.
+        pub fn entry(&mut self, key: K) -> Entry<'_, 'w, K, V> {
+            Entry {
+                guard: self,
+                key,
+                modify: None,
+            }
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(move |table| table.clear())
+        }
+
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
+        pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&key_like))
+        }
+
+        pub fn remove_entry<Q>(&mut self, key_like: Q) -> Option<(K, V)>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove_entry(&key_like))
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        /// Returns the value for `key`, inserting the value returned by `f`
+        /// if `key` isn't in the table yet. Like
+        /// `entry(key).or_insert_with(f)`, but `f` is only called once, and
+        /// no update is recorded if `key` is already present.
+        pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+            if self.contains_key(&key) {
+                return self.get(&key).unwrap();
+            }
+            self.guard.update_tables(GetOrInsert { key, value: f() })
+        }
+
+        /// Call `f` on the value for `key_like` and return the updated value,
+        /// or `None`, without recording an update, if the key isn't in the
+        /// table. `f` is called on both tables, so it must make the same change
+        /// each time.
+        pub fn get_mut_with<Q, F>(&mut self, key_like: Q, f: F) -> Option<&V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+            F: 'static + Send + Clone + FnMut(&mut V),
+        {
+            if !self.contains_key(&key_like) {
+                return None;
+            }
+            self.guard.update_tables(GetMutWith { key_like, f })
+        }
+
+        pub fn drain(&'a mut self) -> std::collections::hash_map::Drain<'a, K, V> {
+            self.guard.update_tables(Drain {})
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: false,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: true,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Removes the entries for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same entries
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed entries instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.extract_if(move |k, v| !f(k, v))
+        }
+
+        /// Removes `n` entries picked at random, or all of them if the table is
+        /// smaller, and returns them. The entries are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the entries picked for a given seed depend on the order the table
+        /// iterates in.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<(K, V)> {
+            self.guard.update_tables(RandomEvict {
+                n,
+                seed,
+                evicted: vec![],
+            })
+        }
+    }
+
+    /// A view into a single entry of the table, built by
+    /// `AsLockWriteGuard::entry`. Only `or_insert`, `or_insert_with` and
+    /// `or_default` update the table. They record a single update, which
+    /// applies the closures passed to `and_modify` identically on both tables.
+    #[must_use = "the table is only updated by or_insert, or_insert_with or or_default"]
+    pub struct Entry<'g, 'w, K, V, F = fn(&mut V)> {
+        guard: &'g mut AsLockWriteGuard<'w, K, V>,
+        key: K,
+        modify: Option<F>,
+    }
+
+    impl<'g, 'w, K, V, F> Entry<'g, 'w, K, V, F>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+        F: 'static + Send + Clone + FnMut(&mut V),
+    {
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Modify the value if the key is in the table. `f` is called on both
+        /// tables, so it must make the same change each time.
+        pub fn and_modify<G>(
+            self,
+            mut f: G,
+        ) -> Entry<'g, 'w, K, V, impl 'static + Send + Clone + FnMut(&mut V)>
+        where
+            G: 'static + Send + Clone + FnMut(&mut V),
+        {
+            let mut modify = self.modify;
+            Entry {
+                guard: self.guard,
+                key: self.key,
+                modify: Some(move |value: &mut V| {
+                    if let Some(modify) = &mut modify {
+                        modify(value);
+                    }
+                    f(value);
+                }),
+            }
+        }
+
+        /// Insert `default` if the key isn't in the table, and return the
+        /// value in the table.
+        pub fn or_insert(self, default: V) -> &'g V {
+            self.or_insert_with(move || default)
+        }
+
+        /// Like `or_insert`, but `default` is only called if the key isn't in
+        /// the table. It is called once, and the value is cloned into the
+        /// first table.
+        pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'g V {
+            let default = match self.guard.contains_key(&self.key) {
+                true => None,
+                false => Some(default()),
+            };
+            self.guard.update_tables(Upsert {
+                key: self.key,
+                modify: self.modify,
+                default,
+            })
+        }
+
+        pub fn or_default(self) -> &'g V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+    }
+
+    impl<'w, K, V> Extend<(K, V)> for AsLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+            self.guard.update_tables(InsertAll {
+                entries: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(HashMap<K, V>);
+
+    impl<'w, 'a, K, V> AsArcLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+            self.guard.update_tables(Insert { key, value })
+        }
+
+        /// Insert `value` for `key`, unless `key` already maps to an equal
+        /// value, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        pub fn insert_if_different(&mut self, key: K, value: V) -> bool
+        where
+            V: PartialEq,
+        {
+            if self.get(&key) == Some(&value) {
+                return false;
+            }
+            self.insert(key, value);
+            true
+        }
+
+        /// Insert `value` for `key` only if `key` isn't in the table yet.
+        /// Otherwise no update is recorded, and the key and value are handed
+        /// back in the error.
+        pub fn try_insert(&mut self, key: K, value: V) -> Result<(), OccupiedError<K, V>> {
+            if self.contains_key(&key) {
+                return Err(OccupiedError { key, value });
+            }
+            self.insert(key, value);
+            Ok(())
+        }
+
+        /// Returns the entry for `key`, to update it in place like
+        /// `HashMap::entry`.
+        pub fn entry(&mut self, key: K) -> Entry<'_, 'w, K, V> {
+            Entry {
+                guard: self,
+                key,
+                modify: None,
+            }
+        }
+
         pub fn clear(&mut self) {
             self.guard.update_tables_closure(move |table| table.clear())
         }
 
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
         pub fn remove<Q>(&mut self, key_like: Q) -> Option<V>
         where
             K: std::borrow::Borrow<Q>,
@@ -171,6 +965,38 @@ pub mod sync {
                 .update_tables_closure(move |table| table.shrink_to_fit())
         }
 
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        /// Returns the value for `key`, inserting the value returned by `f`
+        /// if `key` isn't in the table yet. Like
+        /// `entry(key).or_insert_with(f)`, but `f` is only called once, and
+        /// no update is recorded if `key` is already present.
+        pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+            if self.contains_key(&key) {
+                return self.get(&key).unwrap();
+            }
+            self.guard.update_tables(GetOrInsert { key, value: f() })
+        }
+
+        /// Call `f` on the value for `key_like` and return the updated value,
+        /// or `None`, without recording an update, if the key isn't in the
+        /// table. `f` is called on both tables, so it must make the same change
+        /// each time.
+        pub fn get_mut_with<Q, F>(&mut self, key_like: Q, f: F) -> Option<&V>
+        where
+            K: std::borrow::Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+            F: 'static + Send + Clone + FnMut(&mut V),
+        {
+            if !self.contains_key(&key_like) {
+                return None;
+            }
+            self.guard.update_tables(GetMutWith { key_like, f })
+        }
+
         pub fn drain(&'a mut self) -> std::collections::hash_map::Drain<'a, K, V> {
             self.guard.update_tables(Drain {})
         }
@@ -181,9 +1007,139 @@ pub mod sync {
         {
             self.guard.update_tables(Retain {
                 f,
+                shrink: false,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: true,
                 _compile_k_v: std::marker::PhantomData,
             })
         }
+
+        /// Removes the entries for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same entries
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed entries instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<(K, V)>
+        where
+            F: 'static + Send + Clone + FnMut(&K, &mut V) -> bool,
+        {
+            self.extract_if(move |k, v| !f(k, v))
+        }
+
+        /// Removes `n` entries picked at random, or all of them if the table is
+        /// smaller, and returns them. The entries are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the entries picked for a given seed depend on the order the table
+        /// iterates in.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<(K, V)> {
+            self.guard.update_tables(RandomEvict {
+                n,
+                seed,
+                evicted: vec![],
+            })
+        }
+    }
+
+    /// A view into a single entry of the table, built by
+    /// `AsArcLockWriteGuard::entry`. Only `or_insert`, `or_insert_with` and
+    /// `or_default` update the table. They record a single update, which
+    /// applies the closures passed to `and_modify` identically on both tables.
+    #[must_use = "the table is only updated by or_insert, or_insert_with or or_default"]
+    pub struct Entry<'g, 'w, K, V, F = fn(&mut V)> {
+        guard: &'g mut AsArcLockWriteGuard<'w, K, V>,
+        key: K,
+        modify: Option<F>,
+    }
+
+    impl<'g, 'w, K, V, F> Entry<'g, 'w, K, V, F>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+        F: 'static + Send + Clone + FnMut(&mut V),
+    {
+        pub fn key(&self) -> &K {
+            &self.key
+        }
+
+        /// Modify the value if the key is in the table. `f` is called on both
+        /// tables, so it must make the same change each time.
+        pub fn and_modify<G>(
+            self,
+            mut f: G,
+        ) -> Entry<'g, 'w, K, V, impl 'static + Send + Clone + FnMut(&mut V)>
+        where
+            G: 'static + Send + Clone + FnMut(&mut V),
+        {
+            let mut modify = self.modify;
+            Entry {
+                guard: self.guard,
+                key: self.key,
+                modify: Some(move |value: &mut V| {
+                    if let Some(modify) = &mut modify {
+                        modify(value);
+                    }
+                    f(value);
+                }),
+            }
+        }
+
+        /// Insert `default` if the key isn't in the table, and return the
+        /// value in the table.
+        pub fn or_insert(self, default: V) -> &'g V {
+            self.or_insert_with(move || default)
+        }
+
+        /// Like `or_insert`, but `default` is only called if the key isn't in
+        /// the table. It is called once, and the value is cloned into the
+        /// first table.
+        pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'g V {
+            let default = match self.guard.contains_key(&self.key) {
+                true => None,
+                false => Some(default()),
+            };
+            self.guard.update_tables(Upsert {
+                key: self.key,
+                modify: self.modify,
+                default,
+            })
+        }
+
+        pub fn or_default(self) -> &'g V
+        where
+            V: Default,
+        {
+            self.or_insert_with(V::default)
+        }
+    }
+
+    impl<'w, K, V> Extend<(K, V)> for AsArcLockWriteGuard<'w, K, V>
+    where
+        K: 'static + Eq + Hash + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+            self.guard.update_tables(InsertAll {
+                entries: iter.into_iter().collect(),
+            })
+        }
     }
 }
 
@@ -194,6 +1150,38 @@ mod lockless_test {
     use maplit::*;
     use more_asserts::*;
 
+    #[test]
+    fn get_many_cloned() {
+        let table = lockless::AsLockHandle::new(hashmap! { "a" => 1, "b" => 2 });
+        assert_eq!(
+            table.get_many_cloned(["b", "c", "a"].iter()),
+            vec![Some(2), None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn read_chunks() {
+        let table = lockless::AsLockHandle::new(hashmap! { 1 => 'a', 2 => 'b', 3 => 'c' });
+        let mut entries = vec![];
+        table.read_chunks(2, |chunk| {
+            if entries.is_empty() {
+                // Update the entry which hasn't been seen yet, and insert one
+                // which won't be seen.
+                let mut wg = table.write();
+                for key in [1, 2, 3] {
+                    if chunk.iter().all(|(k, _)| **k != key) {
+                        wg.insert(key, 'z');
+                    }
+                }
+                wg.insert(4, 'd');
+            }
+            entries.extend(chunk.iter().map(|(k, v)| (**k, **v)));
+        });
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.iter().filter(|(_, v)| *v == 'z').count(), 1);
+        assert!(entries.iter().all(|(k, _)| *k != 4));
+    }
+
     #[test]
     fn insert() {
         let expected = hashmap! {
@@ -211,6 +1199,50 @@ mod lockless_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn new_parallel() {
+        let map: HashMap<i32, String> = (0..1000).map(|i| (i, i.to_string())).collect();
+        let table = lockless::AsLockHandle::new_parallel(map.clone());
+        table.write().insert(1000, "1000".to_string());
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 1001);
+        assert_eq!(table.read()[&999], "999");
+    }
+
+    #[test]
+    fn try_insert() {
+        let table = lockless::AsLockHandle::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(
+                wg.try_insert("hello", 2),
+                Err(OccupiedError {
+                    key: "hello",
+                    value: 2
+                })
+            );
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert_eq!(wg.try_insert("world", 3), Ok(()));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 1, "world" => 3 });
+    }
+
+    #[test]
+    fn insert_if_different() {
+        let table = lockless::AsLockHandle::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert!(!wg.insert_if_different("hello", 1));
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert!(wg.insert_if_different("hello", 2));
+            assert!(wg.insert_if_different("world", 3));
+            assert_eq!(wg.num_pending_ops(), 2);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 2, "world" => 3 });
+    }
+
     #[test]
     fn clear() {
         let table = lockless::AsLockHandle::new(hashmap! {
@@ -254,6 +1286,52 @@ mod lockless_test {
         );
     }
 
+    #[test]
+    fn get_or_insert_with() {
+        let table = lockless::AsLockHandle::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.get_or_insert_with("hello", || unreachable!()), 1);
+            assert_eq!(*wg.get_or_insert_with("world", || 2), 2);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 1, "world" => 2 });
+    }
+
+    #[test]
+    fn entry() {
+        let table = lockless::AsLockHandle::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.entry("hello").and_modify(|v| *v += 1).or_insert(10), 2);
+            assert_eq!(*wg.entry("world").and_modify(|v| *v += 1).or_insert(10), 10);
+            assert_eq!(
+                *wg.entry("hello")
+                    .and_modify(|v| *v *= 3)
+                    .and_modify(|v| *v += 1)
+                    .or_insert_with(|| unreachable!()),
+                7
+            );
+            assert_eq!(*wg.entry("default").or_default(), 0);
+            assert_eq!(wg.num_pending_ops(), 4);
+        }
+        assert_tables_eq!(
+            table,
+            hashmap! { "hello" => 7, "world" => 10, "default" => 0 }
+        );
+    }
+
+    #[test]
+    fn get_mut_with() {
+        let table = lockless::AsLockHandle::new(hashmap! { "hello" => vec![1] });
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.get_mut_with("hello", |v| v.push(2)), Some(&vec![1, 2]));
+            assert_eq!(wg.get_mut_with("world", |v| v.push(2)), None);
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => vec![1, 2] });
+    }
+
     #[test]
     fn shrink_to_fit_and_reserve() {
         let table = lockless::AsLockHandle::new(hashmap! {
@@ -275,6 +1353,48 @@ mod lockless_test {
         assert_ge!(table.read().capacity(), initial_capacity + additional);
     }
 
+    #[test]
+    fn fused_shrink() {
+        let table = lockless::AsLockHandle::new(hashmap! {
+            "hello" => 1,
+            "world"=> 2,
+        });
+        {
+            let mut wg = table.write();
+            wg.reserve(100);
+            wg.retain_and_shrink(|_, value| *value == 1);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 1 });
+        assert_lt!(table.read().capacity(), 100);
+        assert_lt!(table.write().capacity(), 100);
+
+        table.write().clear_and_shrink();
+        assert_tables_eq!(table, HashMap::<&str, i32>::new());
+        assert_eq!(table.read().capacity(), 0);
+        assert_eq!(table.write().capacity(), 0);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let table = lockless::AsLockHandle::<&str, i32>::with_capacity(100);
+        assert_ge!(table.read().capacity(), 100);
+        assert_ge!(table.write().capacity(), 100);
+
+        let table =
+            lockless::AsLockHandle::<&str, i32>::with_capacity_and_hasher(100, RandomState::new());
+        {
+            let mut wg = table.write();
+            for (i, key) in ["a", "b", "c", "d", "e", "f"].into_iter().enumerate() {
+                wg.insert(key, i as i32);
+            }
+        }
+        assert_ge!(table.read().capacity(), 100);
+        assert_eq!(
+            table.read().iter().collect::<Vec<_>>(),
+            table.write().iter().collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn retain() {
         let expected = hashmap! {
@@ -297,6 +1417,55 @@ mod lockless_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[test]
+    fn extract_if() {
+        let table = lockless::AsLockHandle::new(hashmap! {
+            "hello" => 1,
+            "world" => 0,
+            "my" => 2,
+            "name" => -1,
+        });
+        let mut extracted = table.write().extract_if(|_, &mut v| v % 2 != 0);
+        extracted.sort();
+        assert_eq!(extracted, vec![("hello", 1), ("name", -1)]);
+        assert_tables_eq!(table, hashmap! { "world" => 0, "my" => 2 });
+    }
+
+    #[test]
+    fn extend() {
+        let table = lockless::AsLockHandle::new(hashmap! { "hello" => 1 });
+        table.write().extend([("hello", 2), ("world", 3)]);
+        assert_tables_eq!(table, hashmap! { "hello" => 2, "world" => 3 });
+    }
+
+    #[test]
+    fn retain_collect() {
+        let table = lockless::AsLockHandle::new(hashmap! {
+            "hello" => 1,
+            "world" => 0,
+            "my" => 2,
+        });
+        let mut removed = table.write().retain_collect(|k, _| *k != "world");
+        removed.sort();
+        assert_eq!(removed, vec![("world", 0)]);
+        assert_tables_eq!(table, hashmap! { "hello" => 1, "my" => 2 });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = lockless::AsLockHandle::new((0..10).map(|i| (i, i * 10)).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for (key, _) in &evicted {
+            assert!(!table.read().contains_key(key));
+        }
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, HashMap::new());
+    }
+
     #[test]
     fn drain() {
         let expected = hashmap! {
@@ -343,6 +1512,38 @@ mod sync_test {
     use more_asserts::*;
     use std::sync::Arc;
 
+    #[test]
+    fn get_many_cloned() {
+        let table = sync::AsLock::new(hashmap! { "a" => 1, "b" => 2 });
+        assert_eq!(
+            table.get_many_cloned(["b", "c", "a"].iter()),
+            vec![Some(2), None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn read_chunks() {
+        let table = sync::AsLock::new(hashmap! { 1 => 'a', 2 => 'b', 3 => 'c' });
+        let mut entries = vec![];
+        table.read_chunks(2, |chunk| {
+            if entries.is_empty() {
+                // Update the entry which hasn't been seen yet, and insert one
+                // which won't be seen.
+                let mut wg = table.write();
+                for key in [1, 2, 3] {
+                    if chunk.iter().all(|(k, _)| **k != key) {
+                        wg.insert(key, 'z');
+                    }
+                }
+                wg.insert(4, 'd');
+            }
+            entries.extend(chunk.iter().map(|(k, v)| (**k, **v)));
+        });
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.iter().filter(|(_, v)| *v == 'z').count(), 1);
+        assert!(entries.iter().all(|(k, _)| *k != 4));
+    }
+
     #[test]
     fn insert() {
         let expected = hashmap! {
@@ -360,6 +1561,50 @@ mod sync_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn new_parallel() {
+        let map: HashMap<i32, String> = (0..1000).map(|i| (i, i.to_string())).collect();
+        let table = sync::AsLock::new_parallel(map.clone());
+        table.write().insert(1000, "1000".to_string());
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 1001);
+        assert_eq!(table.read()[&999], "999");
+    }
+
+    #[test]
+    fn try_insert() {
+        let table = sync::AsLock::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(
+                wg.try_insert("hello", 2),
+                Err(OccupiedError {
+                    key: "hello",
+                    value: 2
+                })
+            );
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert_eq!(wg.try_insert("world", 3), Ok(()));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 1, "world" => 3 });
+    }
+
+    #[test]
+    fn insert_if_different() {
+        let table = sync::AsLock::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert!(!wg.insert_if_different("hello", 1));
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert!(wg.insert_if_different("hello", 2));
+            assert!(wg.insert_if_different("world", 3));
+            assert_eq!(wg.num_pending_ops(), 2);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 2, "world" => 3 });
+    }
+
     #[test]
     fn clear() {
         let table = Arc::new(sync::AsLock::new(hashmap! {
@@ -403,6 +1648,52 @@ mod sync_test {
         );
     }
 
+    #[test]
+    fn get_or_insert_with() {
+        let table = sync::AsLock::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.get_or_insert_with("hello", || unreachable!()), 1);
+            assert_eq!(*wg.get_or_insert_with("world", || 2), 2);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 1, "world" => 2 });
+    }
+
+    #[test]
+    fn entry() {
+        let table = sync::AsLock::new(hashmap! { "hello" => 1 });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.entry("hello").and_modify(|v| *v += 1).or_insert(10), 2);
+            assert_eq!(*wg.entry("world").and_modify(|v| *v += 1).or_insert(10), 10);
+            assert_eq!(
+                *wg.entry("hello")
+                    .and_modify(|v| *v *= 3)
+                    .and_modify(|v| *v += 1)
+                    .or_insert_with(|| unreachable!()),
+                7
+            );
+            assert_eq!(*wg.entry("default").or_default(), 0);
+            assert_eq!(wg.num_pending_ops(), 4);
+        }
+        assert_tables_eq!(
+            table,
+            hashmap! { "hello" => 7, "world" => 10, "default" => 0 }
+        );
+    }
+
+    #[test]
+    fn get_mut_with() {
+        let table = sync::AsLock::new(hashmap! { "hello" => vec![1] });
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.get_mut_with("hello", |v| v.push(2)), Some(&vec![1, 2]));
+            assert_eq!(wg.get_mut_with("world", |v| v.push(2)), None);
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => vec![1, 2] });
+    }
+
     #[test]
     fn shrink_to_fit_and_reserve() {
         let table = sync::AsLock::new(hashmap! {
@@ -424,6 +1715,47 @@ mod sync_test {
         assert_ge!(table.read().capacity(), initial_capacity + additional);
     }
 
+    #[test]
+    fn fused_shrink() {
+        let table = sync::AsLock::new(hashmap! {
+            "hello" => 1,
+            "world"=> 2,
+        });
+        {
+            let mut wg = table.write();
+            wg.reserve(100);
+            wg.retain_and_shrink(|_, value| *value == 1);
+        }
+        assert_tables_eq!(table, hashmap! { "hello" => 1 });
+        assert_lt!(table.read().capacity(), 100);
+        assert_lt!(table.write().capacity(), 100);
+
+        table.write().clear_and_shrink();
+        assert_tables_eq!(table, HashMap::<&str, i32>::new());
+        assert_eq!(table.read().capacity(), 0);
+        assert_eq!(table.write().capacity(), 0);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let table = sync::AsLock::<&str, i32>::with_capacity(100);
+        assert_ge!(table.read().capacity(), 100);
+        assert_ge!(table.write().capacity(), 100);
+
+        let table = sync::AsLock::<&str, i32>::with_capacity_and_hasher(100, RandomState::new());
+        {
+            let mut wg = table.write();
+            for (i, key) in ["a", "b", "c", "d", "e", "f"].into_iter().enumerate() {
+                wg.insert(key, i as i32);
+            }
+        }
+        assert_ge!(table.read().capacity(), 100);
+        assert_eq!(
+            table.read().iter().collect::<Vec<_>>(),
+            table.write().iter().collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn retain() {
         let expected = hashmap! {
@@ -446,6 +1778,55 @@ mod sync_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[test]
+    fn extract_if() {
+        let table = sync::AsLock::new(hashmap! {
+            "hello" => 1,
+            "world" => 0,
+            "my" => 2,
+            "name" => -1,
+        });
+        let mut extracted = table.write().extract_if(|_, &mut v| v % 2 != 0);
+        extracted.sort();
+        assert_eq!(extracted, vec![("hello", 1), ("name", -1)]);
+        assert_tables_eq!(table, hashmap! { "world" => 0, "my" => 2 });
+    }
+
+    #[test]
+    fn extend() {
+        let table = sync::AsLock::new(hashmap! { "hello" => 1 });
+        table.write().extend([("hello", 2), ("world", 3)]);
+        assert_tables_eq!(table, hashmap! { "hello" => 2, "world" => 3 });
+    }
+
+    #[test]
+    fn retain_collect() {
+        let table = sync::AsLock::new(hashmap! {
+            "hello" => 1,
+            "world" => 0,
+            "my" => 2,
+        });
+        let mut removed = table.write().retain_collect(|k, _| *k != "world");
+        removed.sort();
+        assert_eq!(removed, vec![("world", 0)]);
+        assert_tables_eq!(table, hashmap! { "hello" => 1, "my" => 2 });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = sync::AsLock::new((0..10).map(|i| (i, i * 10)).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for (key, _) in &evicted {
+            assert!(!table.read().contains_key(key));
+        }
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, HashMap::new());
+    }
+
     #[test]
     fn drain() {
         let expected = hashmap! {
@@ -486,3 +1867,24 @@ mod sync_test {
         assert_eq!(format!("{:?}", table.read()), "{12: -1}",);
     }
 }
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+    use maplit::*;
+
+    #[test]
+    fn insert_and_entry() {
+        let table = arc::AsArcLock::<&str, i32>::default();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.insert("a", 1), None);
+            assert_eq!(*wg.entry("b").or_insert(2), 2);
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(**table.read(), hashmap! { "a" => 1, "b" => 2 });
+        assert_eq!(table.write().remove("a"), Some(1));
+        assert_eq!(*table.write(), hashmap! { "b" => 2 });
+        assert_eq!(**table.read(), hashmap! { "b" => 2 });
+    }
+}
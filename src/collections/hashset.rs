@@ -1,5 +1,8 @@
+use crate::collections::Prng;
+use crate::types::Replace;
 use crate::UpdateTables;
 use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
 use std::collections::HashSet;
 use std::hash::Hash;
 
@@ -20,10 +23,41 @@ where
     }
 }
 
-struct Replace<T> {
+struct GetOrInsert<T> {
     value: T,
 }
 
+impl<'a, T> UpdateTables<'a, HashSet<T>, &'a T> for GetOrInsert<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn apply_first(&mut self, table: &'a mut HashSet<T>) -> &'a T {
+        table.insert(self.value.clone());
+        table.get(&self.value).unwrap()
+    }
+    fn apply_second(self, table: &mut HashSet<T>) {
+        // Move the value instead of cloning.
+        table.insert(self.value);
+    }
+}
+
+struct InsertAll<T> {
+    values: Vec<T>,
+}
+
+impl<'a, T> UpdateTables<'a, HashSet<T>, ()> for InsertAll<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn apply_first(&mut self, table: &'a mut HashSet<T>) {
+        table.extend(self.values.iter().cloned());
+    }
+    fn apply_second(self, table: &mut HashSet<T>) {
+        // Move the values instead of cloning.
+        table.extend(self.values);
+    }
+}
+
 impl<'a, T> UpdateTables<'a, HashSet<T>, Option<T>> for Replace<T>
 where
     T: Eq + Hash + Clone,
@@ -42,6 +76,8 @@ where
     F: 'static + Clone + FnMut(&T) -> bool,
 {
     f: F,
+    // Whether to also shrink the table, for `retain_and_shrink`.
+    shrink: bool,
     _compile_k_v: std::marker::PhantomData<T>,
 }
 
@@ -51,11 +87,59 @@ where
     F: 'static + Clone + FnMut(&T) -> bool,
 {
     fn apply_first(&mut self, table: &'a mut HashSet<T>) {
-        table.retain(self.f.clone())
+        table.retain(self.f.clone());
+        if self.shrink {
+            table.shrink_to_fit();
+        }
     }
 
     fn apply_second(self, table: &mut HashSet<T>) {
-        table.retain(self.f)
+        table.retain(self.f);
+        if self.shrink {
+            table.shrink_to_fit();
+        }
+    }
+}
+
+struct ExtractIf<F> {
+    f: F,
+}
+
+impl<'a, T, F> UpdateTables<'a, HashSet<T>, Vec<T>> for ExtractIf<F>
+where
+    T: Eq + Hash,
+    F: Clone + FnMut(&T) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut HashSet<T>) -> Vec<T> {
+        table.extract_if(self.f.clone()).collect()
+    }
+    fn apply_second(mut self, table: &mut HashSet<T>) {
+        table.retain(|t| !(self.f)(t))
+    }
+}
+
+struct RandomEvict<T> {
+    n: usize,
+    seed: u64,
+    evicted: Vec<T>,
+}
+
+impl<'a, T> UpdateTables<'a, HashSet<T>, Vec<T>> for RandomEvict<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn apply_first(&mut self, table: &'a mut HashSet<T>) -> Vec<T> {
+        let mut evict = Prng::new(self.seed).sample(table.len(), self.n);
+        let evicted: Vec<T> = table.extract_if(|_| evict()).collect();
+        self.evicted = evicted.clone();
+        evicted
+    }
+    fn apply_second(self, table: &mut HashSet<T>) {
+        // The tables don't necessarily iterate in the same order, so remove
+        // the values picked from the first table, rather than sampling again.
+        for value in &self.evicted {
+            table.remove(value);
+        }
     }
 }
 
@@ -81,6 +165,18 @@ pub mod lockless {
     use super::*;
     crate::generate_lockless_aslockhandle!(HashSet<T>);
 
+    impl<T> AsLockHandle<T> {
+        /// Create an `AsLockHandle` where both tables have space for `capacity`
+        /// elements. Both tables use a clone of `hasher`, so they also iterate in
+        /// the same order.
+        pub fn with_capacity_and_hasher(capacity: usize, hasher: RandomState) -> AsLockHandle<T> {
+            AsLockHandle::from_identical(
+                HashSet::with_capacity_and_hasher(capacity, hasher.clone()),
+                HashSet::with_capacity_and_hasher(capacity, hasher),
+            )
+        }
+    }
+
     impl<'w, 'a, T> AsLockWriteGuard<'w, T>
     where
         T: 'static + Eq + Hash + Clone + Send,
@@ -89,11 +185,25 @@ pub mod lockless {
             self.guard.update_tables_closure(move |table| table.clear())
         }
 
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
         pub fn shrink_to_fit(&mut self) {
             self.guard
                 .update_tables_closure(move |table| table.shrink_to_fit())
         }
 
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
         pub fn reserve(&mut self, additional: usize) {
             self.guard
                 .update_tables_closure(move |table| table.reserve(additional))
@@ -107,6 +217,16 @@ pub mod lockless {
             self.guard.update_tables(Replace { value })
         }
 
+        /// Returns the element equal to `value`, inserting `value` if there
+        /// is none yet. No update is recorded if an equal element is already
+        /// present.
+        pub fn get_or_insert(&mut self, value: T) -> &T {
+            if self.contains(&value) {
+                return self.get(&value).unwrap();
+            }
+            self.guard.update_tables(GetOrInsert { value })
+        }
+
         pub fn remove<Q>(&mut self, value_like: Q) -> bool
         where
             T: Borrow<Q>,
@@ -131,14 +251,71 @@ pub mod lockless {
         {
             self.guard.update_tables(Retain {
                 f,
+                shrink: false,
                 _compile_k_v: std::marker::PhantomData,
             })
         }
 
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: true,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        /// Removes `n` values picked at random, or all of them if the table is
+        /// smaller, and returns them. The values are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the values picked for a given seed depend on the order the table
+        /// iterates in.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<T> {
+            self.guard.update_tables(RandomEvict {
+                n,
+                seed,
+                evicted: vec![],
+            })
+        }
+
         pub fn drain(&'a mut self) -> std::collections::hash_set::Drain<'a, T> {
             self.guard.update_tables(Drain {})
         }
     }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Eq + Hash + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(InsertAll {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
 }
 
 /// Implementation of HashSet for use in the active_standby model.
@@ -147,6 +324,18 @@ pub mod sync {
     use super::*;
     crate::generate_sync_aslock!(HashSet<T>);
 
+    impl<T> AsLock<T> {
+        /// Create an `AsLock` where both tables have space for `capacity`
+        /// elements. Both tables use a clone of `hasher`, so they also iterate in
+        /// the same order.
+        pub fn with_capacity_and_hasher(capacity: usize, hasher: RandomState) -> AsLock<T> {
+            AsLock::from_identical(
+                HashSet::with_capacity_and_hasher(capacity, hasher.clone()),
+                HashSet::with_capacity_and_hasher(capacity, hasher),
+            )
+        }
+    }
+
     impl<'w, 'a, T> AsLockWriteGuard<'w, T>
     where
         T: 'static + Eq + Hash + Clone + Send,
@@ -155,11 +344,172 @@ pub mod sync {
             self.guard.update_tables_closure(move |table| table.clear())
         }
 
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn insert(&mut self, value: T) -> bool {
+            self.guard.update_tables(Insert { value })
+        }
+
+        pub fn replace(&mut self, value: T) -> Option<T> {
+            self.guard.update_tables(Replace { value })
+        }
+
+        /// Returns the element equal to `value`, inserting `value` if there
+        /// is none yet. No update is recorded if an equal element is already
+        /// present.
+        pub fn get_or_insert(&mut self, value: T) -> &T {
+            if self.contains(&value) {
+                return self.get(&value).unwrap();
+            }
+            self.guard.update_tables(GetOrInsert { value })
+        }
+
+        pub fn remove<Q>(&mut self, value_like: Q) -> bool
+        where
+            T: Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.remove(&value_like))
+        }
+
+        pub fn take<Q>(&mut self, value_like: Q) -> Option<T>
+        where
+            T: Borrow<Q>,
+            Q: 'static + Hash + Eq + Send,
+        {
+            self.guard
+                .update_tables_closure(move |table| table.take(&value_like))
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: false,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: true,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        /// Removes `n` values picked at random, or all of them if the table is
+        /// smaller, and returns them. The values are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the values picked for a given seed depend on the order the table
+        /// iterates in.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<T> {
+            self.guard.update_tables(RandomEvict {
+                n,
+                seed,
+                evicted: vec![],
+            })
+        }
+
+        pub fn drain(&'a mut self) -> std::collections::hash_set::Drain<'a, T> {
+            self.guard.update_tables(Drain {})
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Eq + Hash + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(InsertAll {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(HashSet<T>);
+
+    impl<'w, 'a, T> AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Eq + Hash + Clone + Send,
+    {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(move |table| table.clear())
+        }
+
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
         pub fn shrink_to_fit(&mut self) {
             self.guard
                 .update_tables_closure(move |table| table.shrink_to_fit())
         }
 
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
         pub fn reserve(&mut self, additional: usize) {
             self.guard
                 .update_tables_closure(move |table| table.reserve(additional))
@@ -173,6 +523,16 @@ pub mod sync {
             self.guard.update_tables(Replace { value })
         }
 
+        /// Returns the element equal to `value`, inserting `value` if there
+        /// is none yet. No update is recorded if an equal element is already
+        /// present.
+        pub fn get_or_insert(&mut self, value: T) -> &T {
+            if self.contains(&value) {
+                return self.get(&value).unwrap();
+            }
+            self.guard.update_tables(GetOrInsert { value })
+        }
+
         pub fn remove<Q>(&mut self, value_like: Q) -> bool
         where
             T: Borrow<Q>,
@@ -197,14 +557,71 @@ pub mod sync {
         {
             self.guard.update_tables(Retain {
                 f,
+                shrink: false,
                 _compile_k_v: std::marker::PhantomData,
             })
         }
 
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain {
+                f,
+                shrink: true,
+                _compile_k_v: std::marker::PhantomData,
+            })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        /// Removes `n` values picked at random, or all of them if the table is
+        /// smaller, and returns them. The values are picked by a PRNG seeded by
+        /// `seed`, so the update can be replayed on the second table. Note that
+        /// the values picked for a given seed depend on the order the table
+        /// iterates in.
+        pub fn random_evict_with_seed(&mut self, n: usize, seed: u64) -> Vec<T> {
+            self.guard.update_tables(RandomEvict {
+                n,
+                seed,
+                evicted: vec![],
+            })
+        }
+
         pub fn drain(&'a mut self) -> std::collections::hash_set::Drain<'a, T> {
             self.guard.update_tables(Drain {})
         }
     }
+
+    impl<'w, T> Extend<T> for AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Eq + Hash + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(InsertAll {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
 }
 
 #[cfg(test)]
@@ -259,6 +676,17 @@ mod lockless_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[test]
+    fn get_or_insert() {
+        let table = lockless::AsLockHandle::new(hashset! { "hello" });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.get_or_insert("hello"), "hello");
+            assert_eq!(*wg.get_or_insert("world"), "world");
+        }
+        assert_tables_eq!(table, hashset! { "hello", "world" });
+    }
+
     #[test]
     fn shrink_to_fit_and_reserve() {
         let table = lockless::AsLockHandle::<&str>::from_identical(
@@ -282,6 +710,45 @@ mod lockless_test {
         assert_ge!(table.read().capacity(), initial_capacity + additional);
     }
 
+    #[test]
+    fn fused_shrink() {
+        let table = lockless::AsLockHandle::new(hashset! { "hello", "world" });
+        {
+            let mut wg = table.write();
+            wg.reserve(100);
+            wg.retain_and_shrink(|value| *value == "hello");
+        }
+        assert_tables_eq!(table, hashset! { "hello" });
+        assert_lt!(table.read().capacity(), 100);
+        assert_lt!(table.write().capacity(), 100);
+
+        table.write().clear_and_shrink();
+        assert_tables_eq!(table, HashSet::<&str>::new());
+        assert_eq!(table.read().capacity(), 0);
+        assert_eq!(table.write().capacity(), 0);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let table = lockless::AsLockHandle::<&str>::with_capacity(100);
+        assert_ge!(table.read().capacity(), 100);
+        assert_ge!(table.write().capacity(), 100);
+
+        let table =
+            lockless::AsLockHandle::<&str>::with_capacity_and_hasher(100, RandomState::new());
+        {
+            let mut wg = table.write();
+            for key in ["a", "b", "c", "d", "e", "f"] {
+                wg.insert(key);
+            }
+        }
+        assert_ge!(table.read().capacity(), 100);
+        assert_eq!(
+            table.read().iter().collect::<Vec<_>>(),
+            table.write().iter().collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn retain() {
         let table = lockless::AsLockHandle::new(hashset! {
@@ -304,6 +771,37 @@ mod lockless_test {
         );
     }
 
+    #[test]
+    fn extract_if() {
+        let table = lockless::AsLockHandle::new(hashset! {
+            "hello",
+            "world",
+            "my",
+            "name",
+            "is",
+            "joe",
+        });
+        let mut extracted = table.write().extract_if(|&k| k.len() <= 2);
+        extracted.sort();
+        assert_eq!(extracted, vec!["is", "my"]);
+        assert_tables_eq!(table, hashset! { "hello", "world", "name", "joe" });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = lockless::AsLockHandle::new((0..10).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for key in &evicted {
+            assert!(!table.read().contains(key));
+        }
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, HashSet::new());
+    }
+
     #[test]
     fn drain() {
         let expected = hashset! {
@@ -392,6 +890,17 @@ mod sync_test {
         assert_tables_eq!(table, expected);
     }
 
+    #[test]
+    fn get_or_insert() {
+        let table = sync::AsLock::new(hashset! { "hello" });
+        {
+            let mut wg = table.write();
+            assert_eq!(*wg.get_or_insert("hello"), "hello");
+            assert_eq!(*wg.get_or_insert("world"), "world");
+        }
+        assert_tables_eq!(table, hashset! { "hello", "world" });
+    }
+
     #[test]
     fn shrink_to_fit_and_reserve() {
         let table = sync::AsLock::<&str>::from_identical(
@@ -415,6 +924,44 @@ mod sync_test {
         assert_ge!(table.read().capacity(), initial_capacity + additional);
     }
 
+    #[test]
+    fn fused_shrink() {
+        let table = sync::AsLock::new(hashset! { "hello", "world" });
+        {
+            let mut wg = table.write();
+            wg.reserve(100);
+            wg.retain_and_shrink(|value| *value == "hello");
+        }
+        assert_tables_eq!(table, hashset! { "hello" });
+        assert_lt!(table.read().capacity(), 100);
+        assert_lt!(table.write().capacity(), 100);
+
+        table.write().clear_and_shrink();
+        assert_tables_eq!(table, HashSet::<&str>::new());
+        assert_eq!(table.read().capacity(), 0);
+        assert_eq!(table.write().capacity(), 0);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let table = sync::AsLock::<&str>::with_capacity(100);
+        assert_ge!(table.read().capacity(), 100);
+        assert_ge!(table.write().capacity(), 100);
+
+        let table = sync::AsLock::<&str>::with_capacity_and_hasher(100, RandomState::new());
+        {
+            let mut wg = table.write();
+            for key in ["a", "b", "c", "d", "e", "f"] {
+                wg.insert(key);
+            }
+        }
+        assert_ge!(table.read().capacity(), 100);
+        assert_eq!(
+            table.read().iter().collect::<Vec<_>>(),
+            table.write().iter().collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn retain() {
         let table = sync::AsLock::new(hashset! {
@@ -437,6 +984,37 @@ mod sync_test {
         );
     }
 
+    #[test]
+    fn extract_if() {
+        let table = sync::AsLock::new(hashset! {
+            "hello",
+            "world",
+            "my",
+            "name",
+            "is",
+            "joe",
+        });
+        let mut extracted = table.write().extract_if(|&k| k.len() <= 2);
+        extracted.sort();
+        assert_eq!(extracted, vec!["is", "my"]);
+        assert_tables_eq!(table, hashset! { "hello", "world", "name", "joe" });
+    }
+
+    #[test]
+    fn random_evict_with_seed() {
+        let table = sync::AsLock::new((0..10).collect());
+        let evicted = table.write().random_evict_with_seed(3, 11);
+        assert_eq!(evicted.len(), 3);
+        table.write().assert_synced();
+        assert_eq!(table.read().len(), 7);
+        for key in &evicted {
+            assert!(!table.read().contains(key));
+        }
+
+        assert_eq!(table.write().random_evict_with_seed(10, 11).len(), 7);
+        assert_tables_eq!(table, HashSet::new());
+    }
+
     #[test]
     fn drain() {
         let expected = hashset! {
@@ -474,3 +1052,22 @@ mod sync_test {
         assert_eq!(format!("{:?}", table.read()), "{12}",);
     }
 }
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+    use maplit::*;
+
+    #[test]
+    fn insert() {
+        let table = arc::AsArcLock::<&str>::default();
+        {
+            let mut wg = table.write();
+            assert!(wg.insert("hello"));
+            assert!(wg.insert("world"));
+        }
+        assert_eq!(**table.read(), hashset! { "hello", "world" });
+        assert!(table.write().remove("hello"));
+        assert_eq!(*table.write(), hashset! { "world" });
+    }
+}
@@ -0,0 +1,650 @@
+use crate::UpdateTables;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// Vec with secondary indexes, so that elements can be looked up by a field
+/// instead of by position. Each index is declared by a function extracting the
+/// key of an element, and maps each key to the positions of the elements with
+/// that key.
+///
+/// The indexes are kept up to date by the same updates which change the
+/// elements, so they can't drift apart from the Vec the way 2 separate tables
+/// can. Indexes are numbered in the order they are added with `with_index`.
+///
+/// Removal is done by `swap_remove`, so that the position of only a single
+/// element changes. Elements which share a key are listed in the order they
+/// were indexed, which only depends on the updates made to the Vec. This means
+/// that both tables list them in the same order.
+pub struct IndexedVec<T, K> {
+    values: Vec<T>,
+    indexes: Vec<Index<T, K>>,
+}
+
+struct Index<T, K> {
+    key: fn(&T) -> K,
+    positions: HashMap<K, Vec<usize>>,
+}
+
+impl<T, K> Index<T, K>
+where
+    K: Hash + Eq,
+{
+    fn insert(&mut self, value: &T, position: usize) {
+        self.positions
+            .entry((self.key)(value))
+            .or_default()
+            .push(position);
+    }
+
+    fn remove(&mut self, value: &T, position: usize) {
+        let key = (self.key)(value);
+        let positions = self.positions.get_mut(&key).expect("value is indexed");
+        positions.retain(|&p| p != position);
+        if positions.is_empty() {
+            self.positions.remove(&key);
+        }
+    }
+
+    fn moved(&mut self, value: &T, from: usize, to: usize) {
+        let positions = self
+            .positions
+            .get_mut(&(self.key)(value))
+            .expect("value is indexed");
+        for position in positions.iter_mut().filter(|p| **p == from) {
+            *position = to;
+        }
+    }
+}
+
+impl<T, K> IndexedVec<T, K>
+where
+    K: Hash + Eq,
+{
+    pub fn new() -> IndexedVec<T, K> {
+        IndexedVec {
+            values: vec![],
+            indexes: vec![],
+        }
+    }
+
+    /// Add an index, keyed by `key`. Its number is the number of indexes
+    /// added before it.
+    pub fn with_index(mut self, key: fn(&T) -> K) -> IndexedVec<T, K> {
+        let mut index = Index {
+            key,
+            positions: HashMap::new(),
+        };
+        for (position, value) in self.values.iter().enumerate() {
+            index.insert(value, position);
+        }
+        self.indexes.push(index);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, position: usize) -> Option<&T> {
+        self.values.get(position)
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.values.iter()
+    }
+
+    pub fn num_indexes(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// Positions of the elements whose key in index number `index` is `key`.
+    ///
+    /// Panics if there is no such index.
+    pub fn positions<Q>(&self, index: usize, key: &Q) -> &[usize]
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.indexes[index]
+            .positions
+            .get(key)
+            .map_or(&[], |positions| positions.as_slice())
+    }
+
+    /// Elements whose key in index number `index` is `key`.
+    ///
+    /// Panics if there is no such index.
+    pub fn get_by<'a, Q>(&'a self, index: usize, key: &Q) -> impl Iterator<Item = &'a T> + 'a
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.positions(index, key)
+            .iter()
+            .map(|&position| &self.values[position])
+    }
+
+    pub fn contains_key<Q>(&self, index: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.indexes[index].positions.contains_key(key)
+    }
+
+    pub fn push(&mut self, value: T) {
+        let position = self.values.len();
+        for index in self.indexes.iter_mut() {
+            index.insert(&value, position);
+        }
+        self.values.push(value);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let position = self.values.len().checked_sub(1)?;
+        Some(self.swap_remove(position))
+    }
+
+    /// Remove the element at `position`, replacing it with the last element.
+    ///
+    /// Panics if `position` is out of bounds.
+    pub fn swap_remove(&mut self, position: usize) -> T {
+        let value = self.values.swap_remove(position);
+        for index in self.indexes.iter_mut() {
+            index.remove(&value, position);
+            if let Some(moved) = self.values.get(position) {
+                index.moved(moved, self.values.len(), position);
+            }
+        }
+        value
+    }
+
+    /// Replace the element at `position`, returning the old one.
+    ///
+    /// Panics if `position` is out of bounds.
+    pub fn set(&mut self, position: usize, value: T) -> T {
+        for index in self.indexes.iter_mut() {
+            index.remove(&self.values[position], position);
+            index.insert(&value, position);
+        }
+        std::mem::replace(&mut self.values[position], value)
+    }
+
+    /// Keep only the elements for which `f` returns true. Unlike `swap_remove`
+    /// this keeps the order of the elements, and so rebuilds the indexes.
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.values.retain(f);
+        for index in self.indexes.iter_mut() {
+            index.positions.clear();
+            for (position, value) in self.values.iter().enumerate() {
+                index.insert(value, position);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        for index in self.indexes.iter_mut() {
+            index.positions.clear();
+        }
+    }
+}
+
+impl<T, K> Default for IndexedVec<T, K>
+where
+    K: Hash + Eq,
+{
+    fn default() -> IndexedVec<T, K> {
+        IndexedVec::new()
+    }
+}
+
+impl<T, K> Clone for IndexedVec<T, K>
+where
+    T: Clone,
+    K: Clone,
+{
+    fn clone(&self) -> IndexedVec<T, K> {
+        IndexedVec {
+            values: self.values.clone(),
+            indexes: self
+                .indexes
+                .iter()
+                .map(|index| Index {
+                    key: index.key,
+                    positions: index.positions.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Vecs are equal if they hold the same elements. The indexes are derived from
+/// the elements, so they aren't compared.
+impl<T: PartialEq, K> PartialEq for IndexedVec<T, K> {
+    fn eq(&self, other: &IndexedVec<T, K>) -> bool {
+        self.values == other.values
+    }
+}
+
+impl<T: fmt::Debug, K> fmt::Debug for IndexedVec<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.values.iter()).finish()
+    }
+}
+
+struct Push<T> {
+    value: T,
+}
+
+impl<'a, T, K> UpdateTables<'a, IndexedVec<T, K>, ()> for Push<T>
+where
+    T: Clone,
+    K: Hash + Eq,
+{
+    fn apply_first(&mut self, table: &'a mut IndexedVec<T, K>) {
+        table.push(self.value.clone())
+    }
+    fn apply_second(self, table: &mut IndexedVec<T, K>) {
+        // Move the value instead of cloning.
+        table.push(self.value);
+    }
+}
+
+struct Set<T> {
+    position: usize,
+    value: T,
+}
+
+impl<'a, T, K> UpdateTables<'a, IndexedVec<T, K>, T> for Set<T>
+where
+    T: Clone,
+    K: Hash + Eq,
+{
+    fn apply_first(&mut self, table: &'a mut IndexedVec<T, K>) -> T {
+        table.set(self.position, self.value.clone())
+    }
+    fn apply_second(self, table: &mut IndexedVec<T, K>) {
+        // Move the value instead of cloning.
+        table.set(self.position, self.value);
+    }
+}
+
+struct Retain<F> {
+    f: F,
+}
+
+impl<'a, T, K, F> UpdateTables<'a, IndexedVec<T, K>, ()> for Retain<F>
+where
+    K: Hash + Eq,
+    F: Clone + FnMut(&T) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut IndexedVec<T, K>) {
+        table.retain(self.f.clone())
+    }
+    fn apply_second(self, table: &mut IndexedVec<T, K>) {
+        table.retain(self.f)
+    }
+}
+
+/// Implementation of IndexedVec for use in the active_standby model.
+/// `lockless::AsLockHandle`, should function similarly to
+/// `Arc<RwLock<IndexedVec<T, K>>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(IndexedVec<T, K>);
+
+    impl<'w, T, K> AsLockWriteGuard<'w, T, K>
+    where
+        T: 'static + Clone + Send,
+        K: 'static + Hash + Eq + Send,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        /// See `IndexedVec::swap_remove`.
+        pub fn swap_remove(&mut self, position: usize) -> T {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove(position))
+        }
+
+        /// See `IndexedVec::set`.
+        pub fn set(&mut self, position: usize, value: T) -> T {
+            self.guard.update_tables(Set { position, value })
+        }
+
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// Implementation of IndexedVec for use in the active_standby model.
+/// `sync::AsLock`, should function similarly to `RwLock<IndexedVec<T, K>>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(IndexedVec<T, K>);
+
+    impl<'w, T, K> AsLockWriteGuard<'w, T, K>
+    where
+        T: 'static + Clone + Send,
+        K: 'static + Hash + Eq + Send,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        /// See `IndexedVec::swap_remove`.
+        pub fn swap_remove(&mut self, position: usize) -> T {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove(position))
+        }
+
+        /// See `IndexedVec::set`.
+        pub fn set(&mut self, position: usize, value: T) -> T {
+            self.guard.update_tables(Set { position, value })
+        }
+
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(IndexedVec<T, K>);
+
+    impl<'w, T, K> AsArcLockWriteGuard<'w, T, K>
+    where
+        T: 'static + Clone + Send,
+        K: 'static + Hash + Eq + Send,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        /// See `IndexedVec::swap_remove`.
+        pub fn swap_remove(&mut self, position: usize) -> T {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove(position))
+        }
+
+        /// See `IndexedVec::set`.
+        pub fn set(&mut self, position: usize, value: T) -> T {
+            self.guard.update_tables(Set { position, value })
+        }
+
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Send + Clone + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+    }
+}
+
+#[cfg(test)]
+mod indexedvec_test {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct User {
+        id: u32,
+        team: &'static str,
+    }
+
+    fn user(id: u32, team: &'static str) -> User {
+        User { id, team }
+    }
+
+    fn users() -> IndexedVec<User, String> {
+        IndexedVec::new()
+            .with_index(|user: &User| user.id.to_string())
+            .with_index(|user: &User| user.team.to_string())
+    }
+
+    fn ids<'a>(users: impl Iterator<Item = &'a User>) -> Vec<u32> {
+        users.map(|user| user.id).collect()
+    }
+
+    #[test]
+    fn get_by() {
+        let mut vec = users();
+        vec.push(user(1, "red"));
+        vec.push(user(2, "blue"));
+        vec.push(user(3, "red"));
+        assert_eq!(ids(vec.get_by(0, "2")), vec![2]);
+        assert_eq!(ids(vec.get_by(1, "red")), vec![1, 3]);
+        assert_eq!(vec.positions(1, "red"), &[0, 2]);
+        assert!(vec.get_by(1, "green").next().is_none());
+        assert!(!vec.contains_key(0, "4"));
+    }
+
+    #[test]
+    fn swap_remove_and_set() {
+        let mut vec = users();
+        vec.push(user(1, "red"));
+        vec.push(user(2, "blue"));
+        vec.push(user(3, "red"));
+
+        // The last element moves into the removed one's position.
+        assert_eq!(vec.swap_remove(0), user(1, "red"));
+        assert_eq!(vec.positions(0, "3"), &[0]);
+        assert_eq!(vec.positions(1, "red"), &[0]);
+        assert!(!vec.contains_key(0, "1"));
+
+        assert_eq!(vec.set(1, user(4, "red")), user(2, "blue"));
+        assert_eq!(ids(vec.get_by(1, "red")), vec![3, 4]);
+        assert!(!vec.contains_key(1, "blue"));
+
+        assert_eq!(vec.pop(), Some(user(4, "red")));
+        assert_eq!(vec.pop(), Some(user(3, "red")));
+        assert_eq!(vec.pop(), None);
+        assert!(!vec.contains_key(1, "red"));
+    }
+
+    #[test]
+    fn retain_and_late_index() {
+        let mut vec = IndexedVec::new();
+        vec.push(user(1, "red"));
+        vec.push(user(2, "blue"));
+        vec.push(user(3, "red"));
+        let mut vec = vec.with_index(|user| user.team);
+        vec.retain(|user| user.id != 1);
+        assert_eq!(vec.as_slice(), &[user(2, "blue"), user(3, "red")]);
+        assert_eq!(vec.positions(0, "red"), &[1]);
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    fn by_len() -> IndexedVec<&'static str, usize> {
+        IndexedVec::new().with_index(|s: &&str| s.len())
+    }
+
+    #[test]
+    fn push() {
+        let table = lockless::AsLockHandle::new(by_len());
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            wg.push("a");
+            wg.push("bb");
+            wg.push("c");
+            assert!(table2.read().is_empty());
+        }
+        assert_eq!(
+            table2.read().get_by(0, &1).collect::<Vec<_>>(),
+            [&"a", &"c"]
+        );
+
+        let mut expected = by_len();
+        expected.push("a");
+        expected.push("bb");
+        expected.push("c");
+        assert_tables_eq!(table, expected);
+    }
+
+    #[test]
+    fn remove_and_set() {
+        let table = lockless::AsLockHandle::new(by_len());
+        {
+            let mut wg = table.write();
+            wg.push("a");
+            wg.push("bb");
+            wg.push("c");
+            wg.push("ddd");
+            assert_eq!(wg.swap_remove(0), "a");
+            assert_eq!(wg.set(1, "ee"), "bb");
+            assert_eq!(wg.pop(), Some("c"));
+            wg.retain(|s| s.len() != 3);
+        }
+        // The indexes are updated identically on both tables.
+        let mut expected = by_len();
+        expected.push("ee");
+        assert_eq!(*table.read(), expected);
+        assert_eq!(table.read().positions(0, &2), &[0]);
+        {
+            let wg = table.write();
+            assert_eq!(*wg, expected);
+            assert_eq!(wg.positions(0, &2), &[0]);
+            assert!(!wg.contains_key(0, &1));
+            assert!(!wg.contains_key(0, &3));
+        }
+
+        table.write().clear();
+        assert_tables_eq!(table, by_len());
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    fn by_len() -> IndexedVec<&'static str, usize> {
+        IndexedVec::new().with_index(|s: &&str| s.len())
+    }
+
+    #[test]
+    fn push() {
+        let table = std::sync::Arc::new(sync::AsLock::new(by_len()));
+        {
+            let mut wg = table.write();
+            wg.push("a");
+            wg.push("bb");
+            wg.push("c");
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(table.read().get_by(0, &1).collect::<Vec<_>>(), [&"a", &"c"]);
+
+        let mut expected = by_len();
+        expected.push("a");
+        expected.push("bb");
+        expected.push("c");
+        assert_tables_eq!(table, expected);
+    }
+
+    #[test]
+    fn remove_and_set() {
+        let table = sync::AsLock::new(by_len());
+        {
+            let mut wg = table.write();
+            wg.push("a");
+            wg.push("bb");
+            wg.push("c");
+            wg.push("ddd");
+            assert_eq!(wg.swap_remove(0), "a");
+            assert_eq!(wg.set(1, "ee"), "bb");
+            assert_eq!(wg.pop(), Some("c"));
+            wg.retain(|s| s.len() != 3);
+        }
+        // The indexes are updated identically on both tables.
+        let mut expected = by_len();
+        expected.push("ee");
+        assert_eq!(*table.read(), expected);
+        assert_eq!(table.read().positions(0, &2), &[0]);
+        {
+            let wg = table.write();
+            assert_eq!(*wg, expected);
+            assert_eq!(wg.positions(0, &2), &[0]);
+            assert!(!wg.contains_key(0, &1));
+            assert!(!wg.contains_key(0, &3));
+        }
+
+        table.write().clear();
+        assert_tables_eq!(table, by_len());
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn push() {
+        let table = arc::AsArcLock::new(IndexedVec::new().with_index(|s: &&str| s.len()));
+        {
+            let mut wg = table.write();
+            wg.push("a");
+            wg.push("bb");
+            wg.push("c");
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(table.read().get_by(0, &1).collect::<Vec<_>>(), [&"a", &"c"]);
+        assert_eq!(table.write().get_by(0, &2).collect::<Vec<_>>(), [&"bb"]);
+    }
+}
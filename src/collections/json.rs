@@ -0,0 +1,581 @@
+use crate::UpdateTables;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Error returned when a path passed to a json write guard can't be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonPathError {
+    /// The path could not be parsed. Paths look like `a.b[2].c`.
+    InvalidPath(String),
+    /// The segment at this position expected an object (for keys) or an array
+    /// (for indices), but found a different type of value.
+    TypeMismatch { segment: usize },
+    /// The segment at this position indexed past the end of an array.
+    IndexOutOfBounds { segment: usize, len: usize },
+}
+
+impl fmt::Display for JsonPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonPathError::InvalidPath(path) => write!(f, "invalid json path: {:?}", path),
+            JsonPathError::TypeMismatch { segment } => {
+                write!(
+                    f,
+                    "path segment {} does not match the value's type",
+                    segment
+                )
+            }
+            JsonPathError::IndexOutOfBounds { segment, len } => {
+                write!(f, "path segment {} is out of bounds (len {})", segment, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonPathError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a path such as `a.b[2]` into its segments. The path is parsed once,
+/// before creating the op, so that both tables are walked identically.
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, JsonPathError> {
+    let invalid = || JsonPathError::InvalidPath(path.to_string());
+
+    let mut segments = vec![];
+    let mut rest = path;
+    // Only the very first segment may be an index without a preceding key.
+    let mut key_required = !rest.starts_with('[');
+    loop {
+        if key_required {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Key(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+
+        // Any number of indices may follow a key.
+        while let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(invalid)?;
+            let index = after_bracket[..end].parse().map_err(|_| invalid())?;
+            segments.push(PathSegment::Index(index));
+            rest = &after_bracket[end + 1..];
+        }
+
+        if rest.is_empty() {
+            return Ok(segments);
+        }
+        rest = rest.strip_prefix('.').ok_or_else(invalid)?;
+        key_required = true;
+    }
+}
+
+/// Walk the path up until the final segment, returning the parent of the
+/// value the path points to. If `create` is set, missing object keys are
+/// filled in with an empty object/array based on the type of the next segment.
+fn walk_to_parent<'v>(
+    mut value: &'v mut Value,
+    segments: &[PathSegment],
+    create: bool,
+) -> Result<Option<&'v mut Value>, JsonPathError> {
+    for (i, segment) in segments[..segments.len() - 1].iter().enumerate() {
+        value = match (segment, value) {
+            (PathSegment::Key(key), Value::Object(map)) => {
+                if !map.contains_key(key) {
+                    if !create {
+                        return Ok(None);
+                    }
+                    let child = match segments[i + 1] {
+                        PathSegment::Key(_) => Value::Object(Map::new()),
+                        PathSegment::Index(_) => Value::Array(vec![]),
+                    };
+                    map.insert(key.clone(), child);
+                }
+                map.get_mut(key).unwrap()
+            }
+            (PathSegment::Index(index), Value::Array(vec)) => {
+                let len = vec.len();
+                match vec.get_mut(*index) {
+                    Some(child) => child,
+                    None if create => {
+                        return Err(JsonPathError::IndexOutOfBounds { segment: i, len })
+                    }
+                    None => return Ok(None),
+                }
+            }
+            _ => return Err(JsonPathError::TypeMismatch { segment: i }),
+        };
+    }
+    Ok(Some(value))
+}
+
+/// Check that `set_path` would succeed on `table`, without changing it.
+/// `set_path` creates missing parents before reaching the final segment, so
+/// this is done before recording the op, to leave the tables untouched on
+/// error.
+fn check_set_path(table: &Value, segments: &[PathSegment]) -> Result<(), JsonPathError> {
+    let last = segments.len() - 1;
+    // `None` once the path leaves the table, where `set_path` would create an
+    // empty object or array to match each segment.
+    let mut value = Some(table);
+    for (i, segment) in segments.iter().enumerate() {
+        value = match (segment, value) {
+            (PathSegment::Key(key), Some(Value::Object(map))) => map.get(key),
+            (PathSegment::Key(_), None) => None,
+            (PathSegment::Index(index), Some(Value::Array(vec))) => match vec.get(*index) {
+                Some(child) => Some(child),
+                None if i == last && *index == vec.len() => None,
+                None => {
+                    return Err(JsonPathError::IndexOutOfBounds {
+                        segment: i,
+                        len: vec.len(),
+                    })
+                }
+            },
+            (PathSegment::Index(index), None) => match i == last && *index == 0 {
+                true => None,
+                false => return Err(JsonPathError::IndexOutOfBounds { segment: i, len: 0 }),
+            },
+            _ => return Err(JsonPathError::TypeMismatch { segment: i }),
+        };
+    }
+    Ok(())
+}
+
+fn set_path(
+    table: &mut Value,
+    segments: &[PathSegment],
+    new_value: Value,
+) -> Result<Option<Value>, JsonPathError> {
+    let last = segments.len() - 1;
+    let parent = walk_to_parent(table, segments, true)?.unwrap();
+    match (&segments[last], parent) {
+        (PathSegment::Key(key), Value::Object(map)) => Ok(map.insert(key.clone(), new_value)),
+        (PathSegment::Index(index), Value::Array(vec)) => {
+            if *index < vec.len() {
+                Ok(Some(std::mem::replace(&mut vec[*index], new_value)))
+            } else if *index == vec.len() {
+                vec.push(new_value);
+                Ok(None)
+            } else {
+                Err(JsonPathError::IndexOutOfBounds {
+                    segment: last,
+                    len: vec.len(),
+                })
+            }
+        }
+        _ => Err(JsonPathError::TypeMismatch { segment: last }),
+    }
+}
+
+fn remove_path(
+    table: &mut Value,
+    segments: &[PathSegment],
+) -> Result<Option<Value>, JsonPathError> {
+    let last = segments.len() - 1;
+    let parent = match walk_to_parent(table, segments, false)? {
+        None => return Ok(None),
+        Some(parent) => parent,
+    };
+    match (&segments[last], parent) {
+        (PathSegment::Key(key), Value::Object(map)) => Ok(map.remove(key)),
+        (PathSegment::Index(index), Value::Array(vec)) => {
+            if *index < vec.len() {
+                Ok(Some(vec.remove(*index)))
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Err(JsonPathError::TypeMismatch { segment: last }),
+    }
+}
+
+/// JSON Merge Patch (RFC 7396).
+fn merge_patch(table: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *table = patch.clone();
+            return;
+        }
+    };
+
+    if !table.is_object() {
+        *table = Value::Object(Map::new());
+    }
+    let map = table.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            map.remove(key);
+        } else {
+            merge_patch(map.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+struct SetPath {
+    segments: Vec<PathSegment>,
+    value: Value,
+}
+
+impl<'a> UpdateTables<'a, Value, Result<Option<Value>, JsonPathError>> for SetPath {
+    fn apply_first(&mut self, table: &'a mut Value) -> Result<Option<Value>, JsonPathError> {
+        set_path(table, &self.segments, self.value.clone())
+    }
+    fn apply_second(self, table: &mut Value) {
+        // Move the value instead of cloning.
+        let _ = set_path(table, &self.segments, self.value);
+    }
+}
+
+struct RemovePath {
+    segments: Vec<PathSegment>,
+}
+
+impl<'a> UpdateTables<'a, Value, Result<Option<Value>, JsonPathError>> for RemovePath {
+    fn apply_first(&mut self, table: &'a mut Value) -> Result<Option<Value>, JsonPathError> {
+        remove_path(table, &self.segments)
+    }
+    fn apply_second(mut self, table: &mut Value) {
+        let _ = self.apply_first(table);
+    }
+}
+
+struct MergePatch {
+    patch: Value,
+}
+
+impl<'a> UpdateTables<'a, Value, ()> for MergePatch {
+    fn apply_first(&mut self, table: &'a mut Value) {
+        merge_patch(table, &self.patch)
+    }
+    fn apply_second(mut self, table: &mut Value) {
+        self.apply_first(table);
+    }
+}
+
+/// Implementation of a json `Value` for use in the active_standby model.
+/// `lockless::AsLockHandle`, should function similarly to
+/// `Arc<RwLock<serde_json::Value>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(Value);
+
+    impl<'w> AsLockWriteGuard<'w> {
+        /// Set the value at `path` (e.g. `a.b[2]`), returning the value that
+        /// was previously there. Missing object keys along the path are
+        /// created. An array index may point one past the end of the array to
+        /// push a new element.
+        ///
+        /// On error the table is left unchanged, and no update is recorded.
+        pub fn set_path(
+            &mut self,
+            path: &str,
+            value: Value,
+        ) -> Result<Option<Value>, JsonPathError> {
+            let segments = parse_path(path)?;
+            check_set_path(self, &segments)?;
+            self.guard.update_tables(SetPath { segments, value })
+        }
+
+        /// Remove the value at `path` (e.g. `a.b[2]`), returning it if it
+        /// existed.
+        pub fn remove_path(&mut self, path: &str) -> Result<Option<Value>, JsonPathError> {
+            let segments = parse_path(path)?;
+            self.guard.update_tables(RemovePath { segments })
+        }
+
+        /// Apply a JSON Merge Patch (RFC 7396) to the table.
+        pub fn merge_patch(&mut self, patch: Value) {
+            self.guard.update_tables(MergePatch { patch })
+        }
+    }
+}
+
+/// Implementation of a json `Value` for use in the active_standby model.
+/// `sync::AsLock`, should function similarly to `RwLock<serde_json::Value>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(Value);
+
+    impl<'w> AsLockWriteGuard<'w> {
+        /// Set the value at `path` (e.g. `a.b[2]`), returning the value that
+        /// was previously there. Missing object keys along the path are
+        /// created. An array index may point one past the end of the array to
+        /// push a new element.
+        ///
+        /// On error the table is left unchanged, and no update is recorded.
+        pub fn set_path(
+            &mut self,
+            path: &str,
+            value: Value,
+        ) -> Result<Option<Value>, JsonPathError> {
+            let segments = parse_path(path)?;
+            check_set_path(self, &segments)?;
+            self.guard.update_tables(SetPath { segments, value })
+        }
+
+        /// Remove the value at `path` (e.g. `a.b[2]`), returning it if it
+        /// existed.
+        pub fn remove_path(&mut self, path: &str) -> Result<Option<Value>, JsonPathError> {
+            let segments = parse_path(path)?;
+            self.guard.update_tables(RemovePath { segments })
+        }
+
+        /// Apply a JSON Merge Patch (RFC 7396) to the table.
+        pub fn merge_patch(&mut self, patch: Value) {
+            self.guard.update_tables(MergePatch { patch })
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(Value);
+
+    impl<'w> AsArcLockWriteGuard<'w> {
+        /// Set the value at `path` (e.g. `a.b[2]`), returning the value that
+        /// was previously there. Missing object keys along the path are
+        /// created. An array index may point one past the end of the array to
+        /// push a new element.
+        ///
+        /// On error the table is left unchanged, and no update is recorded.
+        pub fn set_path(
+            &mut self,
+            path: &str,
+            value: Value,
+        ) -> Result<Option<Value>, JsonPathError> {
+            let segments = parse_path(path)?;
+            check_set_path(self, &segments)?;
+            self.guard.update_tables(SetPath { segments, value })
+        }
+
+        /// Remove the value at `path` (e.g. `a.b[2]`), returning it if it
+        /// existed.
+        pub fn remove_path(&mut self, path: &str) -> Result<Option<Value>, JsonPathError> {
+            let segments = parse_path(path)?;
+            self.guard.update_tables(RemovePath { segments })
+        }
+
+        /// Apply a JSON Merge Patch (RFC 7396) to the table.
+        pub fn merge_patch(&mut self, patch: Value) {
+            self.guard.update_tables(MergePatch { patch })
+        }
+    }
+}
+
+#[cfg(test)]
+mod parse_test {
+    use super::*;
+
+    #[test]
+    fn parse_path_segments() {
+        assert_eq!(
+            parse_path("a.b[2]"),
+            Ok(vec![
+                PathSegment::Key("a".to_string()),
+                PathSegment::Key("b".to_string()),
+                PathSegment::Index(2),
+            ])
+        );
+        assert_eq!(
+            parse_path("[0][1].c"),
+            Ok(vec![
+                PathSegment::Index(0),
+                PathSegment::Index(1),
+                PathSegment::Key("c".to_string()),
+            ])
+        );
+
+        for path in ["", "a..b", "a.[1]", "a[", "a[x]", "a[1]b", "a."] {
+            assert_eq!(
+                parse_path(path),
+                Err(JsonPathError::InvalidPath(path.to_string()))
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+    use serde_json::json;
+
+    #[test]
+    fn set_path() {
+        let table = lockless::AsLockHandle::new(json!({"a": {"b": [1, 2, 3]}}));
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.set_path("a.b[2]", json!(30)), Ok(Some(json!(3))));
+            assert_eq!(wg.set_path("a.b[3]", json!(4)), Ok(None));
+            assert_eq!(wg.set_path("x.y", json!("new")), Ok(None));
+            assert_eq!(
+                wg.set_path("a.b[9]", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 2, len: 4 })
+            );
+            assert_eq!(
+                wg.set_path("a.b.c", json!(0)),
+                Err(JsonPathError::TypeMismatch { segment: 2 })
+            );
+        }
+        assert_tables_eq!(table, json!({"a": {"b": [1, 2, 30, 4]}, "x": {"y": "new"}}));
+    }
+
+    #[test]
+    fn set_path_error_leaves_table() {
+        let table = lockless::AsLockHandle::new(json!({"a": [1]}));
+        {
+            let mut wg = table.write();
+            assert_eq!(
+                wg.set_path("x[1]", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 1, len: 0 })
+            );
+            assert_eq!(
+                wg.set_path("x.y[3].z", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 2, len: 0 })
+            );
+            assert_eq!(
+                wg.set_path("x.y[0][0]", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 2, len: 0 })
+            );
+            assert_eq!(
+                wg.set_path("a[0].b", json!(0)),
+                Err(JsonPathError::TypeMismatch { segment: 2 })
+            );
+            assert_eq!(wg.num_pending_ops(), 0);
+
+            // A new array can only be pushed to.
+            assert_eq!(wg.set_path("x.y[0]", json!(0)), Ok(None));
+        }
+        assert_tables_eq!(table, json!({"a": [1], "x": {"y": [0]}}));
+    }
+
+    #[test]
+    fn remove_path() {
+        let table = lockless::AsLockHandle::new(json!({"a": {"b": [1, 2, 3]}, "c": 1}));
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.remove_path("a.b[0]"), Ok(Some(json!(1))));
+            assert_eq!(wg.remove_path("c"), Ok(Some(json!(1))));
+            assert_eq!(wg.remove_path("missing.key"), Ok(None));
+        }
+        assert_tables_eq!(table, json!({"a": {"b": [2, 3]}}));
+    }
+
+    #[test]
+    fn merge_patch() {
+        let table = lockless::AsLockHandle::new(json!({
+            "title": "Goodbye!",
+            "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"],
+        }));
+        table.write().merge_patch(json!({
+            "title": "Hello!",
+            "author": {"familyName": null},
+            "tags": ["example"],
+        }));
+        assert_tables_eq!(
+            table,
+            json!({
+                "title": "Hello!",
+                "author": {"givenName": "John"},
+                "tags": ["example"],
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+    use serde_json::json;
+
+    #[test]
+    fn set_path() {
+        let table = sync::AsLock::new(json!({"a": {"b": [1, 2, 3]}}));
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.set_path("a.b[2]", json!(30)), Ok(Some(json!(3))));
+            assert_eq!(wg.set_path("a.b[3]", json!(4)), Ok(None));
+            assert_eq!(wg.set_path("x.y", json!("new")), Ok(None));
+            assert_eq!(
+                wg.set_path("a.b[9]", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 2, len: 4 })
+            );
+        }
+        assert_tables_eq!(table, json!({"a": {"b": [1, 2, 30, 4]}, "x": {"y": "new"}}));
+    }
+
+    #[test]
+    fn set_path_error_leaves_table() {
+        let table = sync::AsLock::new(json!({"a": [1]}));
+        {
+            let mut wg = table.write();
+            assert_eq!(
+                wg.set_path("x[1]", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 1, len: 0 })
+            );
+            assert_eq!(
+                wg.set_path("x.y[3].z", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 2, len: 0 })
+            );
+            assert_eq!(
+                wg.set_path("x.y[0][0]", json!(0)),
+                Err(JsonPathError::IndexOutOfBounds { segment: 2, len: 0 })
+            );
+            assert_eq!(
+                wg.set_path("a[0].b", json!(0)),
+                Err(JsonPathError::TypeMismatch { segment: 2 })
+            );
+            assert_eq!(wg.num_pending_ops(), 0);
+
+            // A new array can only be pushed to.
+            assert_eq!(wg.set_path("x.y[0]", json!(0)), Ok(None));
+        }
+        assert_tables_eq!(table, json!({"a": [1], "x": {"y": [0]}}));
+    }
+
+    #[test]
+    fn remove_path() {
+        let table = sync::AsLock::new(json!({"a": {"b": [1, 2, 3]}, "c": 1}));
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.remove_path("a.b[0]"), Ok(Some(json!(1))));
+            assert_eq!(wg.remove_path("c"), Ok(Some(json!(1))));
+        }
+        assert_tables_eq!(table, json!({"a": {"b": [2, 3]}}));
+    }
+
+    #[test]
+    fn merge_patch() {
+        let table = sync::AsLock::new(json!({"a": {"b": 1, "c": 2}}));
+        table.write().merge_patch(json!({"a": {"b": null, "d": 3}}));
+        assert_tables_eq!(table, json!({"a": {"c": 2, "d": 3}}));
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn set_path() {
+        let table = arc::AsArcLock::new(json!({"a": [1, 2]}));
+        assert_eq!(
+            table.write().set_path("a[1]", json!(20)),
+            Ok(Some(json!(2)))
+        );
+        assert_eq!(**table.read(), json!({"a": [1, 20]}));
+        assert_eq!(*table.write(), json!({"a": [1, 20]}));
+    }
+}
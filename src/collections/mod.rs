@@ -1,5 +1,85 @@
+use std::collections::HashSet;
+
+pub mod binaryheap;
+pub mod bitset;
 pub mod btreemap;
 pub mod btreeset;
+pub mod budgetmap;
+pub mod eventlog;
 pub mod hashmap;
 pub mod hashset;
+pub mod indexedvec;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(test)]
+mod parity;
+pub mod string;
 pub mod vec;
+pub mod vecdeque;
+
+/// Error returned by the map guards' `try_insert` when the key is already in
+/// the table. Holds the key and value which weren't inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccupiedError<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+impl<K: std::fmt::Debug, V> std::fmt::Display for OccupiedError<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "key {:?} is already in the table", self.key)
+    }
+}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug> std::error::Error for OccupiedError<K, V> {}
+
+/// Small deterministic PRNG (splitmix64), so that randomized updates can be
+/// replayed from their seed without depending on an external crate.
+#[derive(Debug, Clone)]
+pub(crate) struct Prng(u64);
+
+impl Prng {
+    pub(crate) fn new(seed: u64) -> Prng {
+        Prng(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub(crate) fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.below(i + 1));
+        }
+    }
+
+    /// Picks `min(n, len)` of the `len` elements of a table, using Floyd's
+    /// algorithm so that the cost is in `n` rather than `len`. The returned
+    /// closure is called once per element, in order, and returns whether that
+    /// element was picked.
+    pub(crate) fn sample(&mut self, len: usize, n: usize) -> impl FnMut() -> bool {
+        let mut picked = HashSet::with_capacity(n.min(len));
+        for j in len - n.min(len)..len {
+            let index = self.below(j + 1);
+            if !picked.insert(index) {
+                picked.insert(j);
+            }
+        }
+
+        let mut index = 0;
+        move || {
+            index += 1;
+            picked.contains(&(index - 1))
+        }
+    }
+}
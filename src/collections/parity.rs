@@ -0,0 +1,190 @@
+//! Checks that the mutators on the collections' write guards line up with the
+//! std collections, so that moving from `RwLock<std collection>` is mostly a
+//! mechanical change.
+//!
+//! Each call is made on a std collection and on the write guard of each
+//! flavor, and the return values and resulting tables are compared. Since
+//! the same tokens are compiled against both, a signature which drifts from
+//! std fails to compile here. Where a guard deliberately differs, e.g.
+//! taking keys by value because they are moved into the recorded update, the
+//! std call is given separately.
+
+use crate::assert_tables_eq;
+use crate::collections::{btreemap, btreeset, hashmap, hashset, vec, vecdeque};
+use maplit::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+macro_rules! assert_parity {
+    ($std:ident, $($table:ident),+ => |$t:ident| $call:expr) => {
+        assert_parity!($std, $($table),+ => |$t| $call, $call)
+    };
+    ($std:ident, $($table:ident),+ => |$t:ident| $std_call:expr, $call:expr) => {{
+        {
+            let $t = &mut $std;
+            let expected = $std_call;
+            $({
+                let mut wg = $table.write();
+                let $t = &mut wg;
+                assert_eq!($call, expected);
+            })+
+        }
+        $(assert_tables_eq!($table, $std);)+
+    }};
+}
+
+#[test]
+fn vec() {
+    let mut std = vec![3, 1, 2];
+    let lockless = vec::lockless::AsLockHandle::new(std.clone());
+    let sync = vec::sync::AsLock::new(std.clone());
+
+    assert_parity!(std, lockless, sync => |t| t.push(4));
+    assert_parity!(std, lockless, sync => |t| t.extend_from_slice(&[5, 5]));
+    assert_parity!(std, lockless, sync => |t| t.append(&mut vec![6]));
+    assert_parity!(std, lockless, sync => |t| t.extend([8, 9]));
+    assert_parity!(std, lockless, sync => |t| t.insert(0, 7));
+    assert_parity!(std, lockless, sync => |t| t.pop());
+    assert_parity!(std, lockless, sync => |t| t.remove(1));
+    assert_parity!(std, lockless, sync => |t| t.swap_remove(0));
+    assert_parity!(std, lockless, sync => |t| t.extend_from_within(..2));
+    assert_parity!(std, lockless, sync => |t| t.dedup_by_key(|x| *x / 2));
+    assert_parity!(std, lockless, sync => |t| t.retain(|x| *x != 1));
+    assert_parity!(std, lockless, sync => |t| t.sort());
+    assert_parity!(std, lockless, sync => |t| t.dedup());
+    assert_parity!(std, lockless, sync => |t| t.reverse());
+    assert_parity!(std, lockless, sync => |t| t.resize_with(6, Default::default));
+    assert_parity!(std, lockless, sync => |t| t.truncate(4));
+    assert_parity!(std, lockless, sync => |t| t.reserve(10));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to(2));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to_fit());
+    assert_parity!(std, lockless, sync => |t| t.clear());
+}
+
+#[test]
+fn vecdeque() {
+    let mut std = VecDeque::from([3, 1, 2]);
+    let lockless = vecdeque::lockless::AsLockHandle::new(std.clone());
+    let sync = vecdeque::sync::AsLock::new(std.clone());
+
+    assert_parity!(std, lockless, sync => |t| t.push_back(4));
+    assert_parity!(std, lockless, sync => |t| t.push_front(5));
+    assert_parity!(std, lockless, sync => |t| t.append(&mut VecDeque::from([6])));
+    assert_parity!(std, lockless, sync => |t| t.extend([8, 9]));
+    assert_parity!(std, lockless, sync => |t| t.insert(1, 7));
+    assert_parity!(std, lockless, sync => |t| t.pop_back());
+    assert_parity!(std, lockless, sync => |t| t.pop_front());
+    assert_parity!(std, lockless, sync => |t| t.remove(1));
+    assert_parity!(std, lockless, sync => |t| t.swap_remove_back(0));
+    assert_parity!(std, lockless, sync => |t| t.swap_remove_front(2));
+    assert_parity!(std, lockless, sync => |t| t.swap(0, 1));
+    assert_parity!(std, lockless, sync => |t| t.rotate_left(1));
+    assert_parity!(std, lockless, sync => |t| t.rotate_right(2));
+    assert_parity!(std, lockless, sync => |t| t.retain(|x| *x != 1));
+    assert_parity!(std, lockless, sync => |t| t.resize_with(6, Default::default));
+    assert_parity!(std, lockless, sync => |t| t.truncate(4));
+    assert_parity!(std, lockless, sync => |t| t.reserve(10));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to(2));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to_fit());
+    assert_parity!(std, lockless, sync => |t| t.clear());
+}
+
+#[test]
+fn hashmap() {
+    let mut std: HashMap<&str, i32> = hashmap! { "a" => 1, "b" => 2 };
+    let lockless = hashmap::lockless::AsLockHandle::new(std.clone());
+    let sync = hashmap::sync::AsLock::new(std.clone());
+
+    assert_parity!(std, lockless, sync => |t| t.insert("c", 3));
+    assert_parity!(std, lockless, sync => |t| t.insert("a", 4));
+    assert_parity!(std, lockless, sync => |t| t.extend([("a", 5), ("e", 6)]));
+    assert_parity!(std, lockless, sync =>
+        |t| *t.entry("d").or_insert_with(|| 5),
+        *t.get_or_insert_with("d", || 5));
+    assert_parity!(std, lockless, sync =>
+        |t| *t.entry("d").or_insert_with(|| 6),
+        *t.get_or_insert_with("d", || 6));
+    assert_parity!(std, lockless, sync => |t| *t.entry("d").and_modify(|v| *v += 1).or_insert(0));
+    assert_parity!(std, lockless, sync => |t| *t.entry("f").or_default());
+    assert_parity!(std, lockless, sync =>
+        |t| t.get_mut(&"a").map(|v| { *v += 1; *v }),
+        t.get_mut_with("a", |v| *v += 1).copied());
+    assert_parity!(std, lockless, sync => |t| t.remove(&"b"), t.remove("b"));
+    assert_parity!(std, lockless, sync => |t| t.remove_entry(&"c"), t.remove_entry("c"));
+    assert_parity!(std, lockless, sync => |t| t.retain(|_, v| *v != 5));
+    assert_parity!(std, lockless, sync => |t| t.reserve(10));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to(2));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to_fit());
+    assert_parity!(std, lockless, sync => |t| t.clear());
+}
+
+#[test]
+fn hashset() {
+    let mut std: HashSet<&str> = hashset! { "a", "b" };
+    let lockless = hashset::lockless::AsLockHandle::new(std.clone());
+    let sync = hashset::sync::AsLock::new(std.clone());
+
+    assert_parity!(std, lockless, sync => |t| t.insert("c"));
+    assert_parity!(std, lockless, sync => |t| t.insert("a"));
+    assert_parity!(std, lockless, sync => |t| t.replace("a"));
+    assert_parity!(std, lockless, sync => |t| t.extend(["e", "f"]));
+    // `HashSet::get_or_insert` isn't stable yet.
+    assert_parity!(std, lockless, sync =>
+        |t| { t.insert("d"); *t.get("d").unwrap() },
+        *t.get_or_insert("d"));
+    assert_parity!(std, lockless, sync => |t| t.remove(&"b"), t.remove("b"));
+    assert_parity!(std, lockless, sync => |t| t.take(&"c"), t.take("c"));
+    assert_parity!(std, lockless, sync => |t| t.retain(|v| *v != "d"));
+    assert_parity!(std, lockless, sync => |t| t.reserve(10));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to(2));
+    assert_parity!(std, lockless, sync => |t| t.shrink_to_fit());
+    assert_parity!(std, lockless, sync => |t| t.clear());
+}
+
+#[test]
+fn btreemap() {
+    let mut std: BTreeMap<&str, i32> = btreemap! { "a" => 1, "b" => 2 };
+    let lockless = btreemap::lockless::AsLockHandle::new(std.clone());
+    let sync = btreemap::sync::AsLock::new(std.clone());
+
+    assert_parity!(std, lockless, sync => |t| t.insert("c", 3));
+    assert_parity!(std, lockless, sync => |t| t.insert("a", 4));
+    assert_parity!(std, lockless, sync => |t| *t.entry("d").or_insert(5));
+    assert_parity!(std, lockless, sync => |t| *t.entry("d").or_insert_with(|| 6));
+    assert_parity!(std, lockless, sync => |t| *t.entry("e").or_default());
+    assert_parity!(std, lockless, sync =>
+        |t| *t.entry("h").or_insert_with(|| 3),
+        *t.get_or_insert_with("h", || 3));
+    assert_parity!(std, lockless, sync =>
+        |t| t.get_mut(&"a").map(|v| { *v += 1; *v }),
+        t.get_mut_with("a", |v| *v += 1).copied());
+    assert_parity!(std, lockless, sync => |t| t.extend([("a", 8), ("g", 9)]));
+    assert_parity!(std, lockless, sync => |t| t.remove(&"b"), t.remove("b"));
+    assert_parity!(std, lockless, sync => |t| t.remove_entry(&"c"), t.remove_entry("c"));
+    assert_parity!(std, lockless, sync =>
+        |t| t.append(&mut btreemap! { "f" => 7 }),
+        t.append(btreemap! { "f" => 7 }));
+    assert_parity!(std, lockless, sync => |t| t.retain(|_, v| *v != 5));
+    assert_parity!(std, lockless, sync => |t| { let _ = t; }, t.shrink_to_fit());
+    assert_parity!(std, lockless, sync => |t| t.clear());
+}
+
+#[test]
+fn btreeset() {
+    let mut std: BTreeSet<i32> = btreeset! { 1, 2, 3 };
+    let lockless = btreeset::lockless::AsLockHandle::new(std.clone());
+    let sync = btreeset::sync::AsLock::new(std.clone());
+
+    assert_parity!(std, lockless, sync => |t| t.insert(4));
+    assert_parity!(std, lockless, sync => |t| t.insert(1));
+    assert_parity!(std, lockless, sync => |t| t.replace(2));
+    assert_parity!(std, lockless, sync => |t| t.extend([1, 7]));
+    assert_parity!(std, lockless, sync => |t| t.remove(&3), t.remove(3));
+    assert_parity!(std, lockless, sync => |t| t.take(&4), t.take(4));
+    assert_parity!(std, lockless, sync =>
+        |t| t.append(&mut btreeset! { 5, 6 }),
+        t.append(btreeset! { 5, 6 }));
+    assert_parity!(std, lockless, sync => |t| t.pop_first());
+    assert_parity!(std, lockless, sync => |t| t.pop_last());
+    assert_parity!(std, lockless, sync => |t| t.retain(|v| *v != 5));
+    assert_parity!(std, lockless, sync => |t| t.clear());
+}
@@ -0,0 +1,486 @@
+use crate::UpdateTables;
+use std::ops::RangeBounds;
+
+// Most updates only borrow their arguments, so they are recorded as closures
+// which hold onto them for the second table, instead of cloning per table.
+
+struct Retain<F> {
+    f: F,
+}
+
+impl<'a, F> UpdateTables<'a, String, ()> for Retain<F>
+where
+    F: 'static + Clone + FnMut(char) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut String) {
+        table.retain(self.f.clone())
+    }
+    fn apply_second(self, table: &mut String) {
+        table.retain(self.f)
+    }
+}
+
+struct Drain<R> {
+    range: R,
+}
+
+impl<'a, R> UpdateTables<'a, String, std::string::Drain<'a>> for Drain<R>
+where
+    R: 'static + Clone + RangeBounds<usize>,
+{
+    fn apply_first(&mut self, table: &'a mut String) -> std::string::Drain<'a> {
+        table.drain(self.range.clone())
+    }
+    fn apply_second(self, table: &mut String) {
+        table.drain(self.range);
+    }
+}
+
+/// Implementation of String for use in the active_standby model.
+/// `lockless::AsLockHandle`, should function similarly to
+/// `Arc<RwLock<String>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(String);
+
+    impl<'w> AsLockWriteGuard<'w> {
+        pub fn push(&mut self, ch: char) {
+            self.guard
+                .update_tables_closure(move |table| table.push(ch))
+        }
+
+        pub fn push_str(&mut self, string: &str) {
+            let string = string.to_owned();
+            self.guard
+                .update_tables_closure(move |table| table.push_str(&string))
+        }
+
+        pub fn insert(&mut self, index: usize, ch: char) {
+            self.guard
+                .update_tables_closure(move |table| table.insert(index, ch))
+        }
+
+        pub fn insert_str(&mut self, index: usize, string: &str) {
+            let string = string.to_owned();
+            self.guard
+                .update_tables_closure(move |table| table.insert_str(index, &string))
+        }
+
+        pub fn pop(&mut self) -> Option<char> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        pub fn remove(&mut self, index: usize) -> char {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn truncate(&mut self, new_len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(new_len))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            let replace_with = replace_with.to_owned();
+            self.guard.update_tables_closure(move |table| {
+                table.replace_range(range.clone(), &replace_with)
+            })
+        }
+
+        /// Replace the whole string with `string`.
+        pub fn set(&mut self, string: &str) {
+            self.replace_range(.., string)
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(char) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+    }
+
+    impl<'w, 'a> AsLockWriteGuard<'w> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::string::Drain<'a>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+    }
+
+    impl<'w> Extend<char> for AsLockWriteGuard<'w> {
+        fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+            self.push_str(&iter.into_iter().collect::<String>())
+        }
+    }
+
+    impl<'w, 's> Extend<&'s str> for AsLockWriteGuard<'w> {
+        fn extend<I: IntoIterator<Item = &'s str>>(&mut self, iter: I) {
+            self.push_str(&iter.into_iter().collect::<String>())
+        }
+    }
+}
+
+/// Implementation of String for use in the active_standby model.
+/// `sync::AsLock`, should function similarly to `RwLock<String>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(String);
+
+    impl<'w> AsLockWriteGuard<'w> {
+        pub fn push(&mut self, ch: char) {
+            self.guard
+                .update_tables_closure(move |table| table.push(ch))
+        }
+
+        pub fn push_str(&mut self, string: &str) {
+            let string = string.to_owned();
+            self.guard
+                .update_tables_closure(move |table| table.push_str(&string))
+        }
+
+        pub fn insert(&mut self, index: usize, ch: char) {
+            self.guard
+                .update_tables_closure(move |table| table.insert(index, ch))
+        }
+
+        pub fn insert_str(&mut self, index: usize, string: &str) {
+            let string = string.to_owned();
+            self.guard
+                .update_tables_closure(move |table| table.insert_str(index, &string))
+        }
+
+        pub fn pop(&mut self) -> Option<char> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        pub fn remove(&mut self, index: usize) -> char {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn truncate(&mut self, new_len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(new_len))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            let replace_with = replace_with.to_owned();
+            self.guard.update_tables_closure(move |table| {
+                table.replace_range(range.clone(), &replace_with)
+            })
+        }
+
+        /// Replace the whole string with `string`.
+        pub fn set(&mut self, string: &str) {
+            self.replace_range(.., string)
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(char) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+    }
+
+    impl<'w, 'a> AsLockWriteGuard<'w> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::string::Drain<'a>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+    }
+
+    impl<'w> Extend<char> for AsLockWriteGuard<'w> {
+        fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+            self.push_str(&iter.into_iter().collect::<String>())
+        }
+    }
+
+    impl<'w, 's> Extend<&'s str> for AsLockWriteGuard<'w> {
+        fn extend<I: IntoIterator<Item = &'s str>>(&mut self, iter: I) {
+            self.push_str(&iter.into_iter().collect::<String>())
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(String);
+
+    impl<'w> AsArcLockWriteGuard<'w> {
+        pub fn push(&mut self, ch: char) {
+            self.guard
+                .update_tables_closure(move |table| table.push(ch))
+        }
+
+        pub fn push_str(&mut self, string: &str) {
+            let string = string.to_owned();
+            self.guard
+                .update_tables_closure(move |table| table.push_str(&string))
+        }
+
+        pub fn insert(&mut self, index: usize, ch: char) {
+            self.guard
+                .update_tables_closure(move |table| table.insert(index, ch))
+        }
+
+        pub fn insert_str(&mut self, index: usize, string: &str) {
+            let string = string.to_owned();
+            self.guard
+                .update_tables_closure(move |table| table.insert_str(index, &string))
+        }
+
+        pub fn pop(&mut self) -> Option<char> {
+            self.guard.update_tables_closure(|table| table.pop())
+        }
+
+        pub fn remove(&mut self, index: usize) -> char {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn truncate(&mut self, new_len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(new_len))
+        }
+
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            let replace_with = replace_with.to_owned();
+            self.guard.update_tables_closure(move |table| {
+                table.replace_range(range.clone(), &replace_with)
+            })
+        }
+
+        /// Replace the whole string with `string`.
+        pub fn set(&mut self, string: &str) {
+            self.replace_range(.., string)
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(char) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+    }
+
+    impl<'w, 'a> AsArcLockWriteGuard<'w> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::string::Drain<'a>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+    }
+
+    impl<'w> Extend<char> for AsArcLockWriteGuard<'w> {
+        fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+            self.push_str(&iter.into_iter().collect::<String>())
+        }
+    }
+
+    impl<'w, 's> Extend<&'s str> for AsArcLockWriteGuard<'w> {
+        fn extend<I: IntoIterator<Item = &'s str>>(&mut self, iter: I) {
+            self.push_str(&iter.into_iter().collect::<String>())
+        }
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn push_and_insert() {
+        let table = lockless::AsLockHandle::new("b".to_string());
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            wg.push_str("cd");
+            wg.push('e');
+            wg.insert(0, 'a');
+            wg.insert_str(5, "fg");
+            assert_eq!(*wg, "abcdefg");
+            assert_eq!(*table2.read(), "b");
+        }
+        assert_tables_eq!(table, "abcdefg");
+
+        table.write().extend(['h', 'i']);
+        table.write().extend(["jk", "l"]);
+        assert_tables_eq!(table, "abcdefghijkl");
+    }
+
+    #[test]
+    fn remove() {
+        let table = lockless::AsLockHandle::new("hello, world".to_string());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.pop(), Some('d'));
+            assert_eq!(wg.remove(0), 'h');
+            assert_eq!(wg.drain(..4).collect::<String>(), "ello");
+            wg.retain(|ch| ch != ' ');
+        }
+        assert_tables_eq!(table, ",worl");
+
+        table.write().truncate(1);
+        assert_tables_eq!(table, ",");
+        table.write().clear();
+        assert_tables_eq!(table, "");
+    }
+
+    #[test]
+    fn replace_range() {
+        let table = lockless::AsLockHandle::new("log_level=info".to_string());
+        table.write().replace_range(10.., "debug");
+        assert_tables_eq!(table, "log_level=debug");
+
+        table.write().set("log_level=warn");
+        assert_tables_eq!(table, "log_level=warn");
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn push_and_insert() {
+        let table = sync::AsLock::new("b".to_string());
+        {
+            let mut wg = table.write();
+            wg.push_str("cd");
+            wg.push('e');
+            wg.insert(0, 'a');
+            wg.insert_str(5, "fg");
+            assert_eq!(*wg, "abcdefg");
+            assert_eq!(*table.read(), "b");
+        }
+        assert_tables_eq!(table, "abcdefg");
+
+        table.write().extend(['h', 'i']);
+        table.write().extend(["jk", "l"]);
+        assert_tables_eq!(table, "abcdefghijkl");
+    }
+
+    #[test]
+    fn remove() {
+        let table = sync::AsLock::new("hello, world".to_string());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.pop(), Some('d'));
+            assert_eq!(wg.remove(0), 'h');
+            assert_eq!(wg.drain(..4).collect::<String>(), "ello");
+            wg.retain(|ch| ch != ' ');
+        }
+        assert_tables_eq!(table, ",worl");
+
+        table.write().truncate(1);
+        assert_tables_eq!(table, ",");
+        table.write().clear();
+        assert_tables_eq!(table, "");
+    }
+
+    #[test]
+    fn replace_range() {
+        let table = sync::AsLock::new("log_level=info".to_string());
+        table.write().replace_range(10.., "debug");
+        assert_tables_eq!(table, "log_level=debug");
+
+        table.write().set("log_level=warn");
+        assert_tables_eq!(table, "log_level=warn");
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn push_and_insert() {
+        let table = arc::AsArcLock::new("b".to_string());
+        {
+            let mut wg = table.write();
+            wg.push_str("cd");
+            wg.insert(0, 'a');
+            assert_eq!(**table.read(), "b");
+        }
+        assert_eq!(**table.read(), "abcd");
+        table.write().push('e');
+        assert_eq!(*table.write(), "abcde");
+        assert_eq!(**table.read(), "abcde");
+    }
+}
@@ -1,5 +1,9 @@
+use crate::collections::Prng;
 use crate::UpdateTables;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
 // use std::collections::TryReserveError;
+use std::fmt;
 use std::ops::RangeBounds;
 
 // Define the functions that the active_standby vector will have. Note that we
@@ -40,6 +44,24 @@ where
     }
 }
 
+struct Set<T> {
+    index: usize,
+    element: T,
+}
+
+impl<'a, T> UpdateTables<'a, Vec<T>, ()> for Set<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut Vec<T>) {
+        table[self.index] = self.element.clone();
+    }
+    fn apply_second(self, table: &mut Vec<T>) {
+        // Move the value instead of cloning.
+        table[self.index] = self.element;
+    }
+}
+
 struct Append<T> {
     value: Vec<T>,
 }
@@ -106,8 +128,26 @@ where
     }
 }
 
+struct ExtractRange<R> {
+    range: R,
+}
+
+impl<'a, T, R> UpdateTables<'a, Vec<T>, Vec<T>> for ExtractRange<R>
+where
+    R: 'static + Clone + RangeBounds<usize>,
+{
+    fn apply_first(&mut self, table: &'a mut Vec<T>) -> Vec<T> {
+        table.drain(self.range.clone()).collect()
+    }
+    fn apply_second(self, table: &mut Vec<T>) {
+        table.drain(self.range);
+    }
+}
+
 struct Retain<F> {
     f: F,
+    // Whether to also shrink the table, for `retain_and_shrink`.
+    shrink: bool,
 }
 
 impl<'a, T, F> UpdateTables<'a, Vec<T>, ()> for Retain<F>
@@ -115,10 +155,32 @@ where
     F: 'static + Clone + FnMut(&T) -> bool,
 {
     fn apply_first(&mut self, table: &'a mut Vec<T>) {
-        table.retain(self.f.clone())
+        table.retain(self.f.clone());
+        if self.shrink {
+            table.shrink_to_fit();
+        }
     }
     fn apply_second(self, table: &mut Vec<T>) {
-        table.retain(self.f)
+        table.retain(self.f);
+        if self.shrink {
+            table.shrink_to_fit();
+        }
+    }
+}
+
+struct ExtractIf<F> {
+    f: F,
+}
+
+impl<'a, T, F> UpdateTables<'a, Vec<T>, Vec<T>> for ExtractIf<F>
+where
+    F: 'static + Clone + FnMut(&mut T) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut Vec<T>) -> Vec<T> {
+        table.extract_if(.., self.f.clone()).collect()
+    }
+    fn apply_second(mut self, table: &mut Vec<T>) {
+        table.retain_mut(|t| !(self.f)(t))
     }
 }
 
@@ -171,6 +233,212 @@ where
     }
 }
 
+/// Error returned when staging an edit in a `VecEditPlan`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VecEditPlanError {
+    /// The index is past the end of the table (or past its length for
+    /// `set`/`remove`).
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The element at this index was already set or removed in this plan.
+    ConflictingEdit { index: usize },
+}
+
+impl fmt::Display for VecEditPlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VecEditPlanError::IndexOutOfBounds { index, len } => {
+                write!(f, "index {} is out of bounds (len {})", index, len)
+            }
+            VecEditPlanError::ConflictingEdit { index } => {
+                write!(f, "index {} was already edited in this plan", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VecEditPlanError {}
+
+pub struct ApplyPlan<T> {
+    // Elements to insert before the element at each index (or at the end for
+    // index == len), in the order they were staged.
+    inserts: BTreeMap<usize, Vec<T>>,
+    // `Some` replaces the element at the index, `None` removes it.
+    replacements: BTreeMap<usize, Option<T>>,
+}
+
+impl<T> ApplyPlan<T> {
+    /// Rebuild the table with the edits applied, returning the removed
+    /// elements. Returns them in index order.
+    fn apply(mut self, table: &mut Vec<T>) -> Vec<T> {
+        let num_inserts: usize = self.inserts.values().map(Vec::len).sum();
+        let old_table = std::mem::replace(table, Vec::with_capacity(table.len() + num_inserts));
+        let len = old_table.len();
+
+        let mut removed = vec![];
+        for (index, element) in old_table.into_iter().enumerate() {
+            if let Some(inserts) = self.inserts.remove(&index) {
+                table.extend(inserts);
+            }
+            match self.replacements.remove(&index) {
+                None => table.push(element),
+                Some(Some(replacement)) => table.push(replacement),
+                Some(None) => removed.push(element),
+            }
+        }
+        if let Some(inserts) = self.inserts.remove(&len) {
+            table.extend(inserts);
+        }
+        removed
+    }
+}
+
+impl<'a, T> UpdateTables<'a, Vec<T>, Vec<T>> for ApplyPlan<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut Vec<T>) -> Vec<T> {
+        ApplyPlan {
+            inserts: self.inserts.clone(),
+            replacements: self.replacements.clone(),
+        }
+        .apply(table)
+    }
+    fn apply_second(self, table: &mut Vec<T>) {
+        // Move the values instead of cloning.
+        self.apply(table);
+    }
+}
+
+mod sealed {
+    /// Implemented by the Vec write guards, so that `VecEditPlan` can be shared
+    /// between lockless and sync.
+    pub trait PlanTarget<T> {
+        fn table_len(&self) -> usize;
+        fn apply_plan(&mut self, plan: super::ApplyPlan<T>) -> Vec<T>;
+    }
+}
+
+/// A set of positional edits staged against a Vec write guard, created by
+/// `plan`. All indices refer to positions in the table as it was when the plan
+/// was created, so the caller doesn't need to adjust indices for earlier
+/// edits. The edits are validated as they are staged, and applied to the
+/// tables together on `commit`. Dropping the plan discards the edits.
+pub struct VecEditPlan<'g, G, T> {
+    guard: &'g mut G,
+    len: usize,
+    plan: ApplyPlan<T>,
+}
+
+impl<'g, G, T> VecEditPlan<'g, G, T>
+where
+    G: sealed::PlanTarget<T>,
+{
+    fn new(guard: &'g mut G) -> Self {
+        let len = guard.table_len();
+        VecEditPlan {
+            guard,
+            len,
+            plan: ApplyPlan {
+                inserts: BTreeMap::new(),
+                replacements: BTreeMap::new(),
+            },
+        }
+    }
+
+    fn replace(&mut self, index: usize, value: Option<T>) -> Result<&mut Self, VecEditPlanError> {
+        if index >= self.len {
+            return Err(VecEditPlanError::IndexOutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        match self.plan.replacements.entry(index) {
+            Entry::Occupied(_) => Err(VecEditPlanError::ConflictingEdit { index }),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                Ok(self)
+            }
+        }
+    }
+
+    /// Replace the element at `index`.
+    pub fn set(&mut self, index: usize, value: T) -> Result<&mut Self, VecEditPlanError> {
+        self.replace(index, Some(value))
+    }
+
+    /// Remove the element at `index`. It is returned by `commit`.
+    pub fn remove(&mut self, index: usize) -> Result<&mut Self, VecEditPlanError> {
+        self.replace(index, None)
+    }
+
+    /// Insert `value` before the element at `index`, or at the end of the
+    /// table if `index` is the table's length. Multiple values inserted at the
+    /// same index keep the order they were staged in.
+    pub fn insert(&mut self, index: usize, value: T) -> Result<&mut Self, VecEditPlanError> {
+        if index > self.len {
+            return Err(VecEditPlanError::IndexOutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        self.plan.inserts.entry(index).or_default().push(value);
+        Ok(self)
+    }
+
+    /// Apply the staged edits to the tables as a single update. Returns the
+    /// removed elements, ordered by their index.
+    pub fn commit(self) -> Vec<T> {
+        self.guard.apply_plan(self.plan)
+    }
+}
+
+/// Shared by lockless & sync `read_chunks`.
+fn read_chunks<G, T>(read: impl Fn() -> G, chunk_size: usize, mut f: impl FnMut(&[T]))
+where
+    G: std::ops::Deref<Target = Vec<T>>,
+{
+    assert_ne!(chunk_size, 0, "chunk_size must be positive");
+    let mut start = 0;
+    loop {
+        let table = read();
+        if start >= table.len() {
+            return;
+        }
+        let end = table.len().min(start + chunk_size);
+        f(&table[start..end]);
+        start = end;
+    }
+}
+
+/// Split `elements` into `num_shards` consecutive ranges, as evenly as
+/// possible. Shared by lockless & sync `shard`.
+fn split_shards<T>(mut elements: Vec<T>, num_shards: usize) -> Vec<Vec<T>> {
+    assert_ne!(num_shards, 0, "num_shards must be positive");
+    let (base, extra) = (elements.len() / num_shards, elements.len() % num_shards);
+    // Split from the back, so that each split only moves the elements after it.
+    let mut shards: Vec<_> = (0..num_shards)
+        .rev()
+        .map(|shard| {
+            let len = base + usize::from(shard < extra);
+            elements.split_off(elements.len() - len)
+        })
+        .collect();
+    shards.reverse();
+    shards
+}
+
+/// The shard holding `index`, and the index within it, given the lengths of
+/// the shards in order. Shared by lockless & sync `ShardedVec`.
+fn locate(lens: impl IntoIterator<Item = usize>, mut index: usize) -> Option<(usize, usize)> {
+    for (shard, len) in lens.into_iter().enumerate() {
+        if index < len {
+            return Some((shard, index));
+        }
+        index -= len;
+    }
+    None
+}
+
 /// Implementation of Vec for use in the active_standby model.
 /// `lockless::AsLockHandle<T>`, should function similarly to
 /// `Arc<RwLock<Vec<T>>>`.
@@ -178,6 +446,512 @@ pub mod lockless {
     use super::*;
     crate::generate_lockless_aslockhandle!(Vec<T>);
 
+    impl<T> AsLockHandle<T> {
+        /// Call `f` on consecutive chunks of at most `chunk_size` elements, taking a
+        /// new read guard for each chunk. This bounds how long a full scan
+        /// holds onto a single guard, so that it never blocks the writer for
+        /// the duration of the scan.
+        ///
+        /// Updates published between chunks are seen by the later chunks.
+        /// Chunks are taken by position, so inserting or removing elements
+        /// before the current position can cause elements to be skipped or
+        /// seen twice.
+        pub fn read_chunks(&self, chunk_size: usize, f: impl FnMut(&[T])) {
+            read_chunks(move || self.read(), chunk_size, f)
+        }
+    }
+
+    impl<T> AsLockHandle<T>
+    where
+        T: 'static + Clone + Send,
+    {
+        /// Move the elements into `num_shards` new tables, each holding a
+        /// consecutive range of them, and leave this table empty. See
+        /// `ShardedVec`.
+        ///
+        /// Panics if `num_shards` is 0.
+        pub fn shard(&self, num_shards: usize) -> ShardedVec<T> {
+            let elements = self.write().guard.update_tables_closure(std::mem::take);
+            ShardedVec {
+                shards: split_shards(elements, num_shards)
+                    .into_iter()
+                    .map(AsLockHandle::new)
+                    .collect(),
+            }
+        }
+    }
+
+    /// A Vec split by index range into shards, each a table of its own, for
+    /// tables which are too large to clone or replay onto as a whole. An
+    /// update to a shard is only replayed onto that shard. Created by `shard`.
+    ///
+    /// Reads are routed by index, using the current lengths of the shards.
+    /// Each shard is read under its own guard, so reads which span shards
+    /// aren't consistent with each other, and an update which changes the
+    /// length of a shard shifts the indices of the shards after it.
+    ///
+    /// Like `AsLockHandle`, each thread should get its own clone.
+    #[derive(Clone, Debug)]
+    pub struct ShardedVec<T> {
+        shards: Vec<AsLockHandle<T>>,
+    }
+
+    impl<T> ShardedVec<T> {
+        pub fn shards(&self) -> &[AsLockHandle<T>] {
+            &self.shards
+        }
+
+        /// The total number of elements across the shards.
+        pub fn len(&self) -> usize {
+            self.shards.iter().map(|shard| shard.read().len()).sum()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// The shard holding `index`, and the index of the element within it.
+        pub fn locate(&self, index: usize) -> Option<(usize, usize)> {
+            locate(self.shards.iter().map(|shard| shard.read().len()), index)
+        }
+
+        /// Clone the element at `index` out of the shard holding it.
+        pub fn get_cloned(&self, index: usize) -> Option<T>
+        where
+            T: Clone,
+        {
+            let (shard, index) = self.locate(index)?;
+            self.shards[shard].read().get(index).cloned()
+        }
+
+        /// Move the elements back into a single table, in order.
+        /// Other handles to the shards see them emptied.
+        pub fn merge(self) -> AsLockHandle<T>
+        where
+            T: 'static + Clone + Send,
+        {
+            let mut elements = vec![];
+            for shard in &self.shards {
+                elements.append(&mut shard.write().guard.update_tables_closure(std::mem::take));
+            }
+            AsLockHandle::new(elements)
+        }
+    }
+
+    impl<'w, T> sealed::PlanTarget<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn table_len(&self) -> usize {
+            self.len()
+        }
+        fn apply_plan(&mut self, plan: ApplyPlan<T>) -> Vec<T> {
+            self.guard.update_tables(plan)
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        pub fn push(&mut self, value: T) {
+            self.guard.update_tables(Push { value })
+        }
+
+        pub fn append(&mut self, other: &mut Vec<T>) {
+            self.guard.update_tables(Append {
+                value: std::mem::take(other),
+            })
+        }
+
+        pub fn extend_from_slice(&mut self, other: &[T]) {
+            self.guard.update_tables(Append {
+                value: other.to_vec(),
+            })
+        }
+
+        pub fn insert(&mut self, index: usize, element: T) {
+            self.guard.update_tables(Insert { index, element })
+        }
+
+        /// Set the element at `index` to `element`, unless they are already
+        /// equal, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        ///
+        /// Panics if `index` is out of bounds.
+        pub fn set_if_different(&mut self, index: usize, element: T) -> bool
+        where
+            T: PartialEq,
+        {
+            if self[index] == element {
+                return false;
+            }
+            self.guard.update_tables(Set { index, element });
+            true
+        }
+
+        /// Insert `value` into a sorted vector, keeping it sorted. Returns the
+        /// index `value` was inserted at. The index is found once, using
+        /// `binary_search`, and the same insert is replayed on the other table.
+        ///
+        /// If the vector isn't sorted, the position is unspecified.
+        pub fn insert_sorted(&mut self, value: T) -> usize
+        where
+            T: Ord,
+        {
+            let index = match self.binary_search(&value) {
+                Ok(index) | Err(index) => index,
+            };
+            self.guard.update_tables(Insert {
+                index,
+                element: value,
+            });
+            index
+        }
+
+        /// Remove an element equal to `value` from a sorted vector. Returns
+        /// whether an element was found and removed.
+        pub fn remove_sorted(&mut self, value: &T) -> bool
+        where
+            T: Ord,
+        {
+            match self.binary_search(value) {
+                Ok(index) => {
+                    self.guard.update_tables_closure(move |table| {
+                        table.remove(index);
+                    });
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+
+        /// Stage positional edits which are applied together. See
+        /// `VecEditPlan`.
+        pub fn plan(&mut self) -> VecEditPlan<'_, Self, T> {
+            VecEditPlan::new(self)
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(move |table| table.clear())
+        }
+
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
+
+        pub fn pop(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(move |table| table.pop())
+        }
+
+        pub fn reverse(&mut self) {
+            self.guard
+                .update_tables_closure(move |table| table.reverse())
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn reserve_exact(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve_exact(additional))
+        }
+
+        // pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        //     self.guard
+        //         .update_tables_closure(move |table| table.try_reserve(additional))
+        // }
+
+        // pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        //     self.guard
+        //         .update_tables_closure(move |table| table.try_reserve_exact(additional))
+        // }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        pub fn truncate(&mut self, len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(len))
+        }
+
+        /// Like `truncate` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn truncate_and_shrink(&mut self, len: usize) {
+            self.guard.update_tables_closure(move |table| {
+                table.truncate(len);
+                table.shrink_to_fit();
+            })
+        }
+
+        pub fn swap_remove(&mut self, index: usize) -> T {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove(index))
+        }
+
+        pub fn remove(&mut self, index: usize) -> T {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn extend_from_within<R>(&mut self, range: R)
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+            T: Clone,
+        {
+            self.guard.update_tables(ExtendFromWithin { range })
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f, shrink: false })
+        }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f, shrink: true })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Clone + Send + FnMut(&mut T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
+        }
+
+        pub fn resize_with<F>(&mut self, new_len: usize, f: F)
+        where
+            F: 'static + Clone + Send + FnMut() -> T,
+        {
+            self.guard.update_tables(ResizeWith { new_len, f })
+        }
+
+        pub fn dedup_by_key<F, K>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&mut T) -> K,
+            K: 'static + PartialEq<K>, // Shouldn't need a lifetime.
+        {
+            self.guard.update_tables(DedupByKey { f })
+        }
+
+        pub fn dedup_by<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&mut T, &mut T) -> bool,
+        {
+            self.guard.update_tables(DedupBy { f })
+        }
+
+        pub fn sort_by<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T, &T) -> std::cmp::Ordering,
+        {
+            self.guard.update_tables(SortBy { f })
+        }
+
+        /// Shuffles the table using a PRNG seeded by `seed`. The PRNG is
+        /// seeded again for the second table, so both tables are shuffled
+        /// the same way. Pass a different seed to get a different order.
+        pub fn shuffle_with_seed(&mut self, seed: u64) {
+            self.guard
+                .update_tables_closure(move |table| Prng::new(seed).shuffle(table))
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: Ord,
+    {
+        pub fn sort(&mut self) {
+            self.guard.update_tables_closure(move |table| table.sort())
+        }
+
+        pub fn sort_unstable(&mut self) {
+            self.guard
+                .update_tables_closure(move |table| table.sort_unstable())
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: PartialEq<T>,
+    {
+        pub fn dedup(&mut self) {
+            self.guard.update_tables_closure(|table| table.dedup())
+        }
+    }
+
+    impl<'w, 'a, T> AsLockWriteGuard<'w, T> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::vec::Drain<'a, T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+
+        /// Like `drain`, but returns the removed elements as a `Vec`, so the
+        /// guard isn't borrowed once this returns.
+        pub fn extract_range<R>(&mut self, range: R) -> Vec<T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(ExtractRange { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                value: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// Implementation of Vec for use in the active_standby model.
+/// `sync::AsLock<T>`, should function similarly to `RwLock<Vec<T>>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(Vec<T>);
+
+    impl<T> AsLock<T> {
+        /// Call `f` on consecutive chunks of at most `chunk_size` elements, taking a
+        /// new read guard for each chunk. This bounds how long a full scan
+        /// holds onto a single guard, so that it never blocks the writer for
+        /// the duration of the scan.
+        ///
+        /// Updates published between chunks are seen by the later chunks.
+        /// Chunks are taken by position, so inserting or removing elements
+        /// before the current position can cause elements to be skipped or
+        /// seen twice.
+        pub fn read_chunks(&self, chunk_size: usize, f: impl FnMut(&[T])) {
+            read_chunks(move || self.read(), chunk_size, f)
+        }
+    }
+
+    impl<T> AsLock<T>
+    where
+        T: 'static + Clone + Send,
+    {
+        /// Move the elements into `num_shards` new tables, each holding a
+        /// consecutive range of them, and leave this table empty. See
+        /// `ShardedVec`.
+        ///
+        /// Panics if `num_shards` is 0.
+        pub fn shard(&self, num_shards: usize) -> ShardedVec<T> {
+            let elements = self.write().guard.update_tables_closure(std::mem::take);
+            ShardedVec {
+                shards: split_shards(elements, num_shards)
+                    .into_iter()
+                    .map(AsLock::new)
+                    .collect(),
+            }
+        }
+    }
+
+    /// A Vec split by index range into shards, each a table of its own, for
+    /// tables which are too large to clone or replay onto as a whole. An
+    /// update to a shard is only replayed onto that shard. Created by `shard`.
+    ///
+    /// Reads are routed by index, using the current lengths of the shards.
+    /// Each shard is read under its own guard, so reads which span shards
+    /// aren't consistent with each other, and an update which changes the
+    /// length of a shard shifts the indices of the shards after it.
+    #[derive(Debug)]
+    pub struct ShardedVec<T> {
+        shards: Vec<AsLock<T>>,
+    }
+
+    impl<T> ShardedVec<T> {
+        pub fn shards(&self) -> &[AsLock<T>] {
+            &self.shards
+        }
+
+        /// The total number of elements across the shards.
+        pub fn len(&self) -> usize {
+            self.shards.iter().map(|shard| shard.read().len()).sum()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// The shard holding `index`, and the index of the element within it.
+        pub fn locate(&self, index: usize) -> Option<(usize, usize)> {
+            locate(self.shards.iter().map(|shard| shard.read().len()), index)
+        }
+
+        /// Clone the element at `index` out of the shard holding it.
+        pub fn get_cloned(&self, index: usize) -> Option<T>
+        where
+            T: Clone,
+        {
+            let (shard, index) = self.locate(index)?;
+            self.shards[shard].read().get(index).cloned()
+        }
+
+        /// Move the elements back into a single table, in order.
+        pub fn merge(self) -> AsLock<T>
+        where
+            T: 'static + Clone + Send,
+        {
+            let mut elements = vec![];
+            for shard in &self.shards {
+                elements.append(&mut shard.write().guard.update_tables_closure(std::mem::take));
+            }
+            AsLock::new(elements)
+        }
+    }
+
+    impl<'w, T> sealed::PlanTarget<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn table_len(&self) -> usize {
+            self.len()
+        }
+        fn apply_plan(&mut self, plan: ApplyPlan<T>) -> Vec<T> {
+            self.guard.update_tables(plan)
+        }
+    }
+
     impl<'w, T> AsLockWriteGuard<'w, T>
     where
         T: 'static + Clone + Send,
@@ -188,13 +962,78 @@ pub mod lockless {
 
         pub fn append(&mut self, other: &mut Vec<T>) {
             self.guard.update_tables(Append {
-                value: other.drain(..).collect(),
+                value: std::mem::take(other),
+            })
+        }
+
+        pub fn extend_from_slice(&mut self, other: &[T]) {
+            self.guard.update_tables(Append {
+                value: other.to_vec(),
             })
         }
 
         pub fn insert(&mut self, index: usize, element: T) {
             self.guard.update_tables(Insert { index, element })
         }
+
+        /// Set the element at `index` to `element`, unless they are already
+        /// equal, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        ///
+        /// Panics if `index` is out of bounds.
+        pub fn set_if_different(&mut self, index: usize, element: T) -> bool
+        where
+            T: PartialEq,
+        {
+            if self[index] == element {
+                return false;
+            }
+            self.guard.update_tables(Set { index, element });
+            true
+        }
+
+        /// Insert `value` into a sorted vector, keeping it sorted. Returns the
+        /// index `value` was inserted at. The index is found once, using
+        /// `binary_search`, and the same insert is replayed on the other table.
+        ///
+        /// If the vector isn't sorted, the position is unspecified.
+        pub fn insert_sorted(&mut self, value: T) -> usize
+        where
+            T: Ord,
+        {
+            let index = match self.binary_search(&value) {
+                Ok(index) | Err(index) => index,
+            };
+            self.guard.update_tables(Insert {
+                index,
+                element: value,
+            });
+            index
+        }
+
+        /// Remove an element equal to `value` from a sorted vector. Returns
+        /// whether an element was found and removed.
+        pub fn remove_sorted(&mut self, value: &T) -> bool
+        where
+            T: Ord,
+        {
+            match self.binary_search(value) {
+                Ok(index) => {
+                    self.guard.update_tables_closure(move |table| {
+                        table.remove(index);
+                    });
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+
+        /// Stage positional edits which are applied together. See
+        /// `VecEditPlan`.
+        pub fn plan(&mut self) -> VecEditPlan<'_, Self, T> {
+            VecEditPlan::new(self)
+        }
     }
 
     impl<'w, T> AsLockWriteGuard<'w, T> {
@@ -202,6 +1041,14 @@ pub mod lockless {
             self.guard.update_tables_closure(move |table| table.clear())
         }
 
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
         pub fn pop(&mut self) -> Option<T> {
             self.guard.update_tables_closure(move |table| table.pop())
         }
@@ -246,6 +1093,15 @@ pub mod lockless {
                 .update_tables_closure(move |table| table.truncate(len))
         }
 
+        /// Like `truncate` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn truncate_and_shrink(&mut self, len: usize) {
+            self.guard.update_tables_closure(move |table| {
+                table.truncate(len);
+                table.shrink_to_fit();
+            })
+        }
+
         pub fn swap_remove(&mut self, index: usize) -> T {
             self.guard
                 .update_tables_closure(move |table| table.swap_remove(index))
@@ -268,7 +1124,35 @@ pub mod lockless {
         where
             F: 'static + Clone + Send + FnMut(&T) -> bool,
         {
-            self.guard.update_tables(Retain { f })
+            self.guard.update_tables(Retain { f, shrink: false })
+        }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f, shrink: true })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Clone + Send + FnMut(&mut T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
         }
 
         pub fn resize_with<F>(&mut self, new_len: usize, f: F)
@@ -299,6 +1183,14 @@ pub mod lockless {
         {
             self.guard.update_tables(SortBy { f })
         }
+
+        /// Shuffles the table using a PRNG seeded by `seed`. The PRNG is
+        /// seeded again for the second table, so both tables are shuffled
+        /// the same way. Pass a different seed to get a different order.
+        pub fn shuffle_with_seed(&mut self, seed: u64) {
+            self.guard
+                .update_tables_closure(move |table| Prng::new(seed).shuffle(table))
+        }
     }
 
     impl<'w, T> AsLockWriteGuard<'w, T>
@@ -331,16 +1223,48 @@ pub mod lockless {
         {
             self.guard.update_tables(Drain { range })
         }
+
+        /// Like `drain`, but returns the removed elements as a `Vec`, so the
+        /// guard isn't borrowed once this returns.
+        pub fn extract_range<R>(&mut self, range: R) -> Vec<T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(ExtractRange { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                value: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(Vec<T>);
+
+    impl<'w, T> sealed::PlanTarget<T> for AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn table_len(&self) -> usize {
+            self.len()
+        }
+        fn apply_plan(&mut self, plan: ApplyPlan<T>) -> Vec<T> {
+            self.guard.update_tables(plan)
+        }
     }
-}
-
-/// Implementation of Vec for use in the active_standby model.
-/// `sync::AsLock<T>`, should function similarly to `RwLock<Vec<T>>`.
-pub mod sync {
-    use super::*;
-    crate::generate_sync_aslock!(Vec<T>);
 
-    impl<'w, T> AsLockWriteGuard<'w, T>
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
     where
         T: 'static + Clone + Send,
     {
@@ -350,23 +1274,102 @@ pub mod sync {
 
         pub fn append(&mut self, other: &mut Vec<T>) {
             self.guard.update_tables(Append {
-                value: other.drain(..).collect(),
+                value: std::mem::take(other),
+            })
+        }
+
+        pub fn extend_from_slice(&mut self, other: &[T]) {
+            self.guard.update_tables(Append {
+                value: other.to_vec(),
             })
         }
 
         pub fn insert(&mut self, index: usize, element: T) {
             self.guard.update_tables(Insert { index, element })
         }
+
+        /// Set the element at `index` to `element`, unless they are already
+        /// equal, in which case no update is recorded. Returns whether the
+        /// table was updated. This keeps loops that rewrite the same values
+        /// from growing the ops to replay.
+        ///
+        /// Panics if `index` is out of bounds.
+        pub fn set_if_different(&mut self, index: usize, element: T) -> bool
+        where
+            T: PartialEq,
+        {
+            if self[index] == element {
+                return false;
+            }
+            self.guard.update_tables(Set { index, element });
+            true
+        }
+
+        /// Insert `value` into a sorted vector, keeping it sorted. Returns the
+        /// index `value` was inserted at. The index is found once, using
+        /// `binary_search`, and the same insert is replayed on the other table.
+        ///
+        /// If the vector isn't sorted, the position is unspecified.
+        pub fn insert_sorted(&mut self, value: T) -> usize
+        where
+            T: Ord,
+        {
+            let index = match self.binary_search(&value) {
+                Ok(index) | Err(index) => index,
+            };
+            self.guard.update_tables(Insert {
+                index,
+                element: value,
+            });
+            index
+        }
+
+        /// Remove an element equal to `value` from a sorted vector. Returns
+        /// whether an element was found and removed.
+        pub fn remove_sorted(&mut self, value: &T) -> bool
+        where
+            T: Ord,
+        {
+            match self.binary_search(value) {
+                Ok(index) => {
+                    self.guard.update_tables_closure(move |table| {
+                        table.remove(index);
+                    });
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+
+        /// Stage positional edits which are applied together. See
+        /// `VecEditPlan`.
+        pub fn plan(&mut self) -> VecEditPlan<'_, Self, T> {
+            VecEditPlan::new(self)
+        }
     }
 
-    impl<'w, T> AsLockWriteGuard<'w, T> {
+    impl<'w, T> AsArcLockWriteGuard<'w, T> {
         pub fn clear(&mut self) {
             self.guard.update_tables_closure(move |table| table.clear())
         }
+
+        /// Like `clear` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn clear_and_shrink(&mut self) {
+            self.guard.update_tables_closure(move |table| {
+                table.clear();
+                table.shrink_to_fit();
+            })
+        }
         pub fn pop(&mut self) -> Option<T> {
             self.guard.update_tables_closure(move |table| table.pop())
         }
 
+        pub fn reverse(&mut self) {
+            self.guard
+                .update_tables_closure(move |table| table.reverse())
+        }
+
         pub fn reserve(&mut self, additional: usize) {
             self.guard
                 .update_tables_closure(move |table| table.reserve(additional))
@@ -402,6 +1405,15 @@ pub mod sync {
                 .update_tables_closure(move |table| table.truncate(len))
         }
 
+        /// Like `truncate` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn truncate_and_shrink(&mut self, len: usize) {
+            self.guard.update_tables_closure(move |table| {
+                table.truncate(len);
+                table.shrink_to_fit();
+            })
+        }
+
         pub fn swap_remove(&mut self, index: usize) -> T {
             self.guard
                 .update_tables_closure(move |table| table.swap_remove(index))
@@ -424,7 +1436,35 @@ pub mod sync {
         where
             F: 'static + Clone + Send + FnMut(&T) -> bool,
         {
-            self.guard.update_tables(Retain { f })
+            self.guard.update_tables(Retain { f, shrink: false })
+        }
+
+        /// Like `retain` followed by `shrink_to_fit`, recorded as a single
+        /// update.
+        pub fn retain_and_shrink<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f, shrink: true })
+        }
+
+        /// Removes the elements for which `f` returns true and returns them.
+        /// `f` is called on both tables, so it must select the same elements
+        /// each time.
+        pub fn extract_if<F>(&mut self, f: F) -> Vec<T>
+        where
+            F: 'static + Clone + Send + FnMut(&mut T) -> bool,
+        {
+            self.guard.update_tables(ExtractIf { f })
+        }
+
+        /// Like `retain`, but returns the removed elements instead of dropping
+        /// them.
+        pub fn retain_collect<F>(&mut self, mut f: F) -> Vec<T>
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.extract_if(move |t| !f(t))
         }
 
         pub fn resize_with<F>(&mut self, new_len: usize, f: F)
@@ -455,9 +1495,17 @@ pub mod sync {
         {
             self.guard.update_tables(SortBy { f })
         }
+
+        /// Shuffles the table using a PRNG seeded by `seed`. The PRNG is
+        /// seeded again for the second table, so both tables are shuffled
+        /// the same way. Pass a different seed to get a different order.
+        pub fn shuffle_with_seed(&mut self, seed: u64) {
+            self.guard
+                .update_tables_closure(move |table| Prng::new(seed).shuffle(table))
+        }
     }
 
-    impl<'w, T> AsLockWriteGuard<'w, T>
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
     where
         T: Ord,
     {
@@ -471,7 +1519,7 @@ pub mod sync {
         }
     }
 
-    impl<'w, T> AsLockWriteGuard<'w, T>
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
     where
         T: PartialEq<T>,
     {
@@ -480,13 +1528,33 @@ pub mod sync {
         }
     }
 
-    impl<'w, 'a, T> AsLockWriteGuard<'w, T> {
+    impl<'w, 'a, T> AsArcLockWriteGuard<'w, T> {
         pub fn drain<R>(&'a mut self, range: R) -> std::vec::Drain<'a, T>
         where
             R: 'static + Clone + Send + RangeBounds<usize>,
         {
             self.guard.update_tables(Drain { range })
         }
+
+        /// Like `drain`, but returns the removed elements as a `Vec`, so the
+        /// guard isn't borrowed once this returns.
+        pub fn extract_range<R>(&mut self, range: R) -> Vec<T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(ExtractRange { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                value: iter.into_iter().collect(),
+            })
+        }
     }
 }
 
@@ -495,6 +1563,44 @@ mod lockless_test {
     use super::*;
     use crate::assert_tables_eq;
 
+    #[test]
+    fn shard_and_merge() {
+        let table = lockless::AsLockHandle::new((0..10).collect());
+        let sharded = table.shard(3);
+        assert!(table.read().is_empty());
+        let lens: Vec<_> = sharded.shards().iter().map(|s| s.read().len()).collect();
+        assert_eq!(lens, vec![4, 3, 3]);
+        assert_eq!(sharded.len(), 10);
+        assert_eq!(sharded.locate(5), Some((1, 1)));
+        assert_eq!(sharded.get_cloned(9), Some(9));
+        assert_eq!(sharded.get_cloned(10), None);
+
+        // Reads are routed by the current lengths of the shards.
+        sharded.shards()[0].write().remove(0);
+        assert_eq!(sharded.locate(3), Some((1, 0)));
+        assert_eq!(sharded.get_cloned(3), Some(4));
+
+        let merged = sharded.merge();
+        assert_tables_eq!(merged, (1..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn read_chunks() {
+        let table = lockless::AsLockHandle::new((0..10).collect::<Vec<i32>>());
+        let mut chunks = vec![];
+        table.read_chunks(4, |chunk| {
+            if chunks.is_empty() {
+                // Published between chunks, so seen by the last chunk.
+                table.write().push(10);
+            }
+            chunks.push(chunk.to_vec());
+        });
+        assert_eq!(
+            chunks,
+            vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9, 10]]
+        );
+    }
+
     #[test]
     fn push() {
         let lock1 = lockless::AsLockHandle::<i32>::default();
@@ -529,7 +1635,7 @@ mod lockless_test {
         assert_tables_eq!(aslock, vec![2]);
 
         aslock.write().clear();
-        assert_tables_eq!(aslock, vec![]);
+        assert_tables_eq!(aslock, Vec::<i32>::new());
     }
 
     #[test]
@@ -556,6 +1662,14 @@ mod lockless_test {
         assert_tables_eq!(table, vec![1, 2, 3]);
     }
 
+    #[test]
+    fn extend_from_slice() {
+        let table = lockless::AsLockHandle::new(vec![1]);
+        let other = [2, 3];
+        table.write().extend_from_slice(&other);
+        assert_tables_eq!(table, vec![1, 2, 3]);
+    }
+
     #[test]
     fn indirect_type() {
         let table = lockless::AsLockHandle::<Box<i32>>::default();
@@ -659,6 +1773,33 @@ mod lockless_test {
         assert_tables_eq!(table, vec![0, 1, 2]);
     }
 
+    #[test]
+    fn fused_shrink() {
+        let table = lockless::AsLockHandle::<i32>::new((0..10).collect());
+        {
+            let mut wg = table.write();
+            wg.reserve_exact(100);
+            wg.retain_and_shrink(|element| element % 2 == 0);
+        }
+        assert_tables_eq!(table, vec![0, 2, 4, 6, 8]);
+        assert_eq!(table.read().capacity(), 5);
+        assert_eq!(table.write().capacity(), 5);
+
+        {
+            let mut wg = table.write();
+            wg.reserve_exact(100);
+            wg.truncate_and_shrink(2);
+        }
+        assert_tables_eq!(table, vec![0, 2]);
+        assert_eq!(table.read().capacity(), 2);
+        assert_eq!(table.write().capacity(), 2);
+
+        table.write().clear_and_shrink();
+        assert_tables_eq!(table, Vec::<i32>::new());
+        assert_eq!(table.read().capacity(), 0);
+        assert_eq!(table.write().capacity(), 0);
+    }
+
     #[test]
     fn swap_remove() {
         let table = lockless::AsLockHandle::<i32>::default();
@@ -708,12 +1849,40 @@ mod lockless_test {
             }
             wg.insert(2, 10);
             assert_eq!(*wg, vec![0, 1, 10, 2, 3, 4]);
-            assert_eq!(*table2.read(), vec![]);
+            assert_eq!(*table2.read(), Vec::<i32>::new());
         }
 
         assert_tables_eq!(table, vec![0, 1, 10, 2, 3, 4]);
     }
 
+    #[test]
+    fn set_if_different() {
+        let table = lockless::AsLockHandle::new(vec![1, 2, 3]);
+        {
+            let mut wg = table.write();
+            assert!(!wg.set_if_different(1, 2));
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert!(wg.set_if_different(1, 5));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, vec![1, 5, 3]);
+    }
+
+    #[test]
+    fn insert_and_remove_sorted() {
+        let table = lockless::AsLockHandle::new(vec![1, 3, 5]);
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.insert_sorted(4), 2);
+            assert_eq!(wg.insert_sorted(0), 0);
+            assert_eq!(wg.insert_sorted(6), 5);
+            assert!(wg.remove_sorted(&3));
+            assert!(!wg.remove_sorted(&3));
+            assert_eq!(*wg, vec![0, 1, 4, 5, 6]);
+        }
+        assert_tables_eq!(table, vec![0, 1, 4, 5, 6]);
+    }
+
     #[test]
     fn retain() {
         let table = lockless::AsLockHandle::<i32>::default();
@@ -729,6 +1898,91 @@ mod lockless_test {
         assert_tables_eq!(table, vec![0, 2, 4]);
     }
 
+    #[test]
+    fn extract_if() {
+        let table = lockless::AsLockHandle::<i32>::new(vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            table.write().extract_if(|element| *element % 2 != 0),
+            vec![1, 3]
+        );
+        assert_tables_eq!(table, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn extend_and_index() {
+        fn fill(table: &mut impl Extend<i32>) {
+            table.extend([1, 2, 3]);
+        }
+        fn first(table: &impl std::ops::Index<usize, Output = i32>) -> i32 {
+            table[0]
+        }
+
+        let table = lockless::AsLockHandle::<i32>::new(vec![0]);
+        fill(&mut table.write());
+        assert_tables_eq!(table, vec![0, 1, 2, 3]);
+        assert_eq!(first(&table.read()), 0);
+        assert_eq!(table.read()[1..3], [1, 2]);
+    }
+
+    #[test]
+    fn retain_collect() {
+        let table = lockless::AsLockHandle::<i32>::new(vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            table.write().retain_collect(|element| *element % 2 == 0),
+            vec![1, 3]
+        );
+        assert_tables_eq!(table, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn plan() {
+        let table = lockless::AsLockHandle::<i32>::new(vec![0, 1, 2, 3, 4]);
+        {
+            let mut wg = table.write();
+            let mut plan = wg.plan();
+            // Indices refer to the table before any of the edits.
+            plan.remove(1)
+                .unwrap()
+                .remove(3)
+                .unwrap()
+                .set(4, 40)
+                .unwrap()
+                .insert(0, -1)
+                .unwrap()
+                .insert(3, 30)
+                .unwrap()
+                .insert(5, 50)
+                .unwrap();
+            assert_eq!(plan.commit(), vec![1, 3]);
+            assert_eq!(*wg, vec![-1, 0, 2, 30, 40, 50]);
+        }
+        assert_tables_eq!(table, vec![-1, 0, 2, 30, 40, 50]);
+    }
+
+    #[test]
+    fn plan_invalid() {
+        let table = lockless::AsLockHandle::<i32>::new(vec![0, 1, 2]);
+        {
+            let mut wg = table.write();
+            let mut plan = wg.plan();
+            assert_eq!(
+                plan.remove(3).err(),
+                Some(VecEditPlanError::IndexOutOfBounds { index: 3, len: 3 })
+            );
+            assert_eq!(
+                plan.insert(4, 4).err(),
+                Some(VecEditPlanError::IndexOutOfBounds { index: 4, len: 3 })
+            );
+            assert!(plan.set(1, 10).is_ok());
+            assert_eq!(
+                plan.remove(1).err(),
+                Some(VecEditPlanError::ConflictingEdit { index: 1 })
+            );
+            // Dropping the plan discards it.
+        }
+        assert_tables_eq!(table, vec![0, 1, 2]);
+    }
+
     #[test]
     fn resize_with() {
         let table = lockless::AsLockHandle::<i32>::new(vec![1, 2]);
@@ -747,6 +2001,24 @@ mod lockless_test {
         assert_tables_eq!(table, vec![-5, -3, 1, 2, 4]);
     }
 
+    #[test]
+    fn shuffle_with_seed() {
+        let table = lockless::AsLockHandle::new((0..20).collect());
+        table.write().shuffle_with_seed(7);
+        let shuffled = table.read().clone();
+        assert_ne!(shuffled, (0..20).collect::<Vec<_>>());
+        assert_tables_eq!(table, shuffled);
+
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+
+        // The same seed shuffles equal tables the same way.
+        let other = lockless::AsLockHandle::new((0..20).collect());
+        other.write().shuffle_with_seed(7);
+        assert_eq!(*other.read(), shuffled);
+    }
+
     #[test]
     fn sort() {
         let table = lockless::AsLockHandle::new(vec![-5, 4, 1, -3, 2]);
@@ -797,6 +2069,18 @@ mod lockless_test {
         assert_tables_eq!(table, vec![1, 5]);
     }
 
+    #[test]
+    fn extract_range() {
+        let table = lockless::AsLockHandle::<i32>::new(vec![0, 1, 2, 3, 4]);
+        {
+            let mut wg = table.write();
+            let extracted = wg.extract_range(1..3);
+            wg.push(5);
+            assert_eq!(extracted, vec![1, 2]);
+        }
+        assert_tables_eq!(table, vec![0, 3, 4, 5]);
+    }
+
     #[test]
     fn lifetimes() {
         let table = lockless::AsLockHandle::<i32>::from_identical(vec![], vec![]);
@@ -856,6 +2140,44 @@ mod sync_test {
     use std::sync::Arc;
     use std::thread;
 
+    #[test]
+    fn shard_and_merge() {
+        let table = sync::AsLock::new((0..10).collect());
+        let sharded = table.shard(3);
+        assert!(table.read().is_empty());
+        let lens: Vec<_> = sharded.shards().iter().map(|s| s.read().len()).collect();
+        assert_eq!(lens, vec![4, 3, 3]);
+        assert_eq!(sharded.len(), 10);
+        assert_eq!(sharded.locate(5), Some((1, 1)));
+        assert_eq!(sharded.get_cloned(9), Some(9));
+        assert_eq!(sharded.get_cloned(10), None);
+
+        // Reads are routed by the current lengths of the shards.
+        sharded.shards()[0].write().remove(0);
+        assert_eq!(sharded.locate(3), Some((1, 0)));
+        assert_eq!(sharded.get_cloned(3), Some(4));
+
+        let merged = sharded.merge();
+        assert_tables_eq!(merged, (1..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn read_chunks() {
+        let table = sync::AsLock::new((0..10).collect::<Vec<i32>>());
+        let mut chunks = vec![];
+        table.read_chunks(4, |chunk| {
+            if chunks.is_empty() {
+                // Published between chunks, so seen by the last chunk.
+                table.write().push(10);
+            }
+            chunks.push(chunk.to_vec());
+        });
+        assert_eq!(
+            chunks,
+            vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9, 10]]
+        );
+    }
+
     #[test]
     fn push() {
         let lock1 = Arc::new(sync::AsLock::<i32>::default());
@@ -906,7 +2228,7 @@ mod sync_test {
         assert_tables_eq!(table, vec![2]);
 
         table.write().clear();
-        assert_tables_eq!(table, vec![]);
+        assert_tables_eq!(table, Vec::<i32>::new());
     }
 
     #[test]
@@ -933,6 +2255,14 @@ mod sync_test {
         assert_tables_eq!(table, vec![1, 2, 3]);
     }
 
+    #[test]
+    fn extend_from_slice() {
+        let table = sync::AsLock::new(vec![1]);
+        let other = [2, 3];
+        table.write().extend_from_slice(&other);
+        assert_tables_eq!(table, vec![1, 2, 3]);
+    }
+
     #[test]
     fn indirect_type() {
         let table = sync::AsLock::<Box<i32>>::default();
@@ -942,7 +2272,7 @@ mod sync_test {
 
     #[test]
     fn reverse() {
-        let table = lockless::AsLockHandle::new(vec![1, 2, 3]);
+        let table = sync::AsLock::new(vec![1, 2, 3]);
         table.write().reverse();
         assert_tables_eq!(table, vec![3, 2, 1]);
     }
@@ -1028,6 +2358,33 @@ mod sync_test {
         assert_tables_eq!(table, vec![0, 1, 2]);
     }
 
+    #[test]
+    fn fused_shrink() {
+        let table = sync::AsLock::<i32>::new((0..10).collect());
+        {
+            let mut wg = table.write();
+            wg.reserve_exact(100);
+            wg.retain_and_shrink(|element| element % 2 == 0);
+        }
+        assert_tables_eq!(table, vec![0, 2, 4, 6, 8]);
+        assert_eq!(table.read().capacity(), 5);
+        assert_eq!(table.write().capacity(), 5);
+
+        {
+            let mut wg = table.write();
+            wg.reserve_exact(100);
+            wg.truncate_and_shrink(2);
+        }
+        assert_tables_eq!(table, vec![0, 2]);
+        assert_eq!(table.read().capacity(), 2);
+        assert_eq!(table.write().capacity(), 2);
+
+        table.write().clear_and_shrink();
+        assert_tables_eq!(table, Vec::<i32>::new());
+        assert_eq!(table.read().capacity(), 0);
+        assert_eq!(table.write().capacity(), 0);
+    }
+
     #[test]
     fn swap_remove() {
         let table = sync::AsLock::<i32>::new(vec![0, 1, 2, 3, 4]);
@@ -1065,7 +2422,7 @@ mod sync_test {
                 // (calling both read and write on aslock at the same time).
                 let table = Arc::clone(&table);
                 assert!(thread::spawn(move || {
-                    assert_eq!(*table.read(), vec![]);
+                    assert_eq!(*table.read(), Vec::<i32>::new());
                 })
                 .join()
                 .is_ok());
@@ -1075,6 +2432,34 @@ mod sync_test {
         assert_tables_eq!(table, vec![0, 1, 10, 2, 3, 4]);
     }
 
+    #[test]
+    fn set_if_different() {
+        let table = sync::AsLock::new(vec![1, 2, 3]);
+        {
+            let mut wg = table.write();
+            assert!(!wg.set_if_different(1, 2));
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert!(wg.set_if_different(1, 5));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        assert_tables_eq!(table, vec![1, 5, 3]);
+    }
+
+    #[test]
+    fn insert_and_remove_sorted() {
+        let table = sync::AsLock::new(vec![1, 3, 5]);
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.insert_sorted(4), 2);
+            assert_eq!(wg.insert_sorted(0), 0);
+            assert_eq!(wg.insert_sorted(6), 5);
+            assert!(wg.remove_sorted(&3));
+            assert!(!wg.remove_sorted(&3));
+            assert_eq!(*wg, vec![0, 1, 4, 5, 6]);
+        }
+        assert_tables_eq!(table, vec![0, 1, 4, 5, 6]);
+    }
+
     #[test]
     fn retain() {
         let table = sync::AsLock::<i32>::new(vec![0, 1, 2, 3, 4]);
@@ -1082,6 +2467,91 @@ mod sync_test {
         assert_tables_eq!(table, vec![0, 2, 4]);
     }
 
+    #[test]
+    fn extract_if() {
+        let table = sync::AsLock::<i32>::new(vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            table.write().extract_if(|element| *element % 2 != 0),
+            vec![1, 3]
+        );
+        assert_tables_eq!(table, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn extend_and_index() {
+        fn fill(table: &mut impl Extend<i32>) {
+            table.extend([1, 2, 3]);
+        }
+        fn first(table: &impl std::ops::Index<usize, Output = i32>) -> i32 {
+            table[0]
+        }
+
+        let table = sync::AsLock::<i32>::new(vec![0]);
+        fill(&mut table.write());
+        assert_tables_eq!(table, vec![0, 1, 2, 3]);
+        assert_eq!(first(&table.read()), 0);
+        assert_eq!(table.read()[1..3], [1, 2]);
+    }
+
+    #[test]
+    fn retain_collect() {
+        let table = sync::AsLock::<i32>::new(vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            table.write().retain_collect(|element| *element % 2 == 0),
+            vec![1, 3]
+        );
+        assert_tables_eq!(table, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn plan() {
+        let table = sync::AsLock::<i32>::new(vec![0, 1, 2, 3, 4]);
+        {
+            let mut wg = table.write();
+            let mut plan = wg.plan();
+            // Indices refer to the table before any of the edits.
+            plan.remove(1)
+                .unwrap()
+                .remove(3)
+                .unwrap()
+                .set(4, 40)
+                .unwrap()
+                .insert(0, -1)
+                .unwrap()
+                .insert(3, 30)
+                .unwrap()
+                .insert(5, 50)
+                .unwrap();
+            assert_eq!(plan.commit(), vec![1, 3]);
+            assert_eq!(*wg, vec![-1, 0, 2, 30, 40, 50]);
+        }
+        assert_tables_eq!(table, vec![-1, 0, 2, 30, 40, 50]);
+    }
+
+    #[test]
+    fn plan_invalid() {
+        let table = sync::AsLock::<i32>::new(vec![0, 1, 2]);
+        {
+            let mut wg = table.write();
+            let mut plan = wg.plan();
+            assert_eq!(
+                plan.remove(3).err(),
+                Some(VecEditPlanError::IndexOutOfBounds { index: 3, len: 3 })
+            );
+            assert_eq!(
+                plan.insert(4, 4).err(),
+                Some(VecEditPlanError::IndexOutOfBounds { index: 4, len: 3 })
+            );
+            assert!(plan.set(1, 10).is_ok());
+            assert_eq!(
+                plan.remove(1).err(),
+                Some(VecEditPlanError::ConflictingEdit { index: 1 })
+            );
+            // Dropping the plan discards it.
+        }
+        assert_tables_eq!(table, vec![0, 1, 2]);
+    }
+
     #[test]
     fn resize_with() {
         let table = sync::AsLock::<i32>::new(vec![1, 2]);
@@ -1100,6 +2570,24 @@ mod sync_test {
         assert_tables_eq!(table, vec![-5, -3, 1, 2, 4]);
     }
 
+    #[test]
+    fn shuffle_with_seed() {
+        let table = sync::AsLock::new((0..20).collect());
+        table.write().shuffle_with_seed(7);
+        let shuffled = table.read().clone();
+        assert_ne!(shuffled, (0..20).collect::<Vec<_>>());
+        assert_tables_eq!(table, shuffled);
+
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+
+        // The same seed shuffles equal tables the same way.
+        let other = sync::AsLock::new((0..20).collect());
+        other.write().shuffle_with_seed(7);
+        assert_eq!(*other.read(), shuffled);
+    }
+
     #[test]
     fn sort() {
         let table = sync::AsLock::new(vec![-5, 4, 1, -3, 2]);
@@ -1142,6 +2630,18 @@ mod sync_test {
         assert_tables_eq!(table, vec![0, 4]);
     }
 
+    #[test]
+    fn extract_range() {
+        let table = sync::AsLock::<i32>::new(vec![0, 1, 2, 3, 4]);
+        {
+            let mut wg = table.write();
+            let extracted = wg.extract_range(3..);
+            wg.push(5);
+            assert_eq!(extracted, vec![3, 4]);
+        }
+        assert_tables_eq!(table, vec![0, 1, 2, 5]);
+    }
+
     #[test]
     fn lifetimes() {
         let table = sync::AsLock::<i32>::from_identical(vec![], vec![]);
@@ -1194,3 +2694,40 @@ mod sync_test {
         assert_eq!(*table.read(), vec![2, 3]);
     }
 }
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn push_and_edit() {
+        let table = arc::AsArcLock::<i32>::default();
+        {
+            let mut wg = table.write();
+            wg.push(2);
+            wg.insert(0, 1);
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(**table.read(), vec![1, 2]);
+
+        let removed = {
+            let mut wg = table.write();
+            let mut plan = wg.plan();
+            plan.remove(0).unwrap().insert(2, 3).unwrap();
+            plan.commit()
+        };
+        assert_eq!(removed, vec![1]);
+        assert_eq!(*table.write(), vec![2, 3]);
+        assert_eq!(**table.read(), vec![2, 3]);
+    }
+
+    #[test]
+    fn held_reader() {
+        let table = arc::AsArcLock::new(vec![1]);
+        table.write().push(2);
+        let old = table.read_arc();
+        table.write().push(3);
+        assert_eq!(*table.write(), vec![1, 2, 3]);
+        assert_eq!(*old, vec![1, 2]);
+    }
+}
@@ -0,0 +1,703 @@
+use crate::UpdateTables;
+use std::collections::VecDeque;
+use std::ops::RangeBounds;
+
+struct PushBack<T> {
+    value: T,
+}
+
+impl<'a, T> UpdateTables<'a, VecDeque<T>, ()> for PushBack<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut VecDeque<T>) {
+        table.push_back(self.value.clone())
+    }
+    fn apply_second(self, table: &mut VecDeque<T>) {
+        table.push_back(self.value); // Move the value instead of cloning.
+    }
+}
+
+struct PushFront<T> {
+    value: T,
+}
+
+impl<'a, T> UpdateTables<'a, VecDeque<T>, ()> for PushFront<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut VecDeque<T>) {
+        table.push_front(self.value.clone())
+    }
+    fn apply_second(self, table: &mut VecDeque<T>) {
+        table.push_front(self.value); // Move the value instead of cloning.
+    }
+}
+
+struct Insert<T> {
+    index: usize,
+    value: T,
+}
+
+impl<'a, T> UpdateTables<'a, VecDeque<T>, ()> for Insert<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut VecDeque<T>) {
+        table.insert(self.index, self.value.clone())
+    }
+    fn apply_second(self, table: &mut VecDeque<T>) {
+        // Move the value instead of cloning.
+        table.insert(self.index, self.value)
+    }
+}
+
+struct Append<T> {
+    values: VecDeque<T>,
+}
+
+impl<'a, T> UpdateTables<'a, VecDeque<T>, ()> for Append<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut VecDeque<T>) {
+        table.append(&mut self.values.clone())
+    }
+    fn apply_second(mut self, table: &mut VecDeque<T>) {
+        table.append(&mut self.values);
+    }
+}
+
+struct Drain<R> {
+    range: R,
+}
+
+impl<'a, T, R> UpdateTables<'a, VecDeque<T>, std::collections::vec_deque::Drain<'a, T>> for Drain<R>
+where
+    R: 'static + Clone + RangeBounds<usize>,
+{
+    fn apply_first(
+        &mut self,
+        table: &'a mut VecDeque<T>,
+    ) -> std::collections::vec_deque::Drain<'a, T> {
+        table.drain(self.range.clone())
+    }
+    fn apply_second(self, table: &mut VecDeque<T>) {
+        table.drain(self.range);
+    }
+}
+
+struct Retain<F> {
+    f: F,
+}
+
+impl<'a, T, F> UpdateTables<'a, VecDeque<T>, ()> for Retain<F>
+where
+    F: 'static + Clone + FnMut(&T) -> bool,
+{
+    fn apply_first(&mut self, table: &'a mut VecDeque<T>) {
+        table.retain(self.f.clone())
+    }
+    fn apply_second(self, table: &mut VecDeque<T>) {
+        table.retain(self.f)
+    }
+}
+
+struct ResizeWith<F> {
+    new_len: usize,
+    f: F,
+}
+
+impl<'a, T, F> UpdateTables<'a, VecDeque<T>, ()> for ResizeWith<F>
+where
+    F: Clone + FnMut() -> T,
+{
+    fn apply_first(&mut self, table: &'a mut VecDeque<T>) {
+        table.resize_with(self.new_len, self.f.clone())
+    }
+    fn apply_second(self, table: &mut VecDeque<T>) {
+        table.resize_with(self.new_len, self.f);
+    }
+}
+
+/// Implementation of VecDeque for use in the active_standby model.
+/// `lockless::AsLockHandle<T>`, should function similarly to
+/// `Arc<RwLock<VecDeque<T>>>`.
+pub mod lockless {
+    use super::*;
+    crate::generate_lockless_aslockhandle!(VecDeque<T>);
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        pub fn push_back(&mut self, value: T) {
+            self.guard.update_tables(PushBack { value })
+        }
+
+        pub fn push_front(&mut self, value: T) {
+            self.guard.update_tables(PushFront { value })
+        }
+
+        /// Push `value` onto the back, popping values off the front until
+        /// there are at most `capacity` values. Returns the popped values, so
+        /// that the table can be used as a ring buffer.
+        pub fn push_back_bounded(&mut self, value: T, capacity: usize) -> Vec<T> {
+            self.push_back(value);
+            let excess = self.len().saturating_sub(capacity);
+            self.drain(..excess).collect()
+        }
+
+        pub fn insert(&mut self, index: usize, value: T) {
+            self.guard.update_tables(Insert { index, value })
+        }
+
+        pub fn append(&mut self, other: &mut VecDeque<T>) {
+            self.guard.update_tables(Append {
+                values: std::mem::take(other),
+            })
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn pop_back(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_back())
+        }
+
+        pub fn pop_front(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_front())
+        }
+
+        pub fn remove(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn swap_remove_back(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove_back(index))
+        }
+
+        pub fn swap_remove_front(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove_front(index))
+        }
+
+        pub fn swap(&mut self, i: usize, j: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.swap(i, j))
+        }
+
+        pub fn rotate_left(&mut self, n: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.rotate_left(n))
+        }
+
+        pub fn rotate_right(&mut self, n: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.rotate_right(n))
+        }
+
+        pub fn truncate(&mut self, len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(len))
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        /// Rearranges both tables so that their contents are contiguous, i.e.
+        /// `as_slices().1` is empty. Unlike `VecDeque::make_contiguous`, the
+        /// slice isn't returned, since mutating it wouldn't be replayed.
+        pub fn make_contiguous(&mut self) {
+            self.guard.update_tables_closure(|table| {
+                table.make_contiguous();
+            })
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn resize_with<F>(&mut self, new_len: usize, f: F)
+        where
+            F: 'static + Clone + Send + FnMut() -> T,
+        {
+            self.guard.update_tables(ResizeWith { new_len, f })
+        }
+    }
+
+    impl<'w, 'a, T> AsLockWriteGuard<'w, T> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::collections::vec_deque::Drain<'a, T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// Implementation of VecDeque for use in the active_standby model.
+/// `sync::AsLock<T>`, should function similarly to `RwLock<VecDeque<T>>`.
+pub mod sync {
+    use super::*;
+    crate::generate_sync_aslock!(VecDeque<T>);
+
+    impl<'w, T> AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        pub fn push_back(&mut self, value: T) {
+            self.guard.update_tables(PushBack { value })
+        }
+
+        pub fn push_front(&mut self, value: T) {
+            self.guard.update_tables(PushFront { value })
+        }
+
+        /// Push `value` onto the back, popping values off the front until
+        /// there are at most `capacity` values. Returns the popped values, so
+        /// that the table can be used as a ring buffer.
+        pub fn push_back_bounded(&mut self, value: T, capacity: usize) -> Vec<T> {
+            self.push_back(value);
+            let excess = self.len().saturating_sub(capacity);
+            self.drain(..excess).collect()
+        }
+
+        pub fn insert(&mut self, index: usize, value: T) {
+            self.guard.update_tables(Insert { index, value })
+        }
+
+        pub fn append(&mut self, other: &mut VecDeque<T>) {
+            self.guard.update_tables(Append {
+                values: std::mem::take(other),
+            })
+        }
+    }
+
+    impl<'w, T> AsLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn pop_back(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_back())
+        }
+
+        pub fn pop_front(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_front())
+        }
+
+        pub fn remove(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn swap_remove_back(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove_back(index))
+        }
+
+        pub fn swap_remove_front(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove_front(index))
+        }
+
+        pub fn swap(&mut self, i: usize, j: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.swap(i, j))
+        }
+
+        pub fn rotate_left(&mut self, n: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.rotate_left(n))
+        }
+
+        pub fn rotate_right(&mut self, n: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.rotate_right(n))
+        }
+
+        pub fn truncate(&mut self, len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(len))
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        /// Rearranges both tables so that their contents are contiguous, i.e.
+        /// `as_slices().1` is empty. Unlike `VecDeque::make_contiguous`, the
+        /// slice isn't returned, since mutating it wouldn't be replayed.
+        pub fn make_contiguous(&mut self) {
+            self.guard.update_tables_closure(|table| {
+                table.make_contiguous();
+            })
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn resize_with<F>(&mut self, new_len: usize, f: F)
+        where
+            F: 'static + Clone + Send + FnMut() -> T,
+        {
+            self.guard.update_tables(ResizeWith { new_len, f })
+        }
+    }
+
+    impl<'w, 'a, T> AsLockWriteGuard<'w, T> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::collections::vec_deque::Drain<'a, T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+/// `arc::AsArcLock`, with the same write interface as `sync`.
+#[cfg(feature = "arc")]
+pub mod arc {
+    use super::*;
+    crate::generate_arc_aslock!(VecDeque<T>);
+
+    impl<'w, T> AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        pub fn push_back(&mut self, value: T) {
+            self.guard.update_tables(PushBack { value })
+        }
+
+        pub fn push_front(&mut self, value: T) {
+            self.guard.update_tables(PushFront { value })
+        }
+
+        /// Push `value` onto the back, popping values off the front until
+        /// there are at most `capacity` values. Returns the popped values, so
+        /// that the table can be used as a ring buffer.
+        pub fn push_back_bounded(&mut self, value: T, capacity: usize) -> Vec<T> {
+            self.push_back(value);
+            let excess = self.len().saturating_sub(capacity);
+            self.drain(..excess).collect()
+        }
+
+        pub fn insert(&mut self, index: usize, value: T) {
+            self.guard.update_tables(Insert { index, value })
+        }
+
+        pub fn append(&mut self, other: &mut VecDeque<T>) {
+            self.guard.update_tables(Append {
+                values: std::mem::take(other),
+            })
+        }
+    }
+
+    impl<'w, T> AsArcLockWriteGuard<'w, T> {
+        pub fn clear(&mut self) {
+            self.guard.update_tables_closure(|table| table.clear())
+        }
+
+        pub fn pop_back(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_back())
+        }
+
+        pub fn pop_front(&mut self) -> Option<T> {
+            self.guard.update_tables_closure(|table| table.pop_front())
+        }
+
+        pub fn remove(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.remove(index))
+        }
+
+        pub fn swap_remove_back(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove_back(index))
+        }
+
+        pub fn swap_remove_front(&mut self, index: usize) -> Option<T> {
+            self.guard
+                .update_tables_closure(move |table| table.swap_remove_front(index))
+        }
+
+        pub fn swap(&mut self, i: usize, j: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.swap(i, j))
+        }
+
+        pub fn rotate_left(&mut self, n: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.rotate_left(n))
+        }
+
+        pub fn rotate_right(&mut self, n: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.rotate_right(n))
+        }
+
+        pub fn truncate(&mut self, len: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.truncate(len))
+        }
+
+        pub fn reserve(&mut self, additional: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.reserve(additional))
+        }
+
+        pub fn shrink_to_fit(&mut self) {
+            self.guard
+                .update_tables_closure(|table| table.shrink_to_fit())
+        }
+
+        pub fn shrink_to(&mut self, min_capacity: usize) {
+            self.guard
+                .update_tables_closure(move |table| table.shrink_to(min_capacity))
+        }
+
+        /// Rearranges both tables so that their contents are contiguous, i.e.
+        /// `as_slices().1` is empty. Unlike `VecDeque::make_contiguous`, the
+        /// slice isn't returned, since mutating it wouldn't be replayed.
+        pub fn make_contiguous(&mut self) {
+            self.guard.update_tables_closure(|table| {
+                table.make_contiguous();
+            })
+        }
+
+        pub fn retain<F>(&mut self, f: F)
+        where
+            F: 'static + Clone + Send + FnMut(&T) -> bool,
+        {
+            self.guard.update_tables(Retain { f })
+        }
+
+        pub fn resize_with<F>(&mut self, new_len: usize, f: F)
+        where
+            F: 'static + Clone + Send + FnMut() -> T,
+        {
+            self.guard.update_tables(ResizeWith { new_len, f })
+        }
+    }
+
+    impl<'w, 'a, T> AsArcLockWriteGuard<'w, T> {
+        pub fn drain<R>(&'a mut self, range: R) -> std::collections::vec_deque::Drain<'a, T>
+        where
+            R: 'static + Clone + Send + RangeBounds<usize>,
+        {
+            self.guard.update_tables(Drain { range })
+        }
+    }
+
+    impl<'w, T> Extend<T> for AsArcLockWriteGuard<'w, T>
+    where
+        T: 'static + Clone + Send,
+    {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.guard.update_tables(Append {
+                values: iter.into_iter().collect(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod lockless_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn push_and_pop() {
+        let table = lockless::AsLockHandle::<i32>::default();
+        let table2 = table.clone();
+        {
+            let mut wg = table.write();
+            wg.push_back(2);
+            wg.push_front(1);
+            wg.push_back(3);
+            assert_eq!(wg.pop_front(), Some(1));
+            assert!(table2.read().is_empty());
+        }
+        assert_tables_eq!(table, VecDeque::from([2, 3]));
+
+        table.write().pop_back();
+        assert_tables_eq!(table, VecDeque::from([2]));
+    }
+
+    #[test]
+    fn push_back_bounded() {
+        let table = lockless::AsLockHandle::<i32>::default();
+        {
+            let mut wg = table.write();
+            for i in 0..4 {
+                assert!(wg.push_back_bounded(i, 4).is_empty());
+            }
+            assert_eq!(wg.push_back_bounded(4, 3), vec![0, 1]);
+        }
+        assert_tables_eq!(table, VecDeque::from([2, 3, 4]));
+    }
+
+    #[test]
+    fn rotate() {
+        let table = lockless::AsLockHandle::new(VecDeque::from([1, 2, 3, 4]));
+        table.write().rotate_left(1);
+        assert_tables_eq!(table, VecDeque::from([2, 3, 4, 1]));
+        table.write().rotate_right(2);
+        assert_tables_eq!(table, VecDeque::from([4, 1, 2, 3]));
+        let mut wg = table.write();
+        wg.make_contiguous();
+        assert_eq!(wg.as_slices(), (&[4, 1, 2, 3][..], &[][..]));
+    }
+
+    #[test]
+    fn drain_and_retain() {
+        let table = lockless::AsLockHandle::new((0..6).collect::<VecDeque<_>>());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.drain(..2).collect::<Vec<_>>(), vec![0, 1]);
+            wg.retain(|x| x % 2 == 0);
+            wg.extend([6, 7]);
+        }
+        assert_tables_eq!(table, VecDeque::from([2, 4, 6, 7]));
+    }
+}
+
+#[cfg(test)]
+mod sync_test {
+    use super::*;
+    use crate::assert_tables_eq;
+
+    #[test]
+    fn push_and_pop() {
+        let table = sync::AsLock::<i32>::default();
+        {
+            let mut wg = table.write();
+            wg.push_back(2);
+            wg.push_front(1);
+            wg.push_back(3);
+            assert_eq!(wg.pop_front(), Some(1));
+            assert!(table.read().is_empty());
+        }
+        assert_tables_eq!(table, VecDeque::from([2, 3]));
+
+        table.write().pop_back();
+        assert_tables_eq!(table, VecDeque::from([2]));
+    }
+
+    #[test]
+    fn push_back_bounded() {
+        let table = sync::AsLock::<i32>::default();
+        {
+            let mut wg = table.write();
+            for i in 0..4 {
+                assert!(wg.push_back_bounded(i, 4).is_empty());
+            }
+            assert_eq!(wg.push_back_bounded(4, 3), vec![0, 1]);
+        }
+        assert_tables_eq!(table, VecDeque::from([2, 3, 4]));
+    }
+
+    #[test]
+    fn rotate() {
+        let table = sync::AsLock::new(VecDeque::from([1, 2, 3, 4]));
+        table.write().rotate_left(1);
+        assert_tables_eq!(table, VecDeque::from([2, 3, 4, 1]));
+        table.write().rotate_right(2);
+        assert_tables_eq!(table, VecDeque::from([4, 1, 2, 3]));
+        let mut wg = table.write();
+        wg.make_contiguous();
+        assert_eq!(wg.as_slices(), (&[4, 1, 2, 3][..], &[][..]));
+    }
+
+    #[test]
+    fn drain_and_retain() {
+        let table = sync::AsLock::new((0..6).collect::<VecDeque<_>>());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.drain(..2).collect::<Vec<_>>(), vec![0, 1]);
+            wg.retain(|x| x % 2 == 0);
+            wg.extend([6, 7]);
+        }
+        assert_tables_eq!(table, VecDeque::from([2, 4, 6, 7]));
+    }
+}
+
+#[cfg(all(test, feature = "arc"))]
+mod arc_test {
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let table = arc::AsArcLock::<i32>::default();
+        {
+            let mut wg = table.write();
+            wg.push_back(2);
+            wg.push_front(1);
+            wg.push_back(3);
+            assert_eq!(wg.pop_front(), Some(1));
+            assert!(table.read().is_empty());
+        }
+        assert_eq!(**table.read(), VecDeque::from([2, 3]));
+        table.write().pop_back();
+        assert_eq!(*table.write(), VecDeque::from([2]));
+        assert_eq!(**table.read(), VecDeque::from([2]));
+    }
+}
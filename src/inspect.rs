@@ -0,0 +1,227 @@
+//! Machine readable state of a table, for admin endpoints and monitoring.
+//!
+//! `Inspect::inspect` reports what the `Debug` output of a table reports,
+//! without formatting the tables themselves. With the `serde` feature,
+//! `Inspection` is `Serialize`, so it can be exposed as JSON directly.
+//!
+//! ```rust
+//! use active_standby::inspect::Inspect;
+//! use active_standby::lockless::collections::AsVecHandle;
+//!
+//! let table = AsVecHandle::new(vec![1, 2]);
+//! table.write().push(3);
+//! let inspection = table.inspect_sized();
+//! assert_eq!(inspection.num_readers, Some(1));
+//! assert_eq!(inspection.version, 1);
+//! assert_eq!(inspection.size, Some(3));
+//! assert_eq!(table.inspect().size, None);
+//! ```
+//!
+//! Inspecting a table never blocks on its writer. If a write guard is held,
+//! the fields which require the writer's lock are `None`.
+//!
+//! For monitoring the writer over time, `stats` also reports how often and
+//! how long writers have waited on readers to leave the standby table.
+//!
+//! ```rust
+//! use active_standby::sync::AsLock;
+//!
+//! let table = AsLock::new(vec![1]);
+//! table.write().update_tables_closure(|vec| vec.push(2));
+//! let stats = table.stats();
+//! assert_eq!(stats.num_publishes, 1);
+//! assert_eq!(stats.num_waits, 0);
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The state of a table at the time it was inspected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Inspection {
+    /// Name of the type of the underlying table.
+    pub type_name: &'static str,
+    /// Number of readers. Only tracked by the lockless flavor.
+    pub num_readers: Option<usize>,
+    /// Number of ops waiting to be replayed on the standby table.
+    pub num_ops_to_replay: Option<usize>,
+    /// Number of times the table has been published.
+    pub version: u64,
+    /// Size of the active table, as reported by `Sizer`. Only filled in by
+    /// `inspect_sized`.
+    pub size: Option<usize>,
+}
+
+/// Counters of a table's writer, returned by `AsLock::stats` and
+/// `AsLockHandle::stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AsLockStats {
+    /// Number of readers. Only tracked by the lockless flavor.
+    pub num_readers: Option<usize>,
+    /// Number of ops waiting to be replayed on the standby table. `None` if
+    /// a write guard is held.
+    pub num_ops_to_replay: Option<usize>,
+    /// Number of times the table has been published.
+    pub num_publishes: u64,
+    /// Number of times a writer had to wait for readers.
+    pub num_waits: u64,
+    /// Total time writers have spent waiting for readers.
+    pub total_wait: Duration,
+    /// Longest time a single writer has waited for readers.
+    pub max_wait: Duration,
+}
+
+// Not part of the active_standby synchronization protocol, so always use std.
+#[derive(Default)]
+pub(crate) struct WaitStats {
+    num_waits: AtomicU64,
+    total_wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+}
+
+impl WaitStats {
+    /// Only called by the writer, so the counters needn't be updated
+    /// atomically together.
+    pub(crate) fn record(&self, wait: Duration) {
+        let nanos = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        self.num_waits.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Fills in the wait fields of `AsLockStats`.
+    pub(crate) fn stats(
+        &self,
+        num_readers: Option<usize>,
+        num_ops_to_replay: Option<usize>,
+        num_publishes: u64,
+    ) -> AsLockStats {
+        AsLockStats {
+            num_readers,
+            num_ops_to_replay,
+            num_publishes,
+            num_waits: self.num_waits.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.total_wait_nanos.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Implemented by tables and the types generated for the collections.
+pub trait Inspect {
+    fn inspect(&self) -> Inspection;
+}
+
+impl<I: Inspect + ?Sized> Inspect for &I {
+    fn inspect(&self) -> Inspection {
+        I::inspect(self)
+    }
+}
+
+impl<I: Inspect + ?Sized> Inspect for std::sync::Arc<I> {
+    fn inspect(&self) -> Inspection {
+        I::inspect(self)
+    }
+}
+
+/// Size of a table reported by `inspect_sized`, such as its number of
+/// elements.
+pub trait Sizer {
+    fn size(&self) -> usize;
+}
+
+macro_rules! impl_sizer_by_len {
+    ($($Table:ident < $($Inner:ident),* >),* $(,)?) => {
+        $(
+            impl<$($Inner),*> Sizer for $Table<$($Inner),*> {
+                fn size(&self) -> usize {
+                    self.len()
+                }
+            }
+        )*
+    };
+}
+
+impl_sizer_by_len!(
+    Vec<T>,
+    VecDeque<T>,
+    BinaryHeap<T>,
+    HashMap<K, V, S>,
+    HashSet<T, S>,
+    BTreeMap<K, V>,
+    BTreeSet<T>,
+);
+
+/// The number of bits set.
+impl Sizer for crate::collections::bitset::BitSet {
+    fn size(&self) -> usize {
+        self.count_ones()
+    }
+}
+
+/// The total cost of the values.
+impl<K, V> Sizer for crate::collections::budgetmap::BudgetMap<K, V>
+where
+    K: std::hash::Hash + Eq + Clone,
+{
+    fn size(&self) -> usize {
+        self.total_cost()
+    }
+}
+
+/// The length in bytes.
+impl Sizer for String {
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<E> Sizer for crate::collections::eventlog::EventLog<E> {
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, K> Sizer for crate::collections::indexedvec::IndexedVec<T, K>
+where
+    K: std::hash::Hash + Eq,
+{
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizer() {
+        assert_eq!(vec![1, 2].size(), 2);
+        assert_eq!(std::collections::HashMap::from([(1, 2)]).size(), 1);
+        let mut bitset = crate::collections::bitset::BitSet::new();
+        bitset.set(3, true);
+        bitset.set(7, true);
+        assert_eq!(bitset.size(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() {
+        let table = crate::sync::AsLock::new(vec![1]);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(
+            serde_json::to_value(table.inspect_sized()).unwrap(),
+            serde_json::json!({
+                "type_name": "alloc::vec::Vec<i32>",
+                "num_readers": null,
+                "num_ops_to_replay": 1,
+                "version": 1,
+                "size": 2,
+            })
+        );
+    }
+}
@@ -134,6 +134,15 @@
 //! }
 //! ```
 //!
+//! ## Safety
+//! The lockless flavor relies on `unsafe` code to hand out references to the
+//! active table. Users who want to avoid `unsafe` can enable the `safe-impl`
+//! feature, which builds the lockless tables on top of `arc-swap` instead, at
+//! the cost of reference counting on every read. The API is unchanged.
+//!
+//! The opt in `seqlock` feature adds seqlock reads, whose copies of the table
+//! may race with the writer. See `sync::AsSeqLock`.
+//!
 //! ## Testing
 //! There are a number of tests that come with active_standby (see
 //! tests/tests_script.sh for examples):
@@ -149,14 +158,53 @@
 //! [Miri](https://github.com/rust-lang/miri)
 //!
 //! [Rudra](https://github.com/sslab-gatech/Rudra)
+//!
+//! With the `failpoints` feature, downstream tests can inject delays and
+//! panics with the [fail](https://crates.io/crates/fail) crate at:
+//! - `active_standby::before_swap`: before the tables are swapped.
+//! - `active_standby::replay`: before each update is replayed on the standby
+//!   table.
+//! - `active_standby::await_readers`: while the writer waits for readers to
+//!   leave the standby table. In the lockless flavor, this is only hit while
+//!   a reader is actually blocking it.
+//!
+//! Downstream crates can check when their updates become visible to readers
+//! with the `testing` feature's `testing::assert_publish_visible_after`.
+//!
+//! Adopters can also soak the collections on their own hardware with
+//! `cargo run --release --example stress`, and fuzz them with the
+//! [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz) targets in `fuzz/`.
+
+#![cfg_attr(feature = "safe-impl", forbid(unsafe_code))]
 
 mod macros;
 pub(crate) mod types;
 
 mod collections;
 mod primitives;
+pub mod inspect;
+#[cfg(feature = "op-stats")]
+pub mod op_stats;
+#[cfg(feature = "optimistic")]
+pub mod optimistic;
+#[cfg(all(feature = "overrides", not(loom)))]
+pub mod overrides;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(all(feature = "reload", not(loom)))]
+pub mod reload;
+#[cfg(all(feature = "replication", not(loom)))]
+pub mod replication;
+#[cfg(all(feature = "sim", not(loom)))]
+pub mod simulate;
+#[cfg(all(feature = "testing", not(loom)))]
+pub mod testing;
 
-pub use crate::types::UpdateTables;
+pub use crate::primitives::changed::{Changed, SwapListener};
+pub use crate::types::{
+    Aggregate, ClosureOp, ClosureUpdate, DynReadInterface, Options, ParallelClone, PendingOpsFull,
+    ReadInterface, Snapshot, UpdateTables, WithCapacity,
+};
 pub mod lockless {
 
     /// Premade structs which wrap standard collection in the active standby
@@ -166,6 +214,24 @@ pub mod lockless {
     pub mod collections {
         // Inline the re-export to make rustdocs more readable.
         #[doc(inline)]
+        pub use crate::collections::binaryheap::lockless::{
+            AsLockHandle as AsBinaryHeapHandle, AsLockWriteGuard as AsBinaryHeapWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::bitset::{
+            lockless::{AsLockHandle as AsBitSetHandle, AsLockWriteGuard as AsBitSetWriteGuard},
+            BitSet,
+        };
+        #[doc(inline)]
+        pub use crate::collections::budgetmap::{
+            lockless::{
+                AsLockHandle as AsBudgetMapHandle, AsLockWriteGuard as AsBudgetMapWriteGuard,
+            },
+            BudgetMap,
+        };
+        #[doc(inline)]
+        pub use crate::collections::OccupiedError;
+        #[doc(inline)]
         pub use crate::collections::btreemap::lockless::{
             AsLockHandle as AsBTreeMapHandle, AsLockWriteGuard as AsBTreeMapWriteGuard,
         };
@@ -174,6 +240,13 @@ pub mod lockless {
             AsLockHandle as AsBTreeSetHandle, AsLockWriteGuard as AsBTreeSetWriteGuard,
         };
         #[doc(inline)]
+        pub use crate::collections::eventlog::{
+            lockless::{
+                AsLockHandle as AsEventLogHandle, AsLockWriteGuard as AsEventLogWriteGuard,
+            },
+            EventLog,
+        };
+        #[doc(inline)]
         pub use crate::collections::hashmap::lockless::{
             AsLockHandle as AsHashMapHandle, AsLockWriteGuard as AsHashMapWriteGuard,
         };
@@ -182,11 +255,44 @@ pub mod lockless {
             AsLockHandle as AsHashSetHandle, AsLockWriteGuard as AsHashSetWriteGuard,
         };
         #[doc(inline)]
-        pub use crate::collections::vec::lockless::{
-            AsLockHandle as AsVecHandle, AsLockWriteGuard as AsVecWriteGuard,
+        pub use crate::collections::indexedvec::{
+            lockless::{
+                AsLockHandle as AsIndexedVecHandle, AsLockWriteGuard as AsIndexedVecWriteGuard,
+            },
+            IndexedVec,
+        };
+        #[cfg(feature = "json")]
+        #[doc(inline)]
+        pub use crate::collections::json::{
+            lockless::{AsLockHandle as AsJsonHandle, AsLockWriteGuard as AsJsonWriteGuard},
+            JsonPathError,
+        };
+        #[doc(inline)]
+        pub use crate::collections::string::lockless::{
+            AsLockHandle as AsStringHandle, AsLockWriteGuard as AsStringWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::vec::{
+            lockless::{
+                AsLockHandle as AsVecHandle, AsLockWriteGuard as AsVecWriteGuard, ShardedVec,
+            },
+            VecEditPlan, VecEditPlanError,
+        };
+        #[doc(inline)]
+        pub use crate::collections::vecdeque::lockless::{
+            AsLockHandle as AsVecDequeHandle, AsLockWriteGuard as AsVecDequeWriteGuard,
         };
     }
-    pub use crate::primitives::lockless::{AsLockHandle, AsLockReadGuard, AsLockWriteGuard};
+    pub use crate::primitives::lockless::{
+        split, AsLockHandle, AsLockOwnedReadGuard, AsLockReadGuard, AsLockWriteGuard, ReadToken,
+        ReaderFactory, ReaderHalf, SpawnWithHandle, WriterHalf,
+    };
+    #[cfg(feature = "reader-stats")]
+    pub use crate::primitives::lockless::{ReaderStats, READ_SAMPLE_RATE};
+    #[cfg(not(feature = "safe-impl"))]
+    pub use crate::primitives::lockless::WaitFreeReadError;
+    #[cfg(not(feature = "safe-impl"))]
+    pub use crate::primitives::inline::{AsInlineHandle, AsInlineReadGuard, AsInlineWriteGuard};
 }
 
 pub mod sync {
@@ -197,6 +303,22 @@ pub mod sync {
     pub mod collections {
         // Inline the re-export to make rustdocs more readable.
         #[doc(inline)]
+        pub use crate::collections::binaryheap::sync::{
+            AsLock as AsBinaryHeap, AsLockWriteGuard as AsBinaryHeapWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::bitset::{
+            sync::{AsLock as AsBitSet, AsLockWriteGuard as AsBitSetWriteGuard},
+            BitSet,
+        };
+        #[doc(inline)]
+        pub use crate::collections::budgetmap::{
+            sync::{AsLock as AsBudgetMap, AsLockWriteGuard as AsBudgetMapWriteGuard},
+            BudgetMap,
+        };
+        #[doc(inline)]
+        pub use crate::collections::OccupiedError;
+        #[doc(inline)]
         pub use crate::collections::btreemap::sync::{
             AsLock as AsBTreeMap, AsLockWriteGuard as AsBTreeMapWriteGuard,
         };
@@ -205,6 +327,11 @@ pub mod sync {
             AsLock as AsBTreeSet, AsLockWriteGuard as AsBTreeSetWriteGuard,
         };
         #[doc(inline)]
+        pub use crate::collections::eventlog::{
+            sync::{AsLock as AsEventLog, AsLockWriteGuard as AsEventLogWriteGuard},
+            EventLog,
+        };
+        #[doc(inline)]
         pub use crate::collections::hashmap::sync::{
             AsLock as AsHashMap, AsLockWriteGuard as AsHashMapWriteGuard,
         };
@@ -213,9 +340,101 @@ pub mod sync {
             AsLock as AsHashSet, AsLockWriteGuard as AsHashSetWriteGuard,
         };
         #[doc(inline)]
-        pub use crate::collections::vec::sync::{
-            AsLock as AsVec, AsLockWriteGuard as AsVecWriteGuard,
+        pub use crate::collections::indexedvec::{
+            sync::{AsLock as AsIndexedVec, AsLockWriteGuard as AsIndexedVecWriteGuard},
+            IndexedVec,
+        };
+        #[cfg(feature = "json")]
+        #[doc(inline)]
+        pub use crate::collections::json::{
+            sync::{AsLock as AsJson, AsLockWriteGuard as AsJsonWriteGuard},
+            JsonPathError,
+        };
+        #[doc(inline)]
+        pub use crate::collections::string::sync::{
+            AsLock as AsString, AsLockWriteGuard as AsStringWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::vec::{
+            sync::{AsLock as AsVec, AsLockWriteGuard as AsVecWriteGuard, ShardedVec},
+            VecEditPlan, VecEditPlanError,
+        };
+        #[doc(inline)]
+        pub use crate::collections::vecdeque::sync::{
+            AsLock as AsVecDeque, AsLockWriteGuard as AsVecDequeWriteGuard,
         };
     }
+    pub use crate::primitives::publish_group::{GroupMember, GroupWriteGuard, PublishGroup};
+    /// Requires the `seqlock` feature. Not available with `safe-impl`.
+    #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+    pub use crate::primitives::seqlock::{AsSeqLock, AsSeqLockWriteGuard};
+    /// Not available with `safe-impl`.
+    #[cfg(not(any(loom, feature = "safe-impl")))]
+    pub use crate::primitives::sync::AsLockOwnedReadGuard;
     pub use crate::primitives::sync::{AsLock, AsLockReadGuard, AsLockWriteGuard};
 }
+
+/// Variant where the tables are held as `Arc`s, so readers never block the
+/// writer. Requires the `arc` feature.
+#[cfg(feature = "arc")]
+pub mod arc {
+    /// Premade structs which wrap standard collections, with the same write
+    /// interface as `sync::collections`.
+    pub mod collections {
+        // Inline the re-export to make rustdocs more readable.
+        #[doc(inline)]
+        pub use crate::collections::binaryheap::arc::{
+            AsArcLock as AsBinaryHeap, AsArcLockWriteGuard as AsBinaryHeapWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::bitset::arc::{
+            AsArcLock as AsBitSet, AsArcLockWriteGuard as AsBitSetWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::budgetmap::arc::{
+            AsArcLock as AsBudgetMap, AsArcLockWriteGuard as AsBudgetMapWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::btreemap::arc::{
+            AsArcLock as AsBTreeMap, AsArcLockWriteGuard as AsBTreeMapWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::btreeset::arc::{
+            AsArcLock as AsBTreeSet, AsArcLockWriteGuard as AsBTreeSetWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::eventlog::arc::{
+            AsArcLock as AsEventLog, AsArcLockWriteGuard as AsEventLogWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::hashmap::arc::{
+            AsArcLock as AsHashMap, AsArcLockWriteGuard as AsHashMapWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::hashset::arc::{
+            AsArcLock as AsHashSet, AsArcLockWriteGuard as AsHashSetWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::indexedvec::arc::{
+            AsArcLock as AsIndexedVec, AsArcLockWriteGuard as AsIndexedVecWriteGuard,
+        };
+        #[cfg(feature = "json")]
+        #[doc(inline)]
+        pub use crate::collections::json::arc::{
+            AsArcLock as AsJson, AsArcLockWriteGuard as AsJsonWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::string::arc::{
+            AsArcLock as AsString, AsArcLockWriteGuard as AsStringWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::vec::arc::{
+            AsArcLock as AsVec, AsArcLockWriteGuard as AsVecWriteGuard,
+        };
+        #[doc(inline)]
+        pub use crate::collections::vecdeque::arc::{
+            AsArcLock as AsVecDeque, AsArcLockWriteGuard as AsVecDequeWriteGuard,
+        };
+    }
+    pub use crate::primitives::arc::{AsArcLock, AsArcLockReadGuard, AsArcLockWriteGuard};
+}
@@ -22,7 +22,6 @@
 ///
 // TODO: Is there some way to get all of the mutable methods of Table and
 // automatically generate `UpdateTables` wrappers for them?
-
 /// Generates a lockless::AsLockHandle for the type passed in.
 #[macro_export]
 macro_rules! generate_lockless_aslockhandle {
@@ -56,6 +55,93 @@ macro_rules! generate_lockless_aslockhandle {
             ) -> R {
                 self.guard.update_tables_closure(update)
             }
+
+            pub fn update_tables_weighted<'a, R>(
+                &'a mut self,
+                weight: usize,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_weighted(weight, update)
+            }
+
+            pub fn update_tables_closure_weighted<R>(
+                &mut self,
+                weight: usize,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_closure_weighted(weight, update)
+            }
+
+            pub fn update_tables_labeled<'a, R>(
+                &'a mut self,
+                label: &'static str,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_labeled(label, update)
+            }
+
+            pub fn update_tables_closure_labeled<R>(
+                &mut self,
+                label: &'static str,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_closure_labeled(label, update)
+            }
+
+            pub fn update_tables_closure_mut<R>(
+                &mut self,
+                update: impl FnMut(&mut $Table$(< $($Inner),* >)?) -> R + Clone + 'static + Send,
+            ) -> R {
+                self.guard.update_tables_closure_mut(update)
+            }
+
+            pub fn try_update_tables<'a, R>(
+                &'a mut self,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> Result<R, $crate::PendingOpsFull> {
+                self.guard.try_update_tables(update)
+            }
+
+            pub fn try_update_tables_closure<R>(
+                &mut self,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> Result<R, $crate::PendingOpsFull> {
+                self.guard.try_update_tables_closure(update)
+            }
+
+            pub fn update_tables_scoped<State, R>(
+                &mut self,
+                state: &State,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?, &State) -> R + 'static + Sized + Send,
+            ) -> R
+            where
+                State: ToOwned + ?Sized,
+                State::Owned: 'static + Send,
+            {
+                self.guard.update_tables_scoped(state, update)
+            }
+
+            pub fn num_pending_ops(&self) -> usize {
+                self.guard.num_pending_ops()
+            }
+
+            pub fn publishing_cost_estimate(&self) -> usize {
+                self.guard.publishing_cost_estimate()
+            }
+
+            pub fn defer_drop(&mut self, value: impl Send + 'static) {
+                self.guard.defer_drop(value)
+            }
+
+            // The higher ranked bound stops this from being a trivial bound,
+            // which would fail to compile for concrete tables that aren't
+            // `PartialEq`.
+            pub fn assert_synced(&mut self)
+            where
+                for<'x> $Table$(< $($Inner),* >)?: PartialEq + std::fmt::Debug,
+            {
+                self.guard.assert_synced()
+            }
         }
 
         // Deref should pass through the wrapper AsLockWriteGuard and look like the
@@ -98,12 +184,67 @@ macro_rules! generate_lockless_aslockhandle {
                 }
             }
 
+            pub fn from_fn(
+                f: impl FnMut() -> $Table $(< $($Inner),* >)?
+            ) -> AsLockHandle$(<$($Inner),*>)? {
+                AsLockHandle {
+                    inner: AsLockHandleAlias::from_fn(f)
+                }
+            }
+
+            pub fn from_identical_with(
+                t1: $Table $(< $($Inner),* >)?,
+                t2: $Table $(< $($Inner),* >)?,
+                options: $crate::Options,
+            ) -> AsLockHandle$(<$($Inner),*>)? {
+                AsLockHandle {
+                    inner: AsLockHandleAlias::from_identical_with(t1, t2, options)
+                }
+            }
+
+            pub fn try_into_inner(
+                self,
+            ) -> Result<$Table $(< $($Inner),* >)?, AsLockHandle$(<$($Inner),*>)?> {
+                self.inner
+                    .try_into_inner()
+                    .map_err(|inner| AsLockHandle { inner })
+            }
+
             pub fn write(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
                 // Type conversion from generic AsLockWriteGuard to the generated AsLockWriteGuard.
                 AsLockWriteGuard {
                     guard: self.inner.write()
                 }
             }
+
+            /// See `lockless::AsLockHandle::write_budgeted`.
+            pub fn write_budgeted(&self, max_ops: usize) -> Option<AsLockWriteGuard<'_, $($($Inner),*)?>> {
+                self.inner.write_budgeted(max_ops).map(|guard| AsLockWriteGuard { guard })
+            }
+
+            /// See `lockless::AsLockHandle::write_timed`.
+            pub fn write_timed(&self, budget: ::std::time::Duration) -> Option<AsLockWriteGuard<'_, $($($Inner),*)?>> {
+                self.inner.write_timed(budget).map(|guard| AsLockWriteGuard { guard })
+            }
+
+            /// See `lockless::AsLockHandle::write_full`.
+            pub fn write_full(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
+                AsLockWriteGuard {
+                    guard: self.inner.write_full()
+                }
+            }
+
+            /// See `lockless::AsLockHandle::try_write`.
+            pub fn try_write(&self) -> Option<AsLockWriteGuard<'_, $($($Inner),*)?>> {
+                self.inner.try_write().map(|guard| AsLockWriteGuard { guard })
+            }
+
+            /// See `lockless::AsLockHandle::write_deferred`.
+            pub fn write_deferred(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
+                AsLockWriteGuard {
+                    guard: self.inner.write_deferred()
+                }
+            }
         }
 
         impl$(< $($Inner),* >)? AsLockHandle$(< $($Inner),* >)?
@@ -115,6 +256,50 @@ macro_rules! generate_lockless_aslockhandle {
                     inner: AsLockHandleAlias::from_identical(t.clone(), t)
                 }
             }
+
+            pub fn new_with(
+                t: $Table $(< $($Inner),* >)?,
+                options: $crate::Options,
+            ) -> AsLockHandle$(<$($Inner),*>)? {
+                AsLockHandle {
+                    inner: AsLockHandleAlias::new_with(t, options)
+                }
+            }
+        }
+
+        impl$(< $($Inner),* >)? AsLockHandle$(< $($Inner),* >)? {
+            // See `assert_synced` for why the bound is higher ranked.
+            pub fn with_capacity(capacity: usize) -> AsLockHandle$(<$($Inner),*>)?
+            where
+                for<'x> $Table$(<$($Inner),*>)? : $crate::WithCapacity,
+            {
+                AsLockHandle {
+                    inner: AsLockHandleAlias::with_capacity(capacity)
+                }
+            }
+
+            pub fn new_parallel(t: $Table $(< $($Inner),* >)?) -> AsLockHandle$(<$($Inner),*>)?
+            where
+                for<'x> $Table$(<$($Inner),*>)? : $crate::ParallelClone,
+            {
+                AsLockHandle {
+                    inner: AsLockHandleAlias::new_parallel(t)
+                }
+            }
+
+            pub fn same_table(a: &Self, b: &Self) -> bool {
+                AsLockHandleAlias::same_table(&a.inner, &b.inner)
+            }
+
+            /// See `lockless::AsLockHandle::pin`.
+            pub fn pin(&mut self) {
+                self.inner.pin()
+            }
+
+            /// See `lockless::AsLockHandle::unpin`.
+            pub fn unpin(&mut self) {
+                self.inner.unpin()
+            }
         }
 
         impl$(< $($Inner),* >)? std::ops::Deref  for AsLockHandle$(< $($Inner),* >)? {
@@ -124,6 +309,39 @@ macro_rules! generate_lockless_aslockhandle {
             }
         }
 
+        impl$(< $($Inner),* >)? $crate::ReadInterface<$Table$(<$($Inner),*>)?>
+            for AsLockHandle$(< $($Inner),* >)?
+        {
+            type Guard<'r> = $crate::lockless::AsLockReadGuard<'r, $Table$(<$($Inner),*>)?>
+            where
+                Self: 'r;
+
+            fn read(&self) -> Self::Guard<'_> {
+                self.inner.read()
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::Snapshot<$Table$(<$($Inner),*>)?>
+            for AsLockHandle$(< $($Inner),* >)?
+        {
+            fn with<R>(&self, f: impl FnOnce(&$Table$(<$($Inner),*>)?) -> R) -> R {
+                f(&self.inner.read())
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::inspect::Inspect for AsLockHandle$(< $($Inner),* >)? {
+            fn inspect(&self) -> $crate::inspect::Inspection {
+                $crate::inspect::Inspect::inspect(&self.inner)
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::lockless::SpawnWithHandle
+            for AsLockHandle$(< $($Inner),* >)?
+        where
+            Self: Clone + Send + 'static,
+        {
+        }
+
         // TODO: derive Default. Not playing nice with Rudra currently...
         impl$(< $($Inner),* >)? Default for AsLockHandle$(< $($Inner),* >)?
         where
@@ -144,6 +362,20 @@ macro_rules! generate_lockless_aslockhandle {
                 self.inner.fmt(f)
             }
         }
+
+        impl$(< $($Inner),* >)? PartialEq for AsLockHandle$(< $($Inner),* >)? {
+            fn eq(&self, other: &Self) -> bool {
+                self.inner == other.inner
+            }
+        }
+
+        impl$(< $($Inner),* >)? Eq for AsLockHandle$(< $($Inner),* >)? {}
+
+        impl$(< $($Inner),* >)? std::hash::Hash for AsLockHandle$(< $($Inner),* >)? {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.inner.hash(state)
+            }
+        }
     }
 }
 
@@ -195,6 +427,93 @@ macro_rules! generate_sync_aslock {
             ) -> R {
                 self.guard.update_tables_closure(update)
             }
+
+            pub fn update_tables_weighted<'a, R>(
+                &'a mut self,
+                weight: usize,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_weighted(weight, update)
+            }
+
+            pub fn update_tables_closure_weighted<R>(
+                &mut self,
+                weight: usize,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_closure_weighted(weight, update)
+            }
+
+            pub fn update_tables_labeled<'a, R>(
+                &'a mut self,
+                label: &'static str,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_labeled(label, update)
+            }
+
+            pub fn update_tables_closure_labeled<R>(
+                &mut self,
+                label: &'static str,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_closure_labeled(label, update)
+            }
+
+            pub fn update_tables_closure_mut<R>(
+                &mut self,
+                update: impl FnMut(&mut $Table$(< $($Inner),* >)?) -> R + Clone + 'static + Send,
+            ) -> R {
+                self.guard.update_tables_closure_mut(update)
+            }
+
+            pub fn try_update_tables<'a, R>(
+                &'a mut self,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> Result<R, $crate::PendingOpsFull> {
+                self.guard.try_update_tables(update)
+            }
+
+            pub fn try_update_tables_closure<R>(
+                &mut self,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> Result<R, $crate::PendingOpsFull> {
+                self.guard.try_update_tables_closure(update)
+            }
+
+            pub fn update_tables_scoped<State, R>(
+                &mut self,
+                state: &State,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?, &State) -> R + 'static + Sized + Send,
+            ) -> R
+            where
+                State: ToOwned + ?Sized,
+                State::Owned: 'static + Send,
+            {
+                self.guard.update_tables_scoped(state, update)
+            }
+
+            pub fn num_pending_ops(&self) -> usize {
+                self.guard.num_pending_ops()
+            }
+
+            pub fn publishing_cost_estimate(&self) -> usize {
+                self.guard.publishing_cost_estimate()
+            }
+
+            pub fn defer_drop(&mut self, value: impl Send + 'static) {
+                self.guard.defer_drop(value)
+            }
+
+            // The higher ranked bound stops this from being a trivial bound,
+            // which would fail to compile for concrete tables that aren't
+            // `PartialEq`.
+            pub fn assert_synced(&mut self)
+            where
+                for<'x> $Table$(< $($Inner),* >)?: PartialEq + std::fmt::Debug,
+            {
+                self.guard.assert_synced()
+            }
         }
 
         // Deref should pass through the wrapper AsLockWriteGuard and look like the
@@ -238,11 +557,71 @@ macro_rules! generate_sync_aslock {
                 }
             }
 
+            pub fn from_fn(
+                f: impl FnMut() -> $Table $(< $($Inner),* >)?
+            ) -> AsLock$(<$($Inner),*>)? {
+                AsLock {
+                    inner: AsLockAlias::from_fn(f)
+                }
+            }
+
+            pub fn from_identical_with(
+                t1: $Table $(< $($Inner),* >)?,
+                t2: $Table $(< $($Inner),* >)?,
+                options: $crate::Options,
+            ) -> AsLock$(<$($Inner),*>)? {
+                AsLock {
+                    inner: AsLockAlias::from_identical_with(t1, t2, options)
+                }
+            }
+
+            pub fn into_inner(self) -> $Table $(< $($Inner),* >)? {
+                self.inner.into_inner()
+            }
+
             pub fn write(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
                 AsLockWriteGuard {
                     guard: self.inner.write()
                 }
             }
+
+            /// See `sync::AsLock::write_budgeted`.
+            pub fn write_budgeted(&self, max_ops: usize) -> Option<AsLockWriteGuard<'_, $($($Inner),*)?>> {
+                self.inner.write_budgeted(max_ops).map(|guard| AsLockWriteGuard { guard })
+            }
+
+            /// See `sync::AsLock::write_timed`.
+            pub fn write_timed(&self, budget: ::std::time::Duration) -> Option<AsLockWriteGuard<'_, $($($Inner),*)?>> {
+                self.inner.write_timed(budget).map(|guard| AsLockWriteGuard { guard })
+            }
+
+            /// See `sync::AsLock::write_full`.
+            pub fn write_full(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
+                AsLockWriteGuard {
+                    guard: self.inner.write_full()
+                }
+            }
+
+            /// See `sync::AsLock::try_write`.
+            pub fn try_write(&self) -> Option<AsLockWriteGuard<'_, $($($Inner),*)?>> {
+                self.inner.try_write().map(|guard| AsLockWriteGuard { guard })
+            }
+
+            /// See `sync::AsLock::write_deferred`.
+            pub fn write_deferred(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
+                AsLockWriteGuard {
+                    guard: self.inner.write_deferred()
+                }
+            }
+
+            $crate::__if_tokio! {
+                /// See `sync::AsLock::write_async`.
+                pub async fn write_async(&self) -> AsLockWriteGuard<'_, $($($Inner),*)?> {
+                    AsLockWriteGuard {
+                        guard: self.inner.write_async().await
+                    }
+                }
+            }
         }
 
         impl$(< $($Inner),* >)? AsLock$(< $($Inner),* >)?
@@ -254,6 +633,40 @@ macro_rules! generate_sync_aslock {
                     inner: AsLockAlias::new(t)
                 }
             }
+
+            pub fn new_with(
+                t: $Table $(< $($Inner),* >)?,
+                options: $crate::Options,
+            ) -> AsLock$(<$($Inner),*>)? {
+                AsLock {
+                    inner: AsLockAlias::new_with(t, options)
+                }
+            }
+        }
+
+        impl$(< $($Inner),* >)? AsLock$(< $($Inner),* >)? {
+            // See `assert_synced` for why the bound is higher ranked.
+            pub fn with_capacity(capacity: usize) -> AsLock$(<$($Inner),*>)?
+            where
+                for<'x> $Table$(<$($Inner),*>)? : $crate::WithCapacity,
+            {
+                AsLock {
+                    inner: AsLockAlias::with_capacity(capacity)
+                }
+            }
+
+            pub fn new_parallel(t: $Table $(< $($Inner),* >)?) -> AsLock$(<$($Inner),*>)?
+            where
+                for<'x> $Table$(<$($Inner),*>)? : $crate::ParallelClone,
+            {
+                AsLock {
+                    inner: AsLockAlias::new_parallel(t)
+                }
+            }
+
+            pub fn same_table(a: &Self, b: &Self) -> bool {
+                AsLockAlias::same_table(&a.inner, &b.inner)
+            }
         }
 
         impl$(< $($Inner),* >)? std::ops::Deref  for AsLock$(< $($Inner),* >)? {
@@ -263,6 +676,49 @@ macro_rules! generate_sync_aslock {
             }
         }
 
+        impl<'g, $($($Inner),*)?> $crate::sync::GroupMember<'g> for AsLock$(< $($Inner),* >)?
+        where
+            Self: 'g,
+        {
+            type WriteGuard = AsLockWriteGuard<'g, $($($Inner),*)?>;
+
+            fn group_write(&'g self) -> Self::WriteGuard {
+                self.write()
+            }
+
+            fn group_publish(guard: &mut Self::WriteGuard) {
+                <AsLockAlias$(< $($Inner),* >)? as $crate::sync::GroupMember<'g>>::group_publish(
+                    &mut guard.guard,
+                )
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::ReadInterface<$Table$(<$($Inner),*>)?>
+            for AsLock$(< $($Inner),* >)?
+        {
+            type Guard<'r> = $crate::sync::AsLockReadGuard<'r, $Table$(<$($Inner),*>)?>
+            where
+                Self: 'r;
+
+            fn read(&self) -> Self::Guard<'_> {
+                self.inner.read()
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::Snapshot<$Table$(<$($Inner),*>)?>
+            for AsLock$(< $($Inner),* >)?
+        {
+            fn with<R>(&self, f: impl FnOnce(&$Table$(<$($Inner),*>)?) -> R) -> R {
+                f(&self.inner.read())
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::inspect::Inspect for AsLock$(< $($Inner),* >)? {
+            fn inspect(&self) -> $crate::inspect::Inspection {
+                $crate::inspect::Inspect::inspect(&self.inner)
+            }
+        }
+
         impl$(< $($Inner),* >)? std::fmt::Debug  for AsLock$(< $($Inner),* >)?
             where $Table$(<$($Inner),*>)? : std::fmt::Debug,
         {
@@ -285,6 +741,261 @@ macro_rules! generate_sync_aslock {
     }
 }
 
+/// Generates an arc::AsArcLock for the type passed in. Requires the `arc`
+/// feature.
+///
+/// `AsArcLock` replays the same `UpdateTables` ops as the other flavors, so
+/// the collection interfaces written against the generated
+/// `AsArcLockWriteGuard` match those of `AsLockWriteGuard`.
+#[macro_export]
+macro_rules! generate_arc_aslock {
+    ( $Table:ident
+        // Table might be a template type.
+        $(<
+            // Any number of inner types.
+            $( $Inner:tt ),*
+        >)?
+    ) => {
+        // AsArcLockWriteGuard must be a new struct, so that clients can
+        // implement the update functions for it. See `generate_sync_aslock`.
+        pub struct AsArcLockWriteGuard<'w, $($($Inner),*)?> {
+            guard: $crate::arc::AsArcLockWriteGuard<'w, $Table $(< $($Inner),* >)?>,
+        }
+
+        // Allow the user to `update_tables` directly in case there is an interface missing.
+        impl<'w, $($($Inner),*)?> AsArcLockWriteGuard<'w, $($($Inner),*)?> {
+            pub fn update_tables<'a, R>(
+                &'a mut self,
+                update: impl $crate::UpdateTables<'a, $Table$(< $($Inner),* >)?, R> + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables(update)
+            }
+
+            pub fn update_tables_closure<R>(
+                &mut self,
+                update: impl Fn(&mut $Table$(< $($Inner),* >)?) -> R + 'static + Sized + Send,
+            ) -> R {
+                self.guard.update_tables_closure(update)
+            }
+        }
+
+        impl<'w, $($($Inner),*)?> std::ops::Deref for AsArcLockWriteGuard<'w, $($($Inner),*)?> {
+            type Target = $Table$(< $($Inner),* >)?;
+            fn deref(&self) -> &Self::Target {
+                &*self.guard
+            }
+        }
+
+        impl<'w, $($($Inner),*)?> std::fmt::Debug for AsArcLockWriteGuard<'w, $($($Inner),*)?>
+            where $Table$(<$($Inner),*>)? : std::fmt::Debug,
+        {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.guard.fmt(f)
+            }
+        }
+
+        type AsArcLockAlias$(< $($Inner),* >)? =
+            $crate::arc::AsArcLock<$Table $(< $($Inner),* >)? >;
+
+        // AsArcLock needs to be a new struct, because we need to "override"
+        // the inner call to 'write' so that it will produce the new
+        // AsArcLockWriteGuard type that is defined here.
+        pub struct AsArcLock$(< $($Inner),* >)? {
+            inner: AsArcLockAlias$(< $($Inner),* >)?,
+        }
+
+        impl$(< $($Inner),* >)? AsArcLock$(< $($Inner),* >)?
+        where
+            $Table$(<$($Inner),*>)? : Clone,
+        {
+            pub fn new(t: $Table $(< $($Inner),* >)?) -> AsArcLock$(<$($Inner),*>)? {
+                AsArcLock {
+                    inner: AsArcLockAlias::new(t)
+                }
+            }
+
+            pub fn write(&self) -> AsArcLockWriteGuard<'_, $($($Inner),*)?> {
+                AsArcLockWriteGuard {
+                    guard: self.inner.write()
+                }
+            }
+        }
+
+        impl$(< $($Inner),* >)? std::ops::Deref for AsArcLock$(< $($Inner),* >)? {
+            type Target = AsArcLockAlias$(< $($Inner),* >)?;
+            fn deref(&self) -> &Self::Target {
+                &self.inner
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::ReadInterface<$Table$(<$($Inner),*>)?>
+            for AsArcLock$(< $($Inner),* >)?
+        {
+            type Guard<'r> = std::sync::Arc<$Table$(<$($Inner),*>)?>
+            where
+                Self: 'r;
+
+            fn read(&self) -> Self::Guard<'_> {
+                self.inner.read_arc()
+            }
+        }
+
+        impl$(< $($Inner),* >)? $crate::Snapshot<$Table$(<$($Inner),*>)?>
+            for AsArcLock$(< $($Inner),* >)?
+        {
+            fn with<R>(&self, f: impl FnOnce(&$Table$(<$($Inner),*>)?) -> R) -> R {
+                f(&self.inner.read_arc())
+            }
+        }
+
+        impl$(< $($Inner),* >)? std::fmt::Debug  for AsArcLock$(< $($Inner),* >)?
+            where $Table$(<$($Inner),*>)? : std::fmt::Debug,
+        {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.inner.fmt(f)
+            }
+        }
+
+        impl$(< $($Inner),* >)? Default for AsArcLock$(< $($Inner),* >)?
+        where
+            AsArcLockAlias$(< $($Inner),* >)?: Default,
+        {
+            fn default() -> Self {
+                AsArcLock {
+                    inner: AsArcLockAlias::default()
+                }
+            }
+        }
+    }
+}
+
+/// Generates `Namespaces`, a struct holding several small tables, so that they
+/// can share a single AsLock/AsLockHandle. The tables then share one publish
+/// cycle and one pair of buffers, instead of each paying for its own.
+///
+/// Each field is a namespace. Readers access a namespace through the read
+/// guard like any other field. Writers update it through `UpdateNamespaces`,
+/// which is implemented for the write guards of both flavors and has a method
+/// per namespace, taking a closure like `update_tables_closure`.
+///
+/// Like the other macros, this generates fixed names, so invoke it in a module
+/// of its own. `Namespaces` derives `Clone`, `Debug` and `Default`; further
+/// attributes can be passed before the fields.
+///
+/// ```rust
+/// mod config {
+///     use std::collections::{HashMap, HashSet};
+///
+///     active_standby::generate_namespaces! {
+///         #[derive(PartialEq)]
+///         pub users: HashMap<u32, String>,
+///         pub flags: HashSet<String>,
+///     }
+/// }
+/// use config::UpdateNamespaces;
+///
+/// let table = active_standby::sync::AsLock::new(config::Namespaces::default());
+/// {
+///     let mut wg = table.write();
+///     wg.users(|users| users.insert(1, "alice".to_string()));
+///     wg.flags(|flags| flags.insert("verbose".to_string()));
+/// }
+/// assert_eq!(table.read().users[&1], "alice");
+/// assert!(table.read().flags.contains("verbose"));
+/// ```
+#[macro_export]
+macro_rules! generate_namespaces {
+    (
+        $(#[$attr:meta])*
+        $( $vis:vis $Namespace:ident : $Table:ty ),* $(,)?
+    ) => {
+        #[derive(Clone, Debug, Default)]
+        $(#[$attr])*
+        pub struct Namespaces {
+            $( $vis $Namespace: $Table, )*
+        }
+
+        /// Update a single namespace. See `update_tables_closure`.
+        pub trait UpdateNamespaces {
+            $(
+                fn $Namespace<R>(
+                    &mut self,
+                    update: impl Fn(&mut $Table) -> R + 'static + Sized + Send,
+                ) -> R;
+            )*
+        }
+
+        impl<'w> UpdateNamespaces for $crate::lockless::AsLockWriteGuard<'w, Namespaces> {
+            $(
+                fn $Namespace<R>(
+                    &mut self,
+                    update: impl Fn(&mut $Table) -> R + 'static + Sized + Send,
+                ) -> R {
+                    self.update_tables_closure(move |namespaces| update(&mut namespaces.$Namespace))
+                }
+            )*
+        }
+
+        impl<'w> UpdateNamespaces for $crate::sync::AsLockWriteGuard<'w, Namespaces> {
+            $(
+                fn $Namespace<R>(
+                    &mut self,
+                    update: impl Fn(&mut $Table) -> R + 'static + Sized + Send,
+                ) -> R {
+                    self.update_tables_closure(move |namespaces| update(&mut namespaces.$Namespace))
+                }
+            )*
+        }
+    };
+}
+
+/// Build a `ClosureUpdate` from a single closure body. The variables listed in
+/// the brackets are cloned for the update of the first table, and moved into
+/// the update of the second, so the body may consume them.
+///
+/// ```rust
+/// use active_standby::sync::AsLock;
+/// use active_standby::update_fn;
+///
+/// let table = AsLock::new(vec![]);
+/// let value = String::from("a");
+/// table
+///     .write()
+///     .update_tables(update_fn!([value] |table: &mut Vec<String>| table.push(value)));
+/// assert_eq!(*table.read(), vec!["a"]);
+/// ```
+#[macro_export]
+macro_rules! update_fn {
+    ([$($capture:ident),* $(,)?] |$table:ident $(: $Table:ty)?| $body:expr) => {
+        $crate::ClosureUpdate::new(
+            {
+                $( let $capture = ::core::clone::Clone::clone(&$capture); )*
+                move |$table $(: $Table)?| $body
+            },
+            move |$table $(: $Table)?| {
+                $body;
+            },
+        )
+    };
+}
+
+// The generated code is compiled in the caller's crate, where
+// `#[cfg(feature = "tokio")]` would check the caller's features, so the check
+// is made here instead.
+#[cfg(feature = "tokio")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_tokio {
+    ($($tokens:tt)*) => { $($tokens)* };
+}
+
+#[cfg(not(feature = "tokio"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_tokio {
+    ($($tokens:tt)*) => {};
+}
+
 /// Check that both tables equal the expected value.
 #[macro_export]
 macro_rules! assert_tables_eq {
@@ -298,3 +1009,53 @@ macro_rules! assert_tables_eq {
         assert_eq!(*$table.read(), $expected);
     };
 }
+
+#[cfg(test)]
+mod namespaces_test {
+    mod tables {
+        use std::collections::{BTreeMap, HashSet};
+
+        crate::generate_namespaces! {
+            #[derive(PartialEq)]
+            pub ids: BTreeMap<&'static str, u32>,
+            pub tags: HashSet<&'static str>,
+            pub log: Vec<u32>,
+        }
+    }
+
+    use maplit::*;
+    use tables::{Namespaces, UpdateNamespaces};
+
+    fn expected() -> Namespaces {
+        Namespaces {
+            ids: btreemap! { "a" => 1 },
+            tags: hashset! { "x" },
+            log: vec![1, 2],
+        }
+    }
+
+    #[test]
+    fn lockless() {
+        let table = crate::lockless::AsLockHandle::new(Namespaces::default());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.ids(|ids| ids.insert("a", 1)), None);
+            assert!(wg.tags(|tags| tags.insert("x")));
+            wg.log(|log| log.extend([1, 2]));
+            assert_eq!(wg.num_pending_ops(), 3);
+        }
+        assert_tables_eq!(table, expected());
+    }
+
+    #[test]
+    fn sync() {
+        let table = crate::sync::AsLock::new(Namespaces::default());
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.ids(|ids| ids.insert("a", 1)), None);
+            assert!(wg.tags(|tags| tags.insert("x")));
+            wg.log(|log| log.extend([1, 2]));
+        }
+        assert_tables_eq!(table, expected());
+    }
+}
@@ -0,0 +1,249 @@
+//! Accounting of how much each kind of update costs to replay.
+//!
+//! Every update is applied twice, once to each table. With the `op-stats`
+//! feature, the tables count the updates made under each label, along with
+//! the time spent replaying them on the second table, so that users can see
+//! which updates dominate the cost of the second application.
+//!
+//! Updates are labeled by `update_tables_labeled` and
+//! `update_tables_closure_labeled`. Other updates are labeled with the name of
+//! their type, meaning the `UpdateTables` struct, or for closures, the function
+//! which the closure was written in.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Counters for the updates made under a single label.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpStat {
+    /// Number of updates made.
+    pub count: u64,
+    /// Number of updates which have been replayed on the second table. Lags
+    /// `count` by the updates of the last write guard.
+    pub replayed: u64,
+    /// Total time spent replaying the updates on the second table.
+    pub replay_time: Duration,
+}
+
+/// Snapshot of the counters of a table, by label.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpStats {
+    pub ops: HashMap<&'static str, OpStat>,
+}
+
+impl OpStats {
+    /// Total across all labels.
+    pub fn total(&self) -> OpStat {
+        self.ops
+            .values()
+            .fold(OpStat::default(), |total, stat| OpStat {
+                count: total.count + stat.count,
+                replayed: total.replayed + stat.replayed,
+                replay_time: total.replay_time + stat.replay_time,
+            })
+    }
+
+    /// Human readable table of the counters, one line per label, sorted by
+    /// replay time with the most expensive first.
+    pub fn report(&self) -> String {
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by(|(a_label, a), (b_label, b)| {
+            b.replay_time
+                .cmp(&a.replay_time)
+                .then_with(|| a_label.cmp(b_label))
+        });
+
+        let total = self.total();
+        let mut report = format!(
+            "{:>10} {:>10} {:>14} {:>12} {:>6}  label\n",
+            "count", "replayed", "replay_time", "per_replay", "share"
+        );
+        for (label, stat) in ops {
+            let per_replay = match stat.replayed {
+                0 => Duration::ZERO,
+                replayed => stat.replay_time / replayed as u32,
+            };
+            let share = if total.replay_time.is_zero() {
+                0.0
+            } else {
+                100.0 * stat.replay_time.as_secs_f64() / total.replay_time.as_secs_f64()
+            };
+            writeln!(
+                report,
+                "{:>10} {:>10} {:>14} {:>12} {:>5.1}%  {}",
+                stat.count,
+                stat.replayed,
+                format!("{:?}", stat.replay_time),
+                format!("{:?}", per_replay),
+                share,
+                label
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+/// Held by a table to count its updates and time their replays.
+//
+// Not part of the active_standby synchronization protocol, so always use std
+// (even under loom).
+#[derive(Default)]
+pub(crate) struct OpStatsRecorder {
+    state: Mutex<RecorderState>,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    stats: OpStats,
+
+    // Labels of the ops waiting to be replayed, in the same order as the ops.
+    pending: Vec<&'static str>,
+}
+
+impl OpStatsRecorder {
+    fn lock(&self) -> MutexGuard<'_, RecorderState> {
+        // The counters can't be broken by a panic, so ignore poison.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn snapshot(&self) -> OpStats {
+        self.lock().stats.clone()
+    }
+
+    /// Count an update under `label`. Must be called once for each op added
+    /// to the ops to replay, once it has been added.
+    pub(crate) fn record(&self, label: &'static str) {
+        let mut state = self.lock();
+        state.stats.ops.entry(label).or_default().count += 1;
+        state.pending.push(label);
+    }
+
+    /// Forget the labels of the pending ops, for when the ops to replay are
+    /// dropped without being replayed.
+    pub(crate) fn clear_pending(&self) {
+        self.lock().pending.clear();
+    }
+
+    /// Replay `ops` on `table`, timing each of them. The lock isn't held
+    /// while replaying, so that `snapshot` doesn't wait on the replay. `ops`
+    /// may be only the first of the pending ops, in which case the labels of
+    /// the rest stay pending. `None`s are ops which were removed before being
+    /// replayed, so their labels are dropped without counting a replay.
+    pub(crate) fn replay<T, L: crate::types::OpLogStorage<T>>(
+        &self,
+        ops: impl Iterator<Item = Option<L::Op>>,
+        table: &mut T,
+    ) {
+        let labels = std::mem::take(&mut self.lock().pending);
+        let mut replay_times = Vec::with_capacity(labels.len());
+        for op in ops {
+            replay_times.push(op.map(|op| {
+                let start = Instant::now();
+                L::apply(op, table);
+                start.elapsed()
+            }));
+        }
+
+        let mut state = self.lock();
+        let (labels, rest) = labels.split_at(replay_times.len());
+        state.pending.splice(0..0, rest.iter().copied());
+        for (label, replay_time) in labels.iter().copied().zip(replay_times) {
+            let Some(replay_time) = replay_time else {
+                continue;
+            };
+            let stat = state.stats.ops.entry(label).or_default();
+            stat.replayed += 1;
+            stat.replay_time += replay_time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::types::OpLog;
+
+    #[test]
+    fn record() {
+        type Op = Box<dyn FnOnce(&mut Vec<i32>) + Send>;
+        let recorder = OpStatsRecorder::default();
+        let ops: Vec<Op> = vec![
+            Box::new(|table| table.push(1)),
+            Box::new(|table| table.push(2)),
+        ];
+        recorder.record("push");
+        recorder.record("push");
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.ops["push"].count, 2);
+        assert_eq!(stats.ops["push"].replayed, 0);
+
+        let mut table = vec![];
+        recorder.replay::<_, OpLog<Vec<i32>>>(ops.into_iter().map(Some), &mut table);
+        assert_eq!(table, vec![1, 2]);
+        assert_eq!(recorder.snapshot().ops["push"].replayed, 2);
+
+        // Only the ops recorded since the last replay are replayed next.
+        let ops: Vec<Op> = vec![Box::new(|table| table.clear())];
+        recorder.record("clear");
+        recorder.replay::<_, OpLog<Vec<i32>>>(ops.into_iter().map(Some), &mut table);
+        assert!(table.is_empty());
+        let stats = recorder.snapshot();
+        assert_eq!(stats.ops["push"].replayed, 2);
+        assert_eq!(stats.ops["clear"].replayed, 1);
+        assert_eq!(stats.total().count, 3);
+
+        // Dropped ops aren't attributed to the ops replayed after them.
+        recorder.record("dropped");
+        recorder.clear_pending();
+        let ops: Vec<Op> = vec![Box::new(|table| table.push(3))];
+        recorder.record("push");
+        recorder.replay::<_, OpLog<Vec<i32>>>(ops.into_iter().map(Some), &mut table);
+        let stats = recorder.snapshot();
+        assert_eq!(stats.ops["dropped"].replayed, 0);
+        assert_eq!(stats.ops["push"].replayed, 3);
+
+        // As are removed ops.
+        recorder.record("removed");
+        recorder.record("push");
+        let ops: Vec<Option<Op>> = vec![None, Some(Box::new(|table| table.push(4)))];
+        recorder.replay::<_, OpLog<Vec<i32>>>(ops.into_iter(), &mut table);
+        assert_eq!(table, vec![3, 4]);
+        let stats = recorder.snapshot();
+        assert_eq!(stats.ops["removed"].count, 1);
+        assert_eq!(stats.ops["removed"].replayed, 0);
+        assert_eq!(stats.ops["push"].replayed, 4);
+    }
+
+    #[test]
+    fn report() {
+        let mut stats = OpStats::default();
+        stats.ops.insert(
+            "cheap",
+            OpStat {
+                count: 4,
+                replayed: 2,
+                replay_time: Duration::from_micros(10),
+            },
+        );
+        stats.ops.insert(
+            "expensive",
+            OpStat {
+                count: 1,
+                replayed: 1,
+                replay_time: Duration::from_micros(30),
+            },
+        );
+
+        let report = stats.report();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with("75.0%  expensive"), "{}", report);
+        assert!(lines[2].contains("5µs"), "{}", report);
+        assert!(lines[2].ends_with("25.0%  cheap"), "{}", report);
+    }
+}
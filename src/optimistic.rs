@@ -0,0 +1,389 @@
+//! Experimental mode allowing multiple writers to prepare updates concurrently.
+//!
+//! Writers take a `Snapshot` of the table, read what they need from it and
+//! build a `Batch` of updates. Batches are then committed serially by the
+//! `Coordinator`. Each batch records the keys it read, and a batch is rejected
+//! if a batch committed after its snapshot wrote a key which conflicts with
+//! one of them. It is up to the user to decide what a key is and when keys
+//! conflict, and to retry rejected batches.
+//!
+//! ```rust
+//! use active_standby::optimistic::{CommitError, Coordinator};
+//! use std::collections::HashMap;
+//!
+//! let balances = Coordinator::new(HashMap::from([("a", 10), ("b", 0)]), |read, written| {
+//!     read == written
+//! });
+//!
+//! // Two writers prepare transfers out of "a" against the same snapshot.
+//! let mut batches = vec![];
+//! for _ in 0..2 {
+//!     let snapshot = balances.snapshot();
+//!     let a = snapshot["a"];
+//!     let mut batch = snapshot.batch();
+//!     batch.reads("a").writes("a").writes("b").update(move |table| {
+//!         table.insert("a", a - 5);
+//!         *table.get_mut("b").unwrap() += 5;
+//!     });
+//!     batches.push(batch);
+//! }
+//!
+//! let mut batches = batches.into_iter();
+//! assert!(balances.commit(batches.next().unwrap()).is_ok());
+//! // The second transfer read "a" before the first one wrote it.
+//! assert_eq!(
+//!     balances.commit(batches.next().unwrap()),
+//!     Err(CommitError::Conflict { version: 1 })
+//! );
+//! assert_eq!(balances.snapshot()["b"], 5);
+//! ```
+
+use crate::sync::{AsLock, AsLockReadGuard};
+use crate::types::*;
+use std::collections::VecDeque;
+use std::fmt;
+
+type Op<T> = Box<dyn Fn(&mut T) + Send>;
+type Conflicts<K> = Box<dyn Fn(&K, &K) -> bool + Send + Sync>;
+
+/// Error returned when a batch can't be committed. The batch should be rebuilt
+/// from a new snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitError {
+    /// A key read by the batch conflicts with a key written by the batch which
+    /// was committed at this version.
+    Conflict { version: u64 },
+    /// The batch's snapshot is older than the history kept by the
+    /// `Coordinator`, so it can't be validated.
+    Stale,
+}
+
+impl fmt::Display for CommitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitError::Conflict { version } => {
+                write!(f, "batch conflicts with the commit at version {}", version)
+            }
+            CommitError::Stale => write!(f, "batch's snapshot is too old to validate"),
+        }
+    }
+}
+
+impl std::error::Error for CommitError {}
+
+/// Read access to the table at a given version. This holds a read guard, so
+/// it should be dropped promptly, just like an `AsLockReadGuard`. In
+/// particular, a thread holding a `Snapshot` across two commits deadlocks
+/// in the second, which waits for the snapshot's table to be released.
+/// `batch` consumes the snapshot for this reason.
+pub struct Snapshot<'c, T> {
+    version: u64,
+    guard: AsLockReadGuard<'c, T>,
+}
+
+/// Updates prepared against a `Snapshot`, waiting to be committed.
+pub struct Batch<T, K> {
+    base_version: u64,
+    read_set: Vec<K>,
+    write_set: Vec<K>,
+    ops: Vec<Op<T>>,
+}
+
+struct CommitLog<K> {
+    version: u64,
+    // The write sets of the most recent commits, oldest first, tagged with
+    // the version they were committed at.
+    commits: VecDeque<(u64, Vec<K>)>,
+}
+
+/// Commits batches of updates to a `sync::AsLock`, one at a time.
+pub struct Coordinator<T, K> {
+    table: AsLock<T>,
+    // Locked for the duration of each commit, which serializes the commits.
+    log: Mutex<CommitLog<K>>,
+    conflicts: Conflicts<K>,
+    history_len: usize,
+}
+
+impl<'c, T> Snapshot<'c, T> {
+    /// The number of batches committed before this snapshot was taken.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Start a batch of updates based on this snapshot, releasing its read
+    /// guard.
+    pub fn batch<K>(self) -> Batch<T, K> {
+        Batch {
+            base_version: self.version,
+            read_set: vec![],
+            write_set: vec![],
+            ops: vec![],
+        }
+    }
+}
+
+impl<'c, T> std::ops::Deref for Snapshot<'c, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'c, T: fmt::Debug> fmt::Debug for Snapshot<'c, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("version", &self.version)
+            .field("table", &*self.guard)
+            .finish()
+    }
+}
+
+impl<T, K> Batch<T, K> {
+    /// Record that the updates depend on `key`.
+    pub fn reads(&mut self, key: K) -> &mut Self {
+        self.read_set.push(key);
+        self
+    }
+
+    /// Record that the updates change `key`.
+    pub fn writes(&mut self, key: K) -> &mut Self {
+        self.write_set.push(key);
+        self
+    }
+
+    /// Add an update to the batch. Like `update_tables_closure`, `update` is
+    /// applied to both tables, so it must be deterministic.
+    pub fn update(&mut self, update: impl Fn(&mut T) + 'static + Send) -> &mut Self {
+        self.ops.push(Box::new(update));
+        self
+    }
+}
+
+impl<T, K> Coordinator<T, K> {
+    /// Create a `Coordinator`. `conflicts(read, written)` returns whether a
+    /// batch which read `read` must be rejected because another batch wrote
+    /// `written` after its snapshot.
+    pub fn new(
+        t: T,
+        conflicts: impl Fn(&K, &K) -> bool + 'static + Send + Sync,
+    ) -> Coordinator<T, K>
+    where
+        T: Clone,
+    {
+        Coordinator {
+            table: AsLock::new(t),
+            log: Mutex::new(CommitLog {
+                version: 0,
+                commits: VecDeque::new(),
+            }),
+            conflicts: Box::new(conflicts),
+            history_len: 1024,
+        }
+    }
+
+    /// Set the number of commits to remember for validating batches. Batches
+    /// based on snapshots older than this are rejected as `Stale`. Defaults
+    /// to 1024.
+    pub fn history_len(mut self, history_len: usize) -> Coordinator<T, K> {
+        self.history_len = history_len;
+        self
+    }
+
+    pub fn snapshot(&self) -> Snapshot<'_, T> {
+        // Commits publish the table while holding the log, so this guarantees
+        // that the version matches the table.
+        let log = self.log.lock();
+        Snapshot {
+            version: log.version,
+            guard: self.table.read(),
+        }
+    }
+
+    /// Validate `batch` against the batches committed since its snapshot, and
+    /// if there is no conflict, apply it to the table. Returns the version of
+    /// the commit.
+    pub fn commit(&self, batch: Batch<T, K>) -> Result<u64, CommitError>
+    where
+        T: 'static,
+    {
+        // Taking the writer first serializes the commits, and waits for
+        // readers of the standby table without holding the log, which would
+        // block `snapshot`. The guard is deferred so that a rejected batch
+        // doesn't publish.
+        let mut wg = self.table.write_deferred();
+        let mut log = self.log.lock();
+
+        let first_logged_version = log.version - log.commits.len() as u64;
+        if batch.base_version < first_logged_version {
+            return Err(CommitError::Stale);
+        }
+        for (version, write_set) in log.commits.iter() {
+            if *version <= batch.base_version {
+                continue;
+            }
+            let conflict = batch.read_set.iter().any(|read| {
+                write_set
+                    .iter()
+                    .any(|written| (self.conflicts)(read, written))
+            });
+            if conflict {
+                return Err(CommitError::Conflict { version: *version });
+            }
+        }
+
+        for op in batch.ops {
+            wg.update_tables_closure(op);
+        }
+        wg.set_deferred(false);
+        drop(wg);
+
+        log.version += 1;
+        let version = log.version;
+        log.commits.push_back((version, batch.write_set));
+        if log.commits.len() > self.history_len {
+            log.commits.pop_front();
+        }
+        Ok(version)
+    }
+}
+
+impl<T: fmt::Debug, K> fmt::Debug for Coordinator<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coordinator")
+            .field("version", &self.log.lock().version)
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::thread;
+
+    fn coordinator() -> Coordinator<HashMap<&'static str, i32>, &'static str> {
+        Coordinator::new(HashMap::from([("a", 0), ("b", 0)]), |read, written| {
+            read == written
+        })
+    }
+
+    fn increment(batch: &mut Batch<HashMap<&'static str, i32>, &'static str>, key: &'static str) {
+        batch
+            .reads(key)
+            .writes(key)
+            .update(move |table| *table.get_mut(key).unwrap() += 1);
+    }
+
+    #[test]
+    fn disjoint_batches() {
+        let table = coordinator();
+        let mut batch_a = table.snapshot().batch();
+        let mut batch_b = table.snapshot().batch();
+        increment(&mut batch_a, "a");
+        increment(&mut batch_b, "b");
+
+        assert_eq!(table.commit(batch_a), Ok(1));
+        assert_eq!(table.commit(batch_b), Ok(2));
+
+        let snapshot = table.snapshot();
+        assert_eq!(snapshot.version(), 2);
+        assert_eq!(*snapshot, HashMap::from([("a", 1), ("b", 1)]));
+    }
+
+    #[test]
+    fn conflicting_batches() {
+        let table = coordinator();
+        let mut batch1 = table.snapshot().batch();
+        let mut batch2 = table.snapshot().batch();
+        increment(&mut batch1, "a");
+        increment(&mut batch2, "a");
+
+        assert_eq!(table.commit(batch1), Ok(1));
+        let generation = table.table.current_generation();
+        assert_eq!(
+            table.commit(batch2),
+            Err(CommitError::Conflict { version: 1 })
+        );
+        // Rejected batches don't publish.
+        assert_eq!(table.table.current_generation(), generation);
+
+        // Rebuilt from a new snapshot, the batch commits.
+        let mut batch2 = table.snapshot().batch();
+        increment(&mut batch2, "a");
+        assert_eq!(table.commit(batch2), Ok(2));
+        assert_eq!(table.snapshot()["a"], 2);
+    }
+
+    #[test]
+    fn commit_with_live_snapshot() {
+        let table = Arc::new(coordinator());
+        let old = table.snapshot();
+        let mut batch = table.snapshot().batch();
+        increment(&mut batch, "a");
+        assert_eq!(table.commit(batch), Ok(1));
+
+        // The next commit waits for `old` to release the standby table.
+        let committer = {
+            let table = Arc::clone(&table);
+            thread::spawn(move || {
+                let mut batch = table.snapshot().batch();
+                increment(&mut batch, "b");
+                table.commit(batch)
+            })
+        };
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!committer.is_finished());
+        // It doesn't hold the log while waiting, so snapshots aren't blocked.
+        assert_eq!(table.snapshot().version(), 1);
+        assert_eq!(old["a"], 0);
+
+        drop(old);
+        assert_eq!(committer.join().unwrap(), Ok(2));
+        assert_eq!(*table.snapshot(), HashMap::from([("a", 1), ("b", 1)]));
+    }
+
+    #[test]
+    fn stale_batch() {
+        let table = coordinator().history_len(1);
+        let mut stale = table.snapshot().batch();
+        increment(&mut stale, "b");
+
+        for _ in 0..2 {
+            let mut batch = table.snapshot().batch();
+            increment(&mut batch, "a");
+            assert!(table.commit(batch).is_ok());
+        }
+        assert_eq!(table.commit(stale), Err(CommitError::Stale));
+    }
+
+    #[test]
+    fn multi_thread() {
+        let table = Arc::new(coordinator());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let table = Arc::clone(&table);
+                let key = if i % 2 == 0 { "a" } else { "b" };
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        loop {
+                            let mut batch = table.snapshot().batch();
+                            increment(&mut batch, key);
+                            if table.commit(batch).is_ok() {
+                                break;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().is_ok());
+        }
+
+        let snapshot = table.snapshot();
+        assert_eq!(snapshot.version(), 400);
+        assert_eq!(*snapshot, HashMap::from([("a", 200), ("b", 200)]));
+    }
+}
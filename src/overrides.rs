@@ -0,0 +1,264 @@
+//! Temporary overrides of a table, which are reverted automatically, e.g. for
+//! flipping a config flag during an incident.
+//!
+//! An `Override` is built from an update which applies it and an update which
+//! reverts it. Applying it to a table returns an `OverrideHandle`, and the
+//! revert is applied when the handle is dropped, or once the override's `ttl`
+//! has passed, whichever comes first.
+//!
+//! ```rust
+//! use active_standby::overrides::Override;
+//! use active_standby::sync::AsLock;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! #[derive(Clone)]
+//! struct Config {
+//!     verbose: bool,
+//! }
+//!
+//! let table = Arc::new(AsLock::new(Config { verbose: false }));
+//! let handle = Override::new(
+//!     |config: &mut Config| config.verbose = true,
+//!     |config: &mut Config| config.verbose = false,
+//! )
+//! .ttl(Duration::from_secs(60))
+//! .apply_sync(Arc::clone(&table));
+//! assert!(table.read().verbose);
+//!
+//! drop(handle);
+//! assert!(!table.read().verbose);
+//! ```
+//!
+//! The revert is applied with a write guard of its own, from whichever thread
+//! drops the handle or from the override's timer thread. Like any other
+//! update, it is then replayed onto the other table by the next write, so it
+//! reaches both tables even if the thread which applied the override is gone.
+//!
+//! The revert is a separate update, so it doesn't undo the updates made
+//! between applying and reverting. E.g. a revert which restores a field
+//! overwrites whatever was written to the field in the meantime.
+
+use crate::types::*;
+use std::fmt;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// Not part of the active_standby synchronization protocol, so always use std.
+type RevertCell = std::sync::Arc<Revert>;
+
+struct Revert {
+    // Taken by whoever reverts the override first.
+    revert: std::sync::Mutex<Option<Box<dyn FnOnce() + Send>>>,
+    // Set once the revert has been published.
+    reverted: std::sync::atomic::AtomicBool,
+}
+
+/// An update to apply to a table temporarily.
+pub struct Override<A, R> {
+    apply: A,
+    revert: R,
+    ttl: Option<Duration>,
+}
+
+/// Handle to an applied override. Dropping it reverts the override, unless
+/// it has already been reverted.
+///
+/// Dropping the handle takes a write guard, so it blocks like `write`, and
+/// must not happen while the dropping thread holds a write guard to the same
+/// table.
+pub struct OverrideHandle {
+    revert: RevertCell,
+
+    // Dropping the sender wakes up the timer thread.
+    timer: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+}
+
+impl<A, R> Override<A, R> {
+    /// `apply` and `revert` are called on both tables, so they must make the
+    /// same change each time.
+    pub fn new<T>(apply: A, revert: R) -> Override<A, R>
+    where
+        A: Fn(&mut T),
+        R: Fn(&mut T),
+    {
+        Override {
+            apply,
+            revert,
+            ttl: None,
+        }
+    }
+
+    /// Also revert once `ttl` has passed since the override was applied.
+    /// Without a ttl, the override is only reverted when its handle is
+    /// dropped.
+    pub fn ttl(mut self, ttl: Duration) -> Override<A, R> {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Apply the override to a `sync::AsLock`, and publish it.
+    pub fn apply_sync<T>(self, table: Arc<crate::sync::AsLock<T>>) -> OverrideHandle
+    where
+        A: 'static + Fn(&mut T) + Send,
+        R: 'static + Fn(&mut T) + Send,
+        T: 'static + Send + Sync,
+    {
+        let Override { apply, revert, ttl } = self;
+        table.write().update_tables_closure(apply);
+        OverrideHandle::new(
+            Box::new(move || table.write().update_tables_closure(revert)),
+            ttl,
+        )
+    }
+
+    /// Apply the override to a `lockless::AsLockHandle`, and publish it.
+    pub fn apply_lockless<T>(self, table: crate::lockless::AsLockHandle<T>) -> OverrideHandle
+    where
+        A: 'static + Fn(&mut T) + Send,
+        R: 'static + Fn(&mut T) + Send,
+        T: 'static + Send + Sync,
+    {
+        let Override { apply, revert, ttl } = self;
+        table.write().update_tables_closure(apply);
+        OverrideHandle::new(
+            Box::new(move || table.write().update_tables_closure(revert)),
+            ttl,
+        )
+    }
+}
+
+impl OverrideHandle {
+    fn new(revert: Box<dyn FnOnce() + Send>, ttl: Option<Duration>) -> OverrideHandle {
+        let revert = RevertCell::new(Revert {
+            revert: std::sync::Mutex::new(Some(revert)),
+            reverted: Default::default(),
+        });
+        let timer = ttl.map(|ttl| {
+            let (sender, receiver) = mpsc::channel();
+            let revert = RevertCell::clone(&revert);
+            let thread = std::thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = receiver.recv_timeout(ttl) {
+                    run(&revert);
+                }
+            });
+            (sender, thread)
+        });
+        OverrideHandle { revert, timer }
+    }
+
+    /// Whether the override has been reverted by its ttl.
+    pub fn is_reverted(&self) -> bool {
+        self.revert
+            .reverted
+            .load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Revert the override now. Equivalent to dropping the handle.
+    pub fn revert(self) {}
+
+    /// Keep the override, so that it is never reverted.
+    pub fn persist(mut self) {
+        self.stop_timer();
+        lock(&self.revert).take();
+    }
+
+    fn stop_timer(&mut self) {
+        if let Some((sender, thread)) = self.timer.take() {
+            drop(sender);
+            // Don't propagate a panic from the revert while dropping.
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for OverrideHandle {
+    fn drop(&mut self) {
+        self.stop_timer();
+        run(&self.revert);
+    }
+}
+
+impl fmt::Debug for OverrideHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverrideHandle")
+            .field("is_reverted", &self.is_reverted())
+            .finish()
+    }
+}
+
+/// Run the revert, unless it has already been run.
+fn run(revert: &RevertCell) {
+    // Release the lock before reverting, which may block on the writer.
+    let f = lock(revert).take();
+    if let Some(f) = f {
+        f();
+        revert
+            .reverted
+            .store(true, std::sync::atomic::Ordering::Release);
+    }
+}
+
+fn lock(revert: &RevertCell) -> std::sync::MutexGuard<'_, Option<Box<dyn FnOnce() + Send>>> {
+    // The cell is only ever taken from, so ignore poison.
+    revert
+        .revert
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    fn set(value: i32) -> impl Fn(&mut i32) {
+        move |table| *table = value
+    }
+
+    #[test]
+    fn revert_on_drop() {
+        let table = Arc::new(crate::sync::AsLock::new(0));
+        let handle = Override::new(set(1), set(0)).apply_sync(Arc::clone(&table));
+        assert_eq!(*table.read(), 1);
+        assert!(!handle.is_reverted());
+
+        handle.revert();
+        assert_eq!(*table.read(), 0);
+
+        // The revert is replayed onto the other table by the next write.
+        let mut wg = table.write();
+        wg.assert_synced();
+        assert_eq!(*wg, 0);
+    }
+
+    #[test]
+    fn revert_after_ttl() {
+        let table = crate::lockless::AsLockHandle::new(0);
+        let handle = Override::new(set(1), set(0))
+            .ttl(Duration::from_millis(1))
+            .apply_lockless(table.clone());
+        let start = Instant::now();
+        while !handle.is_reverted() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::yield_now();
+        }
+        assert_eq!(*table.read(), 0);
+
+        // Dropping the handle doesn't revert a second time.
+        table.write().update_tables_closure(set(2));
+        drop(handle);
+        assert_eq!(*table.read(), 2);
+    }
+
+    #[test]
+    fn persist() {
+        let table = Arc::new(crate::sync::AsLock::new(0));
+        Override::new(set(1), set(0))
+            .ttl(Duration::from_secs(60))
+            .apply_sync(Arc::clone(&table))
+            .persist();
+        assert_eq!(*table.read(), 1);
+    }
+}
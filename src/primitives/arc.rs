@@ -0,0 +1,319 @@
+use crate::types::*;
+use arc_swap::ArcSwap;
+use std::fmt;
+
+type OpsToReplay<T> = Vec<Box<dyn FnOnce(&mut T) + Send>>;
+
+/// Variant of `AsLock` where the tables are held as `Arc<T>`. Readers load the
+/// active table's `Arc` without any locking, and publishing stores the
+/// standby table as the new active table.
+///
+/// Unlike `AsLock`, readers never hold up the writer. If a reader is still
+/// holding onto the old table when the next `AsArcLockWriteGuard` is created,
+/// the writer gives up on reusing that table and clones the active table
+/// instead (copy on write). This makes `AsArcLock` a good fit for read heavy
+/// workloads where readers may hold onto a table for a long time, at the cost
+/// of requiring `T: Clone`.
+pub struct AsArcLock<T> {
+    // The table which readers load.
+    active_table: ArcSwap<T>,
+
+    // The standby table and the ops needed to bring it up to date. This mutex
+    // is used to guarantee that `write` is single threaded.
+    writer: Mutex<ArcWriter<T>>,
+}
+
+struct ArcWriter<T> {
+    // The table which was active before the last publish. Readers may still
+    // hold onto it, in which case it is replaced by a clone of the active
+    // table.
+    standby_table: Arc<T>,
+
+    // Updates applied to the active table since `standby_table` was
+    // published.
+    ops_to_replay: OpsToReplay<T>,
+}
+
+/// Guard used for updating the tables.
+pub struct AsArcLockWriteGuard<'w, T> {
+    active_table: &'w ArcSwap<T>,
+
+    // While the guard exists, `writer.standby_table` is uniquely owned.
+    writer: MutexGuard<'w, ArcWriter<T>>,
+}
+
+/// Guard used for reading the active table. Holding onto this doesn't block
+/// the writer, but does force it to clone the table.
+pub type AsArcLockReadGuard<T> = arc_swap::Guard<Arc<T>>;
+
+impl<T> AsArcLock<T> {
+    pub fn read(&self) -> AsArcLockReadGuard<T> {
+        self.active_table.load()
+    }
+
+    /// Like `read`, but returns the `Arc` itself, which can be held onto
+    /// indefinitely.
+    pub fn read_arc(&self) -> Arc<T> {
+        self.active_table.load_full()
+    }
+}
+
+impl<T> AsArcLock<T>
+where
+    T: Clone,
+{
+    pub fn new(t: T) -> AsArcLock<T> {
+        let standby_table = Arc::new(t.clone());
+        AsArcLock {
+            active_table: ArcSwap::from_pointee(t),
+            writer: Mutex::new(ArcWriter {
+                standby_table,
+                ops_to_replay: vec![],
+            }),
+        }
+    }
+
+    /// Create an AsArcLockWriteGuard to allow users to update the the data.
+    /// There will only be 1 AsArcLockWriteGuard at a time.
+    ///
+    /// This function may be slow because:
+    /// 1. Another AsArcLockWriteGuard exists.
+    /// 2. Replaying all of the updates that were applied to the last
+    ///    AsArcLockWriteGuard.
+    /// 3. A reader is holding onto the standby table, so the active table must
+    ///    be cloned.
+    pub fn write(&self) -> AsArcLockWriteGuard<'_, T> {
+        let mut writer = self.writer.lock();
+        let ArcWriter {
+            standby_table,
+            ops_to_replay,
+        } = &mut *writer;
+
+        match Arc::get_mut(standby_table) {
+            Some(table) => {
+                for op in ops_to_replay.drain(..) {
+                    op(table);
+                }
+            }
+            None => {
+                // A reader still holds the standby table. The ops aren't
+                // needed since the clone is already up to date.
+                ops_to_replay.clear();
+                *standby_table = Arc::new(T::clone(&self.active_table.load()));
+            }
+        }
+
+        AsArcLockWriteGuard {
+            active_table: &self.active_table,
+            writer,
+        }
+    }
+}
+
+impl<T> Default for AsArcLock<T>
+where
+    T: Default + Clone,
+{
+    fn default() -> AsArcLock<T> {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsArcLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsArcLock")
+            .field("active_table", &**self.read())
+            .finish()
+    }
+}
+
+/// The guard is the active table's `Arc`, since `AsArcLockReadGuard`
+/// dereferences to the `Arc` rather than the table.
+impl<T> crate::ReadInterface<T> for AsArcLock<T> {
+    type Guard<'r>
+        = Arc<T>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        self.read_arc()
+    }
+}
+
+impl<T> crate::Snapshot<T> for AsArcLock<T> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read_arc())
+    }
+}
+
+/// `AsArcLock` is usually shared behind an `Arc`.
+impl<T> crate::Snapshot<T> for std::sync::Arc<AsArcLock<T>> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read_arc())
+    }
+}
+
+impl<'w, T> AsArcLockWriteGuard<'w, T> {
+    fn table(&mut self) -> &mut T {
+        Arc::get_mut(&mut self.writer.standby_table)
+            .expect("standby table is uniquely owned while writing")
+    }
+
+    /// Takes an update which will change the state of the underlying data. This
+    /// is done through the interface of UpdateTables.
+    ///
+    /// Users should never use the return value to directly mutate the tables,
+    /// since this will lead to them going out of sync.
+    pub fn update_tables<'a, R>(
+        &'a mut self,
+        mut update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        // Explicitly cast MutexGuard into ArcWriter in order for split
+        // borrowing to work.
+        let writer: &mut ArcWriter<_> = &mut self.writer;
+
+        let table = Arc::get_mut(&mut writer.standby_table)
+            .expect("standby table is uniquely owned while writing");
+        let res = update.apply_first(table);
+
+        writer.ops_to_replay.push(Box::new(move |table| {
+            update.apply_second(table);
+        }));
+
+        res
+    }
+
+    /// Like `update_tables` but allows the user to pass a closure for
+    /// convenience. Only allows return values that own their data.
+    pub fn update_tables_closure<R>(
+        &mut self,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        let res = update(self.table());
+
+        self.writer.ops_to_replay.push(Box::new(move |table| {
+            update(table);
+        }));
+
+        res
+    }
+}
+
+impl<'w, T> Drop for AsArcLockWriteGuard<'w, T> {
+    fn drop(&mut self) {
+        // Publish the new table, and hold onto the old one so that it can be
+        // reused if no readers are holding onto it by the next write.
+        let published = Arc::clone(&self.writer.standby_table);
+        self.writer.standby_table = self.active_table.swap(published);
+    }
+}
+
+impl<'w, T> std::ops::Deref for AsArcLockWriteGuard<'w, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.writer.standby_table
+    }
+}
+
+impl<'w, T: fmt::Debug> fmt::Debug for AsArcLockWriteGuard<'w, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::ops::Deref;
+        f.debug_struct("AsArcLockWriteGuard")
+            .field("num_ops_to_replay", &self.writer.ops_to_replay.len())
+            .field("standby_table", self.deref())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    struct PushVec<T> {
+        value: T,
+    }
+    impl<'a, T> UpdateTables<'a, Vec<T>, ()> for PushVec<T>
+    where
+        T: Clone,
+    {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) {
+            table.push(self.value.clone());
+        }
+        fn apply_second(self, table: &mut Vec<T>) {
+            table.push(self.value);
+        }
+    }
+
+    #[test]
+    fn publish_update() {
+        let aslock = Arc::new(AsArcLock::<Vec<i32>>::default());
+        assert_eq!(aslock.read().len(), 0);
+
+        {
+            let mut wg = aslock.write();
+            wg.update_tables(PushVec { value: 2 });
+            assert_eq!(*wg, vec![2]);
+            {
+                let aslock = Arc::clone(&aslock);
+                assert!(thread::spawn(move || {
+                    assert_eq!(aslock.read().len(), 0);
+                })
+                .join()
+                .is_ok());
+            }
+        }
+
+        assert_eq!(**aslock.read(), vec![2]);
+    }
+
+    #[test]
+    fn reuse_standby_table() {
+        let aslock = AsArcLock::<Vec<i32>>::default();
+        aslock.write().update_tables_closure(|vec| vec.push(2));
+        aslock.write().update_tables_closure(|vec| vec.push(3));
+
+        let wg = aslock.write();
+        assert_eq!(*wg, vec![2, 3]);
+        assert!(wg.writer.ops_to_replay.is_empty());
+    }
+
+    #[test]
+    fn held_reader_forces_clone() {
+        let aslock = AsArcLock::new(vec![1]);
+        aslock.write().update_tables_closure(|vec| vec.push(2));
+
+        // Holding onto the standby table doesn't block the writer.
+        let old_table = aslock.read_arc();
+        aslock.write().update_tables_closure(|vec| vec.push(3));
+        let wg = aslock.write();
+        assert_eq!(*wg, vec![1, 2, 3]);
+        drop(wg);
+
+        assert_eq!(*old_table, vec![1, 2]);
+        assert_eq!(**aslock.read(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn multi_thread() {
+        let aslock = Arc::new(AsArcLock::<Vec<i32>>::default());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let aslock = Arc::clone(&aslock);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let len = aslock.read().len();
+                        aslock.write().update_tables(PushVec { value: i });
+                        assert!(aslock.read().len() > len);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(aslock.read().len(), 400);
+        assert_eq!(*aslock.write(), **aslock.read());
+    }
+}
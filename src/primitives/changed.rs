@@ -0,0 +1,436 @@
+use slab::Slab;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+/// Callback registered by `on_swap`, called with the versions of the table
+/// before and after each swap.
+pub(crate) type OnSwap = Box<dyn FnMut(u64, u64) + Send>;
+
+/// Counts the publishes of a table, runs the `on_swap` callbacks and wakes
+/// the `Changed` futures waiting on them. Not part of the synchronization
+/// between readers and the writer, so this always uses std (even under loom).
+#[derive(Default)]
+pub(crate) struct PublishSignal {
+    num_publishes: AtomicU64,
+    // Nanoseconds since the unix epoch, or 0 before the first publish. Only
+    // reported by the registry.
+    #[cfg(feature = "registry")]
+    last_publish_nanos: AtomicU64,
+    // The waker of each pending `Waiter`, which removes its own on drop, so
+    // that this only holds the wakers of the futures still waiting.
+    wakers: Mutex<Slab<Waker>>,
+    on_swap: Mutex<Vec<OnSwap>>,
+}
+
+impl PublishSignal {
+    fn wakers(&self) -> MutexGuard<'_, Slab<Waker>> {
+        // The wakers can't be broken by a panic, so ignore poison.
+        self.wakers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn num_publishes(&self) -> u64 {
+        self.num_publishes.load(Ordering::SeqCst)
+    }
+
+    /// When the table was last published, or `None` if it never has been.
+    #[cfg(feature = "registry")]
+    pub(crate) fn last_publish(&self) -> Option<std::time::SystemTime> {
+        match self.last_publish_nanos.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(std::time::UNIX_EPOCH + std::time::Duration::from_nanos(nanos)),
+        }
+    }
+
+    #[cfg(feature = "registry")]
+    fn record_publish_time(&self) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(1, |since_epoch| {
+                u64::try_from(since_epoch.as_nanos())
+                    .unwrap_or(u64::MAX)
+                    .max(1)
+            });
+        self.last_publish_nanos.store(now, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_swap(&self, callback: OnSwap) {
+        self.on_swap
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(callback);
+    }
+
+    /// Called by the writer once the tables have been swapped, while it still
+    /// holds the write lock. The callbacks run before the publish is counted,
+    /// so that `Changed` futures only resolve once they are done.
+    pub(crate) fn notify(&self) {
+        let version = self.num_publishes();
+        for callback in self
+            .on_swap
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter_mut()
+        {
+            callback(version, version + 1);
+        }
+
+        #[cfg(feature = "registry")]
+        self.record_publish_time();
+        self.num_publishes.fetch_add(1, Ordering::SeqCst);
+        // Wake outside of the lock, since waking may run arbitrary code. The
+        // wakers stay registered until their waiters see the publish.
+        let wakers: Vec<Waker> = self
+            .wakers()
+            .iter()
+            .map(|(_, waker)| waker.clone())
+            .collect();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn changed(self: &Arc<Self>) -> Changed {
+        Changed {
+            waiter: Waiter::new(self),
+            seen: self.num_publishes(),
+        }
+    }
+}
+
+/// Waits for the publishes of a `PublishSignal`, with a single waker slot
+/// which is reused across polls, and removed once done waiting.
+struct Waiter {
+    signal: Arc<PublishSignal>,
+    slot: Option<usize>,
+}
+
+impl Waiter {
+    fn new(signal: &Arc<PublishSignal>) -> Waiter {
+        Waiter {
+            signal: Arc::clone(signal),
+            slot: None,
+        }
+    }
+
+    /// Ready once the table has been published since `seen`.
+    fn poll(&mut self, seen: u64, cx: &mut Context<'_>) -> Poll<()> {
+        if self.signal.num_publishes() != seen {
+            self.release();
+            return Poll::Ready(());
+        }
+
+        let mut wakers = self.signal.wakers();
+        // Check again while holding the wakers, since the writer takes them
+        // after counting the publish.
+        if self.signal.num_publishes() != seen {
+            drop(wakers);
+            self.release();
+            return Poll::Ready(());
+        }
+        match self.slot {
+            Some(slot) => {
+                let waker = &mut wakers[slot];
+                if !waker.will_wake(cx.waker()) {
+                    waker.clone_from(cx.waker());
+                }
+            }
+            None => self.slot = Some(wakers.insert(cx.waker().clone())),
+        }
+        Poll::Pending
+    }
+
+    fn release(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            self.signal.wakers().remove(slot);
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Future which resolves once the table is published after the `Changed` was
+/// created. Created by `changed` on the tables of either flavor.
+///
+/// This doesn't borrow the table, so it can be awaited from another task,
+/// including as a branch of `tokio::select!`. Publishes which happen before
+/// it is first polled still count, so none are missed between creating it and
+/// awaiting it. A reader which loops on `changed` should create the next one
+/// before reading the table, so that a publish during the read wakes it up.
+#[must_use = "futures do nothing unless polled"]
+pub struct Changed {
+    waiter: Waiter,
+    seen: u64,
+}
+
+impl Future for Changed {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        this.waiter.poll(this.seen, cx)
+    }
+}
+
+impl std::fmt::Debug for Changed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Changed")
+            .field(
+                "changed",
+                &(self.waiter.signal.num_publishes() != self.seen),
+            )
+            .finish()
+    }
+}
+
+/// Listens for the publishes of a table, starting from when it was created by
+/// `subscribe` on the tables of either flavor.
+///
+/// Unlike `Changed`, it can be waited on repeatedly. Each wait returns once
+/// the table has been published since the generation the listener last saw,
+/// so none are missed between waits, though several publishes may be seen as
+/// one. Like `Changed`, it doesn't borrow the table.
+///
+/// With the `stream` feature it is also a `futures_core::Stream` of the
+/// generations it waits for, similar to `tokio::sync::watch::Receiver`.
+/// The stream never ends.
+pub struct SwapListener {
+    // Kept across waits, so that repeated waits reuse its waker slot.
+    waiter: Waiter,
+    seen: u64,
+}
+
+impl PublishSignal {
+    pub(crate) fn subscribe(self: &Arc<Self>) -> SwapListener {
+        SwapListener {
+            waiter: Waiter::new(self),
+            seen: self.num_publishes(),
+        }
+    }
+}
+
+impl SwapListener {
+    /// The generation of the latest publish seen by this listener, which is
+    /// the current one when it is created. See `AsLockReadGuard::generation`.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Whether the table has been published since `seen`, without waiting.
+    pub fn has_changed(&self) -> bool {
+        self.waiter.signal.num_publishes() != self.seen
+    }
+
+    /// Block the thread until the table has been published since `seen`, and
+    /// return the new generation.
+    pub fn wait(&mut self) -> u64 {
+        block_on_until(std::future::poll_fn(|cx| self.poll_changed(cx)), None).unwrap()
+    }
+
+    /// Like `wait`, but gives up after `timeout`, returning `None`.
+    pub fn wait_timeout(&mut self, timeout: std::time::Duration) -> Option<u64> {
+        let deadline = std::time::Instant::now() + timeout;
+        block_on_until(
+            std::future::poll_fn(|cx| self.poll_changed(cx)),
+            Some(deadline),
+        )
+    }
+
+    /// Like `wait`, but awaits instead of blocking the thread.
+    pub async fn changed(&mut self) -> u64 {
+        std::future::poll_fn(|cx| self.poll_changed(cx)).await
+    }
+
+    /// Poll for a publish since `seen`, returning the new generation. Wakes
+    /// the task in `cx` on the next publish if there hasn't been one, e.g.
+    /// for use in `std::future::poll_fn` or a hand written future.
+    pub fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<u64> {
+        match self.waiter.poll(self.seen, cx) {
+            Poll::Ready(()) => {
+                self.seen = self.waiter.signal.num_publishes();
+                Poll::Ready(self.seen)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl futures_core::Stream for SwapListener {
+    type Item = u64;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u64>> {
+        self.get_mut().poll_changed(cx).map(Some)
+    }
+}
+
+impl std::fmt::Debug for SwapListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SwapListener")
+            .field("seen", &self.seen)
+            .field("changed", &self.has_changed())
+            .finish()
+    }
+}
+
+/// Minimal executor, which parks the thread until woken. Gives up once
+/// `deadline` passes, returning `None`.
+fn block_on_until<F: Future>(future: F, deadline: Option<std::time::Instant>) -> Option<F::Output> {
+    struct ThreadWaker(std::thread::Thread);
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        match deadline {
+            None => std::thread::park(),
+            Some(deadline) => {
+                let now = std::time::Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                std::thread::park_timeout(deadline - now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    block_on_until(future, None).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ready_after_notify() {
+        let signal = Arc::new(PublishSignal::default());
+        let changed = signal.changed();
+        signal.notify();
+        block_on(changed);
+
+        // Publishes from before the future was created don't count.
+        let waker = Waker::noop();
+        let mut changed = std::pin::pin!(signal.changed());
+        assert!(changed
+            .as_mut()
+            .poll(&mut Context::from_waker(waker))
+            .is_pending());
+        signal.notify();
+        assert!(changed
+            .as_mut()
+            .poll(&mut Context::from_waker(waker))
+            .is_ready());
+    }
+
+    #[test]
+    fn wakes_other_thread() {
+        let signal = Arc::new(PublishSignal::default());
+        let changed = signal.changed();
+        let handle = std::thread::spawn(move || block_on(changed));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        signal.notify();
+        handle.join().unwrap();
+        assert!(signal.wakers().is_empty());
+    }
+
+    #[test]
+    fn swap_listener() {
+        let signal = Arc::new(PublishSignal::default());
+        signal.notify();
+        // Starts from the current publish.
+        let mut listener = signal.subscribe();
+        assert_eq!(listener.seen(), 1);
+        assert!(!listener.has_changed());
+        assert_eq!(
+            listener.wait_timeout(std::time::Duration::from_millis(1)),
+            None
+        );
+
+        // Publishes between waits aren't missed.
+        signal.notify();
+        signal.notify();
+        assert!(listener.has_changed());
+        assert_eq!(listener.wait(), 3);
+        assert!(!listener.has_changed());
+
+        let handle = std::thread::spawn(move || block_on(listener.changed()));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        signal.notify();
+        assert_eq!(handle.join().unwrap(), 4);
+    }
+
+    #[test]
+    fn poll_changed() {
+        let signal = Arc::new(PublishSignal::default());
+        let mut listener = signal.subscribe();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(listener.poll_changed(&mut cx), Poll::Pending);
+        signal.notify();
+        signal.notify();
+        assert_eq!(listener.poll_changed(&mut cx), Poll::Ready(2));
+        assert_eq!(listener.poll_changed(&mut cx), Poll::Pending);
+
+        let handle = std::thread::spawn(move || {
+            block_on(std::future::poll_fn(|cx| listener.poll_changed(cx)))
+        });
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        signal.notify();
+        assert_eq!(handle.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn wakers_bounded() {
+        let signal = Arc::new(PublishSignal::default());
+        let mut listener = signal.subscribe();
+        for _ in 0..100 {
+            assert_eq!(listener.wait_timeout(std::time::Duration::ZERO), None);
+        }
+        // Each wait reuses the listener's slot, which is removed once the
+        // listener sees a publish.
+        assert_eq!(signal.wakers().len(), 1);
+        signal.notify();
+        assert_eq!(listener.wait(), 1);
+        assert!(signal.wakers().is_empty());
+
+        // Dropped futures remove their waker.
+        for _ in 0..100 {
+            let mut changed = std::pin::pin!(signal.changed());
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(changed.as_mut().poll(&mut cx).is_pending());
+        }
+        assert!(signal.wakers().is_empty());
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn stream() {
+        use futures_core::Stream;
+
+        let signal = Arc::new(PublishSignal::default());
+        let mut listener = signal.subscribe();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(Pin::new(&mut listener).poll_next(&mut cx), Poll::Pending);
+        signal.notify();
+        assert_eq!(
+            Pin::new(&mut listener).poll_next(&mut cx),
+            Poll::Ready(Some(1))
+        );
+    }
+}
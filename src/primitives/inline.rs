@@ -0,0 +1,341 @@
+use crate::types::*;
+use slab::Slab;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Variant of `lockless::AsLockHandle` for small `Copy` tables, which stores
+/// both tables inline instead of boxing them. Reads index into the tables
+/// rather than loading a pointer to them, so scalar tables get the cheapest
+/// reads.
+///
+/// Readers and the Writer follow the same epoch protocol as `AsLockHandle`:
+/// a read guard locks the table it was taken from, and the Writer waits for
+/// the guards which were taken before a swap before writing to the new
+/// standby table. Since the tables are `Copy`, the Writer brings the standby
+/// table up to date by copying the active table instead of replaying the
+/// updates. Therefore updates are only applied once, via
+/// `UpdateTables::apply_first`.
+///
+/// Like `AsLockHandle`, this should not be shared across threads/tasks. Each
+/// thread/task should get its own handle (via clone). Not available with
+/// `safe-impl`.
+pub struct AsInlineHandle<T> {
+    shared: Arc<Shared<T>>,
+
+    // Odd while this handle holds an AsInlineReadGuard.
+    epoch: Arc<AtomicUsize>,
+
+    // Key of `epoch` in `Shared::epochs`.
+    key: usize,
+
+    _not_sync: PhantomData<std::cell::Cell<()>>,
+}
+
+struct Shared<T> {
+    tables: [UnsafeCell<T>; 2],
+    active_index: AtomicUsize,
+
+    // The epoch of every handle.
+    epochs: Mutex<Slab<Arc<AtomicUsize>>>,
+
+    // Handles which may still be reading the standby table, along with their
+    // epoch at the time of the swap. Held by the AsInlineWriteGuard, which
+    // guarantees that there is only 1 writer at a time.
+    blocking_readers: Mutex<Vec<(Arc<AtomicUsize>, usize)>>,
+}
+
+// Readers only take shared references to a table, and the Writer only writes
+// to the standby table once the Readers have left it.
+unsafe impl<T: Copy + Send + Sync> Sync for Shared<T> {}
+
+/// Guard used for reading an AsInlineHandle's active table.
+pub struct AsInlineReadGuard<'r, T> {
+    table: &'r T,
+    epoch: &'r AtomicUsize,
+}
+
+/// Guard used for updating an AsInlineHandle's tables. Publishes the update
+/// on drop.
+pub struct AsInlineWriteGuard<'w, T> {
+    shared: &'w Shared<T>,
+
+    // Index of the standby table, which becomes the active table on drop.
+    standby_index: usize,
+
+    blocking_readers: MutexGuard<'w, Vec<(Arc<AtomicUsize>, usize)>>,
+}
+
+impl<T: Copy> AsInlineHandle<T> {
+    pub fn new(t: T) -> AsInlineHandle<T> {
+        let shared = Arc::new(Shared {
+            tables: [UnsafeCell::new(t), UnsafeCell::new(t)],
+            active_index: AtomicUsize::new(0),
+            epochs: Mutex::new(Slab::new()),
+            blocking_readers: Mutex::new(Vec::new()),
+        });
+        Self::register(shared)
+    }
+
+    /// Create an AsInlineWriteGuard to allow users to update the data. There
+    /// will only be 1 AsInlineWriteGuard at a time.
+    ///
+    /// Like `AsLockHandle::write`, this waits for the AsInlineReadGuards
+    /// which were taken from the current standby table, so this handle must
+    /// not hold one across two writes.
+    pub fn write(&self) -> AsInlineWriteGuard<'_, T> {
+        let mut blocking_readers = self.shared.blocking_readers.lock();
+        loop {
+            // An epoch only changes once the guard taken before the swap was
+            // dropped.
+            blocking_readers
+                .retain(|(epoch, epoch_at_swap)| epoch.load(Ordering::Acquire) == *epoch_at_swap);
+            if blocking_readers.is_empty() {
+                break;
+            }
+            spin_loop();
+        }
+
+        // Only the Writer changes the active index.
+        let active_index = self.shared.active_index.load(Ordering::Relaxed);
+        let standby_index = 1 - active_index;
+
+        // Safety: No Readers are left on the standby table, and Readers of the
+        // active table only take shared references to it.
+        unsafe {
+            *self.shared.tables[standby_index].get() = *self.shared.tables[active_index].get();
+        }
+
+        AsInlineWriteGuard {
+            shared: &self.shared,
+            standby_index,
+            blocking_readers,
+        }
+    }
+}
+
+impl<T> AsInlineHandle<T> {
+    fn register(shared: Arc<Shared<T>>) -> AsInlineHandle<T> {
+        let epoch = Arc::new(AtomicUsize::new(0));
+        let key = shared.epochs.lock().insert(Arc::clone(&epoch));
+        AsInlineHandle {
+            shared,
+            epoch,
+            key,
+            _not_sync: PhantomData,
+        }
+    }
+
+    /// Read the active table. Locks the table until the guard is dropped, but
+    /// never waits on the Writer.
+    ///
+    /// Panics if this handle already holds an AsInlineReadGuard.
+    pub fn read(&self) -> AsInlineReadGuard<'_, T> {
+        let old_epoch = self.epoch.load(Ordering::Relaxed);
+        assert!(old_epoch.is_multiple_of(2), "Reader is not reentrant");
+
+        // Lock the active table before loading it, so that the Writer either
+        // sees this handle reading, or this handle sees the latest swap.
+        self.epoch.store(old_epoch + 1, Ordering::Release);
+        fence(Ordering::SeqCst);
+        let active_index = self.shared.active_index.load(Ordering::Acquire);
+
+        AsInlineReadGuard {
+            // Safety: The Writer doesn't write to this table until the guard
+            // is dropped.
+            table: unsafe { &*self.shared.tables[active_index].get() },
+            epoch: &self.epoch,
+        }
+    }
+}
+
+impl<T> Clone for AsInlineHandle<T> {
+    fn clone(&self) -> AsInlineHandle<T> {
+        Self::register(Arc::clone(&self.shared))
+    }
+}
+
+impl<T> Drop for AsInlineHandle<T> {
+    fn drop(&mut self) {
+        self.shared.epochs.lock().remove(self.key);
+    }
+}
+
+impl<T: Copy + Default> Default for AsInlineHandle<T> {
+    fn default() -> AsInlineHandle<T> {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsInlineHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsInlineHandle")
+            .field("active_table", &*self.read())
+            .finish()
+    }
+}
+
+impl<'r, T> Drop for AsInlineReadGuard<'r, T> {
+    fn drop(&mut self) {
+        self.epoch.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl<'r, T> std::ops::Deref for AsInlineReadGuard<'r, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.table
+    }
+}
+
+impl<'r, T: fmt::Debug> fmt::Debug for AsInlineReadGuard<'r, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        T::fmt(self, f)
+    }
+}
+
+impl<'w, T> AsInlineWriteGuard<'w, T> {
+    fn table(&mut self) -> &mut T {
+        // Safety: The guard is the only writer, and no Readers are left on the
+        // standby table.
+        unsafe { &mut *self.shared.tables[self.standby_index].get() }
+    }
+
+    /// Takes an update which will change the state of the underlying data.
+    /// Only `apply_first` is called, since the other table is brought up to
+    /// date by copying this one.
+    pub fn update_tables<'a, R>(&'a mut self, mut update: impl UpdateTables<'a, T, R>) -> R {
+        update.apply_first(self.table())
+    }
+
+    /// Like `update_tables` but allows the user to pass a closure for
+    /// convenience.
+    pub fn update_tables_closure<R>(&mut self, update: impl FnOnce(&mut T) -> R) -> R {
+        update(self.table())
+    }
+}
+
+impl<'w, T> Drop for AsInlineWriteGuard<'w, T> {
+    /// Swap the tables, and record which handles may still be reading the
+    /// new standby table.
+    fn drop(&mut self) {
+        self.shared
+            .active_index
+            .store(self.standby_index, Ordering::Release);
+
+        // Make sure that the swap occurs before recording the epochs.
+        fence(Ordering::SeqCst);
+        for (_, epoch) in self.shared.epochs.lock().iter() {
+            let epoch_at_swap = epoch.load(Ordering::Acquire);
+            if !epoch_at_swap.is_multiple_of(2) {
+                self.blocking_readers
+                    .push((Arc::clone(epoch), epoch_at_swap));
+            }
+        }
+    }
+}
+
+impl<'w, T> std::ops::Deref for AsInlineWriteGuard<'w, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: The guard is the only writer.
+        unsafe { &*self.shared.tables[self.standby_index].get() }
+    }
+}
+
+impl<'w, T: fmt::Debug> fmt::Debug for AsInlineWriteGuard<'w, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::ops::Deref;
+        f.debug_struct("AsInlineWriteGuard")
+            .field("standby_table", self.deref())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    struct Add {
+        value: i32,
+    }
+    impl<'a> UpdateTables<'a, [i32; 2], ()> for Add {
+        fn apply_first(&mut self, table: &'a mut [i32; 2]) {
+            table[0] += self.value;
+            table[1] += self.value;
+        }
+        fn apply_second(self, _table: &mut [i32; 2]) {
+            unreachable!("the standby table is copied instead");
+        }
+    }
+
+    #[test]
+    fn publish_update() {
+        let table = AsInlineHandle::<[i32; 2]>::default();
+        assert_eq!(*table.read(), [0, 0]);
+
+        {
+            let mut wg = table.write();
+            wg.update_tables(Add { value: 2 });
+            assert_eq!(*wg, [2, 2]);
+            assert_eq!(*table.read(), [0, 0]);
+        }
+        assert_eq!(*table.read(), [2, 2]);
+
+        // The standby table is brought up to date before the next update.
+        table.write().update_tables_closure(|table| table[1] = 5);
+        assert_eq!(*table.read(), [2, 5]);
+    }
+
+    #[test]
+    fn write_waits_for_readers() {
+        let table = AsInlineHandle::new(1);
+        let reader = table.clone();
+        let rg = reader.read();
+
+        // The first write publishes, the second must wait for the guard which
+        // is left on the standby table.
+        table.write().update_tables_closure(|table| *table = 2);
+        let handle = thread::spawn(move || {
+            table.write().update_tables_closure(|table| *table = 3);
+            table
+        });
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        assert_eq!(*rg, 1);
+        drop(rg);
+
+        let table = handle.join().unwrap();
+        assert_eq!(*table.read(), 3);
+        assert_eq!(*reader.read(), 3);
+    }
+
+    #[test]
+    fn multi_thread() {
+        let table = AsInlineHandle::<[i32; 2]>::default();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let table = table.clone();
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while last < 1000 {
+                        let [a, b] = *table.read();
+                        // Reads are never torn or stale.
+                        assert_eq!(a, b);
+                        assert!(a >= last);
+                        last = a;
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..1000 {
+            table.write().update_tables(Add { value: 1 });
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(*table.read(), [1000, 1000]);
+    }
+}
@@ -3,6 +3,8 @@
 /// - AsLockHandle - analagous to Arc<RwLock>.
 /// - AsLockWriteGuard - analagous to RwLockAsLockWriteGuard.
 /// - AsLockReadGuard - analagous to RwLockReadGuard.
+/// - split - creates a single WriterHalf and a ReaderFactory instead of
+///   AsLockHandles, for tables which only ever have one writer.
 ///
 /// A high level outline of how this is achieved and the required invariants to
 /// guarantee safety:
@@ -30,19 +32,89 @@
 /// are single threaded, as Writer does update the systems state. AsLockHandle
 /// must also guarantee that Writer outlives all Readers since Writer owns the
 /// tables.
+use crate::primitives::changed::{Changed, PublishSignal, SwapListener};
+use crate::primitives::table::{borrow_owned, OwnedTableRef, Table, TablePtr, TableRef};
 use crate::types::*;
 use slab::Slab;
 use std::fmt;
 
 struct TableAndEpoch<T> {
-    table: AtomicPtr<T>,
+    table: TablePtr<T>,
     epoch: AtomicUsize,
+
+    // Unique per Reader. Slab reuses the keys of dropped Readers, so this is
+    // used to tell apart a new Reader from the dropped one it replaced.
+    generation: usize,
+
+    // Whether this Reader never reads, like the one held by a ReaderFactory
+    // to create new Readers. While all Readers are passive, the Writer updates
+    // both tables directly. See `WriterHalf::write`.
+    passive: bool,
+
+    // Where the most recent AsLockReadGuard was created, so that guards which
+    // block the Writer can be reported. Not part of the synchronization
+    // between Readers and the Writer, so this always uses std.
+    #[cfg(feature = "leak-detect")]
+    read_backtrace: std::sync::Mutex<Option<std::backtrace::Backtrace>>,
+
+    // Longest time an AsLockReadGuard was held, among the sampled reads. Also
+    // not part of the synchronization.
+    #[cfg(feature = "reader-stats")]
+    max_hold_nanos: std::sync::atomic::AtomicU64,
+}
+
+/// The number of publishes as of when each table was last made active, so that
+/// read guards can tell which publish they read. Looked up by the address of
+/// the table, since the tables never move. The Writer only sets the generation
+/// of a table while no AsLockReadGuard points to it, before pointing Readers
+/// to it. Zero sized tables share an address, so both use the same entry.
+/// Not part of the synchronization between Readers and the Writer, so this
+/// always uses std.
+struct Generations {
+    addresses: [usize; 2],
+    generations: [std::sync::atomic::AtomicU64; 2],
+}
+
+impl Generations {
+    fn new<T>(t1: &T, t2: &T) -> Generations {
+        Generations {
+            addresses: [t1 as *const T as usize, t2 as *const T as usize],
+            generations: Default::default(),
+        }
+    }
+
+    fn entry<T>(&self, table: &T) -> &std::sync::atomic::AtomicU64 {
+        let index = (table as *const T as usize == self.addresses[1]) as usize;
+        &self.generations[index]
+    }
+
+    fn get<T>(&self, table: &T) -> u64 {
+        self.entry(table).load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn set<T>(&self, table: &T, generation: u64) {
+        self.entry(table)
+            .store(generation, std::sync::atomic::Ordering::SeqCst)
+    }
 }
 
 /// The shared state of all Readers. Used to synchronize between Readers and the
 /// Writer.
 type ReadersList<T> = Arc<Mutex<Slab<Arc<TableAndEpoch<T>>>>>;
 
+/// Generation to give the next Reader created. Global so that Readers can be
+/// created without the Writer. Not part of the synchronization between Readers
+/// and the Writer, so this always uses std.
+fn next_reader_generation() -> usize {
+    static NEXT_READER_GENERATION: std::sync::atomic::AtomicUsize =
+        std::sync::atomic::AtomicUsize::new(0);
+    NEXT_READER_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+// When `update_tables` is called, the `standby_table` is updated immediately.
+// We then store the update ops to be replayed on the other table.
+type OpsToReplay<T> = OpLog<T>;
+
 /// Interface used to gain non-blocking read access to one of the tables. One
 /// per thread/task, not meant to be sync.
 struct Reader<T> {
@@ -55,17 +127,62 @@ struct Reader<T> {
 
     // List of all readers, used on Drop.
     readers: ReadersList<T>,
+
+    // The table which `read` returns while pinned. See `AsLockHandle::pin`.
+    pinned: Option<OwnedTableRef<T>>,
+
+    // Shared with the Writer, for `AsLockReadGuard::generation`.
+    generations: std::sync::Arc<Generations>,
 }
 
 /// Guard used for obtaining const access to the active table.
+///
+/// Unlike the `AsLockHandle`, the guard is `Sync` when `T: Sync`, so a single
+/// read of the table can be shared by scoped threads. The guard borrows its
+/// handle, so it can't outlive it or be held across calls to `write`.
 pub struct AsLockReadGuard<'r, T> {
     // Read by callers when dereferenceing the table.
-    active_table: &'r T,
+    active_table: TableRef<'r, T>,
+
+    // Incremented on Drop. `None` for guards of a pinned Reader, whose epoch
+    // stays locked until it is unpinned.
+    epoch: Option<&'r AtomicUsize>,
+
+    generations: &'r Generations,
+
+    // When a sampled read started, and where to record how long it was held.
+    #[cfg(feature = "reader-stats")]
+    sample: Option<(std::time::Instant, &'r std::sync::atomic::AtomicU64)>,
+
+    // When a sampled read started, and the table pointer of its Reader, to
+    // tell if the Writer published while the guard was held.
+    #[cfg(feature = "tracing")]
+    traced: Option<(std::time::Instant, &'r TablePtr<T>)>,
+}
 
-    // Incremented on Drop.
-    epoch: &'r AtomicUsize,
+/// Usage of a single Reader, such as the one held by an `AsLockHandle`.
+///
+/// Timing every read would slow down reads, so the hold time is only measured
+/// for 1 in `READ_SAMPLE_RATE` reads. Which reads are sampled is chosen by
+/// hashing the number of reads, so that readers with periodic access patterns
+/// are still sampled fairly.
+#[cfg(feature = "reader-stats")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderStats {
+    /// Number of AsLockReadGuards created, including any held now.
+    pub reads: u64,
+    /// Number of AsLockReadGuards held now. Since a Reader isn't reentrant,
+    /// this is either 0 or 1.
+    pub guards_held: usize,
+    /// Longest time an AsLockReadGuard was held, among the sampled reads.
+    /// `None` if no sampled read has finished.
+    pub max_hold_time: Option<std::time::Duration>,
 }
 
+/// On average 1 in this many reads has its hold time measured.
+#[cfg(any(feature = "reader-stats", feature = "tracing"))]
+pub const READ_SAMPLE_RATE: u64 = 32;
+
 /// Interface for mutating the state of the system, primarily for updating the
 /// tables.
 struct Writer<T> {
@@ -73,8 +190,8 @@ struct Writer<T> {
     // These are created on Writer construction, and while they are swapped,
     // they always point only to the 2 tables initially passed in, meaning they
     // remain valid pointers until Writer is dropped.
-    active_table: Box<T>,
-    standby_table: Box<T>,
+    active_table: Table<T>,
+    standby_table: Table<T>,
 
     // Log of operations to be performed on the second table.
     //
@@ -83,7 +200,7 @@ struct Writer<T> {
     // the AsLockWriteGuard is dropped. Therefore, the next time an AsLockWriteGuard is
     // created, the standby table it points to will still need to have these
     // updates applied to it to keep the tables sychronized.
-    ops_to_replay: Vec<Box<dyn FnOnce(&mut T) + Send>>,
+    ops_to_replay: OpsToReplay<T>,
 
     // List of all readers. Used for:
     // - Creating new readers.
@@ -97,8 +214,36 @@ struct Writer<T> {
     // block creation of a new AsLockWriteGuard until there are no AsLockReadGuards left
     // pointing to the standby table.
     //
-    // {reader_key : first_epoch_after_swap}.
-    blocking_readers: std::collections::HashMap<usize, usize>,
+    // {reader_key : (reader_generation, first_epoch_after_swap)}.
+    blocking_readers: std::collections::HashMap<usize, (usize, usize)>,
+
+    // Wakes `Changed` futures on swap. Shared with the handles so that they
+    // don't need the Writer to create them.
+    publish_signal: std::sync::Arc<PublishSignal>,
+
+    // Limit on `ops_to_replay` enforced by `try_update_tables`.
+    max_pending_ops: Option<usize>,
+
+    // Shared with the Readers.
+    generations: std::sync::Arc<Generations>,
+
+    // Time spent in `await_standby_table_free`. Shared with the handles so
+    // that reporting it doesn't need the Writer.
+    wait_stats: std::sync::Arc<crate::inspect::WaitStats>,
+
+    // Set when a guard from `write_deferred` is dropped without swapping the
+    // tables, to the replay cost of its updates. The standby table is then
+    // ahead of the active table, and `ops_to_replay` holds the updates the
+    // active table is missing, so the next guard mustn't replay them.
+    unpublished_cost: Option<usize>,
+
+    // How long to wait on blocking readers before reporting them.
+    #[cfg(feature = "leak-detect")]
+    leak_detect_timeout: std::time::Duration,
+
+    // Counts and replay times of the ops, by label.
+    #[cfg(feature = "op-stats")]
+    op_stats: crate::op_stats::OpStatsRecorder,
 }
 
 /// Public primitive for building lockess active_standby data structures. Give
@@ -108,9 +253,14 @@ struct Writer<T> {
 /// threads/tasks. Rather think of it as closer to a parallel of Arc<RwLock>
 /// than a plain RwLock. Meaning that each thread/task should get its own
 /// AsLockHandle (via clone).
+///
+/// The tables are boxed, so reads go through a pointer. For small `Copy`
+/// tables, `AsInlineHandle` stores both tables inline instead.
 pub struct AsLockHandle<T> {
     writer: Arc<Mutex<Writer<T>>>,
     reader: Reader<T>,
+    publish_signal: std::sync::Arc<PublishSignal>,
+    wait_stats: std::sync::Arc<crate::inspect::WaitStats>,
 
     // Make un-sync.
     _not_sync: std::cell::UnsafeCell<fn(&T)>,
@@ -119,10 +269,92 @@ pub struct AsLockHandle<T> {
 /// Interface for updating the tables. Produced by the AsLockHandle, not the
 /// Writer.
 pub struct AsLockWriteGuard<'w, T> {
-    writer: MutexGuard<'w, Writer<T>>,
+    writer: WriterRef<'w, T>,
+
+    // Sum of the weights of the updates in `writer.ops_to_replay`.
+    replay_cost: usize,
+
+    // Whether every update so far was applied to both tables directly, since
+    // all Readers were passive. Then there is nothing to replay, and the
+    // tables needn't be swapped. See `WriterHalf::write`.
+    direct: bool,
+
+    // Keys of the updates in `writer.ops_to_replay`, for `update_tables_keyed`.
+    op_keys: OpKeys,
+
+    // Whether dropping the guard leaves the updates unpublished. See
+    // `AsLockHandle::write_deferred`.
+    deferred: bool,
+
+    // Values to drop once the tables are swapped. Declared after `writer` so
+    // that they are dropped after the lock is released.
+    deferred_drops: Vec<Box<dyn Send>>,
+}
+
+/// How an AsLockWriteGuard holds the Writer. Handles share the Writer behind a
+/// mutex, while the WriterHalf owns it outright.
+enum WriterRef<'w, T> {
+    Locked(MutexGuard<'w, Writer<T>>),
+    Owned(&'w mut Writer<T>),
+}
+
+impl<'w, T> std::ops::Deref for WriterRef<'w, T> {
+    type Target = Writer<T>;
+    fn deref(&self) -> &Writer<T> {
+        match self {
+            WriterRef::Locked(writer) => writer,
+            WriterRef::Owned(writer) => writer,
+        }
+    }
+}
+
+impl<'w, T> std::ops::DerefMut for WriterRef<'w, T> {
+    fn deref_mut(&mut self) -> &mut Writer<T> {
+        match self {
+            WriterRef::Locked(writer) => writer,
+            WriterRef::Owned(writer) => writer,
+        }
+    }
+}
+
+impl<T> TableAndEpoch<T> {
+    fn new(table: TablePtr<T>, passive: bool) -> TableAndEpoch<T> {
+        TableAndEpoch {
+            table,
+            epoch: AtomicUsize::new(0),
+            generation: next_reader_generation(),
+            passive,
+            #[cfg(feature = "leak-detect")]
+            read_backtrace: std::sync::Mutex::new(None),
+            #[cfg(feature = "reader-stats")]
+            max_hold_nanos: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
 }
 
 impl<T> Reader<T> {
+    /// Creates a new `Reader` pointing at the same table as this one. Unlike
+    /// `Writer::new_reader` this doesn't require the Writer, since the Writer
+    /// only repoints Readers while holding `readers`, so the new Reader either
+    /// copies the updated table or is repointed along with the others.
+    fn new_sibling(&self, passive: bool) -> Reader<T> {
+        let mut readers = self.readers.lock();
+        let sync_state = Arc::new(TableAndEpoch::new(
+            self.sync_state.table.duplicate(),
+            passive,
+        ));
+        let key_in_readers = readers.insert(Arc::clone(&sync_state));
+        drop(readers);
+
+        Reader {
+            sync_state,
+            key_in_readers,
+            readers: Arc::clone(&self.readers),
+            pinned: None,
+            generations: std::sync::Arc::clone(&self.generations),
+        }
+    }
+
     /// Obtain a read guard with which to inspect the active table.
     ///
     /// This should never block free since there is nothing to lock, and the
@@ -134,12 +366,114 @@ impl<T> Reader<T> {
     /// 2. AtomicUsize increment to lock the table.
     /// 3. AtomicPtr load to the table.
     pub fn read(&self) -> AsLockReadGuard<'_, T> {
+        if let Some(pinned) = &self.pinned {
+            return AsLockReadGuard {
+                active_table: borrow_owned(pinned),
+                epoch: None,
+                generations: &self.generations,
+                #[cfg(feature = "reader-stats")]
+                sample: None,
+                #[cfg(feature = "tracing")]
+                traced: None,
+            };
+        }
+
         // 1. Load the shared state.
-        let TableAndEpoch { table, epoch } = &*self.sync_state;
+        let TableAndEpoch { table, epoch, .. } = &*self.sync_state;
 
         // 2. Lock the active table.
+        #[cfg_attr(
+            not(any(feature = "reader-stats", feature = "tracing")),
+            allow(unused_variables)
+        )]
+        let old_epoch = self.lock_epoch();
+
+        // 3. Atomic load of the active table. The actual dereference will
+        //    happen when the user makes use the the AsLockReadGuard. See
+        //    `TablePtr::load` for safety.
+        let active_table = table.load();
+        AsLockReadGuard {
+            active_table,
+            epoch: Some(epoch),
+            generations: &self.generations,
+            #[cfg(feature = "reader-stats")]
+            sample: self.sync_state.sample_read(old_epoch),
+            #[cfg(feature = "tracing")]
+            traced: self.sync_state.trace_read(old_epoch),
+        }
+    }
+
+    /// See `AsLockHandle::read_wait_free`.
+    #[cfg(not(feature = "safe-impl"))]
+    fn read_wait_free(&self) -> Result<AsLockReadGuard<'_, T>, WaitFreeReadError> {
+        if let Some(pinned) = &self.pinned {
+            return Ok(AsLockReadGuard {
+                active_table: borrow_owned(pinned),
+                epoch: None,
+                generations: &self.generations,
+                #[cfg(feature = "reader-stats")]
+                sample: None,
+                #[cfg(feature = "tracing")]
+                traced: None,
+            });
+        }
+
+        let TableAndEpoch { table, epoch, .. } = &*self.sync_state;
+        self.try_lock_epoch().ok_or(WaitFreeReadError::Reentrant)?;
+        Ok(AsLockReadGuard {
+            active_table: table.load(),
+            epoch: Some(epoch),
+            generations: &self.generations,
+            #[cfg(feature = "reader-stats")]
+            sample: None,
+            #[cfg(feature = "tracing")]
+            traced: None,
+        })
+    }
+
+    /// Lock the epoch until `unpin`, and have `read` return the table which
+    /// is active now until then.
+    fn pin(&mut self) {
+        if self.pinned.is_none() {
+            self.lock_epoch();
+            self.pinned = Some(self.sync_state.table.load_owned());
+        }
+    }
+
+    fn unpin(&mut self) {
+        if self.pinned.take().is_some() {
+            unlock_epoch(&self.sync_state.epoch);
+        }
+    }
+
+    /// Mark this Reader as reading, which locks the table it points to until
+    /// `unlock_epoch`. Returns the epoch from before locking.
+    fn lock_epoch(&self) -> usize {
+        let old_epoch = self.try_lock_epoch().expect("Reader is not reentrant");
+
+        #[cfg(feature = "leak-detect")]
+        if cfg!(debug_assertions) {
+            *self
+                .sync_state
+                .read_backtrace
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) =
+                Some(std::backtrace::Backtrace::force_capture());
+        }
+
+        old_epoch
+    }
+}
+
+impl<T> Reader<T> {
+    /// Like `lock_epoch`, but returns `None` if this Reader is already
+    /// reading, and never captures a backtrace.
+    fn try_lock_epoch(&self) -> Option<usize> {
+        let epoch = &self.sync_state.epoch;
         let old_epoch = epoch.load(Ordering::Acquire);
-        assert_eq!(old_epoch % 2, 0, "Reader is not reentrant");
+        if !old_epoch.is_multiple_of(2) {
+            return None;
+        }
 
         // The reader must update the epoch before taking the table. This
         // effectively locks the active_table, making it safe for the reader to
@@ -147,26 +481,124 @@ impl<T> Reader<T> {
         // until epoch is incremented again.
         epoch.store(old_epoch + 1, Ordering::Release);
         fence(Ordering::SeqCst);
+        Some(old_epoch)
+    }
+}
 
-        // 3. Atomic load of the active table. The actual dereference will
-        //    happen when the user makes use the the AsLockReadGuard.
-        //
-        // SAFETY: Memory safety (valid pointer) is guaranteed by
-        // AsLockHandle/Writer, which enforce that the tables are created before
-        // any Reader exists and dropped only after all readers. Further the
-        // tables themselves are never moved in memory.
-        //
-        // SAFETY: Thread safety is what must be handled by us manually. The
-        // `epoch` counter by the Reader and `await_standby_table_free` by the
-        // Writer.
-        let active_table = unsafe { &*table.load(Ordering::SeqCst) };
-        AsLockReadGuard {
-            active_table,
-            epoch,
+/// Locks `readers` if all of them are passive, in which case the Writer may
+/// update the active table while holding the lock: passive Readers never read,
+/// and new Readers are only created while holding `readers`. Always `None`
+/// with `safe-impl`, since there the Readers hold references to the active
+/// table.
+fn lock_passive_readers<T>(
+    readers: &ReadersList<T>,
+) -> Option<MutexGuard<'_, Slab<Arc<TableAndEpoch<T>>>>> {
+    if cfg!(feature = "safe-impl") {
+        return None;
+    }
+    let readers = readers.lock();
+    readers
+        .iter()
+        .all(|(_, table_and_epoch)| table_and_epoch.passive)
+        .then_some(readers)
+}
+
+/// Update the epoch counter to notify the Writer that the Reader is done using
+/// the active table and so it is available for use as the new standby table.
+fn unlock_epoch(epoch: &AtomicUsize) {
+    let old_epoch = epoch.load(Ordering::Acquire);
+    debug_assert_eq!(old_epoch % 2, 1);
+    epoch.store(old_epoch + 1, Ordering::Release);
+}
+
+#[cfg(any(feature = "reader-stats", feature = "tracing"))]
+impl<T> TableAndEpoch<T> {
+    /// Decide whether to time the read starting at `epoch`.
+    fn is_sampled(&self, epoch: usize) -> bool {
+        // splitmix64's finalizer, mixed with the generation so that Readers
+        // don't all sample the same reads.
+        let mut hash = (epoch as u64 / 2) ^ (self.generation as u64).rotate_left(32);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        hash ^= hash >> 31;
+
+        hash.is_multiple_of(READ_SAMPLE_RATE)
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<T> TableAndEpoch<T> {
+    fn trace_read(&self, epoch: usize) -> Option<(std::time::Instant, &TablePtr<T>)> {
+        self.is_sampled(epoch)
+            .then(|| (std::time::Instant::now(), &self.table))
+    }
+}
+
+/// Warn if the Writer published while a sampled read guard was held, since
+/// the guard then blocks the next `write` until it is dropped.
+#[cfg(feature = "tracing")]
+fn warn_if_held_across_publish<T>(
+    guard_table: &T,
+    (start, table): (std::time::Instant, &TablePtr<T>),
+) {
+    // The Writer repoints the Reader to the new active table when it
+    // publishes.
+    let current = table.load();
+    // The borrow is only needed for `safe-impl`, where `TableRef` isn't a
+    // reference.
+    #[allow(clippy::needless_borrow)]
+    let current: &T = &current;
+    if !std::ptr::eq(guard_table, current) {
+        tracing::warn!(
+            table = std::any::type_name::<T>(),
+            held_for = ?start.elapsed(),
+            "read guard held across a publish, blocking the next write"
+        );
+    }
+}
+
+#[cfg(feature = "reader-stats")]
+impl<T> TableAndEpoch<T> {
+    fn sample_read(
+        &self,
+        epoch: usize,
+    ) -> Option<(std::time::Instant, &std::sync::atomic::AtomicU64)> {
+        self.is_sampled(epoch)
+            .then(|| (std::time::Instant::now(), &self.max_hold_nanos))
+    }
+
+    fn stats(&self) -> ReaderStats {
+        let epoch = self.epoch.load(Ordering::Acquire);
+        let max_hold_nanos = self
+            .max_hold_nanos
+            .load(std::sync::atomic::Ordering::Relaxed);
+        ReaderStats {
+            // The epoch is incremented both when a guard is created and when
+            // it is dropped.
+            reads: epoch.div_ceil(2) as u64,
+            guards_held: epoch % 2,
+            max_hold_time: (max_hold_nanos > 0)
+                .then(|| std::time::Duration::from_nanos(max_hold_nanos)),
         }
     }
 }
 
+#[cfg(feature = "reader-stats")]
+impl<T> Reader<T> {
+    fn stats(&self) -> ReaderStats {
+        self.sync_state.stats()
+    }
+
+    /// Stats of every Reader of the tables, including this one.
+    fn all_stats(&self) -> Vec<ReaderStats> {
+        self.readers
+            .lock()
+            .iter()
+            .map(|(_, table_and_epoch)| table_and_epoch.stats())
+            .collect()
+    }
+}
+
 impl<T> Drop for Reader<T> {
     /// Remove the reader from the shared state list.
     fn drop(&mut self) {
@@ -187,22 +619,243 @@ impl<'r, T> Drop for AsLockReadGuard<'r, T> {
     /// Update the epoch counter to notify the Writer that we are done using the
     /// active table and so it is available for use as the new standby table.
     fn drop(&mut self) {
-        let old_epoch = self.epoch.load(Ordering::Acquire);
-        debug_assert_eq!(old_epoch % 2, 1);
-        self.epoch.store(old_epoch + 1, Ordering::Release);
+        #[cfg(feature = "reader-stats")]
+        if let Some((start, max_hold_nanos)) = self.sample {
+            let held = start.elapsed().as_nanos().max(1) as u64;
+            max_hold_nanos.fetch_max(held, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(traced) = self.traced {
+            warn_if_held_across_publish(&**self, traced);
+        }
+
+        if let Some(epoch) = self.epoch {
+            unlock_epoch(epoch);
+        }
     }
 }
 
 impl<'r, T> std::ops::Deref for AsLockReadGuard<'r, T> {
     type Target = T;
+    // The borrow is only needed for `safe-impl`, where `TableRef` isn't a
+    // reference.
+    #[allow(clippy::needless_borrow)]
     fn deref(&self) -> &Self::Target {
-        self.active_table
+        &self.active_table
+    }
+}
+
+// Lets read guards be passed to code generic over `Index`, e.g.
+// `impl Index<usize>`.
+impl<'r, T, I> std::ops::Index<I> for AsLockReadGuard<'r, T>
+where
+    T: std::ops::Index<I>,
+{
+    type Output = T::Output;
+    fn index(&self, index: I) -> &Self::Output {
+        &(**self)[index]
     }
 }
 
 impl<'r, T: fmt::Debug> fmt::Debug for AsLockReadGuard<'r, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.active_table.fmt(f)
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'r, T> AsLockReadGuard<'r, T> {
+    /// The number of publishes as of the table this guard reads, which starts
+    /// at 0. Readers can compare it to the generation they last saw to tell if
+    /// the table has changed since, without comparing its contents.
+    pub fn generation(&self) -> u64 {
+        // The borrow is only needed for `safe-impl`, where `TableRef` isn't a
+        // reference.
+        #[allow(clippy::needless_borrow)]
+        let table: &T = &self.active_table;
+        self.generations.get(table)
+    }
+
+    /// Clone the active table.
+    pub fn cloned(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(self)
+    }
+
+    /// Create an owned copy of the active table.
+    pub fn to_owned(&self) -> T::Owned
+    where
+        T: ToOwned,
+    {
+        T::to_owned(self)
+    }
+}
+
+impl<'g, 'r, T: Clone> From<&'g AsLockReadGuard<'r, T>> for std::borrow::Cow<'g, T> {
+    fn from(guard: &'g AsLockReadGuard<'r, T>) -> Self {
+        std::borrow::Cow::Borrowed(guard)
+    }
+}
+
+/// Read guard which owns its own handle to the tables, instead of borrowing
+/// one. Created by `AsLockHandle::read_owned`.
+///
+/// Since it is `'static`, it can be stored in structs or moved into spawned
+/// threads/tasks. It is `Send` when `T: Send + Sync`.
+pub struct AsLockOwnedReadGuard<T> {
+    active_table: OwnedTableRef<T>,
+
+    // Its epoch is locked for the lifetime of the guard. Also keeps the Writer,
+    // and so the tables, alive.
+    handle: AsLockHandle<T>,
+
+    // When a sampled read started.
+    #[cfg(feature = "reader-stats")]
+    sample: Option<std::time::Instant>,
+
+    // When a sampled read started, for warning if it is held across a publish.
+    #[cfg(feature = "tracing")]
+    traced: Option<std::time::Instant>,
+}
+
+impl<T> Drop for AsLockOwnedReadGuard<T> {
+    fn drop(&mut self) {
+        let sync_state = &self.handle.reader.sync_state;
+
+        #[cfg(feature = "reader-stats")]
+        if let Some(start) = self.sample {
+            let held = start.elapsed().as_nanos().max(1) as u64;
+            sync_state
+                .max_hold_nanos
+                .fetch_max(held, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(start) = self.traced {
+            warn_if_held_across_publish(&**self, (start, &sync_state.table));
+        }
+
+        unlock_epoch(&sync_state.epoch);
+    }
+}
+
+/// A read acquired by `AsLockHandle::acquire_read_raw`. The table pointer it
+/// was returned with is valid until it is released.
+pub struct ReadToken<T> {
+    guard: AsLockOwnedReadGuard<T>,
+}
+
+#[cfg(not(feature = "safe-impl"))]
+impl<T> ReadToken<T> {
+    /// Convert the token into an opaque pointer, e.g. to hand to C along with
+    /// the table pointer. It must be converted back with `from_raw` to
+    /// release the read. Not available with `safe-impl`.
+    ///
+    /// ```rust
+    /// use active_standby::lockless::{AsLockHandle, ReadToken};
+    /// use std::ffi::c_void;
+    ///
+    /// #[repr(C)]
+    /// #[derive(Clone)]
+    /// pub struct Config {
+    ///     pub limit: u32,
+    /// }
+    ///
+    /// #[no_mangle]
+    /// pub extern "C" fn config_acquire_read(
+    ///     handle: &AsLockHandle<Config>,
+    ///     token: &mut *mut c_void,
+    /// ) -> *const Config {
+    ///     let (table, read) = handle.acquire_read_raw();
+    ///     *token = read.into_raw();
+    ///     table
+    /// }
+    ///
+    /// /// # Safety
+    /// /// `token` must come from `config_acquire_read`, and be released once.
+    /// #[no_mangle]
+    /// pub unsafe extern "C" fn config_release_read(token: *mut c_void) {
+    ///     AsLockHandle::release_read_raw(ReadToken::<Config>::from_raw(token));
+    /// }
+    ///
+    /// let handle = AsLockHandle::new(Config { limit: 3 });
+    /// let mut token = std::ptr::null_mut();
+    /// let table = config_acquire_read(&handle, &mut token);
+    /// assert_eq!(unsafe { (*table).limit }, 3);
+    /// unsafe { config_release_read(token) };
+    /// ```
+    pub fn into_raw(self) -> *mut std::ffi::c_void {
+        Box::into_raw(Box::new(self)) as *mut std::ffi::c_void
+    }
+
+    /// Recover a token converted by `into_raw`.
+    ///
+    /// # Safety
+    /// `raw` must come from `ReadToken::<T>::into_raw`, and be recovered only
+    /// once.
+    pub unsafe fn from_raw(raw: *mut std::ffi::c_void) -> ReadToken<T> {
+        *Box::from_raw(raw as *mut ReadToken<T>)
+    }
+}
+
+/// Why `AsLockHandle::read_wait_free` couldn't read without waiting.
+#[cfg(not(feature = "safe-impl"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WaitFreeReadError {
+    /// A guard from the same handle is still held.
+    Reentrant,
+}
+
+#[cfg(not(feature = "safe-impl"))]
+impl fmt::Display for WaitFreeReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitFreeReadError::Reentrant => write!(f, "Reader is not reentrant"),
+        }
+    }
+}
+
+#[cfg(not(feature = "safe-impl"))]
+impl std::error::Error for WaitFreeReadError {}
+
+impl<T: fmt::Debug> fmt::Debug for ReadToken<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadToken")
+            .field("table", &*self.guard)
+            .finish()
+    }
+}
+
+impl<T> AsLockOwnedReadGuard<T> {
+    /// See `AsLockReadGuard::generation`.
+    pub fn generation(&self) -> u64 {
+        self.handle.reader.generations.get(&**self)
+    }
+}
+
+impl<T> std::ops::Deref for AsLockOwnedReadGuard<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.active_table
+    }
+}
+
+impl<T, I> std::ops::Index<I> for AsLockOwnedReadGuard<T>
+where
+    T: std::ops::Index<I>,
+{
+    type Output = T::Output;
+    fn index(&self, index: I) -> &Self::Output {
+        &(**self)[index]
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsLockOwnedReadGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
     }
 }
 
@@ -211,12 +864,26 @@ impl<T> Writer<T> {
     /// tables. t1 & t2 must be identical; this is left to the caller to
     /// enforce.
     pub fn from_identical(t1: T, t2: T) -> Writer<T> {
+        Self::from_identical_with(t1, t2, &crate::Options::default())
+    }
+
+    fn from_identical_with(t1: T, t2: T, options: &crate::Options) -> Writer<T> {
+        let (active_table, standby_table) = (Table::new(t1), Table::new(t2));
         Writer {
-            active_table: Box::new(t1),
-            standby_table: Box::new(t2),
-            ops_to_replay: vec![],
-            readers: Arc::new(Mutex::new(Slab::with_capacity(1024))),
+            generations: std::sync::Arc::new(Generations::new(&*active_table, &*standby_table)),
+            active_table,
+            standby_table,
+            ops_to_replay: OpLog::default(),
+            readers: Arc::new(Mutex::new(Slab::with_capacity(options.reader_capacity))),
             blocking_readers: std::collections::HashMap::new(),
+            publish_signal: std::sync::Arc::default(),
+            max_pending_ops: options.max_pending_ops,
+            wait_stats: std::sync::Arc::default(),
+            unpublished_cost: None,
+            #[cfg(feature = "leak-detect")]
+            leak_detect_timeout: options.leak_detect_timeout,
+            #[cfg(feature = "op-stats")]
+            op_stats: Default::default(),
         }
     }
 
@@ -225,19 +892,21 @@ impl<T> Writer<T> {
     /// Note that `Reader` creation is somewhat delicate since if it races with
     /// a swap, we may have a `Reader` pointing to the standby_table. This is
     /// covered though since Writer single threaded (enforced by AsLockHandle).
-    pub fn new_reader(&mut self) -> Reader<T> {
+    pub fn new_reader(&mut self, passive: bool) -> Reader<T> {
         let readers = Arc::clone(&self.readers);
 
-        let sync_state = Arc::new(TableAndEpoch {
-            table: AtomicPtr::new(self.active_table.as_mut() as *mut T),
-            epoch: AtomicUsize::new(0),
-        });
+        let sync_state = Arc::new(TableAndEpoch::new(
+            TablePtr::new(&self.active_table),
+            passive,
+        ));
         let key_in_readers = readers.lock().insert(Arc::clone(&sync_state));
 
         Reader {
             sync_state,
             key_in_readers,
             readers,
+            pinned: None,
+            generations: std::sync::Arc::clone(&self.generations),
         }
     }
 
@@ -245,65 +914,365 @@ impl<T> Writer<T> {
     /// This means that the Writer can produce an AsLockWriteGuard to it and perform
     /// updates.
     fn await_standby_table_free(&mut self) {
-        while !self.blocking_readers.is_empty() {
-            let readers = self.readers.lock();
-            self.blocking_readers.retain(|key, first_epoch_after_swap| {
+        if self.is_standby_table_free() {
+            return;
+        }
+
+        let start = std::time::Instant::now();
+        #[cfg(feature = "leak-detect")]
+        let mut reported = false;
+        loop {
+            #[cfg(feature = "leak-detect")]
+            if !reported && start.elapsed() >= self.leak_detect_timeout {
+                eprintln!("{}", self.leak_report(&self.readers.lock()));
+                reported = true;
+            }
+
+            #[cfg(feature = "failpoints")]
+            fail::fail_point!("active_standby::await_readers");
+
+            // Instead of just busy looping we will (potentially) yield this
+            // thread and come back when the OS returns to us.
+            spin_loop();
+
+            if self.is_standby_table_free() {
+                break;
+            }
+        }
+        self.wait_stats.record(start.elapsed());
+    }
+
+    /// Checks once whether the Readers which were blocking the Writer have
+    /// left the standby table, and stops tracking those which have.
+    fn is_standby_table_free(&mut self) -> bool {
+        if self.blocking_readers.is_empty() {
+            return true;
+        }
+
+        let readers = self.readers.lock();
+        self.blocking_readers
+            .retain(|key, (generation, first_epoch_after_swap)| {
                 let epoch = match readers.get(*key) {
-                    None => {
-                        // This Reader has been dropped.
+                    Some(table_and_epoch) if table_and_epoch.generation == *generation => {
+                        table_and_epoch.epoch.load(Ordering::Acquire)
+                    }
+                    _ => {
+                        // This Reader has been dropped, and its key may have
+                        // been reused by a new Reader. A new Reader is created
+                        // pointing to the active table, so it doesn't block.
                         return false;
                     }
-                    Some(table_and_epoch) => table_and_epoch.epoch.load(Ordering::Acquire),
                 };
 
                 epoch <= *first_epoch_after_swap && *first_epoch_after_swap % 2 != 0
             });
+        self.blocking_readers.is_empty()
+    }
+
+    /// Describe the Readers which are blocking the Writer, including where
+    /// their AsLockReadGuards were created.
+    #[cfg(feature = "leak-detect")]
+    fn leak_report(&self, readers: &Slab<Arc<TableAndEpoch<T>>>) -> String {
+        use std::fmt::Write;
 
-            if !self.blocking_readers.is_empty() {
-                // Instead of just busy looping we will (potentially) yield this
-                // thread and come back when the OS returns to us.
-                spin_loop();
+        let mut report = format!(
+            "active_standby: writer has waited over {:?} for {} AsLockReadGuard(s) to be dropped",
+            self.leak_detect_timeout,
+            self.blocking_readers.len()
+        );
+        for key in self.blocking_readers.keys() {
+            let backtrace = readers.get(*key).and_then(|table_and_epoch| {
+                let backtrace = table_and_epoch
+                    .read_backtrace
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                backtrace.as_ref().map(|backtrace| backtrace.to_string())
+            });
+            match backtrace {
+                Some(backtrace) => write!(report, "\nguard created at:\n{}", backtrace),
+                None => write!(
+                    report,
+                    "\nguard created at: <unknown, backtraces are only captured in debug builds>"
+                ),
             }
+            .unwrap();
         }
+        report
     }
-}
 
-impl<T> std::fmt::Debug for Writer<T>
-where
-    T: std::fmt::Debug,
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Writer")
-            .field("num_readers", &self.readers.lock().len())
-            .field("ops_to_replay", &self.ops_to_replay.len())
-            .field("standby_table", &self.standby_table)
-            .finish()
+    /// Bring the standby table up to date, so that it matches the active
+    /// table and can be handed out to an AsLockWriteGuard. Hangs until the
+    /// standby table is free of AsLockReadGuards.
+    fn sync_standby_table(&mut self) {
+        self.sync_standby_table_budgeted(usize::MAX);
     }
-}
 
-impl<T> AsLockHandle<T> {
-    /// Create an `AsLockHandle`. t1 & t2 must be identical; this is left to the
-    /// caller to enforce.
-    pub fn from_identical(t1: T, t2: T) -> AsLockHandle<T> {
-        let mut writer = Writer::from_identical(t1, t2);
-        let reader = writer.new_reader();
+    /// Like `sync_standby_table`, but replays at most `max_ops` updates,
+    /// leaving the rest queued for the next call. Returns whether the standby
+    /// table is up to date.
+    fn sync_standby_table_budgeted(&mut self, max_ops: usize) -> bool {
+        if self.unpublished_cost.is_some() {
+            return true;
+        }
 
-        AsLockHandle {
-            writer: Arc::new(Mutex::new(writer)),
-            reader,
-            _not_sync: std::cell::UnsafeCell::new(|_| {}),
+        // Wait until the standby table is free of AsLockReadGuards so it is safe to
+        // update. Once a partial replay has started, no reader can be pointing
+        // to the standby table, so this returns immediately.
+        self.await_standby_table_free();
+        self.replay(max_ops)
+    }
+
+    /// Like `sync_standby_table`, but returns false instead of waiting for
+    /// AsLockReadGuards to leave the standby table.
+    fn try_sync_standby_table(&mut self) -> bool {
+        if self.unpublished_cost.is_some() {
+            return true;
         }
+        self.is_standby_table_free() && self.replay(usize::MAX)
     }
 
-    /// Obtain a read guard with which to inspect the active table.
-    ///
-    /// This is wait free since there is nothing to lock, and the Writer is
-    /// responsible for never mutating the table that an AsLockReadGuard points to.
-    pub fn read(&self) -> AsLockReadGuard<'_, T> {
-        self.reader.read()
+    /// Replay at most `max_ops` updates onto the standby table, which must be
+    /// free of AsLockReadGuards. Returns whether it is now up to date.
+    fn replay(&mut self, max_ops: usize) -> bool {
+        std::sync::atomic::compiler_fence(Ordering::SeqCst);
+
+        // Bring the standby table up to date, it should now match the active
+        // table.
+        #[cfg(feature = "failpoints")]
+        let ops = self.ops_to_replay.drain(max_ops).inspect(|_| {
+            fail::fail_point!("active_standby::replay");
+        });
+        #[cfg(not(feature = "failpoints"))]
+        let ops = self.ops_to_replay.drain(max_ops);
+        #[cfg(feature = "op-stats")]
+        self.op_stats
+            .replay::<_, OpsToReplay<T>>(ops, &mut self.standby_table);
+        #[cfg(not(feature = "op-stats"))]
+        for op in ops.flatten() {
+            OpsToReplay::apply(op, &mut self.standby_table);
+        }
+        self.ops_to_replay.is_empty()
     }
 
-    /// Create a `AsLockWriteGuard` which is used to update the underlying tables.
+    /// Record `op` to be replayed on the other table by the next
+    /// `sync_standby_table`.
+    fn record_op(&mut self, label: &'static str, op: Box<dyn FnOnce(&mut T) + Send>) {
+        self.ops_to_replay.push(op);
+        #[cfg(feature = "op-stats")]
+        self.op_stats.record(label);
+        #[cfg(not(feature = "op-stats"))]
+        let _ = label;
+    }
+
+    /// The table with all of the updates, which is the active one unless
+    /// `write_deferred` left updates unpublished on the standby one. Only
+    /// called once there are no Readers left.
+    fn into_inner(self) -> T {
+        if self.unpublished_cost.is_some() {
+            self.standby_table.into_inner()
+        } else {
+            self.active_table.into_inner()
+        }
+    }
+
+    /// Swap the active and standby tables, publishing all updates applied to
+    /// the standby table to the Readers. Records which Readers may still be
+    /// looking at the new standby table.
+    fn swap_tables(&mut self) {
+        assert!(self.blocking_readers.is_empty());
+        self.unpublished_cost = None;
+        #[cfg(feature = "failpoints")]
+        fail::fail_point!("active_standby::before_swap");
+
+        // Swap the active and standby tables according to the Writer's
+        // accounting.
+        std::mem::swap(&mut self.active_table, &mut self.standby_table);
+        self.generations
+            .set(&*self.active_table, self.publish_signal.num_publishes() + 1);
+
+        for (key, table_and_epoch) in self.readers.lock().iter_mut() {
+            // Swap the active table for each Reader.
+            table_and_epoch
+                .table
+                .replace(&self.standby_table, &self.active_table);
+
+            // Make sure that swap occurs before recording the epoch.
+            fence(Ordering::SeqCst);
+
+            // Once the tables have been swapped, record the epoch of each
+            // reader so that we will know if it is safe to update the new
+            // standby table.
+            let first_epoch_after_swap = table_and_epoch.epoch.load(Ordering::Acquire);
+            if first_epoch_after_swap % 2 != 0 {
+                // If the epoch is even, it means that there is no AsLockReadGuard
+                // active.
+                self.blocking_readers
+                    .insert(key, (table_and_epoch.generation, first_epoch_after_swap));
+            }
+        }
+
+        self.publish_signal.notify();
+    }
+}
+
+impl<T> std::fmt::Debug for Writer<T>
+where
+    T: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Writer")
+            .field("num_readers", &self.readers.lock().len())
+            .field("ops_to_replay", &self.ops_to_replay.len())
+            .field("standby_table", &self.standby_table)
+            .finish()
+    }
+}
+
+impl<T> AsLockHandle<T> {
+    /// Create an `AsLockHandle`. t1 & t2 must be identical; this is left to the
+    /// caller to enforce.
+    pub fn from_identical(t1: T, t2: T) -> AsLockHandle<T> {
+        Self::from_identical_with(t1, t2, crate::Options::default())
+    }
+
+    /// Create an `AsLockHandle` from two calls to `f`, for tables which can't be
+    /// cloned but can be built again, e.g. from a config. The calls must
+    /// return identical tables; this is left to the caller to enforce.
+    pub fn from_fn(mut f: impl FnMut() -> T) -> AsLockHandle<T> {
+        let t1 = f();
+        Self::from_identical(t1, f())
+    }
+
+    /// Like `from_identical`, but configured by `options`.
+    pub fn from_identical_with(t1: T, t2: T, options: crate::Options) -> AsLockHandle<T> {
+        let mut writer = Writer::from_identical_with(t1, t2, &options);
+        let reader = writer.new_reader(false);
+        let publish_signal = std::sync::Arc::clone(&writer.publish_signal);
+        let wait_stats = std::sync::Arc::clone(&writer.wait_stats);
+
+        AsLockHandle {
+            writer: Arc::new(Mutex::new(writer)),
+            reader,
+            publish_signal,
+            wait_stats,
+            _not_sync: std::cell::UnsafeCell::new(|_| {}),
+        }
+    }
+
+    /// Obtain a read guard with which to inspect the active table.
+    ///
+    /// This is wait free since there is nothing to lock, and the Writer is
+    /// responsible for never mutating the table that an AsLockReadGuard points to.
+    pub fn read(&self) -> AsLockReadGuard<'_, T> {
+        self.reader.read()
+    }
+
+    /// Like `read`, but guaranteed to be wait free, for readers with hard
+    /// latency requirements such as audio or trading threads. It runs a
+    /// bounded number of steps: no loops, locks, allocations, or system
+    /// calls, regardless of what the Writer and other Readers are doing.
+    ///
+    /// Instead of panicking, it fails with:
+    /// - `WaitFreeReadError::Reentrant` if a guard from this handle is still
+    ///   held, where `read` would panic.
+    ///
+    /// Unlike `read`, it never samples the read for `reader-stats` or
+    /// `tracing`, nor records a backtrace for `leak-detect`. Not available
+    /// with `safe-impl`, since `arc-swap` can fall back to a lock free, but
+    /// not wait free, path.
+    ///
+    /// The guard is released by its drop, which is also wait free. The
+    /// guarantee covers this handle only: holding the guard still blocks
+    /// the next `write`.
+    #[cfg(not(feature = "safe-impl"))]
+    pub fn read_wait_free(&self) -> Result<AsLockReadGuard<'_, T>, WaitFreeReadError> {
+        self.reader.read_wait_free()
+    }
+
+    /// Pin the handle to the active table, so that every `read` until `unpin`
+    /// returns that same table, even if newer versions are published in the
+    /// meantime. This gives a consistent view across many short reads, e.g.
+    /// in an iterative algorithm, without holding onto a single guard.
+    ///
+    /// While pinned, reads are served from the pinned table without touching
+    /// the epoch. The pin blocks the Writer the same way a read guard would:
+    /// the first publish after pinning succeeds, but the write after it waits
+    /// until `unpin`. So don't keep a handle pinned for long, and never
+    /// `write` twice through a pinned handle, which deadlocks. Pinning an
+    /// already pinned handle does nothing.
+    pub fn pin(&mut self) {
+        self.reader.pin()
+    }
+
+    /// Release the pin, so that the next `read` returns the latest table.
+    /// Unpinning a handle which isn't pinned does nothing.
+    pub fn unpin(&mut self) {
+        self.reader.unpin()
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.reader.pinned.is_some()
+    }
+
+    /// Like `read`, but the guard holds its own handle to the tables, so that
+    /// it isn't tied to the lifetime of `self`. Useful for storing a read in a
+    /// struct, or passing it into a spawned thread/task.
+    ///
+    /// Creating the guard registers a new Reader, which costs a lock on the
+    /// list of Readers but never waits for the Writer. Like any read guard, it
+    /// blocks the next `write` until it is dropped, and since an owned guard
+    /// is easy to keep around, it is easy to stall the writer
+    /// indefinitely. With the `leak-detect` feature, owned guards which block
+    /// the writer are reported like `AsLockReadGuard`s.
+    pub fn read_owned(&self) -> AsLockOwnedReadGuard<T> {
+        let handle = AsLockHandle {
+            writer: Arc::clone(&self.writer),
+            reader: self.reader.new_sibling(false),
+            publish_signal: std::sync::Arc::clone(&self.publish_signal),
+            wait_stats: std::sync::Arc::clone(&self.wait_stats),
+            _not_sync: std::cell::UnsafeCell::new(|_| {}),
+        };
+
+        #[cfg_attr(
+            not(any(feature = "reader-stats", feature = "tracing")),
+            allow(unused_variables)
+        )]
+        let old_epoch = handle.reader.lock_epoch();
+        AsLockOwnedReadGuard {
+            active_table: handle.reader.sync_state.table.load_owned(),
+            #[cfg(feature = "reader-stats")]
+            sample: handle
+                .reader
+                .sync_state
+                .sample_read(old_epoch)
+                .map(|(start, _)| start),
+            #[cfg(feature = "tracing")]
+            traced: handle
+                .reader
+                .sync_state
+                .trace_read(old_epoch)
+                .map(|(start, _)| start),
+            handle,
+        }
+    }
+
+    /// Like `read_owned`, but the table is returned as a pointer, for readers
+    /// which can't hold a Rust guard, such as code on the other side of an
+    /// FFI boundary. The pointer is valid until `token` is released with
+    /// `release_read_raw` (or dropped), and like any read guard, the token
+    /// blocks the next `write` until then.
+    pub fn acquire_read_raw(&self) -> (*const T, ReadToken<T>) {
+        let guard = self.read_owned();
+        (&*guard as *const T, ReadToken { guard })
+    }
+
+    /// Release a read acquired by `acquire_read_raw`, invalidating its
+    /// pointer. Equivalent to dropping `token`.
+    pub fn release_read_raw(token: ReadToken<T>) {
+        drop(token);
+    }
+
+    /// Create a `AsLockWriteGuard` which is used to update the underlying tables.
     ///
     /// This function may be slow because:
     /// 1. Another AsLockWriteGuard exists. In practice this means lock contention on
@@ -314,26 +1283,86 @@ impl<T> AsLockHandle<T> {
     /// 3. Replaying all of the updates that were applied to the last
     ///    `AsLockWriteGuard`.
     pub fn write(&self) -> AsLockWriteGuard<'_, T> {
-        let mut mg = self.writer.lock();
-        // Explicitly cast MutexGuard into Writer in order for split borrowing
-        // to work. Without this line the compiler thinks that the borrow of
-        // standby_table and ops_to_replay are conflicting mutable borrows
-        // https://doc.rust-lang.org/nomicon/borrow-splitting.html
-        let writer: &mut Writer<_> = &mut mg;
+        let mut writer = self.writer.lock();
+        writer.sync_standby_table();
+        AsLockWriteGuard::new(WriterRef::Locked(writer), false)
+    }
 
-        // Wait until the standby table is free of AsLockReadGuards so it is safe to
-        // update.
-        writer.await_standby_table_free();
-        std::sync::atomic::compiler_fence(Ordering::SeqCst);
+    /// Like `write`, but replays at most `max_ops` of the last guard's
+    /// updates, bounding how long this holds the writer for a long backlog of
+    /// updates. Returns `None` if updates remain to be replayed, in which case
+    /// they stay queued for the next call. Readers are unaffected, since they
+    /// never see the standby table mid replay.
+    ///
+    /// Callers which must make progress can fall back to `write_full`, which
+    /// finishes the replay regardless of its length. Until then, `read` keeps
+    /// returning the last published table.
+    pub fn write_budgeted(&self, max_ops: usize) -> Option<AsLockWriteGuard<'_, T>> {
+        let mut writer = self.writer.lock();
+        if !writer.sync_standby_table_budgeted(max_ops) {
+            return None;
+        }
+        Some(AsLockWriteGuard::new(WriterRef::Locked(writer), false))
+    }
 
-        // Bring the standby table up to date, it should now match the active
-        // table.
-        for op in writer.ops_to_replay.drain(..) {
-            op(&mut writer.standby_table);
+    /// Like `write_budgeted`, but the budget is the time spent replaying
+    /// rather than a number of updates. The deadline is checked between
+    /// updates, so a single slow update can overrun it. Waiting for
+    /// AsLockReadGuards to leave the standby table counts against the budget,
+    /// but isn't cut short by it.
+    pub fn write_timed(&self, budget: std::time::Duration) -> Option<AsLockWriteGuard<'_, T>> {
+        let start = std::time::Instant::now();
+        let mut writer = self.writer.lock();
+        while !writer.sync_standby_table_budgeted(1) {
+            if start.elapsed() >= budget {
+                return None;
+            }
+        }
+        Some(AsLockWriteGuard::new(WriterRef::Locked(writer), false))
+    }
+
+    /// Same as `write`, which always finishes replaying the updates queued by
+    /// `write_budgeted` and `write_timed`. Named for forcing the replay to
+    /// complete, next to the budgeted calls.
+    pub fn write_full(&self) -> AsLockWriteGuard<'_, T> {
+        self.write()
+    }
+
+    /// Like `write`, but dropping the guard doesn't publish its updates.
+    /// Readers keep seeing the table from before them until `publish` is
+    /// called, or until a guard from `write` is dropped, which publishes them
+    /// along with its own. This batches many small writes into a single swap,
+    /// skipping the wait for readers and the replay of updates between them.
+    ///
+    /// Like any write guard, the next guard waits for this one to be dropped.
+    pub fn write_deferred(&self) -> AsLockWriteGuard<'_, T> {
+        let mut wg = self.write();
+        wg.deferred = true;
+        wg
+    }
+
+    /// Publish the updates left unpublished by guards from `write_deferred`.
+    /// Returns whether there were any. Waits for the writer lock like `write`,
+    /// but not for readers.
+    pub fn publish(&self) -> bool {
+        let mut writer = self.writer.lock();
+        if writer.unpublished_cost.is_none() {
+            return false;
         }
-        writer.ops_to_replay.clear();
+        writer.swap_tables();
+        true
+    }
 
-        AsLockWriteGuard { writer: mg }
+    /// Like `write`, but returns `None` instead of waiting if another
+    /// AsLockWriteGuard exists, or if AsLockReadGuards still point to the
+    /// standby table. The updates of the last guard are still replayed, so
+    /// this isn't free, but it never waits on other threads.
+    pub fn try_write(&self) -> Option<AsLockWriteGuard<'_, T>> {
+        let mut writer = self.writer.try_lock()?;
+        if !writer.try_sync_standby_table() {
+            return None;
+        }
+        Some(AsLockWriteGuard::new(WriterRef::Locked(writer), false))
     }
 }
 
@@ -344,6 +1373,33 @@ where
     pub fn new(t: T) -> AsLockHandle<T> {
         Self::from_identical(t.clone(), t)
     }
+
+    /// Like `new`, but configured by `options`.
+    pub fn new_with(t: T, options: crate::Options) -> AsLockHandle<T> {
+        Self::from_identical_with(t.clone(), t, options)
+    }
+}
+
+impl<T> AsLockHandle<T>
+where
+    T: ParallelClone,
+{
+    /// Like `new`, but the second table is cloned using multiple threads,
+    /// which is faster for large tables. See `ParallelClone`.
+    pub fn new_parallel(t: T) -> AsLockHandle<T> {
+        Self::from_identical(t.par_clone(), t)
+    }
+}
+
+impl<T> AsLockHandle<T>
+where
+    T: WithCapacity,
+{
+    /// Create an `AsLockHandle` where both tables have space for `capacity`
+    /// elements.
+    pub fn with_capacity(capacity: usize) -> AsLockHandle<T> {
+        Self::from_identical(T::with_capacity(capacity), T::with_capacity(capacity))
+    }
 }
 
 impl<T> Default for AsLockHandle<T>
@@ -358,15 +1414,311 @@ where
 impl<T> Clone for AsLockHandle<T> {
     fn clone(&self) -> AsLockHandle<T> {
         let writer = Arc::clone(&self.writer);
-        let reader = writer.lock().new_reader();
+        let reader = writer.lock().new_reader(false);
         AsLockHandle {
             writer,
             reader,
+            publish_signal: std::sync::Arc::clone(&self.publish_signal),
+            wait_stats: std::sync::Arc::clone(&self.wait_stats),
             _not_sync: std::cell::UnsafeCell::new(|_| {}),
         }
     }
 }
 
+/// Spawn a thread or task with its own clone of a handle. Each thread/task
+/// needs its own handle, and moving the original into a closure while it's
+/// still in use fails to compile, so this clones the handle and moves the
+/// clone in instead.
+///
+/// ```rust
+/// use active_standby::lockless::collections::AsVecHandle;
+/// use active_standby::lockless::SpawnWithHandle;
+///
+/// let table = AsVecHandle::new(vec![1]);
+/// let thread = table.spawn_thread_with(|table| table.write().push(2));
+/// thread.join().unwrap();
+/// assert_eq!(*table.read(), vec![1, 2]);
+/// ```
+///
+/// Implemented for `AsLockHandle` and the collections' handles.
+pub trait SpawnWithHandle: Clone + Send + 'static {
+    fn spawn_thread_with<F, R>(&self, f: F) -> std::thread::JoinHandle<R>
+    where
+        F: FnOnce(Self) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let handle = self.clone();
+        std::thread::spawn(move || f(handle))
+    }
+
+    /// Spawn the future returned by `f` onto the current tokio runtime.
+    /// Requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    fn spawn_with<F, Fut>(&self, f: F) -> tokio::task::JoinHandle<Fut::Output>
+    where
+        F: FnOnce(Self) -> Fut,
+        Fut: std::future::Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        tokio::spawn(f(self.clone()))
+    }
+}
+
+impl<T: 'static> SpawnWithHandle for AsLockHandle<T> where AsLockHandle<T>: Send {}
+
+impl<T> AsLockHandle<T> {
+    /// Returns whether `a` and `b` share the same tables, meaning that one was
+    /// cloned from the other (directly or indirectly).
+    pub fn same_table(a: &AsLockHandle<T>, b: &AsLockHandle<T>) -> bool {
+        Arc::ptr_eq(&a.writer, &b.writer)
+    }
+
+    /// Future which resolves once the table is next published. See
+    /// `Changed`. Doesn't wait on the writer.
+    pub fn changed(&self) -> Changed {
+        self.publish_signal.changed()
+    }
+
+    /// Listen for the publishes of the table from now on. See
+    /// `SwapListener`. Doesn't wait on the writer.
+    pub fn subscribe(&self) -> SwapListener {
+        self.publish_signal.subscribe()
+    }
+
+    /// `subscribe`, for use as a `futures_core::Stream` of the generations
+    /// of the table's publishes. Requires the `stream` feature.
+    #[cfg(feature = "stream")]
+    pub fn changes(&self) -> SwapListener {
+        self.subscribe()
+    }
+
+    /// Limit how many updates a single AsLockWriteGuard can hold through
+    /// `try_update_tables`, or remove the limit with `None`. See
+    /// `AsLockWriteGuard::try_update_tables`.
+    pub fn set_max_pending_ops(&self, max_pending_ops: Option<usize>) {
+        self.writer.lock().max_pending_ops = max_pending_ops;
+    }
+
+    /// Register `callback` to be called with the version of the table before
+    /// and after each swap, where the version counts the publishes since the
+    /// table was created. It is called by the AsLockWriteGuard which publishes,
+    /// once readers see the new active table but before the write lock is
+    /// released, so it is done before the next `write` returns. Meant for
+    /// keeping external state, such as an index, in lockstep with publishes.
+    ///
+    /// Callbacks run in the order they were registered, and can't be removed.
+    /// They must not call `on_swap` or `write` on this table.
+    pub fn on_swap(&self, callback: impl FnMut(u64, u64) + Send + 'static) {
+        self.publish_signal.on_swap(Box::new(callback));
+    }
+}
+
+/// Handles are equal if they share the same tables, like `same_table`, so that
+/// they can be used as keys, e.g. to deduplicate tables.
+impl<T> PartialEq for AsLockHandle<T> {
+    fn eq(&self, other: &AsLockHandle<T>) -> bool {
+        AsLockHandle::same_table(self, other)
+    }
+}
+
+impl<T> Eq for AsLockHandle<T> {}
+
+/// Hashes the identity of the tables, consistent with `PartialEq`.
+impl<T> std::hash::Hash for AsLockHandle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(&*self.writer, state)
+    }
+}
+
+#[cfg(feature = "leak-detect")]
+impl<T> AsLockHandle<T> {
+    /// Set how long `write` waits on AsLockReadGuards from before the last
+    /// swap before printing where they were created. Defaults to 5 seconds.
+    ///
+    /// Backtraces are only captured in debug builds.
+    pub fn set_leak_detect_timeout(&self, timeout: std::time::Duration) {
+        self.writer.lock().leak_detect_timeout = timeout;
+    }
+}
+
+#[cfg(feature = "reader-stats")]
+impl<T> AsLockHandle<T> {
+    /// Usage of this handle's Reader.
+    pub fn reader_stats(&self) -> ReaderStats {
+        self.reader.stats()
+    }
+
+    /// Usage of the Readers of every handle to the tables, including this
+    /// one, in no particular order. Handles which are only used for writing
+    /// still hold a Reader, which shows up as never reading. Doesn't wait on
+    /// the writer.
+    pub fn all_reader_stats(&self) -> Vec<ReaderStats> {
+        self.reader.all_stats()
+    }
+}
+
+#[cfg(feature = "op-stats")]
+impl<T> AsLockHandle<T> {
+    /// Counts and replay times of the updates made to the table, by label.
+    ///
+    /// This takes the writer's lock, so it will deadlock if this thread holds
+    /// an AsLockWriteGuard; use `AsLockWriteGuard::op_stats` instead.
+    pub fn op_stats(&self) -> crate::op_stats::OpStats {
+        self.writer.lock().op_stats.snapshot()
+    }
+}
+
+impl<T> crate::inspect::Inspect for AsLockHandle<T> {
+    fn inspect(&self) -> crate::inspect::Inspection {
+        crate::inspect::Inspection {
+            type_name: std::any::type_name::<T>(),
+            num_readers: Some(self.reader.readers.lock().len()),
+            num_ops_to_replay: self
+                .writer
+                .try_lock()
+                .map(|writer| writer.ops_to_replay.len()),
+            version: self.publish_signal.num_publishes(),
+            size: None,
+        }
+    }
+}
+
+impl<T> AsLockHandle<T> {
+    /// Counters of the writer, for monitoring. Like `inspect`, this never
+    /// waits on the writer.
+    pub fn stats(&self) -> crate::inspect::AsLockStats {
+        self.wait_stats.stats(
+            Some(self.reader.readers.lock().len()),
+            self.writer
+                .try_lock()
+                .map(|writer| writer.ops_to_replay.len()),
+            self.publish_signal.num_publishes(),
+        )
+    }
+
+    /// The generation of the latest publish, see
+    /// `AsLockReadGuard::generation`. A read racing with a publish may see the
+    /// new table slightly before this counts it.
+    pub fn current_generation(&self) -> u64 {
+        self.publish_signal.num_publishes()
+    }
+}
+
+impl<T: PartialEq> AsLockHandle<T> {
+    /// Returns whether the active and standby tables are equal, for canaries
+    /// which check that the updates are deterministic in production. See
+    /// `AsLockWriteGuard::tables_eq`.
+    ///
+    /// Nothing is published. Like `write`, this waits for the write lock and
+    /// for readers to leave the standby table, and replays the last guard's
+    /// updates onto it. It then compares the whole tables, so a check costs
+    /// about as much as an empty write plus a full comparison. If updates from
+    /// `write_deferred` are waiting to be published, the standby table is
+    /// ahead by design, so the check is skipped and this returns true.
+    pub fn tables_eq(&self) -> bool {
+        self.write_deferred().tables_eq()
+    }
+}
+
+impl<T> AsLockHandle<T> {
+    /// Tear down the tables and return the one with all of the updates,
+    /// including any left unpublished by `write_deferred`, if this is the
+    /// last handle. Otherwise the handle is returned, since other handles,
+    /// including those held by `AsLockOwnedReadGuard`s, may still read.
+    pub fn try_into_inner(self) -> Result<T, AsLockHandle<T>> {
+        let AsLockHandle {
+            writer,
+            reader,
+            publish_signal,
+            wait_stats,
+            _not_sync,
+        } = self;
+        let writer = match Arc::try_unwrap(writer) {
+            Ok(writer) => writer.into_inner(),
+            Err(writer) => {
+                return Err(AsLockHandle {
+                    writer,
+                    reader,
+                    publish_signal,
+                    wait_stats,
+                    _not_sync,
+                })
+            }
+        };
+        // The Reader points to one of the tables, so it must go first.
+        drop(reader);
+        Ok(writer.into_inner())
+    }
+}
+
+impl<T: Clone> AsLockHandle<T> {
+    /// Clone the active table. The read guard is only held while cloning, so
+    /// slow work on the copy, like persisting it, doesn't hold up the writer.
+    pub fn snapshot(&self) -> T {
+        self.read().cloned()
+    }
+}
+
+impl<T: crate::inspect::Sizer> AsLockHandle<T> {
+    /// Like `inspect`, but also reports the size of the active table.
+    pub fn inspect_sized(&self) -> crate::inspect::Inspection {
+        crate::inspect::Inspection {
+            size: Some(self.read().size()),
+            ..crate::inspect::Inspect::inspect(self)
+        }
+    }
+}
+
+#[cfg(feature = "registry")]
+impl<T> AsLockHandle<T>
+where
+    T: 'static + fmt::Debug + Send + Sync,
+{
+    /// Register this table in the process wide `registry` under `name`. The
+    /// registry doesn't keep the table alive; it is removed once all handles
+    /// are dropped.
+    pub fn register(&self, name: impl Into<String>) {
+        self.register_with(name.into(), |_| None)
+    }
+
+    /// Like `register`, but also reports the size of the table.
+    pub fn register_sized(&self, name: impl Into<String>)
+    where
+        T: crate::inspect::Sizer,
+    {
+        self.register_with(name.into(), |table| Some(table.size()))
+    }
+
+    fn register_with(&self, name: String, size: fn(&T) -> Option<usize>) {
+        let id = Arc::as_ptr(&self.writer) as usize;
+        let writer = Arc::downgrade(&self.writer);
+        let publish_signal = std::sync::Arc::clone(&self.publish_signal);
+        crate::registry::register(id, name, std::any::type_name::<T>(), move || {
+            let writer = writer.upgrade()?;
+            let publish_signal = std::sync::Arc::clone(&publish_signal);
+            Some(Box::new(move || {
+                let mut state = crate::registry::TableState {
+                    num_readers: None,
+                    num_ops_to_replay: None,
+                    version: publish_signal.num_publishes(),
+                    last_publish: publish_signal.last_publish(),
+                    size: None,
+                    active_table: None,
+                };
+                // The active table is never mutated while the writer is
+                // locked, so it's safe to read alongside the Readers.
+                if let Some(writer) = writer.try_lock() {
+                    state.num_readers = Some(writer.readers.lock().len());
+                    state.num_ops_to_replay = Some(writer.ops_to_replay.len());
+                    state.size = size(&writer.active_table);
+                    state.active_table = Some(format!("{:?}", writer.active_table));
+                }
+                state
+            }))
+        });
+    }
+}
+
 impl<T> std::fmt::Debug for AsLockHandle<T>
 where
     T: std::fmt::Debug,
@@ -387,213 +1739,1860 @@ where
     }
 }
 
-impl<'w, T> AsLockWriteGuard<'w, T> {
-    /// Takes an update which will change the state of the underlying data. This
-    /// is done through the interface of UpdateTables.
-    ///
-    /// Users should never use the return value to directly mutate the tables,
-    /// since this will lead to them going out of sync.
-    ///
-    /// The update passed in must be valid for 'static because it will outlive
-    /// the AsLockWriteGuard taking the update, so we can't make any limitations on
-    /// it.
-    pub fn update_tables<'a, R>(
-        &'a mut self,
-        mut update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
-    ) -> R {
-        // Explicitly cast MutexGuard into Writer in order for split borrowing
-        // to work. Without this line the compiler thinks that the borrow of
-        // standby_table and ops_to_replay are conflicting mutable borrows
-        // https://doc.rust-lang.org/nomicon/borrow-splitting.html
-        let writer: &mut Writer<_> = &mut self.writer;
+impl<T> crate::ReadInterface<T> for AsLockHandle<T> {
+    type Guard<'r>
+        = AsLockReadGuard<'r, T>
+    where
+        Self: 'r;
 
-        let res = update.apply_first(&mut writer.standby_table);
+    fn read(&self) -> Self::Guard<'_> {
+        AsLockHandle::read(self)
+    }
+}
+
+impl<T> crate::Snapshot<T> for AsLockHandle<T> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+}
+
+/// Create a table with a single writer, for topologies where only one
+/// thread/task ever writes. Returns the `WriterHalf`, which can't be cloned,
+/// and a `ReaderFactory` for creating `ReaderHalf`s.
+///
+/// Since the `WriterHalf` owns the Writer, `write` doesn't go through the
+/// mutex which `AsLockHandle`s share, and creating Readers never waits on a
+/// write guard. As with `AsLockHandle`, each thread/task should have its own
+/// `ReaderHalf`.
+///
+/// ```rust
+/// use active_standby::lockless;
+///
+/// let (mut writer, readers) = lockless::split(vec![1]);
+/// let reader = readers.new_reader();
+///
+/// let handle = std::thread::spawn(move || {
+///     let reader = readers.new_reader();
+///     while *reader.read() != vec![1, 2] {
+///         std::thread::yield_now();
+///     }
+/// });
+///
+/// writer.write().update_tables_closure(|table| table.push(2));
+/// handle.join().unwrap();
+/// assert_eq!(*reader.read(), vec![1, 2]);
+/// ```
+pub fn split<T: Clone>(t: T) -> (WriterHalf<T>, ReaderFactory<T>) {
+    WriterHalf::from_identical(t.clone(), t)
+}
+
+// Keeps the tables alive for the Readers once the WriterHalf is dropped.
+struct SplitState<T> {
+    parked_writer: Mutex<Option<Writer<T>>>,
+    publish_signal: std::sync::Arc<PublishSignal>,
+}
+
+/// The only writer of a table created by `split`.
+pub struct WriterHalf<T> {
+    // Only `None` while being dropped.
+    writer: Option<Writer<T>>,
+    state: Arc<SplitState<T>>,
+}
+
+/// Creates `ReaderHalf`s for a table created by `split`. Unlike a
+/// `ReaderHalf`, this can be shared across threads/tasks.
+pub struct ReaderFactory<T> {
+    // Never reads, so it doesn't hold up the Writer. It only tracks the active
+    // table for creating new Readers.
+    reader: Reader<T>,
+    state: Arc<SplitState<T>>,
+}
+
+/// Read access to a table created by `split`. These aren't meant to be shared
+/// across threads/tasks; create one per thread/task via the `ReaderFactory`.
+pub struct ReaderHalf<T> {
+    reader: Reader<T>,
+    state: Arc<SplitState<T>>,
+
+    // Make un-sync.
+    _not_sync: std::cell::UnsafeCell<fn(&T)>,
+}
+
+impl<T> WriterHalf<T> {
+    /// Like `split`, but t1 & t2 must be identical; this is left to the
+    /// caller to enforce.
+    pub fn from_identical(t1: T, t2: T) -> (WriterHalf<T>, ReaderFactory<T>) {
+        let mut writer = Writer::from_identical(t1, t2);
+        let reader = writer.new_reader(true);
+        let state = Arc::new(SplitState {
+            parked_writer: Mutex::new(None),
+            publish_signal: std::sync::Arc::clone(&writer.publish_signal),
+        });
+
+        let factory = ReaderFactory {
+            reader,
+            state: Arc::clone(&state),
+        };
+        let writer = WriterHalf {
+            writer: Some(writer),
+            state,
+        };
+        (writer, factory)
+    }
+
+    /// Create a `AsLockWriteGuard` which is used to update the underlying
+    /// tables. Like `AsLockHandle::write`, this waits for AsLockReadGuards to
+    /// leave the standby table, and replays the updates of the last guard, but
+    /// there is no lock to wait on.
+    ///
+    /// While no `ReaderHalf` exists, e.g. when bulk loading the table before
+    /// handing out readers, updates are applied to both tables directly
+    /// instead of being recorded for replay, and dropping the guard publishes
+    /// without swapping the tables. Once a `ReaderHalf` is created the guard
+    /// falls back to recording its updates. A `ReaderHalf` created while the
+    /// guard is held may see the updates applied directly before the guard
+    /// is dropped. Not available with `safe-impl`.
+    pub fn write(&mut self) -> AsLockWriteGuard<'_, T> {
+        let writer = self.writer.as_mut().expect("writer is only taken on drop");
+        writer.sync_standby_table();
+        AsLockWriteGuard::new(WriterRef::Owned(writer), true)
+    }
+
+    /// Like `write`, but returns `None` instead of waiting for
+    /// AsLockReadGuards to leave the standby table.
+    pub fn try_write(&mut self) -> Option<AsLockWriteGuard<'_, T>> {
+        let writer = self.writer.as_mut().expect("writer is only taken on drop");
+        if !writer.try_sync_standby_table() {
+            return None;
+        }
+        Some(AsLockWriteGuard::new(WriterRef::Owned(writer), true))
+    }
+
+    /// See `AsLockHandle::set_max_pending_ops`.
+    pub fn set_max_pending_ops(&mut self, max_pending_ops: Option<usize>) {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.max_pending_ops = max_pending_ops;
+        }
+    }
+
+    /// See `AsLockHandle::on_swap`.
+    pub fn on_swap(&mut self, callback: impl FnMut(u64, u64) + Send + 'static) {
+        self.state.publish_signal.on_swap(Box::new(callback));
+    }
+}
+
+#[cfg(feature = "leak-detect")]
+impl<T> WriterHalf<T> {
+    /// See `AsLockHandle::set_leak_detect_timeout`.
+    pub fn set_leak_detect_timeout(&mut self, timeout: std::time::Duration) {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.leak_detect_timeout = timeout;
+        }
+    }
+}
+
+#[cfg(feature = "op-stats")]
+impl<T> WriterHalf<T> {
+    /// See `AsLockHandle::op_stats`.
+    pub fn op_stats(&self) -> crate::op_stats::OpStats {
+        let writer = self.writer.as_ref().expect("writer is only taken on drop");
+        writer.op_stats.snapshot()
+    }
+}
+
+impl<T> Drop for WriterHalf<T> {
+    fn drop(&mut self) {
+        // Readers may still point to the tables, so hand them over.
+        *self.state.parked_writer.lock() = self.writer.take();
+    }
+}
+
+impl<T> ReaderFactory<T> {
+    /// Create a Reader of the active table. This never waits on the writer.
+    pub fn new_reader(&self) -> ReaderHalf<T> {
+        ReaderHalf {
+            reader: self.reader.new_sibling(false),
+            state: Arc::clone(&self.state),
+            _not_sync: std::cell::UnsafeCell::new(|_| {}),
+        }
+    }
+
+    /// See `AsLockHandle::changed`.
+    pub fn changed(&self) -> Changed {
+        self.state.publish_signal.changed()
+    }
+
+    /// See `AsLockHandle::subscribe`.
+    pub fn subscribe(&self) -> SwapListener {
+        self.state.publish_signal.subscribe()
+    }
+}
+
+impl<T> Clone for ReaderFactory<T> {
+    fn clone(&self) -> ReaderFactory<T> {
+        ReaderFactory {
+            reader: self.reader.new_sibling(true),
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<T> ReaderHalf<T> {
+    /// Obtain a read guard with which to inspect the active table. Wait free,
+    /// like `AsLockHandle::read`.
+    pub fn read(&self) -> AsLockReadGuard<'_, T> {
+        self.reader.read()
+    }
+
+    /// See `AsLockHandle::read_wait_free`.
+    #[cfg(not(feature = "safe-impl"))]
+    pub fn read_wait_free(&self) -> Result<AsLockReadGuard<'_, T>, WaitFreeReadError> {
+        self.reader.read_wait_free()
+    }
+
+    /// See `AsLockHandle::changed`.
+    pub fn changed(&self) -> Changed {
+        self.state.publish_signal.changed()
+    }
+
+    /// See `AsLockHandle::subscribe`.
+    pub fn subscribe(&self) -> SwapListener {
+        self.state.publish_signal.subscribe()
+    }
+
+    /// See `AsLockHandle::changes`.
+    #[cfg(feature = "stream")]
+    pub fn changes(&self) -> SwapListener {
+        self.subscribe()
+    }
+
+    /// See `AsLockHandle::pin`.
+    pub fn pin(&mut self) {
+        self.reader.pin()
+    }
+
+    /// See `AsLockHandle::unpin`.
+    pub fn unpin(&mut self) {
+        self.reader.unpin()
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.reader.pinned.is_some()
+    }
+}
+
+/// The ops to replay are held by the `WriterHalf`, so they aren't reported.
+impl<T> crate::inspect::Inspect for ReaderHalf<T> {
+    fn inspect(&self) -> crate::inspect::Inspection {
+        crate::inspect::Inspection {
+            type_name: std::any::type_name::<T>(),
+            num_readers: Some(self.reader.readers.lock().len()),
+            num_ops_to_replay: None,
+            version: self.state.publish_signal.num_publishes(),
+            size: None,
+        }
+    }
+}
+
+impl<T: crate::inspect::Sizer> ReaderHalf<T> {
+    /// See `AsLockHandle::inspect_sized`.
+    pub fn inspect_sized(&self) -> crate::inspect::Inspection {
+        crate::inspect::Inspection {
+            size: Some(self.read().size()),
+            ..crate::inspect::Inspect::inspect(self)
+        }
+    }
+}
+
+#[cfg(feature = "reader-stats")]
+impl<T> ReaderHalf<T> {
+    /// See `AsLockHandle::reader_stats`.
+    pub fn reader_stats(&self) -> ReaderStats {
+        self.reader.stats()
+    }
+
+    /// See `AsLockHandle::all_reader_stats`. The ReaderFactory holds a Reader
+    /// which never reads.
+    pub fn all_reader_stats(&self) -> Vec<ReaderStats> {
+        self.reader.all_stats()
+    }
+}
+
+impl<T> Clone for ReaderHalf<T> {
+    fn clone(&self) -> ReaderHalf<T> {
+        ReaderHalf {
+            reader: self.reader.new_sibling(false),
+            state: Arc::clone(&self.state),
+            _not_sync: std::cell::UnsafeCell::new(|_| {}),
+        }
+    }
+}
+
+impl<T> crate::ReadInterface<T> for ReaderHalf<T> {
+    type Guard<'r>
+        = AsLockReadGuard<'r, T>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        ReaderHalf::read(self)
+    }
+}
+
+impl<T> crate::Snapshot<T> for ReaderHalf<T> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for WriterHalf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriterHalf")
+            .field("writer", &self.writer)
+            .finish()
+    }
+}
+
+impl<T> fmt::Debug for ReaderFactory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReaderFactory")
+            .field("num_readers", &self.reader.readers.lock().len())
+            .finish()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ReaderHalf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReaderHalf")
+            .field("reader", &self.reader)
+            .finish()
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Takes an update which will change the state of the underlying data. This
+    /// is done through the interface of UpdateTables.
+    ///
+    /// Users should never use the return value to directly mutate the tables,
+    /// since this will lead to them going out of sync.
+    ///
+    /// The update passed in must be valid for 'static because it will outlive
+    /// the AsLockWriteGuard taking the update, so we can't make any limitations on
+    /// it.
+    pub fn update_tables<'a, R>(
+        &'a mut self,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_weighted(1, update)
+    }
+
+    /// Like `update_tables`, but the update counts as `weight` towards
+    /// `publishing_cost_estimate`, instead of 1. Useful when updates vary in how
+    /// expensive they are to replay.
+    pub fn update_tables_weighted<'a, R>(
+        &'a mut self,
+        weight: usize,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_impl(std::any::type_name_of_val(&update), weight, None, update)
+    }
+
+    /// Like `update_tables`, but the update is counted under `label` in the
+    /// `op_stats`, instead of under the name of its type. Without the
+    /// `op-stats` feature the label is ignored.
+    pub fn update_tables_labeled<'a, R>(
+        &'a mut self,
+        label: &'static str,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_impl(label, 1, None, update)
+    }
+
+    /// Like `update_tables`, but the update is recorded under `key`, and
+    /// replaces the replay of the last update recorded under the same key by
+    /// this guard. This keeps writers which repeatedly set the same values,
+    /// like config reconcilers, from growing the updates to replay.
+    ///
+    /// The update must make the one it replaces redundant, regardless of the
+    /// updates made in between, e.g. by overwriting the same field.
+    /// Otherwise the tables diverge.
+    pub fn update_tables_keyed<'a, R>(
+        &'a mut self,
+        key: impl Into<String>,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        let label = std::any::type_name_of_val(&update);
+        self.update_tables_impl(label, 1, Some(key.into()), update)
+    }
+
+    fn update_tables_impl<'a, R>(
+        &'a mut self,
+        label: &'static str,
+        weight: usize,
+        key: Option<String>,
+        mut update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.replay_cost += weight;
+
+        // Explicitly cast MutexGuard into Writer in order for split borrowing
+        // to work. Without this line the compiler thinks that the borrow of
+        // standby_table and ops_to_replay are conflicting mutable borrows
+        // https://doc.rust-lang.org/nomicon/borrow-splitting.html
+        let writer: &mut Writer<_> = &mut self.writer;
+
+        if self.direct {
+            if let Some(_readers) = lock_passive_readers(&writer.readers) {
+                let res = update.apply_first(&mut writer.standby_table);
+                update.apply_second(&mut writer.active_table);
+                return res;
+            }
+            self.direct = false;
+        }
+
+        if let Some(key) = &key {
+            if self.op_keys.remove(key, &mut writer.ops_to_replay) {
+                self.replay_cost -= 1;
+            }
+        }
+
+        let res = update.apply_first(&mut writer.standby_table);
+
+        // Can't go through `record_op`, since `res` may borrow the standby
+        // table from `writer`.
+        let index = writer.ops_to_replay.push(Box::new(move |table| {
+            update.apply_second(table);
+        }));
+        if let Some(key) = key {
+            self.op_keys.insert(key, index);
+        }
+        #[cfg(feature = "op-stats")]
+        writer.op_stats.record(label);
+        #[cfg(not(feature = "op-stats"))]
+        let _ = label;
+
+        res
+    }
+
+    /// Like `update_tables`, but fails instead of applying `update` if this
+    /// guard already holds `max_pending_ops` updates (see
+    /// `AsLockHandle::set_max_pending_ops`). Without a limit this always
+    /// succeeds. The other `update_tables` methods aren't limited, but their
+    /// updates count towards the limit.
+    ///
+    /// The updates held by a guard are replayed by the next `write`, after
+    /// waiting for any AsLockReadGuards on the standby table, so a stuck
+    /// reader doesn't grow them past the updates of a single guard. The limit
+    /// bounds that. When it is hit, drop the guard to publish its updates;
+    /// any stuck reader then holds up the next `write` rather than growing
+    /// the ops held in memory.
+    pub fn try_update_tables<'a, R>(
+        &'a mut self,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> Result<R, crate::PendingOpsFull> {
+        self.check_max_pending_ops()?;
+        Ok(self.update_tables(update))
+    }
+
+    /// Like `update_tables_closure`, but limited like `try_update_tables`.
+    pub fn try_update_tables_closure<R>(
+        &mut self,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> Result<R, crate::PendingOpsFull> {
+        self.check_max_pending_ops()?;
+        Ok(self.update_tables_closure(update))
+    }
+
+    fn check_max_pending_ops(&self) -> Result<(), crate::PendingOpsFull> {
+        match self.writer.max_pending_ops {
+            Some(max_pending_ops) if self.writer.ops_to_replay.len() >= max_pending_ops => {
+                Err(crate::PendingOpsFull { max_pending_ops })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `update_tables` but allows the user to pass a closure for
+    /// convenience. Only allows return values that own their data. See
+    /// `update_tables_closure_mut` for closures which mutate what they
+    /// capture.
+    ///
+    /// TODO: Consider allowing return values that have lifetimes, this should
+    /// likely be as safe as the explicit UpdateTables trait.
+    pub fn update_tables_closure<R>(
+        &mut self,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_closure_weighted(1, update)
+    }
+
+    /// Like `update_tables_closure`, but the update counts as `weight` towards
+    /// `publishing_cost_estimate`, instead of 1.
+    pub fn update_tables_closure_weighted<R>(
+        &mut self,
+        weight: usize,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_closure_impl(std::any::type_name_of_val(&update), weight, update)
+    }
+
+    /// Like `update_tables_closure`, but the update is counted under `label`
+    /// in the `op_stats`. Without the `op-stats` feature the label is ignored.
+    pub fn update_tables_closure_labeled<R>(
+        &mut self,
+        label: &'static str,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_closure_impl(label, 1, update)
+    }
+
+    /// Like `update_tables_closure`, but `update` may mutate what it captures,
+    /// e.g. a counter or an iterator being consumed. It is cloned to update
+    /// the first table, and the original replays the update on the second, so
+    /// both start from the same captured state.
+    pub fn update_tables_closure_mut<R>(
+        &mut self,
+        mut update: impl FnMut(&mut T) -> R + Clone + 'static + Send,
+    ) -> R {
+        let label = std::any::type_name_of_val(&update);
+        let first = update.clone();
+        self.update_tables_impl(
+            label,
+            1,
+            None,
+            crate::ClosureUpdate::new(first, move |table: &mut T| {
+                update(table);
+            }),
+        )
+    }
+
+    fn update_tables_closure_impl<R>(
+        &mut self,
+        label: &'static str,
+        weight: usize,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.replay_cost += weight;
+
+        if self.direct {
+            let writer: &mut Writer<_> = &mut self.writer;
+            if let Some(_readers) = lock_passive_readers(&writer.readers) {
+                let res = update(&mut writer.standby_table);
+                update(&mut writer.active_table);
+                return res;
+            }
+            self.direct = false;
+        }
+
+        // See comments on `Table::standby_table_mut` for safety.
+        let res = update(&mut self.writer.standby_table);
+
+        self.writer.record_op(
+            label,
+            Box::new(move |table| {
+                update(table);
+            }),
+        );
+
+        res
+    }
+
+    /// Like `update_tables_closure` but `update` may borrow `state` instead of
+    /// capturing it, which lifts the 'static requirement off of `state`. The
+    /// standby table is updated with the borrowed `state`, and an owned copy
+    /// is recorded to replay the update on the other table.
+    pub fn update_tables_scoped<State, R>(
+        &mut self,
+        state: &State,
+        update: impl Fn(&mut T, &State) -> R + 'static + Sized + Send,
+    ) -> R
+    where
+        State: ToOwned + ?Sized,
+        State::Owned: 'static + Send,
+    {
+        if self.direct {
+            let writer: &mut Writer<_> = &mut self.writer;
+            if let Some(_readers) = lock_passive_readers(&writer.readers) {
+                let res = update(&mut writer.standby_table, state);
+                update(&mut writer.active_table, state);
+                self.replay_cost += 1;
+                return res;
+            }
+            self.direct = false;
+        }
+
+        let res = update(&mut self.writer.standby_table, state);
+
+        self.replay_cost += 1;
+        let label = std::any::type_name_of_val(&update);
+        let state = state.to_owned();
+        self.writer.record_op(
+            label,
+            Box::new(move |table| {
+                update(table, std::borrow::Borrow::borrow(&state));
+            }),
+        );
+
+        res
+    }
+
+    /// The number of updates made through this guard, which will be replayed
+    /// on the other table by the next `write`.
+    pub fn num_pending_ops(&self) -> usize {
+        self.writer.ops_to_replay.len()
+    }
+
+    /// Estimate of the cost of replaying the pending updates, which is paid by
+    /// the next `write`. This is the sum of the updates' weights, where updates
+    /// made without a weight count as 1. Can be used to decide when to drop
+    /// the guard to publish the updates.
+    pub fn publishing_cost_estimate(&self) -> usize {
+        self.replay_cost
+    }
+
+    /// Hold onto `value` and drop it only once this guard has published its
+    /// updates and released the write lock. Meant for values removed from the
+    /// table, such as `Arc`s shared with readers, so that their destructors
+    /// run at a deterministic point, outside of the swap.
+    ///
+    /// Note that this only covers the value removed from the standby table;
+    /// when the removal is replayed on the other table, that copy is dropped
+    /// during the replay.
+    pub fn defer_drop(&mut self, value: impl Send + 'static) {
+        self.deferred_drops.push(Box::new(value));
+    }
+}
+
+#[cfg(feature = "op-stats")]
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// See `AsLockHandle::op_stats`. The updates made through this guard are
+    /// counted, but not yet replayed.
+    pub fn op_stats(&self) -> crate::op_stats::OpStats {
+        self.writer.op_stats.snapshot()
+    }
+}
+
+#[cfg(all(feature = "unchecked-swap", not(feature = "safe-impl")))]
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Publish the updates made so far and keep writing to the other table,
+    /// without dropping the guard. Unlike `write`, this doesn't wait for
+    /// AsLockReadGuards to leave the new standby table; the updates are
+    /// replayed onto it immediately.
+    ///
+    /// Meant for measuring the cost of a swap in isolation, and for building
+    /// custom publish protocols which track the readers some other way. Not
+    /// meant for production use.
+    ///
+    /// # Safety
+    /// No AsLockReadGuard may point to the active table when this is called,
+    /// including guards of other handles to the same tables. Otherwise the
+    /// replay mutates a table which is being read.
+    pub unsafe fn swap_tables_now_unchecked(&mut self) {
+        self.writer.swap_tables();
+        // The caller guarantees that none of the readers recorded by the swap
+        // are still reading.
+        self.writer.blocking_readers.clear();
+        self.writer.sync_standby_table();
+        self.replay_cost = 0;
+        self.op_keys.clear();
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    fn new(writer: WriterRef<'w, T>, direct: bool) -> AsLockWriteGuard<'w, T> {
+        AsLockWriteGuard {
+            // Picks up the cost of unpublished updates from deferred guards.
+            replay_cost: writer.unpublished_cost.unwrap_or(0),
+            writer,
+            direct,
+            op_keys: OpKeys::default(),
+            deferred: false,
+            deferred_drops: vec![],
+        }
+    }
+}
+
+impl<'w, T> Drop for AsLockWriteGuard<'w, T> {
+    fn drop(&mut self) {
+        if self.deferred {
+            if !self.writer.ops_to_replay.is_empty() {
+                self.writer.unpublished_cost = Some(self.replay_cost);
+            }
+        } else if self.direct {
+            // Both tables are already up to date.
+            let writer: &Writer<_> = &self.writer;
+            let generation = writer.publish_signal.num_publishes() + 1;
+            writer.generations.set(&*writer.active_table, generation);
+            writer.generations.set(&*writer.standby_table, generation);
+            writer.publish_signal.notify();
+        } else {
+            self.writer.swap_tables();
+        }
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Publish the updates made so far and replay them on the standby table,
+    /// as if this guard were dropped and a new one created.
+    fn resync(&mut self) {
+        self.writer.swap_tables();
+        self.writer.sync_standby_table();
+        self.replay_cost = 0;
+        self.op_keys.clear();
+    }
+}
+
+impl<'w, T: PartialEq> AsLockWriteGuard<'w, T> {
+    /// Like `assert_synced`, but returns whether the tables are equal instead
+    /// of panicking.
+    ///
+    /// If this guard has no updates to replay, the tables are compared as they
+    /// are, without publishing. Otherwise they are published first, like
+    /// `assert_synced` does, except by a guard from `write_deferred`, whose
+    /// updates must stay unpublished, so the check is skipped and this
+    /// returns true.
+    pub fn tables_eq(&mut self) -> bool {
+        if !self.writer.ops_to_replay.is_empty() {
+            if self.deferred {
+                return true;
+            }
+            self.resync();
+        }
+        *self.writer.active_table == *self.writer.standby_table
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T>
+where
+    T: PartialEq + fmt::Debug,
+{
+    /// Test helper which panics if the tables have diverged. This publishes the
+    /// updates made so far, as if this guard were dropped and a new one
+    /// created, and then compares the active and standby tables.
+    ///
+    /// Like `write`, this waits for AsLockReadGuards to leave the standby
+    /// table, so it will deadlock if this thread holds an AsLockReadGuard.
+    pub fn assert_synced(&mut self) {
+        self.resync();
+        assert_eq!(
+            *self.writer.active_table, *self.writer.standby_table,
+            "active and standby tables have diverged"
+        );
+    }
+}
+
+impl<'w, T> std::ops::Deref for AsLockWriteGuard<'w, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.writer.standby_table
+    }
+}
+
+impl<'w, T> std::fmt::Debug for AsLockWriteGuard<'w, T>
+where
+    T: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsLockWriteGuard")
+            .field("num_readers", &self.writer.readers.lock().len())
+            .field("ops_to_replay", &self.writer.ops_to_replay.len())
+            .field("standby_table", &self.writer.standby_table)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use more_asserts::*;
+    use std::thread;
+
+    struct PushVec<T> {
+        value: T,
+    }
+    impl<'a, T> UpdateTables<'a, Vec<T>, ()> for PushVec<T>
+    where
+        T: Clone + std::fmt::Debug,
+    {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) {
+            dbg!(&table);
+            table.push(self.value.clone());
+            dbg!(&table);
+        }
+        fn apply_second(self, table: &mut Vec<T>) {
+            table.push(self.value); // Move the value instead of cloning.
+        }
+    }
+
+    struct PopVec {}
+    impl PopVec {
+        fn apply<T>(&mut self, table: &mut Vec<T>) -> Option<T> {
+            table.pop()
+        }
+    }
+    impl<'a, T> UpdateTables<'a, Vec<T>, Option<T>> for PopVec {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) -> Option<T> {
+            self.apply(table)
+        }
+        fn apply_second(mut self, table: &mut Vec<T>) {
+            self.apply(table);
+        }
+    }
+
+    /// This is an example of what not to do!
+    struct MutableRef {}
+    impl<'a, T> UpdateTables<'a, Vec<T>, &'a mut T> for MutableRef {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) -> &'a mut T {
+            &mut table[0]
+        }
+        fn apply_second(self, table: &mut Vec<T>) {
+            let _ = &mut table[0];
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Reader is not reentrant")]
+    fn reader_not_reentrant() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let _rg1 = table.read();
+        let _rg2 = table.read();
+    }
+
+    #[cfg(not(feature = "safe-impl"))]
+    #[test]
+    fn read_wait_free() {
+        let mut table = AsLockHandle::new(vec![1]);
+        {
+            let rg = table.read_wait_free().unwrap();
+            assert_eq!(*rg, vec![1]);
+            // Fails instead of panicking like `read`.
+            assert_eq!(
+                table.read_wait_free().unwrap_err(),
+                WaitFreeReadError::Reentrant
+            );
+        }
+
+        table.pin();
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(*table.read_wait_free().unwrap(), vec![1]);
+        table.unpin();
+        assert_eq!(*table.read_wait_free().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn writer_not_reentrant() {
+        let table = AsLockHandle::<Vec<i32>>::from_identical(vec![], vec![]);
+        let _wg = table.write();
+
+        // If we uncomment this line the test fails due to Mutex not being
+        // re-entrant. While it is well defined that the program will not
+        // proceed it is not defined how exactly the failure will occur, so we
+        // cannot expect a panic as this may deadlock and hang.
+        //
+        // let wg2 = table.write();
+    }
+
+    #[test]
+    fn publish_update() {
+        let table = AsLockHandle::<Vec<i32>>::new(vec![]);
+        assert_eq!(table.read().len(), 0);
+
+        {
+            let mut wg = table.write();
+            wg.update_tables(PushVec { value: 2 });
+            assert_eq!(wg.len(), 1);
+            assert_eq!(table.read().len(), 0);
+        }
+
+        // When the write guard is dropped it publishes the changes to the readers.
+        assert_eq!(*table.read(), vec![2]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn new_parallel() {
+        let table = AsLockHandle::new_parallel((0..1000).collect::<Vec<i32>>());
+        table.write().update_tables(PushVec { value: 1000 });
+        table.write().assert_synced();
+        assert_eq!(*table.read(), (0..=1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn changed() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let changed = table.changed();
+        let writer = table.clone();
+        let handle = thread::spawn(move || {
+            writer.write().update_tables(PushVec { value: 2 });
+        });
+
+        // The future resolves once the update is visible to readers.
+        crate::primitives::changed::block_on(changed);
+        assert_eq!(*table.read(), vec![2]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn subscribe() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let mut listener = table.subscribe();
+        let writer = table.clone();
+        let handle = thread::spawn(move || {
+            for value in 1..=3 {
+                writer.write().update_tables(PushVec { value });
+            }
+        });
+
+        // Each wait sees the update which was visible when it returned.
+        while table.read().len() < 3 {
+            let generation = listener.wait();
+            assert!(table.read().generation() >= generation);
+        }
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn changes() {
+        use futures_core::Stream;
+
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let mut changes = table.changes();
+        let waker = std::task::Waker::noop();
+        let mut cx = std::task::Context::from_waker(waker);
+        assert!(std::pin::Pin::new(&mut changes)
+            .poll_next(&mut cx)
+            .is_pending());
+
+        table.write().update_tables(PushVec { value: 1 });
+        table.write().update_tables(PushVec { value: 2 });
+        // Publishes between polls are seen as one.
+        let next = crate::primitives::changed::block_on(std::future::poll_fn(|cx| {
+            std::pin::Pin::new(&mut changes).poll_next(cx)
+        }));
+        assert_eq!(next, Some(table.current_generation()));
+        assert!(std::pin::Pin::new(&mut changes)
+            .poll_next(&mut cx)
+            .is_pending());
+    }
+
+    #[test]
+    fn spawn_thread_with() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let thread =
+            table.spawn_thread_with(|table| table.write().update_tables(PushVec { value: 2 }));
+        // The original handle is still usable while the thread runs.
+        let _ = table.read().len();
+        thread.join().unwrap();
+        assert_eq!(*table.read(), vec![2]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawn_with() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let table = crate::lockless::collections::AsVecHandle::<i32>::default();
+        // Tasks can only be spawned from within the runtime.
+        let len = runtime.block_on(async {
+            let task = table.spawn_with(|table| async move {
+                table.write().push(2);
+                table.read().len()
+            });
+            task.await.unwrap()
+        });
+        assert_eq!(len, 1);
+        assert_eq!(*table.read(), vec![2]);
+    }
+
+    #[test]
+    fn on_swap() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let swaps = Arc::new(Mutex::new(vec![]));
+        table.on_swap({
+            let (reader, swaps) = (table.clone(), Arc::clone(&swaps));
+            move |old, new| swaps.lock().push((old, new, reader.read().clone()))
+        });
+
+        table.write().update_tables(PushVec { value: 2 });
+        {
+            let mut wg = table.write();
+            wg.update_tables(PushVec { value: 3 });
+            // Each publish made by the guard is reported.
+            wg.assert_synced();
+            wg.update_tables(PushVec { value: 4 });
+        }
+        assert_eq!(
+            *swaps.lock(),
+            vec![(0, 1, vec![2]), (1, 2, vec![2, 3]), (2, 3, vec![2, 3, 4])]
+        );
+    }
+
+    #[test]
+    fn inspect() {
+        use crate::inspect::Inspect;
+
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let _reader = table.clone();
+        table.write().update_tables(PushVec { value: 2 });
+        assert_eq!(
+            table.inspect_sized(),
+            crate::inspect::Inspection {
+                type_name: "alloc::vec::Vec<i32>",
+                num_readers: Some(2),
+                num_ops_to_replay: Some(1),
+                version: 1,
+                size: Some(1),
+            }
+        );
+
+        // Doesn't wait on the writer.
+        let wg = table.write();
+        assert_eq!(table.inspect().num_ops_to_replay, None);
+        drop(wg);
+    }
+
+    #[test]
+    fn update_tables_closure_mut() {
+        let table = AsLockHandle::new(vec![]);
+        let mut wg = table.write();
+        // Without cloning, the second table would get [3, 4].
+        let mut next = 0;
+        wg.update_tables_closure_mut(move |vec| {
+            for _ in 0..2 {
+                next += 1;
+                vec.push(next);
+            }
+        });
+        let mut values = vec![5, 6].into_iter();
+        wg.update_tables_closure_mut(move |vec| vec.extend(values.by_ref()));
+        wg.assert_synced();
+        assert_eq!(*wg, vec![1, 2, 5, 6]);
+    }
+
+    #[test]
+    fn update_tables_closure() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        assert_eq!(table.read().len(), 0);
+
+        {
+            let mut wg = table.write();
+            wg.update_tables_closure(|vec| vec.push(2));
+            assert_eq!(wg.len(), 1);
+            assert_eq!(table.read().len(), 0);
+        }
+
+        // When the write guard is dropped it publishes the changes to the readers.
+        assert_eq!(*table.read(), vec![2]);
+    }
+
+    #[cfg(feature = "op-stats")]
+    #[test]
+    fn op_stats() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        {
+            let mut wg = table.write();
+            wg.update_tables_labeled("push", PushVec { value: 2 });
+            wg.update_tables_closure_labeled("push", |vec| vec.push(3));
+            wg.update_tables_closure(|vec| vec.clear());
+            let stats = wg.op_stats();
+            assert_eq!(stats.ops["push"].count, 2);
+            assert_eq!(stats.total().replayed, 0);
+        }
+
+        // The updates are replayed by the next write.
+        drop(table.write());
+        let stats = table.op_stats();
+        assert_eq!(stats.ops.len(), 2);
+        assert_eq!(stats.ops["push"].replayed, 2);
+        assert_eq!(stats.total().count, 3);
+        assert_eq!(stats.total().replayed, 3);
+
+        // Unlabeled updates are counted under the name of their type.
+        let label = stats.ops.keys().find(|label| **label != "push").unwrap();
+        assert!(label.contains("op_stats::{{closure}}"), "{}", label);
+        assert!(stats.report().contains(label));
+    }
+
+    #[test]
+    fn update_tables_scoped() {
+        let table = AsLockHandle::<Vec<String>>::default();
+        {
+            // Borrow a local which doesn't live for 'static.
+            let local = String::from("hello");
+            let mut wg = table.write();
+            let len = wg.update_tables_scoped(local.as_str(), |vec, s: &str| {
+                vec.push(s.to_string());
+                vec.len()
+            });
+            assert_eq!(len, 1);
+            assert_eq!(table.read().len(), 0);
+        }
+
+        assert_eq!(*table.read(), vec!["hello"]);
+        assert_eq!(*table.write(), vec!["hello"]);
+        assert_eq!(*table.read(), vec!["hello"]);
+    }
+
+    #[test]
+    fn pending_ops() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert_eq!(wg.publishing_cost_estimate(), 0);
+
+            wg.update_tables(PushVec { value: 2 });
+            wg.update_tables_closure(|vec| vec.push(3));
+            wg.update_tables_weighted(10, PushVec { value: 4 });
+            wg.update_tables_closure_weighted(5, |vec| vec.push(5));
+            assert_eq!(wg.num_pending_ops(), 4);
+            assert_eq!(wg.publishing_cost_estimate(), 17);
+        }
+
+        // The ops were replayed when creating the new guard.
+        let wg = table.write();
+        assert_eq!(*wg, vec![2, 3, 4, 5]);
+        assert_eq!(wg.num_pending_ops(), 0);
+        assert_eq!(wg.publishing_cost_estimate(), 0);
+    }
+
+    #[test]
+    fn from_identical_with() {
+        let options = crate::Options::default()
+            .max_pending_ops(Some(1))
+            .reader_capacity(1);
+        let table = AsLockHandle::from_identical_with(vec![1], vec![1], options);
+        // The Readers aren't limited to `reader_capacity`.
+        let handles: Vec<_> = (0..4).map(|_| table.clone()).collect();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.try_update_tables_closure(|vec| vec.push(2)), Ok(()));
+            assert!(wg.try_update_tables_closure(|vec| vec.push(3)).is_err());
+        }
+        assert_eq!(*handles[3].read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn from_fn() {
+        // Not Clone, so `new` isn't available.
+        #[derive(Debug, PartialEq)]
+        struct Conn(u32);
+
+        let mut built = 0;
+        let table = AsLockHandle::from_fn(|| {
+            built += 1;
+            vec![Conn(1)]
+        });
+        assert_eq!(built, 2);
+
+        table.write().update_tables_closure(|vec| vec.push(Conn(2)));
+        assert_eq!(*table.read(), vec![Conn(1), Conn(2)]);
+        assert_eq!(*table.write(), vec![Conn(1), Conn(2)]);
+    }
+
+    #[test]
+    fn try_into_inner() {
+        let table = AsLockHandle::new(vec![1]);
+        let table2 = table.clone();
+        let table = table.try_into_inner().unwrap_err();
+        drop(table2);
+
+        let rg = table.read_owned();
+        let table = table.try_into_inner().unwrap_err();
+        drop(rg);
+
+        table.write().update_tables_closure(|vec| vec.push(2));
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(3));
+        assert_eq!(table.try_into_inner().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn generation() {
+        let table = AsLockHandle::new(vec![1]);
+        let table2 = table.clone();
+        assert_eq!(table.read().generation(), 0);
+        assert_eq!(table.current_generation(), 0);
+
+        let rg = table2.read_owned();
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(table.current_generation(), 1);
+        assert_eq!(table.read().generation(), 1);
+        // The guard still reads the table from before the publish.
+        assert_eq!(rg.generation(), 0);
+        drop(rg);
+
+        // Publishing without updates still counts.
+        drop(table.write());
+        assert_eq!(table2.read().generation(), 2);
+        assert_eq!(*table2.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn snapshot() {
+        let table = AsLockHandle::new(vec![1, 2]);
+        let snapshot = table.snapshot();
+        // Doesn't hold a read guard, so the writer isn't held up.
+        table.write().update_tables_closure(|vec| vec.push(3));
+        table.write().update_tables_closure(|vec| vec.push(4));
+        assert_eq!(snapshot, vec![1, 2]);
+        assert_eq!(table.snapshot(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn stats() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = std::thread::spawn({
+            let reader = table.clone();
+            move || {
+                let rg = reader.read();
+                sender.send(()).unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                drop(rg);
+            }
+        });
+        receiver.recv().unwrap();
+        table.write().update_tables(PushVec { value: 1 });
+        // Waits for the reader, which now points to the standby table.
+        table.write().update_tables(PushVec { value: 2 });
+        thread.join().unwrap();
+
+        let stats = table.stats();
+        assert_eq!(stats.num_readers, Some(1));
+        assert_eq!(stats.num_ops_to_replay, Some(1));
+        assert_eq!(stats.num_publishes, 2);
+        assert_eq!(stats.num_waits, 1);
+        assert!(stats.max_wait >= std::time::Duration::from_millis(10));
+        assert_eq!(stats.total_wait, stats.max_wait);
+
+        let _wg = table.write();
+        assert_eq!(table.stats().num_ops_to_replay, None);
+    }
+
+    #[test]
+    fn write_deferred() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let reader = table.clone();
+        let rg = reader.read();
+        table.write_deferred().update_tables(PushVec { value: 1 });
+        table.write_deferred().update_tables(PushVec { value: 2 });
+        // The reader didn't hold up the writes.
+        assert!(rg.is_empty());
+        drop(rg);
+        assert!(reader.read().is_empty());
+
+        assert!(table.publish());
+        assert!(!table.publish());
+        assert_eq!(*reader.read(), vec![1, 2]);
+
+        // A guard from `write` publishes the deferred updates too.
+        table.write_deferred().update_tables(PushVec { value: 3 });
+        table.write().update_tables(PushVec { value: 4 });
+        assert_eq!(*reader.read(), vec![1, 2, 3, 4]);
+        table.write().assert_synced();
+    }
+
+    #[test]
+    fn try_write() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let reader = table.clone();
+        let rg = reader.read();
+        // Publishing leaves `rg` pointing to the standby table.
+        table.write().update_tables(PushVec { value: 2 });
+        assert!(table.try_write().is_none());
+        drop(rg);
+
+        {
+            let mut wg = table.try_write().unwrap();
+            wg.update_tables(PushVec { value: 3 });
+            // Another guard exists.
+            assert!(reader.try_write().is_none());
+        }
+        assert_eq!(*table.read(), vec![2, 3]);
+
+        let (mut writer, readers) = crate::lockless::split(vec![]);
+        let reader = readers.new_reader();
+        let rg = reader.read();
+        writer.write().update_tables(PushVec { value: 1 });
+        assert!(writer.try_write().is_none());
+        drop(rg);
+        assert_eq!(*writer.try_write().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn write_budgeted() {
+        let table = AsLockHandle::new(vec![]);
+        {
+            let mut wg = table.write();
+            for i in 0..5 {
+                wg.update_tables_closure(move |vec| vec.push(i));
+            }
+        }
+        // Replays 2 of the 5 updates each time.
+        assert!(table.write_budgeted(2).is_none());
+        assert!(table.write_budgeted(2).is_none());
+        assert_eq!(*table.read(), vec![0, 1, 2, 3, 4]);
+        table
+            .write_budgeted(2)
+            .unwrap()
+            .update_tables_closure(|vec| vec.push(5));
+
+        table.write().update_tables_closure(|vec| vec.push(6));
+        assert!(table.write_budgeted(0).is_none());
+        // `write_full` finishes the replay regardless of the budget.
+        table.write_full().assert_synced();
+        assert_eq!(*table.read(), vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn write_timed() {
+        let table = AsLockHandle::new(vec![]);
+        {
+            let mut wg = table.write();
+            for i in 0..3 {
+                wg.update_tables_closure(move |vec| {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    vec.push(i)
+                });
+            }
+        }
+        // Stops once the first update overruns the budget.
+        assert!(table.write_timed(std::time::Duration::ZERO).is_none());
+        assert!(table.write_budgeted(1).is_none());
+        let mut wg = table
+            .write_timed(std::time::Duration::from_secs(10))
+            .unwrap();
+        wg.assert_synced();
+        assert_eq!(*wg, vec![0, 1, 2]);
+    }
 
-        writer.ops_to_replay.push(Box::new(move |table| {
-            update.apply_second(table);
-        }));
+    #[test]
+    fn tables_eq() {
+        let table = AsLockHandle::new(vec![1]);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert!(table.tables_eq());
+
+        // An update which isn't deterministic diverges the tables.
+        let counter = std::sync::atomic::AtomicI32::new(0);
+        table.write().update_tables_closure(move |vec| {
+            vec.push(counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+        });
+        assert!(!table.tables_eq());
+    }
 
-        res
+    #[test]
+    fn tables_eq_doesnt_publish() {
+        let table = AsLockHandle::new(vec![1]);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        let generation = table.current_generation();
+        assert!(table.tables_eq());
+        assert_eq!(table.current_generation(), generation);
+
+        // Deferred updates stay unpublished.
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(3));
+        assert!(table.tables_eq());
+        assert_eq!(table.current_generation(), generation);
+        assert_eq!(*table.read(), vec![1, 2]);
+        assert!(table.publish());
+        assert_eq!(*table.read(), vec![1, 2, 3]);
+        assert!(table.tables_eq());
     }
 
-    /// Like `update_tables` but allows the user to pass a closure for
-    /// convenience. Only allows return values that own their data.
-    ///
-    /// TODO: Consider allowing return values that have lifetimes, this should
-    /// likely be as safe as the explicit UpdateTables trait.
-    pub fn update_tables_closure<R>(
-        &mut self,
-        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
-    ) -> R {
-        // See comments on `Table::standby_table_mut` for safety.
-        let res = update(&mut self.writer.standby_table);
+    #[cfg(feature = "failpoints")]
+    #[test]
+    fn failpoints() {
+        // Other tests may hit the failpoints concurrently, so only record the
+        // hits from this thread.
+        thread_local! {
+            static HITS: std::cell::RefCell<Vec<&'static str>> = Default::default();
+        }
+        let scenario = fail::FailScenario::setup();
+        for name in [
+            "active_standby::before_swap",
+            "active_standby::replay",
+            "active_standby::await_readers",
+        ] {
+            fail::cfg_callback(name, move || HITS.with(|hits| hits.borrow_mut().push(name)))
+                .unwrap();
+        }
 
-        self.writer.ops_to_replay.push(Box::new(move |table| {
-            update(table);
-        }));
+        let table = AsLockHandle::new(vec![]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let reader = thread::spawn({
+            let table = table.clone();
+            move || {
+                let _rg = table.read();
+                sender.send(()).unwrap();
+                thread::sleep(std::time::Duration::from_millis(20));
+            }
+        });
+        receiver.recv().unwrap();
+        table.write().update_tables_closure(|vec| vec.push(1));
+        // Blocked by the reader until it is done sleeping.
+        table.write().update_tables_closure(|vec| vec.push(2));
+        reader.join().unwrap();
+
+        let mut hits = HITS.with(|hits| hits.take());
+        hits.dedup();
+        assert_eq!(
+            hits,
+            [
+                "active_standby::before_swap",
+                "active_standby::await_readers",
+                "active_standby::replay",
+                "active_standby::before_swap",
+            ]
+        );
+        scenario.teardown();
+    }
 
-        res
+    #[test]
+    fn update_tables_keyed() {
+        let table = AsLockHandle::new(vec![0, 0]);
+        let set = |index: usize, value: i32| {
+            crate::ClosureOp::new(value, move |vec: &mut Vec<i32>, value| vec[index] = value)
+        };
+        {
+            let mut wg = table.write();
+            for value in 1..=3 {
+                wg.update_tables_keyed("first", set(0, value));
+                wg.update_tables_keyed("second", set(1, value * 10));
+            }
+            wg.update_tables(PushVec { value: 4 });
+            wg.update_tables_keyed("first", set(0, 5));
+            assert_eq!(wg.num_pending_ops(), 3);
+            assert_eq!(wg.publishing_cost_estimate(), 3);
+            wg.assert_synced();
+
+            // Updates which were already replayed aren't replaced.
+            wg.update_tables_keyed("first", set(0, 6));
+            assert_eq!(wg.num_pending_ops(), 1);
+        }
+        let mut wg = table.write();
+        wg.assert_synced();
+        assert_eq!(*wg, vec![6, 30, 4]);
     }
-}
 
-impl<'w, T> Drop for AsLockWriteGuard<'w, T> {
-    fn drop(&mut self) {
-        // Explicitly cast mg into the InnerWriter that it guards in order for
-        // split borrowing to work. Without this line the compiler thinks that
-        // the usage of readers and blocking_readers are conflicting mutable borrows
-        // https://doc.rust-lang.org/nomicon/borrow-splitting.html
-        let writer: &mut Writer<_> = &mut self.writer;
-        assert!(writer.blocking_readers.is_empty());
+    #[cfg(feature = "op-stats")]
+    #[test]
+    fn op_stats_update_tables_keyed() {
+        struct Set(i32);
+        impl<'a> UpdateTables<'a, Vec<i32>, ()> for Set {
+            fn apply_first(&mut self, table: &'a mut Vec<i32>) {
+                table[0] = self.0;
+            }
+            fn apply_second(self, table: &mut Vec<i32>) {
+                table[0] = self.0;
+            }
+        }
 
-        // Swap the active and standby tables according to the Writer's
-        // accounting.
-        std::mem::swap(&mut writer.active_table, &mut writer.standby_table);
+        let table = AsLockHandle::new(vec![0]);
+        {
+            let mut wg = table.write();
+            wg.update_tables_keyed("k", Set(1));
+            wg.update_tables_keyed("k", Set(2));
+            wg.update_tables_labeled("other", Set(3));
+        }
+        drop(table.write());
+
+        // The replaced update is counted, but never replayed, and doesn't
+        // take the label of the updates after it.
+        let stats = table.op_stats();
+        let set = stats.ops[std::any::type_name::<Set>()];
+        assert_eq!((set.count, set.replayed), (2, 1));
+        assert_eq!(stats.ops["other"].replayed, 1);
+        assert_eq!(*table.read(), vec![3]);
+    }
 
-        for (key, table_and_epoch) in writer.readers.lock().iter_mut() {
-            // Swap the active table for each Reader.
-            let res = table_and_epoch.table.compare_exchange(
-                writer.standby_table.as_mut() as *mut T,
-                writer.active_table.as_mut() as *mut T,
-                Ordering::SeqCst,
-                Ordering::SeqCst,
-            );
-            assert_eq!(res, Ok(writer.standby_table.as_mut() as *mut T));
+    #[test]
+    fn try_update_tables() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.try_update_tables(PushVec { value: 1 }), Ok(()));
+        }
 
-            // Make sure that swap occurs before recording the epoch.
-            fence(Ordering::SeqCst);
+        table.set_max_pending_ops(Some(2));
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.try_update_tables(PushVec { value: 2 }), Ok(()));
+            wg.update_tables_closure(|vec| vec.push(3));
+            let full = Err(crate::PendingOpsFull { max_pending_ops: 2 });
+            assert_eq!(wg.try_update_tables(PushVec { value: 4 }), full);
+            assert_eq!(wg.try_update_tables_closure(|vec| vec.push(4)), full);
+            assert_eq!(wg.num_pending_ops(), 2);
+        }
 
-            // Once the tables have been swapped, record the epoch of each
-            // reader so that we will know if it is safe to update the new
-            // standby table.
-            let first_epoch_after_swap = table_and_epoch.epoch.load(Ordering::Acquire);
-            if first_epoch_after_swap % 2 != 0 {
-                // If the epoch is even, it means that there is no AsLockReadGuard
-                // active.
-                writer.blocking_readers.insert(key, first_epoch_after_swap);
+        // A new guard starts without pending ops.
+        assert_eq!(
+            table.write().try_update_tables_closure(|vec| vec.push(4)),
+            Ok(())
+        );
+        table.set_max_pending_ops(None);
+        assert_eq!(*table.write(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn defer_drop() {
+        struct OnDrop<F: FnMut()>(F);
+        impl<F: FnMut()> Drop for OnDrop<F> {
+            fn drop(&mut self) {
+                (self.0)()
             }
         }
+
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        {
+            // Cloning the handle takes the write lock, so do it up front.
+            let table2 = table.clone();
+            let mut wg = table.write();
+            wg.update_tables_closure(|vec| vec.push(2));
+
+            wg.defer_drop({
+                let dropped = Arc::clone(&dropped);
+                OnDrop(move || {
+                    // The update is published, and the write lock released.
+                    assert_eq!(*table2.read(), vec![2]);
+                    assert_eq!(*table2.write(), vec![2]);
+                    dropped.fetch_add(1, Ordering::SeqCst);
+                })
+            });
+            assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        }
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
     }
-}
 
-impl<'w, T> std::ops::Deref for AsLockWriteGuard<'w, T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.writer.standby_table
+    #[test]
+    fn same_table() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let table2 = table.clone();
+        let table3 = table2.clone();
+        assert!(AsLockHandle::same_table(&table, &table3));
+        assert!(!AsLockHandle::same_table(
+            &table,
+            &AsLockHandle::<Vec<i32>>::default()
+        ));
     }
-}
 
-impl<'w, T> std::fmt::Debug for AsLockWriteGuard<'w, T>
-where
-    T: std::fmt::Debug,
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("AsLockWriteGuard")
-            .field("num_readers", &self.writer.readers.lock().len())
-            .field("ops_to_replay", &self.writer.ops_to_replay.len())
-            .field("standby_table", &self.writer.standby_table)
-            .finish()
+    // The hash is the identity of the tables, not their contents, so mutating
+    // a table doesn't change it.
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn hash_by_table() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let other = AsLockHandle::<Vec<i32>>::default();
+        assert_eq!(table, table.clone());
+        assert_ne!(table, other);
+
+        let handles: std::collections::HashSet<_> = [table.clone(), other.clone(), table.clone()]
+            .into_iter()
+            .collect();
+        assert_eq!(handles.len(), 2);
+        assert!(handles.contains(&table) && handles.contains(&other));
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::thread;
+    #[test]
+    fn split() {
+        let (mut writer, readers) = super::split(vec![1]);
+        let reader = readers.new_reader();
+        {
+            let mut wg = writer.write();
+            wg.update_tables(PushVec { value: 2 });
 
-    struct PushVec<T> {
-        value: T,
-    }
-    impl<'a, T> UpdateTables<'a, Vec<T>, ()> for PushVec<T>
-    where
-        T: Clone + std::fmt::Debug,
-    {
-        fn apply_first(&mut self, table: &'a mut Vec<T>) {
-            dbg!(&table);
-            table.push(self.value.clone());
-            dbg!(&table);
-        }
-        fn apply_second(self, table: &mut Vec<T>) {
-            table.push(self.value); // Move the value instead of cloning.
+            // Creating Readers doesn't wait on the write guard.
+            let reader2 = readers.new_reader();
+            assert_eq!(*reader2.read(), vec![1]);
+            assert_eq!(*reader.clone().read(), vec![1]);
         }
+        assert_eq!(*reader.read(), vec![1, 2]);
+        assert_eq!(*readers.new_reader().read(), vec![1, 2]);
+
+        writer.write().assert_synced();
     }
 
-    struct PopVec {}
-    impl PopVec {
-        fn apply<T>(&mut self, table: &mut Vec<T>) -> Option<T> {
-            table.pop()
-        }
+    #[test]
+    fn split_writer_dropped_first() {
+        let (mut writer, readers) = super::split(vec![1]);
+        writer.write().update_tables(PushVec { value: 2 });
+        drop(writer);
+
+        let reader = readers.new_reader();
+        drop(readers);
+        assert_eq!(*reader.read(), vec![1, 2]);
     }
-    impl<'a, T> UpdateTables<'a, Vec<T>, Option<T>> for PopVec {
-        fn apply_first(&mut self, table: &'a mut Vec<T>) -> Option<T> {
-            self.apply(table)
-        }
-        fn apply_second(mut self, table: &mut Vec<T>) {
-            (&mut self).apply(table);
+
+    #[test]
+    fn split_without_readers() {
+        let (mut writer, readers) = super::split(vec![1]);
+        let changed = readers.changed();
+        {
+            let mut wg = writer.write();
+            wg.update_tables(PushVec { value: 2 });
+            wg.update_tables_closure(|table| table.push(3));
+            // Without a ReaderHalf the updates are applied to both tables.
+            let expected_pending = if cfg!(feature = "safe-impl") { 2 } else { 0 };
+            assert_eq!(wg.num_pending_ops(), expected_pending);
+
+            // Once there is a ReaderHalf, updates are recorded again.
+            let reader = readers.new_reader();
+            wg.update_tables(PushVec { value: 4 });
+            assert_eq!(wg.num_pending_ops(), expected_pending + 1);
+            assert_ne!(*reader.read(), vec![1, 2, 3, 4]);
         }
+        // Publishing without a swap still notifies.
+        crate::primitives::changed::block_on(changed);
+        assert_eq!(*readers.new_reader().read(), vec![1, 2, 3, 4]);
+
+        writer.write().assert_synced();
     }
 
-    /// This is an example of what not to do!
-    struct MutableRef {}
-    impl<'a, T> UpdateTables<'a, Vec<T>, &'a mut T> for MutableRef {
-        fn apply_first(&mut self, table: &'a mut Vec<T>) -> &'a mut T {
-            &mut table[0]
+    #[test]
+    fn split_generation() {
+        let (mut writer, readers) = super::split(vec![1]);
+        // Published directly to both tables without any ReaderHalf.
+        writer.write().update_tables_closure(|table| table.push(2));
+        writer.write().update_tables_closure(|table| table.push(3));
+        let reader = readers.new_reader();
+        assert_eq!(reader.read().generation(), 2);
+
+        writer.write().update_tables_closure(|table| table.push(4));
+        let rg = reader.read();
+        assert_eq!((rg.generation(), &*rg), (3, &vec![1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn split_multi_thread() {
+        let (mut writer, readers) = super::split(vec![]);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let readers = readers.clone();
+                thread::spawn(move || {
+                    let mut len = 0;
+                    while len < 100 {
+                        let reader = readers.new_reader();
+                        let new_len = reader.read().len();
+                        assert_ge!(new_len, len);
+                        len = new_len;
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..100 {
+            writer.write().update_tables(PushVec { value: i });
         }
-        fn apply_second(self, table: &mut Vec<T>) {
-            let _ = &mut table[0];
+        for handle in handles {
+            assert!(handle.join().is_ok());
         }
+        assert_eq!(*readers.new_reader().read(), (0..100).collect::<Vec<_>>());
     }
 
     #[test]
-    #[should_panic(expected = "Reader is not reentrant")]
-    fn reader_not_reentrant() {
-        let table = AsLockHandle::<Vec<i32>>::default();
-        let _rg1 = table.read();
-        let _rg2 = table.read();
+    fn read_guard_is_sync() {
+        fn assert_sync<T: Sync>(_: &T) {}
+
+        let table = AsLockHandle::new(vec![1, 2, 3, 4]);
+        let guard = table.read();
+        assert_sync(&guard);
+
+        let sums: Vec<i32> = thread::scope(|scope| {
+            let guard = &guard;
+            let handles: Vec<_> = (0..2)
+                .map(|i| scope.spawn(move || guard[i * 2..i * 2 + 2].iter().sum()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(sums, vec![3, 7]);
     }
 
     #[test]
-    fn writer_not_reentrant() {
-        let table = AsLockHandle::<Vec<i32>>::from_identical(vec![], vec![]);
-        let _wg = table.write();
+    fn pin() {
+        let mut table = AsLockHandle::new(vec![1]);
+        let writer = table.clone();
+        table.pin();
+        table.pin();
+        assert!(table.is_pinned());
+
+        writer.write().update_tables(PushVec { value: 2 });
+        assert_eq!(*writer.read(), vec![1, 2]);
+        {
+            // Reads while pinned may overlap.
+            let (a, b) = (table.read(), table.read());
+            assert_eq!(*a, vec![1]);
+            assert_eq!(*b, vec![1]);
+        }
 
-        // If we uncomment this line the test fails due to Mutex not being
-        // re-entrant. While it is well defined that the program will not
-        // proceed it is not defined how exactly the failure will occur, so we
-        // cannot expect a panic as this may deadlock and hang.
-        //
-        // let wg2 = table.write();
+        // The next write waits for the pinned table.
+        let write = thread::spawn(move || writer.write().update_tables(PushVec { value: 3 }));
+        thread::sleep(std::time::Duration::from_millis(10));
+        assert!(!write.is_finished());
+        assert_eq!(*table.read(), vec![1]);
+
+        table.unpin();
+        table.unpin();
+        write.join().unwrap();
+        assert_eq!(*table.read(), vec![1, 2, 3]);
+
+        let (mut writer, readers) = super::split(vec![1]);
+        let mut reader = readers.new_reader();
+        reader.pin();
+        writer.write().update_tables(PushVec { value: 2 });
+        assert_eq!(*reader.read(), vec![1]);
+        reader.unpin();
+        assert_eq!(*reader.read(), vec![1, 2]);
     }
 
     #[test]
-    fn publish_update() {
-        let table = AsLockHandle::<Vec<i32>>::new(vec![]);
-        assert_eq!(table.read().len(), 0);
+    fn acquire_read_raw() {
+        let table = AsLockHandle::new(vec![1]);
+        let (ptr, token) = table.acquire_read_raw();
+        table.write().update_tables(PushVec { value: 2 });
+
+        // The next write waits for the token to be released.
+        let writer = {
+            let table = table.clone();
+            thread::spawn(move || table.write().update_tables(PushVec { value: 3 }))
+        };
+        thread::sleep(std::time::Duration::from_millis(10));
+        assert!(!writer.is_finished());
+        assert!(std::ptr::eq(ptr, &*token.guard));
+        assert_eq!(*token.guard, vec![1]);
+
+        AsLockHandle::release_read_raw(token);
+        writer.join().unwrap();
+        assert_eq!(*table.read(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn read_owned() {
+        let table = AsLockHandle::new(vec![1]);
+        let guard = table.read_owned();
 
+        // Doesn't block on the Writer, and isn't tied to `table`.
         {
             let mut wg = table.write();
             wg.update_tables(PushVec { value: 2 });
-            assert_eq!(wg.len(), 1);
-            assert_eq!(table.read().len(), 0);
+            assert_eq!(*table.read_owned(), vec![1]);
         }
-
-        // When the write guard is dropped it publishes the changes to the readers.
-        assert_eq!(*table.read(), vec![2]);
+        let guard = thread::spawn(move || {
+            assert_eq!(*guard, vec![1]);
+            guard
+        })
+        .join()
+        .unwrap();
+        assert_eq!(*table.read(), vec![1, 2]);
+
+        // The next write waits for the guard to be dropped.
+        let writer = {
+            let table = table.clone();
+            thread::spawn(move || table.write().update_tables(PushVec { value: 3 }))
+        };
+        thread::sleep(std::time::Duration::from_millis(10));
+        assert!(!writer.is_finished());
+        drop(guard);
+        writer.join().unwrap();
+        assert_eq!(*table.read(), vec![1, 2, 3]);
     }
 
+    #[cfg(feature = "reader-stats")]
     #[test]
-    fn update_tables_closure() {
-        let table = AsLockHandle::<Vec<i32>>::default();
-        assert_eq!(table.read().len(), 0);
-
+    fn reader_stats() {
+        let table = AsLockHandle::new(vec![1]);
+        let idle = table.clone();
+        for _ in 0..1000 {
+            drop(table.read());
+        }
         {
-            let mut wg = table.write();
-            wg.update_tables_closure(|vec| vec.push(2));
-            assert_eq!(wg.len(), 1);
-            assert_eq!(table.read().len(), 0);
+            let _guard = table.read();
+            let stats = table.reader_stats();
+            assert_eq!(stats.reads, 1001);
+            assert_eq!(stats.guards_held, 1);
         }
 
-        // When the write guard is dropped it publishes the changes to the readers.
-        assert_eq!(*table.read(), vec![2]);
+        // With this many reads, some are all but certain to be sampled.
+        let stats = table.reader_stats();
+        assert_eq!(stats.guards_held, 0);
+        assert!(stats.max_hold_time.is_some());
+
+        let idle_stats = idle.reader_stats();
+        assert_eq!(idle_stats.reads, 0);
+        assert_eq!(idle_stats.max_hold_time, None);
+
+        let mut all_stats = idle.all_reader_stats();
+        all_stats.sort_by_key(|stats| stats.reads);
+        assert_eq!(all_stats, vec![idle_stats, stats]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn warn_held_across_publish() {
+        // Counts the events emitted on this thread.
+        #[derive(Default)]
+        struct CountEvents(std::sync::atomic::AtomicUsize);
+        impl tracing::Subscriber for CountEvents {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+                tracing::span::Id::from_u64(1)
+            }
+            fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+            fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+            fn event(&self, _: &tracing::Event<'_>) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            fn enter(&self, _: &tracing::span::Id) {}
+            fn exit(&self, _: &tracing::span::Id) {}
+        }
+
+        let subscriber = std::sync::Arc::new(CountEvents::default());
+        let num_events = || subscriber.0.load(std::sync::atomic::Ordering::Relaxed);
+        let table = AsLockHandle::new(0);
+        let reader = table.clone();
+        tracing::subscriber::with_default(std::sync::Arc::clone(&subscriber), || {
+            // Guards which aren't held across a publish aren't reported.
+            for _ in 0..1000 {
+                drop(reader.read());
+                table.write().update_tables_closure(|t| *t += 1);
+            }
+            assert_eq!(num_events(), 0);
+
+            for _ in 0..1000 {
+                let guard = reader.read();
+                table.write().update_tables_closure(|t| *t += 1);
+                drop(guard);
+            }
+        });
+        // With this many reads, some are all but certain to be sampled.
+        assert!(num_events() > 0);
+        assert!(num_events() < 1000);
+    }
+
+    #[cfg(all(feature = "unchecked-swap", not(feature = "safe-impl")))]
+    #[test]
+    fn swap_tables_now_unchecked() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let reader = table.clone();
+        let mut wg = table.write();
+        wg.update_tables(PushVec { value: 2 });
+
+        // Safety: neither handle is reading.
+        unsafe { wg.swap_tables_now_unchecked() };
+        assert_eq!(*reader.read(), vec![2]);
+        assert_eq!(*wg, vec![2]);
+        assert_eq!(wg.num_pending_ops(), 0);
+
+        wg.update_tables(PushVec { value: 3 });
+        assert_eq!(*reader.read(), vec![2]);
+        drop(wg);
+        assert_eq!(*reader.read(), vec![2, 3]);
+        table.write().assert_synced();
     }
 
     #[test]
@@ -690,6 +3689,76 @@ mod test {
         assert_eq!(*table.read(), vec![2, 3, 5]);
     }
 
+    #[test]
+    fn reused_reader_key() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+
+        // Leave a Reader blocking the standby table after the swap.
+        let table2 = table.clone();
+        let rg = table2.read();
+        table.write().update_tables(PushVec { value: 2 });
+        drop(rg);
+        drop(table2);
+
+        // The new Reader reuses the dropped Reader's key in the Slab. Its
+        // epoch must not be mistaken for the dropped Reader's, otherwise the
+        // writer would wait on it.
+        let table3 = table.clone();
+        table.write().update_tables(PushVec { value: 3 });
+        assert_eq!(*table3.read(), vec![2, 3]);
+    }
+
+    #[test]
+    fn reader_churn() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let table = table.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        let table = table.clone();
+                        let len = table.read().len();
+                        assert_ge!(table.read().len(), len);
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..200 {
+            table.write().update_tables(PushVec { value: i });
+        }
+        for handle in handles {
+            assert!(handle.join().is_ok());
+        }
+        assert_eq!(table.read().len(), 200);
+        assert_eq!(table.write().len(), 200);
+    }
+
+    #[cfg(feature = "leak-detect")]
+    #[test]
+    fn leak_report() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        table.set_leak_detect_timeout(std::time::Duration::ZERO);
+
+        // Leave a Reader blocking the standby table after the swap.
+        let table2 = table.clone();
+        let rg = table2.read();
+        table.write().update_tables(PushVec { value: 2 });
+
+        let report = {
+            let writer = table.writer.lock();
+            let readers = writer.readers.lock();
+            writer.leak_report(&readers)
+        };
+        assert!(report.contains("for 1 AsLockReadGuard(s)"), "{}", report);
+        if cfg!(debug_assertions) {
+            assert!(report.contains("leak_report"), "{}", report);
+        }
+
+        drop(rg);
+        table.write().update_tables(PushVec { value: 3 });
+    }
+
     #[test]
     fn mutable_ref() {
         let table = AsLockHandle::<Vec<i32>>::default();
@@ -711,6 +3780,31 @@ mod test {
         assert_ne!(*table.read(), *table.write());
     }
 
+    #[test]
+    fn assert_synced() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let mut wg = table.write();
+        wg.update_tables(PushVec { value: 2 });
+        wg.assert_synced();
+
+        // The updates have been published, and the guard remains usable.
+        assert_eq!(*table.read(), vec![2]);
+        wg.update_tables(PushVec { value: 3 });
+        wg.assert_synced();
+        drop(wg);
+        assert_eq!(*table.read(), vec![2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "active and standby tables have diverged")]
+    fn assert_synced_diverged() {
+        let table = AsLockHandle::<Vec<i32>>::default();
+        let mut wg = table.write();
+        wg.update_tables(PushVec { value: 2 });
+        *wg.update_tables(MutableRef {}) = 10;
+        wg.assert_synced();
+    }
+
     #[test]
     fn debug_str() {
         let table = AsLockHandle::<Vec<i32>>::default();
@@ -736,4 +3830,17 @@ mod test {
         );
         assert_eq!(format!("{:?}", table.read()), "[2]");
     }
+
+    #[test]
+    fn read_guard_cloned() {
+        let table = AsLockHandle::new(vec![1, 2]);
+        let rg = table.read();
+        assert_eq!(rg.cloned(), vec![1, 2]);
+        assert_eq!(rg.to_owned(), vec![1, 2]);
+
+        let table = AsLockHandle::new(3);
+        let rg = table.read();
+        let cow: std::borrow::Cow<'_, i32> = (&rg).into();
+        assert!(matches!(cow, std::borrow::Cow::Borrowed(3)));
+    }
 }
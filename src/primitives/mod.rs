@@ -1,2 +1,11 @@
+#[cfg(feature = "arc")]
+pub mod arc;
+pub mod changed;
+#[cfg(not(feature = "safe-impl"))]
+pub mod inline;
 pub mod lockless;
+pub mod publish_group;
+#[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+pub mod seqlock;
 pub mod sync;
+mod table;
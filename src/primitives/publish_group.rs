@@ -0,0 +1,356 @@
+use crate::primitives::sync::{AsLock, AsLockWriteGuard};
+use std::cell::RefCell;
+use std::fmt;
+
+/// Publishes the updates to multiple `sync::AsLock`s together.
+///
+/// Write guards taken through the group don't publish when dropped. Instead
+/// the group holds onto them, and publishes all of them when it is committed
+/// (or dropped). Since the group keeps each `AsLock` write locked until then,
+/// no other writer can interleave with the group's updates.
+///
+/// Note that this only coordinates the publish point; the tables are swapped
+/// one after another, so a reader may still see one table updated before
+/// another. There is also no rollback, all updates made through the group
+/// are published.
+///
+/// Like holding multiple locks, groups which take the same `AsLock`s in
+/// different orders can deadlock.
+///
+/// ```rust
+/// use active_standby::sync::collections::{AsHashMap, AsVec};
+/// use active_standby::sync::PublishGroup;
+///
+/// let names = AsVec::default();
+/// let ids = AsHashMap::default();
+///
+/// let group = PublishGroup::new();
+/// group.write(&names).push("alice");
+/// group.write(&ids).insert("alice", 0);
+/// assert!(names.read().is_empty());
+///
+/// group.commit();
+/// assert_eq!(names.read()[0], "alice");
+/// assert_eq!(ids.read()["alice"], 0);
+/// ```
+pub struct PublishGroup<'g> {
+    // Write guards which have been dropped, waiting to be published.
+    members: RefCell<Vec<Box<dyn Publish + 'g>>>,
+
+    // Addresses of the `AsLock`s in the group, to catch taking a second guard
+    // to the same `AsLock`, which would deadlock.
+    locks: RefCell<Vec<usize>>,
+}
+
+/// `AsLock`s which can join a `PublishGroup`. Implemented by `sync::AsLock` and
+/// the sync collections.
+pub trait GroupMember<'g> {
+    type WriteGuard: 'g;
+
+    #[doc(hidden)]
+    fn group_write(&'g self) -> Self::WriteGuard;
+
+    /// Publish without releasing the write lock. Only to be called by
+    /// `PublishGroup`.
+    #[doc(hidden)]
+    fn group_publish(guard: &mut Self::WriteGuard);
+}
+
+/// Write guard taken through a `PublishGroup`. Dereferences to the `AsLock`'s
+/// usual write guard. When dropped, the guard is handed back to the group
+/// instead of publishing.
+pub struct GroupWriteGuard<'p, 'g, L: GroupMember<'g> + 'g> {
+    // Only `None` while being handed back to the group.
+    guard: Option<L::WriteGuard>,
+    group: &'p PublishGroup<'g>,
+}
+
+trait Publish {
+    fn publish(&mut self);
+}
+
+struct Member<'g, L: GroupMember<'g> + 'g> {
+    guard: L::WriteGuard,
+}
+
+impl<'g, L: GroupMember<'g> + 'g> Publish for Member<'g, L> {
+    fn publish(&mut self) {
+        L::group_publish(&mut self.guard)
+    }
+}
+
+impl<'g, T> GroupMember<'g> for AsLock<T>
+where
+    T: 'g,
+{
+    type WriteGuard = AsLockWriteGuard<'g, T>;
+
+    fn group_write(&'g self) -> Self::WriteGuard {
+        self.write()
+    }
+
+    fn group_publish(guard: &mut Self::WriteGuard) {
+        guard.publish()
+    }
+}
+
+impl<'g> PublishGroup<'g> {
+    pub fn new() -> PublishGroup<'g> {
+        PublishGroup {
+            members: RefCell::new(vec![]),
+            locks: RefCell::new(vec![]),
+        }
+    }
+
+    /// Take a write guard to `lock`, which will be published along with the
+    /// rest of the group. `lock` stays write locked until the group is
+    /// committed.
+    ///
+    /// Panics if the group already holds a guard to `lock`.
+    pub fn write<'p, L>(&'p self, lock: &'g L) -> GroupWriteGuard<'p, 'g, L>
+    where
+        L: GroupMember<'g>,
+    {
+        let address = lock as *const L as usize;
+        {
+            let mut locks = self.locks.borrow_mut();
+            assert!(
+                !locks.contains(&address),
+                "PublishGroup already holds a write guard to this AsLock"
+            );
+            locks.push(address);
+        }
+
+        GroupWriteGuard {
+            guard: Some(lock.group_write()),
+            group: self,
+        }
+    }
+
+    /// The number of `AsLock`s in the group.
+    pub fn len(&self) -> usize {
+        self.locks.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Publish the updates made through the group to all of its `AsLock`s.
+    /// This is the same as dropping the group.
+    pub fn commit(self) {}
+}
+
+impl<'g> Default for PublishGroup<'g> {
+    fn default() -> PublishGroup<'g> {
+        PublishGroup::new()
+    }
+}
+
+impl<'g> Drop for PublishGroup<'g> {
+    fn drop(&mut self) {
+        let mut members = self.members.take();
+        // Swap all of the tables before releasing any of the write locks.
+        for member in members.iter_mut() {
+            member.publish();
+        }
+    }
+}
+
+impl<'g> fmt::Debug for PublishGroup<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PublishGroup")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<'p, 'g, L: GroupMember<'g> + 'g> GroupWriteGuard<'p, 'g, L> {
+    /// Make an update through this guard only if `check` accepts `other`,
+    /// another guard of the same group. This allows for checks across tables,
+    /// e.g. only inserting rows which refer to existing rows of another table.
+    ///
+    /// `check` sees the standby table of `other`, including the updates made
+    /// through the group which are not yet published. The group holds the
+    /// write lock of `other` until it commits, so no other writer can
+    /// invalidate the check before this update is published.
+    ///
+    /// Panics if `other` was taken through a different group.
+    pub fn update_if<'q, M, R, E>(
+        &mut self,
+        other: &GroupWriteGuard<'q, 'g, M>,
+        check: impl FnOnce(&M::WriteGuard) -> Result<(), E>,
+        update: impl FnOnce(&mut L::WriteGuard) -> R,
+    ) -> Result<R, E>
+    where
+        M: GroupMember<'g> + 'g,
+    {
+        assert!(
+            std::ptr::eq(self.group, other.group),
+            "update_if requires guards of the same PublishGroup"
+        );
+        check(other)?;
+        Ok(update(self))
+    }
+}
+
+impl<'p, 'g, L: GroupMember<'g> + 'g> std::ops::Deref for GroupWriteGuard<'p, 'g, L> {
+    type Target = L::WriteGuard;
+    fn deref(&self) -> &Self::Target {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl<'p, 'g, L: GroupMember<'g> + 'g> std::ops::DerefMut for GroupWriteGuard<'p, 'g, L> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl<'p, 'g, L: GroupMember<'g> + 'g> Drop for GroupWriteGuard<'p, 'g, L> {
+    fn drop(&mut self) {
+        if let Some(guard) = self.guard.take() {
+            self.group
+                .members
+                .borrow_mut()
+                .push(Box::new(Member::<L> { guard }));
+        }
+    }
+}
+
+impl<'p, 'g, L> fmt::Debug for GroupWriteGuard<'p, 'g, L>
+where
+    L: GroupMember<'g> + 'g,
+    L::WriteGuard: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupWriteGuard")
+            .field("guard", &**self)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn commit() {
+        let numbers = AsLock::<Vec<i32>>::default();
+        let words = AsLock::<Vec<&str>>::default();
+
+        let group = PublishGroup::new();
+        group
+            .write(&numbers)
+            .update_tables_closure(|vec| vec.push(1));
+        {
+            let mut wg = group.write(&words);
+            wg.update_tables_closure(|vec| vec.push("one"));
+            assert_eq!(**wg, vec!["one"]);
+        }
+        assert_eq!(group.len(), 2);
+        assert!(numbers.read().is_empty());
+        assert!(words.read().is_empty());
+
+        group.commit();
+        assert_eq!(*numbers.read(), vec![1]);
+        assert_eq!(*words.read(), vec!["one"]);
+        assert_eq!(*numbers.write(), vec![1]);
+        assert_eq!(*words.write(), vec!["one"]);
+    }
+
+    #[test]
+    fn drop_publishes() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        {
+            let group = PublishGroup::default();
+            group
+                .write(&aslock)
+                .update_tables_closure(|vec| vec.push(1));
+        }
+        assert_eq!(*aslock.read(), vec![1]);
+    }
+
+    #[test]
+    fn blocks_other_writers() {
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        let group = PublishGroup::new();
+        group
+            .write(&*aslock)
+            .update_tables_closure(|vec| vec.push(1));
+
+        let handle = {
+            let aslock = Arc::clone(&aslock);
+            thread::spawn(move || aslock.write().update_tables_closure(|vec| vec.push(2)))
+        };
+        thread::sleep(Duration::from_millis(10));
+        assert!(!handle.is_finished());
+
+        group.commit();
+        handle.join().unwrap();
+        assert_eq!(*aslock.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn update_if() {
+        let users = AsLock::<Vec<&str>>::default();
+        let orders = AsLock::<Vec<(&str, i32)>>::default();
+
+        let group = PublishGroup::new();
+        let mut users_wg = group.write(&users);
+        users_wg.update_tables_closure(|vec| vec.push("alice"));
+
+        let mut orders_wg = group.write(&orders);
+        let user_exists = |user| {
+            move |users: &AsLockWriteGuard<'_, Vec<&str>>| {
+                if users.contains(&user) {
+                    Ok(())
+                } else {
+                    Err(user)
+                }
+            }
+        };
+        assert_eq!(
+            orders_wg.update_if(&users_wg, user_exists("alice"), |orders| {
+                orders.update_tables_closure(|vec| vec.push(("alice", 1)))
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            orders_wg.update_if(&users_wg, user_exists("bob"), |orders| {
+                orders.update_tables_closure(|vec| vec.push(("bob", 2)))
+            }),
+            Err("bob")
+        );
+        drop(orders_wg);
+        drop(users_wg);
+
+        group.commit();
+        assert_eq!(*orders.read(), vec![("alice", 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "same PublishGroup")]
+    fn update_if_other_group() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        let other = AsLock::<Vec<i32>>::default();
+        let group = PublishGroup::new();
+        let other_group = PublishGroup::new();
+        let other_wg = other_group.write(&other);
+        let _ = group
+            .write(&aslock)
+            .update_if(&other_wg, |_| Ok::<_, ()>(()), |_| ());
+    }
+
+    #[test]
+    #[should_panic(expected = "already holds a write guard")]
+    fn duplicate_lock() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        let group = PublishGroup::new();
+        drop(group.write(&aslock));
+        drop(group.write(&aslock));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::types::*;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+
+/// Variant of `AsLock` for small `Copy` tables, where reading the active table
+/// doesn't lock it. Each table is guarded by a sequence number instead of an
+/// RwLock. Readers copy the active table out, and retry if the writer started
+/// writing to it during the copy.
+///
+/// Like `AsLock`, this is shared across threads via `Arc`. Since readers only
+/// use atomic loads, reads are close to the cost of the lockless variant. The
+/// tradeoffs are that `read` returns a copy of the table rather than a guard,
+/// and that readers which are slow to copy the table may have to retry if the
+/// writer is quick to write again.
+///
+/// Since the tables are `Copy`, the writer brings the standby table up to date
+/// by copying the active table instead of replaying the updates. Therefore
+/// updates are only applied once, via `UpdateTables::apply_first`.
+///
+/// To use the full `AsLock` API with seqlock reads, see
+/// `AsLock::new_seqlock`.
+///
+/// Requires the `seqlock` feature. A reader's copy can race with the writer
+/// writing to the same table, which the sequence number detects, after which
+/// the copy is discarded. This is the usual seqlock pattern, but under the
+/// Rust memory model such a race is undefined behavior, even with the copy
+/// made by `read_volatile` and never used. It is sound in practice, as for
+/// other seqlocks, which is why the feature is opt in.
+pub struct AsSeqLock<T> {
+    tables: [SeqTable<T>; 2],
+    active_index: AtomicUsize,
+
+    // Used to guarantee that `write` is single threaded.
+    writer: Mutex<()>,
+}
+
+struct SeqTable<T> {
+    // Odd while the writer is writing to the table.
+    seq: AtomicUsize,
+    table: UnsafeCell<T>,
+}
+
+// Readers only ever copy the tables out, and the writer is single threaded.
+unsafe impl<T: Copy + Send> Sync for AsSeqLock<T> {}
+
+/// Guard used for updating the tables.
+pub struct AsSeqLockWriteGuard<'w, T> {
+    standby_table: &'w SeqTable<T>,
+    active_index: &'w AtomicUsize,
+
+    // Index of the standby table, which becomes the active table on drop.
+    standby_index: usize,
+
+    _writer: MutexGuard<'w, ()>,
+}
+
+impl<T: Copy> AsSeqLock<T> {
+    pub fn new(t: T) -> AsSeqLock<T> {
+        let table = |t| SeqTable {
+            seq: AtomicUsize::new(0),
+            table: UnsafeCell::new(t),
+        };
+        AsSeqLock {
+            tables: [table(t), table(t)],
+            active_index: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Copy out the active table. Doesn't take any locks, but may spin while
+    /// the writer is writing to the table being copied.
+    pub fn read(&self) -> T {
+        loop {
+            let table = &self.tables[self.active_index.load(Ordering::Acquire)];
+            let seq = table.seq.load(Ordering::Acquire);
+            if seq.is_multiple_of(2) {
+                // Safety: The copy may race with the writer, in which case it
+                // may be torn. It is read as `MaybeUninit` so that a torn copy
+                // is never treated as a `T`, and is only used once the sequence
+                // number shows that the writer didn't touch the table.
+                let copy =
+                    unsafe { std::ptr::read_volatile(table.table.get() as *const MaybeUninit<T>) };
+                fence(Ordering::Acquire);
+                if table.seq.load(Ordering::Relaxed) == seq {
+                    // Safety: The table wasn't written to during the copy.
+                    return unsafe { copy.assume_init() };
+                }
+            }
+            spin_loop();
+        }
+    }
+
+    /// Create an AsSeqLockWriteGuard to allow users to update the data. There
+    /// will only be 1 AsSeqLockWriteGuard at a time.
+    ///
+    /// Waits for other AsSeqLockWriteGuards, but never for readers.
+    pub fn write(&self) -> AsSeqLockWriteGuard<'_, T> {
+        let writer = self.writer.lock();
+
+        // Only the writer changes the active index.
+        let active_index = self.active_index.load(Ordering::Relaxed);
+        let standby_index = 1 - active_index;
+        let standby_table = &self.tables[standby_index];
+
+        // Readers which loaded the active index before the last swap may still
+        // be copying the standby table. Mark it as being written, so that they
+        // retry.
+        let seq = standby_table.seq.load(Ordering::Relaxed);
+        standby_table.seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        // Safety: Only the writer writes to the tables, and readers which race
+        // with this discard their copy.
+        unsafe {
+            *standby_table.table.get() = *self.tables[active_index].table.get();
+        }
+
+        AsSeqLockWriteGuard {
+            standby_table,
+            active_index: &self.active_index,
+            standby_index,
+            _writer: writer,
+        }
+    }
+}
+
+/// Copy of an `AsLock`'s active table, which `read` copies out instead of
+/// locking the table. See `AsLock::new_seqlock`. Type erased, since `AsLock`
+/// doesn't require `T: Copy`.
+pub(crate) trait SeqReads<T>: Send + Sync {
+    /// The table and its generation.
+    fn read(&self) -> (T, u64);
+
+    fn store(&self, table: &T, generation: u64);
+}
+
+impl<T: Copy + Send> SeqReads<T> for AsSeqLock<(T, u64)> {
+    fn read(&self) -> (T, u64) {
+        AsSeqLock::read(self)
+    }
+
+    fn store(&self, table: &T, generation: u64) {
+        let published = (*table, generation);
+        self.write()
+            .update_tables_closure(|table| *table = published);
+    }
+}
+
+impl<T: Copy + Default> Default for AsSeqLock<T> {
+    fn default() -> AsSeqLock<T> {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for AsSeqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsSeqLock")
+            .field("active_table", &self.read())
+            .finish()
+    }
+}
+
+impl<T: Copy> crate::Snapshot<T> for AsSeqLock<T> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+}
+
+impl<'w, T> AsSeqLockWriteGuard<'w, T> {
+    fn table(&mut self) -> &mut T {
+        // Safety: The guard is the only writer, and readers don't create
+        // references to the tables.
+        unsafe { &mut *self.standby_table.table.get() }
+    }
+
+    /// Takes an update which will change the state of the underlying data.
+    /// Only `apply_first` is called, since the other table is brought up to
+    /// date by copying this one.
+    pub fn update_tables<'a, R>(&'a mut self, mut update: impl UpdateTables<'a, T, R>) -> R {
+        update.apply_first(self.table())
+    }
+
+    /// Like `update_tables` but allows the user to pass a closure for
+    /// convenience.
+    pub fn update_tables_closure<R>(&mut self, update: impl FnOnce(&mut T) -> R) -> R {
+        update(self.table())
+    }
+}
+
+impl<'w, T> Drop for AsSeqLockWriteGuard<'w, T> {
+    fn drop(&mut self) {
+        self.standby_table.seq.fetch_add(1, Ordering::Release);
+        self.active_index
+            .store(self.standby_index, Ordering::Release);
+    }
+}
+
+impl<'w, T> std::ops::Deref for AsSeqLockWriteGuard<'w, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: The guard is the only writer.
+        unsafe { &*self.standby_table.table.get() }
+    }
+}
+
+impl<'w, T: fmt::Debug> fmt::Debug for AsSeqLockWriteGuard<'w, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::ops::Deref;
+        f.debug_struct("AsSeqLockWriteGuard")
+            .field("standby_table", self.deref())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    struct Add {
+        value: i32,
+    }
+    impl<'a> UpdateTables<'a, [i32; 2], ()> for Add {
+        fn apply_first(&mut self, table: &'a mut [i32; 2]) {
+            table[0] += self.value;
+            table[1] += self.value;
+        }
+        fn apply_second(self, _table: &mut [i32; 2]) {
+            unreachable!("the standby table is copied instead");
+        }
+    }
+
+    #[test]
+    fn publish_update() {
+        let aslock = Arc::new(AsSeqLock::<[i32; 2]>::default());
+        assert_eq!(aslock.read(), [0, 0]);
+
+        {
+            let mut wg = aslock.write();
+            wg.update_tables(Add { value: 2 });
+            assert_eq!(*wg, [2, 2]);
+            {
+                let aslock = Arc::clone(&aslock);
+                assert!(thread::spawn(move || {
+                    assert_eq!(aslock.read(), [0, 0]);
+                })
+                .join()
+                .is_ok());
+            }
+        }
+        assert_eq!(aslock.read(), [2, 2]);
+
+        // The standby table is brought up to date before the next update.
+        aslock.write().update_tables_closure(|table| table[1] = 5);
+        assert_eq!(aslock.read(), [2, 5]);
+    }
+
+    #[test]
+    fn multi_thread() {
+        let aslock = Arc::new(AsSeqLock::<[i32; 2]>::default());
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let aslock = Arc::clone(&aslock);
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while last < 1000 {
+                        let [a, b] = aslock.read();
+                        // Copies are never torn or stale.
+                        assert_eq!(a, b);
+                        assert!(a >= last);
+                        last = a;
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..1000 {
+            aslock.write().update_tables(Add { value: 1 });
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(aslock.read(), [1000, 1000]);
+    }
+}
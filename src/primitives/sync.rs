@@ -1,11 +1,11 @@
+use crate::primitives::changed::{Changed, PublishSignal, SwapListener};
 use crate::types::*;
 use std::fmt;
-use std::mem::ManuallyDrop;
 
 // When `update_tables` is called, the `standby_table` is updated immediately.
 // We then store the update ops to be replayed on the other table.
-type OpsToReplay<T> = Mutex<Vec<Box<dyn FnOnce(&mut T) + Send>>>;
-type OpsToReplayGuard<'w, T> = MutexGuard<'w, Vec<Box<dyn FnOnce(&mut T) + Send>>>;
+type OpsToReplay<T> = Mutex<OpLog<T>>;
+type OpsToReplayGuard<'w, T> = MutexGuard<'w, OpLog<T>>;
 
 /// Struct for holding tables that can be interacted with like an RwLock,
 /// including being shared across threads/tasks via Arc (as opposed to the
@@ -13,11 +13,11 @@ type OpsToReplayGuard<'w, T> = MutexGuard<'w, Vec<Box<dyn FnOnce(&mut T) + Send>
 pub struct AsLock<T> {
     // The underlying tables. These tables will be utilized directly both for
     // writing and reading. The RwLock guarantees that this will be safe; in
-    // practice blocking writes when there are pre-existing read guards. The use
-    // of AtomicPtr allows this struct to be `Sync`, so that when a `AsLockWriteGuard`
-    // is dropped, the tables can be swapped without ever blocking reads.
-    active_table: AtomicPtr<RwLock<T>>,
-    standby_table: AtomicPtr<RwLock<T>>,
+    // practice blocking writes when there are pre-existing read guards. The
+    // active table is tracked by index, so that when a `AsLockWriteGuard` is
+    // dropped, the tables can be swapped without ever blocking reads.
+    tables: Box<[RwLock<T>; 2]>,
+    active_index: AtomicUsize,
 
     /// Log of operations to be performed on the second table. This gets played
     /// on the standby table when creating an AsLockWriteGuard, as opposed to when
@@ -28,52 +28,300 @@ pub struct AsLock<T> {
     /// This mutex is used to guarantee that `write` is single threaded, and so
     /// locking it must be done before any operation other that `read`.
     ops_to_replay: OpsToReplay<T>,
+
+    // Wakes `Changed` futures on publish.
+    publish_signal: std::sync::Arc<PublishSignal>,
+
+    // The number of publishes as of when each table was last made active, for
+    // `AsLockReadGuard::generation`. Only set while holding the write lock on
+    // the table, so readers see the generation of the contents they read.
+    generations: [std::sync::atomic::AtomicU64; 2],
+
+    // Limit on `ops_to_replay` enforced by `try_update_tables`, or usize::MAX
+    // for no limit.
+    max_pending_ops: std::sync::atomic::AtomicUsize,
+
+    // Whether the standby table has been dropped, and writes go to the active
+    // table directly. Only changed while holding `ops_to_replay`.
+    low_memory: std::sync::atomic::AtomicBool,
+
+    // Replay cost of the updates applied to the standby table by guards from
+    // `write_deferred`, which haven't been published yet, or usize::MAX if
+    // there are none. Only accessed while holding `ops_to_replay`.
+    unpublished_cost: std::sync::atomic::AtomicUsize,
+
+    // Time writers spent waiting for readers to release a table.
+    wait_stats: crate::inspect::WaitStats,
+
+    // Longest sleep between the tries of `write_async`.
+    #[cfg(feature = "tokio")]
+    max_write_backoff: std::time::Duration,
+
+    // Counts and replay times of the ops, by label.
+    #[cfg(feature = "op-stats")]
+    op_stats: crate::op_stats::OpStatsRecorder,
+
+    // Copy of the active table which readers copy out instead of locking a
+    // table. Set by `new_seqlock`.
+    #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+    seq_reads: Option<Box<dyn crate::primitives::seqlock::SeqReads<T>>>,
 }
 
 /// Guard used for updating the tables.
 pub struct AsLockWriteGuard<'w, T> {
-    // Pointers to the tables. Used to swap them on drop.
-    active_table: &'w AtomicPtr<RwLock<T>>,
-    standby_table: &'w AtomicPtr<RwLock<T>>,
-
-    // Guard used to mutably access `standby_table` without constantly having to
-    // load the atomic pointer and write lock the table. This is wrapped inside
-    // of `ManuallyDrop` to guarantee that the table is unlocked before swapping
-    // the active and standby tables. This is needed to gurantee that reads are
+    // The tables and the index of the active one. Used to swap them on drop.
+    tables: &'w [RwLock<T>; 2],
+    active_index: &'w AtomicUsize,
+
+    // Guard used to mutably access the standby table without constantly having
+    // to load the active index and write lock the table. This is wrapped inside
+    // of `Option` to guarantee that the table is unlocked before swapping the
+    // active and standby tables. This is needed to gurantee that reads are
     // never blocked.
     //
-    // This introduces the risk of a deadlock though if we forget to release
-    // `guard`.
-    guard: ManuallyDrop<RwLockWriteGuard<'w, T>>,
+    // This is only `None` while swapping the tables and replaying ops.
+    guard: Option<RwLockWriteGuard<'w, T>>,
 
     // Hold onto updates for replay when the next AsLockWriteGuard is created. This
     // Mutex also prevents any other thread from utilizing the `AsLock`, other
     // than calls to `read`.
     ops_to_replay: OpsToReplayGuard<'w, T>,
+
+    // Sum of the weights of the updates in `ops_to_replay`.
+    replay_cost: usize,
+
+    publish_signal: &'w PublishSignal,
+    generations: &'w [std::sync::atomic::AtomicU64; 2],
+
+    // Copied from the AsLock when the guard is created.
+    max_pending_ops: usize,
+    low_memory: bool,
+
+    unpublished_cost: &'w std::sync::atomic::AtomicUsize,
+    wait_stats: &'w crate::inspect::WaitStats,
+
+    // Whether dropping the guard leaves the updates unpublished. See
+    // `AsLock::write_deferred`.
+    deferred: bool,
+
+    #[cfg(feature = "op-stats")]
+    op_stats: &'w crate::op_stats::OpStatsRecorder,
+
+    // Keys of the updates in `ops_to_replay`, for `update_tables_keyed`.
+    op_keys: OpKeys,
+
+    // Updated with each published table. See `AsLock::new_seqlock`.
+    #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+    seq_reads: Option<&'w dyn crate::primitives::seqlock::SeqReads<T>>,
+
+    // Values to drop once the tables are swapped. Declared after
+    // `ops_to_replay` so that they are dropped after the lock is released.
+    deferred_drops: Vec<Box<dyn Send>>,
+}
+
+/// Debug check that `read` and `write` aren't called on an AsLock from within
+/// an update being applied to the same AsLock. `write` would deadlock waiting
+/// on the guard which is applying the update, and `read` can end up waiting on
+/// the standby table which that guard holds.
+#[cfg(debug_assertions)]
+mod reentrancy {
+    use std::cell::RefCell;
+
+    thread_local! {
+        // Identifies the AsLocks whose updates are being applied on this
+        // thread.
+        static APPLYING: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+    }
+
+    pub(super) fn check(id: usize, method: &str) {
+        if APPLYING.with(|applying| applying.borrow().contains(&id)) {
+            panic!(
+                "AsLock::{} called from within an update applied to the same AsLock, \
+                 which can deadlock",
+                method
+            );
+        }
+    }
+
+    /// Marks the AsLock as applying an update on this thread until dropped.
+    pub(super) struct Applying(usize);
+
+    impl Applying {
+        pub(super) fn new(id: usize) -> Applying {
+            APPLYING.with(|applying| applying.borrow_mut().push(id));
+            Applying(id)
+        }
+    }
+
+    impl Drop for Applying {
+        fn drop(&mut self) {
+            APPLYING.with(|applying| {
+                let mut applying = applying.borrow_mut();
+                let index = applying.iter().rposition(|id| *id == self.0).unwrap();
+                applying.remove(index);
+            });
+        }
+    }
+}
+
+#[cfg(not(debug_assertions))]
+mod reentrancy {
+    pub(super) fn check(_id: usize, _method: &str) {}
+
+    pub(super) struct Applying;
+
+    impl Applying {
+        pub(super) fn new(_id: usize) -> Applying {
+            Applying
+        }
+    }
 }
 
-// Define AsLockReadGuard locally so that the type names are consistent; across
-// lockless & sync, as well as internally (AsLockWriteGuard & RwLockAsLockReadGuard
-// seem unwieldy).
-pub type AsLockReadGuard<'r, T> = RwLockReadGuard<'r, T>;
+// Identifies an AsLock for `reentrancy` by the address of its tables, which
+// the guard can reach as well.
+fn tables_id<T>(tables: &[RwLock<T>; 2]) -> usize {
+    tables as *const [RwLock<T>; 2] as usize
+}
+
+/// Guard used for obtaining const access to the active table. Wraps the
+/// underlying RwLock's guard so that the type names are consistent across
+/// lockless & sync. Like the RwLock's guard, it is `Sync` when `T: Sync`, so
+/// a single read of the table can be shared by scoped threads.
+pub struct AsLockReadGuard<'r, T> {
+    guard: ReadGuardInner<'r, T>,
+    generation: u64,
+}
+
+enum ReadGuardInner<'r, T> {
+    Locked(RwLockReadGuard<'r, T>),
+    // Copied out of `AsLock::seq_reads`.
+    #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+    Copied(T),
+}
 
 impl<T> AsLock<T> {
     /// Create an `AsLock`. t1 & t2 must be identical; this is left to the
     /// caller to enforce.
     pub fn from_identical(t1: T, t2: T) -> AsLock<T> {
+        Self::from_identical_with(t1, t2, crate::Options::default())
+    }
+
+    /// Create an `AsLock` from two calls to `f`, for tables which can't be
+    /// cloned but can be built again, e.g. from a config. The calls must
+    /// return identical tables; this is left to the caller to enforce.
+    pub fn from_fn(mut f: impl FnMut() -> T) -> AsLock<T> {
+        let t1 = f();
+        Self::from_identical(t1, f())
+    }
+
+    /// Like `from_identical`, but configured by `options`.
+    pub fn from_identical_with(t1: T, t2: T, options: crate::Options) -> AsLock<T> {
         AsLock {
-            active_table: AtomicPtr::new(Box::into_raw(Box::new(RwLock::new(t1)))),
-            standby_table: AtomicPtr::new(Box::into_raw(Box::new(RwLock::new(t2)))),
+            tables: Box::new([RwLock::new(t1), RwLock::new(t2)]),
+            active_index: AtomicUsize::new(0),
             ops_to_replay: Mutex::default(),
+            publish_signal: std::sync::Arc::default(),
+            generations: Default::default(),
+            max_pending_ops: std::sync::atomic::AtomicUsize::new(
+                options.max_pending_ops.unwrap_or(usize::MAX),
+            ),
+            low_memory: std::sync::atomic::AtomicBool::new(options.low_memory_mode),
+            unpublished_cost: std::sync::atomic::AtomicUsize::new(usize::MAX),
+            wait_stats: Default::default(),
+            #[cfg(feature = "tokio")]
+            max_write_backoff: options.max_write_backoff,
+            #[cfg(feature = "op-stats")]
+            op_stats: Default::default(),
+            #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+            seq_reads: None,
         }
     }
 
     pub fn read(&self) -> AsLockReadGuard<'_, T> {
-        // SAFETY: The safety issue here is active_table being an invalid ptr.
-        // This should never happen since standby/active table are created on
-        // creation and only dropped when AsLock is dropped. In between they are
-        // swapped, but that shouldn't affect their valididty as pointers.
-        unsafe { &*self.active_table.load(Ordering::SeqCst) }.read()
+        if let Some(guard) = self.seq_read() {
+            return guard;
+        }
+        reentrancy::check(tables_id(&self.tables), "read");
+
+        let index = self.active_index.load(Ordering::SeqCst);
+        self.lock_active(index, |table| Some(table.read())).unwrap()
+    }
+
+    /// Like `read`, but returns `None` instead of waiting if the active table
+    /// is locked. Reads only contend with a writer in low memory mode, or
+    /// briefly while racing with a swap, so this is for code paths which must
+    /// never stall.
+    pub fn try_read(&self) -> Option<AsLockReadGuard<'_, T>> {
+        if let Some(guard) = self.seq_read() {
+            return Some(guard);
+        }
+        let index = self.active_index.load(Ordering::SeqCst);
+        self.lock_active(index, RwLock::try_read)
+    }
+
+    /// Like `try_read`, but waits up to `timeout` for the active table.
+    pub fn read_timeout(&self, timeout: std::time::Duration) -> Option<AsLockReadGuard<'_, T>> {
+        if let Some(guard) = self.seq_read() {
+            return Some(guard);
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        let index = self.active_index.load(Ordering::SeqCst);
+        self.lock_active(index, |table| {
+            table.try_read_for(deadline.saturating_duration_since(std::time::Instant::now()))
+        })
+    }
+
+    /// Copy the table out of `seq_reads`, if set by `new_seqlock`, which
+    /// never waits on the writer.
+    #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+    fn seq_read(&self) -> Option<AsLockReadGuard<'_, T>> {
+        let (table, generation) = self.seq_reads.as_ref()?.read();
+        Some(AsLockReadGuard {
+            guard: ReadGuardInner::Copied(table),
+            generation,
+        })
+    }
+
+    #[cfg(not(all(feature = "seqlock", not(feature = "safe-impl"))))]
+    fn seq_read(&self) -> Option<AsLockReadGuard<'_, T>> {
+        None
+    }
+
+    /// Read lock `tables[index]` with `lock`, where `index` was the active
+    /// table when it was loaded.
+    ///
+    /// If this races with a swap, `index` may be the standby table by the
+    /// time it is locked. The writer releases the standby table without
+    /// publishing it, e.g. partway through a budgeted replay, after a
+    /// deferred write, or when resetting it in low memory mode, so the lock
+    /// is only kept if the table is still active once locked. Otherwise it
+    /// retries on the new active table.
+    fn lock_active<'r>(
+        &'r self,
+        mut index: usize,
+        mut lock: impl FnMut(&'r RwLock<T>) -> Option<RwLockReadGuard<'r, T>>,
+    ) -> Option<AsLockReadGuard<'r, T>> {
+        loop {
+            let guard = lock(&self.tables[index])?;
+            let active_index = self.active_index.load(Ordering::SeqCst);
+            if active_index == index {
+                return Some(self.read_guard(index, guard));
+            }
+            drop(guard);
+            index = active_index;
+        }
+    }
+
+    fn read_guard<'r>(
+        &'r self,
+        index: usize,
+        guard: RwLockReadGuard<'r, T>,
+    ) -> AsLockReadGuard<'r, T> {
+        AsLockReadGuard {
+            guard: ReadGuardInner::Locked(guard),
+            // Read while locking the table, so it matches the contents.
+            generation: self.generations[index].load(Ordering::SeqCst),
+        }
     }
 
     /// Create an AsLockWriteGuard to allow users to update the the data. There will
@@ -87,40 +335,154 @@ impl<T> AsLock<T> {
     /// 3. Replaying all of the updates that were applied to the last
     ///    AsLockWriteGuard.
     pub fn write(&self) -> AsLockWriteGuard<'_, T> {
+        reentrancy::check(tables_id(&self.tables), "write");
+
         // Done first to ensure that writes are single threaded.
-        let mut ops_to_replay = self.ops_to_replay.lock();
+        let ops_to_replay = self.ops_to_replay.lock();
+
+        let mut wg = self.new_write_guard(ops_to_replay);
+        wg.sync_standby_table();
+        wg
+    }
+
+    /// Like `write`, but replays at most `max_ops` of the last guard's
+    /// updates, bounding how long this holds the writer for a long backlog of
+    /// updates. Returns `None` if updates remain to be replayed, in which case
+    /// they stay queued for the next call. Readers are unaffected, since they
+    /// never keep a lock on the half replayed standby table.
+    ///
+    /// Callers which must make progress can fall back to `write_full`, which
+    /// finishes the replay regardless of its length. Until then, `read` keeps
+    /// returning the last published table.
+    pub fn write_budgeted(&self, max_ops: usize) -> Option<AsLockWriteGuard<'_, T>> {
+        reentrancy::check(tables_id(&self.tables), "write");
+
+        let ops_to_replay = self.ops_to_replay.lock();
+        let mut wg = self.new_write_guard(ops_to_replay);
+        // Without a table guard, dropping `wg` doesn't publish.
+        wg.sync_standby_table_budgeted(max_ops).then_some(wg)
+    }
 
-        // Grab the standby table and obtain a `AsLockWriteGuard` to it. This may hang
-        // on `AsLockReadGuard`s which exist from before the last swap.
-        //
-        // SAFETY: The safety issue here is standby_table being an invalid ptr.
-        // This should never happen since standby/active table are created on
-        // creation and only dropped when `AsLock` is dropped. In between they
-        // are swapped, but that shouldn't affect their valididty as pointers.
-        let mut wg = unsafe { &*self.standby_table.load(Ordering::SeqCst) }.write();
+    /// Like `write_budgeted`, but the budget is the time spent replaying
+    /// rather than a number of updates. The deadline is checked between
+    /// updates, so a single slow update can overrun it. Waiting for
+    /// AsLockReadGuards to leave the standby table counts against the budget,
+    /// but isn't cut short by it.
+    pub fn write_timed(&self, budget: std::time::Duration) -> Option<AsLockWriteGuard<'_, T>> {
+        reentrancy::check(tables_id(&self.tables), "write");
 
-        // Replay all ops on the standby table.
-        for op in ops_to_replay.drain(..) {
-            op(&mut wg);
+        let start = std::time::Instant::now();
+        let ops_to_replay = self.ops_to_replay.lock();
+        let mut wg = self.new_write_guard(ops_to_replay);
+        while !wg.sync_standby_table_budgeted(1) {
+            if start.elapsed() >= budget {
+                return None;
+            }
         }
-        ops_to_replay.clear();
+        Some(wg)
+    }
 
-        AsLockWriteGuard {
-            guard: ManuallyDrop::new(wg),
-            active_table: &self.active_table,
-            standby_table: &self.standby_table,
-            ops_to_replay,
+    /// Same as `write`, which always finishes replaying the updates queued by
+    /// `write_budgeted` and `write_timed`. Named for forcing the replay to
+    /// complete, next to the budgeted calls.
+    pub fn write_full(&self) -> AsLockWriteGuard<'_, T> {
+        self.write()
+    }
+
+    /// Like `write`, but dropping the guard doesn't publish its updates.
+    /// Readers keep seeing the table from before them until `publish` is
+    /// called, or until a guard from `write` is dropped, which publishes them
+    /// along with its own. This batches many small writes into a single swap,
+    /// skipping the wait for readers and the replay of updates between them.
+    /// In low memory mode, where updates are made to the active table, the
+    /// guard behaves like one from `write`.
+    pub fn write_deferred(&self) -> AsLockWriteGuard<'_, T> {
+        let mut wg = self.write();
+        wg.deferred = true;
+        wg
+    }
+
+    /// Publish the updates left unpublished by guards from `write_deferred`.
+    /// Returns whether there were any. Waits for the write lock like `write`,
+    /// but not for readers.
+    pub fn publish(&self) -> bool {
+        let ops_to_replay = self.ops_to_replay.lock();
+        if self.unpublished_cost.load(Ordering::Relaxed) == usize::MAX {
+            return false;
         }
+        // Syncing only locks the standby table, which is already up to date.
+        let mut wg = self.new_write_guard(ops_to_replay);
+        wg.sync_standby_table();
+        true
     }
-}
 
-impl<T> Drop for AsLock<T> {
-    fn drop(&mut self) {
-        // SAFETY: Tables are created on class creation, and while swapped, they
-        // are never changed to an invalid state during the life of `AsLock`.
-        unsafe {
-            let _active_table = Box::from_raw(self.active_table.load(Ordering::SeqCst));
-            let _standby_table = Box::from_raw(self.standby_table.load(Ordering::SeqCst));
+    /// Like `write`, but returns `None` instead of waiting if another
+    /// AsLockWriteGuard exists, or if AsLockReadGuards still point to the
+    /// standby table. In low memory mode, this is any AsLockReadGuard.
+    pub fn try_write(&self) -> Option<AsLockWriteGuard<'_, T>> {
+        reentrancy::check(tables_id(&self.tables), "write");
+
+        let ops_to_replay = self.ops_to_replay.try_lock()?;
+        let mut wg = self.new_write_guard(ops_to_replay);
+        wg.try_sync_standby_table().then_some(wg)
+    }
+
+    /// Like `write`, but sleeps on the tokio timer instead of blocking the
+    /// thread while another AsLockWriteGuard exists, or while
+    /// AsLockReadGuards still point to the standby table. Nothing is locked
+    /// across the sleeps, so the future is `Send`. Requires the `tokio`
+    /// feature, and a runtime with the time driver enabled.
+    ///
+    /// There is no notification for readers leaving the standby table, so
+    /// this retries with an exponential backoff, of up to 10ms between tries
+    /// by default. See `Options::max_write_backoff`.
+    /// Only the waiting is async; replaying the last guard's updates still
+    /// runs on the polling thread. Like any lock guard, the returned
+    /// AsLockWriteGuard shouldn't be held across an `.await`.
+    #[cfg(feature = "tokio")]
+    pub async fn write_async(&self) -> AsLockWriteGuard<'_, T> {
+        let mut backoff = std::time::Duration::from_micros(100);
+        loop {
+            if let Some(wg) = self.try_write() {
+                return wg;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_write_backoff);
+        }
+    }
+
+    /// The guard doesn't hold a table until it is synced.
+    fn new_write_guard<'w>(
+        &'w self,
+        ops_to_replay: OpsToReplayGuard<'w, T>,
+    ) -> AsLockWriteGuard<'w, T> {
+        let unpublished_cost = self.unpublished_cost.load(Ordering::Relaxed);
+        AsLockWriteGuard {
+            guard: None,
+            tables: &self.tables,
+            active_index: &self.active_index,
+            ops_to_replay,
+            // Picks up the cost of unpublished updates from deferred guards.
+            replay_cost: if unpublished_cost == usize::MAX {
+                0
+            } else {
+                unpublished_cost
+            },
+            publish_signal: &self.publish_signal,
+            generations: &self.generations,
+            max_pending_ops: self
+                .max_pending_ops
+                .load(std::sync::atomic::Ordering::Relaxed),
+            low_memory: self.low_memory.load(std::sync::atomic::Ordering::Relaxed),
+            unpublished_cost: &self.unpublished_cost,
+            wait_stats: &self.wait_stats,
+            deferred: false,
+            op_keys: OpKeys::default(),
+            deferred_drops: vec![],
+            #[cfg(feature = "op-stats")]
+            op_stats: &self.op_stats,
+            #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+            seq_reads: self.seq_reads.as_deref(),
         }
     }
 }
@@ -132,230 +494,1966 @@ where
     pub fn new(t: T) -> AsLock<T> {
         Self::from_identical(t.clone(), t)
     }
-}
 
-impl<T> Default for AsLock<T>
-where
-    T: Default,
-{
-    fn default() -> AsLock<T> {
-        Self::from_identical(T::default(), T::default())
+    /// Like `new`, but configured by `options`.
+    pub fn new_with(t: T, options: crate::Options) -> AsLock<T> {
+        Self::from_identical_with(t.clone(), t, options)
+    }
+}
+
+#[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+impl<T> AsLock<T>
+where
+    T: Copy + Send + 'static,
+{
+    /// Like `new`, but for small `Copy` tables, reads copy the active table out
+    /// from under a sequence number instead of read locking it, like
+    /// `AsSeqLock`. This brings the cost of `read` close to the lockless
+    /// flavor, and reads never wait on the writer, even in low memory mode.
+    /// The rest of the API is unchanged, and the returned guards hold the
+    /// copy. Requires the `seqlock` feature; see `AsSeqLock` for its caveat.
+    pub fn new_seqlock(t: T) -> AsLock<T> {
+        let mut aslock = Self::new(t);
+        aslock.seq_reads = Some(Box::new(crate::primitives::seqlock::AsSeqLock::new((t, 0))));
+        aslock
+    }
+}
+
+impl<T> AsLock<T>
+where
+    T: ParallelClone,
+{
+    /// Like `new`, but the second table is cloned using multiple threads,
+    /// which is faster for large tables. See `ParallelClone`.
+    pub fn new_parallel(t: T) -> AsLock<T> {
+        Self::from_identical(t.par_clone(), t)
+    }
+}
+
+impl<T> AsLock<T> {
+    /// Returns whether `a` and `b` are the same `AsLock`, such as when both
+    /// are reached through clones of the same `Arc<AsLock>`.
+    pub fn same_table(a: &AsLock<T>, b: &AsLock<T>) -> bool {
+        std::ptr::eq(a, b)
+    }
+
+    /// Future which resolves once the table is next published. See
+    /// `Changed`.
+    pub fn changed(&self) -> Changed {
+        self.publish_signal.changed()
+    }
+
+    /// Listen for the publishes of the table from now on. See
+    /// `SwapListener`.
+    pub fn subscribe(&self) -> SwapListener {
+        self.publish_signal.subscribe()
+    }
+
+    /// See `lockless::AsLockHandle::changes`.
+    #[cfg(feature = "stream")]
+    pub fn changes(&self) -> SwapListener {
+        self.subscribe()
+    }
+
+    /// Limit how many updates a single AsLockWriteGuard can hold through
+    /// `try_update_tables`, or remove the limit with `None`. Applies to guards
+    /// created after this call. See `AsLockWriteGuard::try_update_tables`.
+    pub fn set_max_pending_ops(&self, max_pending_ops: Option<usize>) {
+        self.max_pending_ops.store(
+            max_pending_ops.unwrap_or(usize::MAX),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+    }
+
+    /// Register `callback` to be called with the version of the table before
+    /// and after each swap, where the version counts the publishes since the
+    /// table was created. It is called by the AsLockWriteGuard which publishes,
+    /// once readers see the new active table but before the write lock is
+    /// released, so it is done before the next `write` returns. Meant for
+    /// keeping external state, such as an index, in lockstep with publishes.
+    ///
+    /// Callbacks run in the order they were registered, and can't be removed.
+    /// They must not call `on_swap` or `write` on this table.
+    pub fn on_swap(&self, callback: impl FnMut(u64, u64) + Send + 'static) {
+        self.publish_signal.on_swap(Box::new(callback));
+    }
+
+    /// Whether the table is in low memory mode. See `set_low_memory_mode`.
+    pub fn is_low_memory_mode(&self) -> bool {
+        self.low_memory.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+impl<T> AsLock<T>
+where
+    T: Clone + Default,
+{
+    /// Trade read latency for memory, e.g. while the process is under memory
+    /// pressure. Enabling low memory mode drops the standby table, replacing
+    /// it with `T::default()`, after which the AsLock behaves like a plain
+    /// RwLock: AsLockWriteGuards update the active table in place, and reads
+    /// block while a write guard is held. Disabling it rebuilds the standby
+    /// table as a clone of the active one, and writes no longer block reads.
+    ///
+    /// Changing the mode waits for the write lock, like `write`. In low memory
+    /// mode, calling `read` on a thread which holds an AsLockWriteGuard to the
+    /// same table deadlocks.
+    pub fn set_low_memory_mode(&self, enabled: bool) {
+        let mut ops_to_replay = self.ops_to_replay.lock();
+        if enabled == self.is_low_memory_mode() {
+            return;
+        }
+
+        if enabled && self.unpublished_cost.load(Ordering::Relaxed) != usize::MAX {
+            // Publish the updates on the standby table before dropping it.
+            let mut wg = self.new_write_guard(ops_to_replay);
+            wg.sync_standby_table();
+            drop(wg);
+            ops_to_replay = self.ops_to_replay.lock();
+        }
+
+        let active_index = self.active_index.load(Ordering::SeqCst);
+        let mut standby_table = self.tables[1 - active_index].write();
+        if enabled {
+            // The updates the standby table is missing go along with it.
+            ops_to_replay.clear();
+            #[cfg(feature = "op-stats")]
+            self.op_stats.clear_pending();
+            *standby_table = T::default();
+        } else {
+            *standby_table = self.tables[active_index].read().clone();
+        }
+        self.low_memory
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+impl<T> AsLock<T>
+where
+    T: WithCapacity,
+{
+    /// Create an `AsLock` where both tables have space for `capacity`
+    /// elements.
+    pub fn with_capacity(capacity: usize) -> AsLock<T> {
+        Self::from_identical(T::with_capacity(capacity), T::with_capacity(capacity))
+    }
+}
+
+impl<T> Default for AsLock<T>
+where
+    T: Default,
+{
+    fn default() -> AsLock<T> {
+        Self::from_identical(T::default(), T::default())
+    }
+}
+
+#[cfg(feature = "op-stats")]
+impl<T> AsLock<T> {
+    /// Counts and replay times of the updates made to the table, by label.
+    /// Doesn't wait on the writer.
+    pub fn op_stats(&self) -> crate::op_stats::OpStats {
+        self.op_stats.snapshot()
+    }
+}
+
+/// The RwLocks don't track their readers, so `num_readers` isn't reported.
+impl<T> crate::inspect::Inspect for AsLock<T> {
+    fn inspect(&self) -> crate::inspect::Inspection {
+        crate::inspect::Inspection {
+            type_name: std::any::type_name::<T>(),
+            num_readers: None,
+            num_ops_to_replay: self.ops_to_replay.try_lock().map(|ops| ops.len()),
+            version: self.publish_signal.num_publishes(),
+            size: None,
+        }
+    }
+}
+
+impl<T> AsLock<T> {
+    /// Counters of the writer, for monitoring. Like `inspect`, this never
+    /// waits on the writer. Readers aren't tracked.
+    pub fn stats(&self) -> crate::inspect::AsLockStats {
+        self.wait_stats.stats(
+            None,
+            self.ops_to_replay.try_lock().map(|ops| ops.len()),
+            self.publish_signal.num_publishes(),
+        )
+    }
+
+    /// The generation of the latest publish, see
+    /// `AsLockReadGuard::generation`. A read racing with a publish may see the
+    /// new table slightly before this counts it.
+    pub fn current_generation(&self) -> u64 {
+        self.publish_signal.num_publishes()
+    }
+}
+
+impl<T: PartialEq> AsLock<T> {
+    /// Returns whether the active and standby tables are equal, for canaries
+    /// which check that the updates are deterministic in production. See
+    /// `AsLockWriteGuard::tables_eq`.
+    ///
+    /// Nothing is published. Like `write`, this waits for the write lock and
+    /// for readers to leave the standby table, and replays the last guard's
+    /// updates onto it. It then compares the whole tables, so a check costs
+    /// about as much as an empty write plus a full comparison. If updates from
+    /// `write_deferred` are waiting to be published, the standby table is
+    /// ahead by design, so the check is skipped and this returns true.
+    pub fn tables_eq(&self) -> bool {
+        if self.is_low_memory_mode() {
+            // There is only a single table, and dropping even a deferred
+            // guard publishes.
+            return true;
+        }
+        self.write_deferred().tables_eq()
+    }
+}
+
+impl<T> AsLock<T> {
+    /// Tear down the tables and return the one with all of the updates,
+    /// including any left unpublished by `write_deferred`.
+    pub fn into_inner(self) -> T {
+        let mut index = self.active_index.load(Ordering::SeqCst);
+        if self.unpublished_cost.load(Ordering::Relaxed) != usize::MAX {
+            index = 1 - index;
+        }
+        let [first, second] = *self.tables;
+        if index == 0 {
+            first.into_inner()
+        } else {
+            second.into_inner()
+        }
+    }
+}
+
+impl<T: Clone> AsLock<T> {
+    /// Clone the active table. The read guard is only held while cloning, so
+    /// slow work on the copy, like persisting it, doesn't hold up the writer.
+    pub fn snapshot(&self) -> T {
+        self.read().cloned()
+    }
+}
+
+impl<T: crate::inspect::Sizer> AsLock<T> {
+    /// Like `inspect`, but also reports the size of the active table.
+    pub fn inspect_sized(&self) -> crate::inspect::Inspection {
+        crate::inspect::Inspection {
+            size: Some(self.read().size()),
+            ..crate::inspect::Inspect::inspect(self)
+        }
+    }
+}
+
+#[cfg(feature = "registry")]
+impl<T> AsLock<T>
+where
+    T: 'static + fmt::Debug + Send + Sync,
+{
+    /// Register this table in the process wide `registry` under `name`. The
+    /// registry doesn't keep the table alive.
+    pub fn register(self: &Arc<Self>, name: impl Into<String>) {
+        crate::registry::register_sync_with(name.into(), self, |table| table, |_| None)
+    }
+
+    /// Like `register`, but also reports the size of the table.
+    pub fn register_sized(self: &Arc<Self>, name: impl Into<String>)
+    where
+        T: crate::inspect::Sizer,
+    {
+        crate::registry::register_sync_with(
+            name.into(),
+            self,
+            |table| table,
+            |table| Some(table.size()),
+        )
+    }
+
+    pub(crate) fn registry_state(
+        &self,
+        size: fn(&T) -> Option<usize>,
+    ) -> crate::registry::TableState {
+        // Reads only block in low memory mode, where the writer holds the
+        // active table.
+        let active_table = self.try_read();
+        crate::registry::TableState {
+            num_readers: None,
+            num_ops_to_replay: self.ops_to_replay.try_lock().map(|ops| ops.len()),
+            version: self.publish_signal.num_publishes(),
+            last_publish: self.publish_signal.last_publish(),
+            size: active_table.as_ref().and_then(|table| size(table)),
+            active_table: active_table.map(|table| format!("{:?}", *table)),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_ops_to_replay = self.ops_to_replay.lock().len();
+        let wg = self.write();
+        let mut debug = f.debug_struct("AsLock");
+        debug.field("num_ops_to_replay", &num_ops_to_replay);
+        if wg.low_memory {
+            // The guard holds the only table, so reading would deadlock.
+            debug.field("active_table", &*wg)
+        } else {
+            debug
+                .field("standby_table", &*wg)
+                .field("active_table", &*self.read())
+        }
+        .finish()
+    }
+}
+
+impl<T> crate::ReadInterface<T> for AsLock<T> {
+    type Guard<'r>
+        = AsLockReadGuard<'r, T>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        AsLock::read(self)
+    }
+}
+
+impl<T> crate::Snapshot<T> for AsLock<T> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+}
+
+/// `AsLock` is usually shared behind an `Arc`.
+impl<T> crate::Snapshot<T> for std::sync::Arc<AsLock<T>> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+}
+
+impl<'r, T> AsLockReadGuard<'r, T> {
+    /// The number of publishes as of the table this guard reads, which starts
+    /// at 0. Readers can compare it to the generation they last saw to tell if
+    /// the table has changed since, without comparing its contents.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Clone the active table.
+    pub fn cloned(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(self)
+    }
+
+    /// Create an owned copy of the active table.
+    pub fn to_owned(&self) -> T::Owned
+    where
+        T: ToOwned,
+    {
+        T::to_owned(self)
+    }
+}
+
+impl<'r, T> std::ops::Deref for AsLockReadGuard<'r, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        match &self.guard {
+            ReadGuardInner::Locked(guard) => guard,
+            #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+            ReadGuardInner::Copied(table) => table,
+        }
+    }
+}
+
+// Like the lockless guards, for code generic over `Index`.
+impl<'r, T, I> std::ops::Index<I> for AsLockReadGuard<'r, T>
+where
+    T: std::ops::Index<I>,
+{
+    type Output = T::Output;
+    fn index(&self, index: I) -> &Self::Output {
+        &(**self)[index]
+    }
+}
+
+impl<'r, T: fmt::Debug> fmt::Debug for AsLockReadGuard<'r, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        T::fmt(self, f)
+    }
+}
+
+impl<'g, 'r, T: Clone> From<&'g AsLockReadGuard<'r, T>> for std::borrow::Cow<'g, T> {
+    fn from(guard: &'g AsLockReadGuard<'r, T>) -> Self {
+        std::borrow::Cow::Borrowed(guard)
+    }
+}
+
+/// Read guard which holds its own `Arc` to the `AsLock`, instead of borrowing
+/// it. Created by `AsLock::read_owned`.
+///
+/// Since it is `'static`, it can be stored in structs. Like the RwLock's guard
+/// it isn't `Send`, so it can't be moved into a spawned thread, but it can be
+/// held across `.await`s on a single threaded runtime.
+///
+/// Not available with `safe-impl`.
+#[cfg(not(any(loom, feature = "safe-impl")))]
+pub struct AsLockOwnedReadGuard<T: 'static> {
+    // Declared first, so that the guard is dropped before the AsLock it
+    // borrows from.
+    guard: AsLockReadGuard<'static, T>,
+    _aslock: std::sync::Arc<AsLock<T>>,
+}
+
+#[cfg(not(any(loom, feature = "safe-impl")))]
+impl<T> AsLock<T> {
+    /// Like `read`, but the guard holds a clone of `this`, so that it isn't
+    /// tied to the lifetime of the borrow. Useful for storing a read in a
+    /// struct.
+    ///
+    /// Like any read guard, it blocks the next `write` until it is dropped. An
+    /// owned guard is easy to keep around, so take care not to stall the
+    /// writer indefinitely.
+    pub fn read_owned(this: &std::sync::Arc<AsLock<T>>) -> AsLockOwnedReadGuard<T> {
+        let aslock = std::sync::Arc::clone(this);
+        let guard = aslock.read();
+        // Safety: The guard borrows from the AsLock, which is kept alive, and
+        // never moved, by the Arc held alongside it. The guard is dropped
+        // first.
+        let guard = unsafe {
+            std::mem::transmute::<AsLockReadGuard<'_, T>, AsLockReadGuard<'static, T>>(guard)
+        };
+        AsLockOwnedReadGuard {
+            guard,
+            _aslock: aslock,
+        }
+    }
+}
+
+#[cfg(not(any(loom, feature = "safe-impl")))]
+impl<T> std::ops::Deref for AsLockOwnedReadGuard<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+#[cfg(not(any(loom, feature = "safe-impl")))]
+impl<T, I> std::ops::Index<I> for AsLockOwnedReadGuard<T>
+where
+    T: std::ops::Index<I>,
+{
+    type Output = T::Output;
+    fn index(&self, index: I) -> &Self::Output {
+        &(**self)[index]
+    }
+}
+
+#[cfg(not(any(loom, feature = "safe-impl")))]
+impl<T: fmt::Debug> fmt::Debug for AsLockOwnedReadGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.guard.fmt(f)
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Takes an update which will change the state of the underlying data. This
+    /// is done through the interface of UpdateTables.
+    ///
+    /// Users should never use the return value to directly mutate the tables,
+    /// since this will lead to them going out of sync.
+    ///
+    /// The update passed in must be valid for 'static because it will outlive
+    /// the AsLockWriteGuard taking the update, so we can't make any limitations on
+    /// it.
+    pub fn update_tables<'a, R>(
+        &'a mut self,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_weighted(1, update)
+    }
+
+    /// Like `update_tables`, but the update counts as `weight` towards
+    /// `publishing_cost_estimate`, instead of 1. Useful when updates vary in how
+    /// expensive they are to replay.
+    pub fn update_tables_weighted<'a, R>(
+        &'a mut self,
+        weight: usize,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_impl(std::any::type_name_of_val(&update), weight, None, update)
+    }
+
+    /// Like `update_tables`, but the update is counted under `label` in the
+    /// `op_stats`, instead of under the name of its type. Without the
+    /// `op-stats` feature the label is ignored.
+    pub fn update_tables_labeled<'a, R>(
+        &'a mut self,
+        label: &'static str,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_impl(label, 1, None, update)
+    }
+
+    /// Like `update_tables`, but the update is recorded under `key`, and
+    /// replaces the replay of the last update recorded under the same key by
+    /// this guard. This keeps writers which repeatedly set the same values,
+    /// like config reconcilers, from growing the updates to replay.
+    ///
+    /// The update must make the one it replaces redundant, regardless of the
+    /// updates made in between, e.g. by overwriting the same field.
+    /// Otherwise the tables diverge.
+    pub fn update_tables_keyed<'a, R>(
+        &'a mut self,
+        key: impl Into<String>,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        let label = std::any::type_name_of_val(&update);
+        self.update_tables_impl(label, 1, Some(key.into()), update)
+    }
+
+    fn update_tables_impl<'a, R>(
+        &'a mut self,
+        label: &'static str,
+        weight: usize,
+        key: Option<String>,
+        mut update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> R {
+        self.replay_cost += weight;
+        let low_memory = self.low_memory;
+        if let (Some(key), false) = (&key, low_memory) {
+            if self.op_keys.remove(key, &mut *self.ops_to_replay) {
+                self.replay_cost -= 1;
+            }
+        }
+        let res = {
+            let _applying = reentrancy::Applying::new(tables_id(self.tables));
+            update.apply_first(self.guard.as_deref_mut().unwrap())
+        };
+
+        if low_memory {
+            // There is no other table to replay the update on.
+            return res;
+        }
+
+        // Can't go through `record_op`, since `res` may borrow the standby
+        // table from `self`.
+        let index = self.ops_to_replay.push(Box::new(move |table| {
+            update.apply_second(table);
+        }));
+        if let Some(key) = key {
+            self.op_keys.insert(key, index);
+        }
+        #[cfg(feature = "op-stats")]
+        self.op_stats.record(label);
+        #[cfg(not(feature = "op-stats"))]
+        let _ = label;
+
+        res
+    }
+
+    /// Like `update_tables`, but fails instead of applying `update` if this
+    /// guard already holds `max_pending_ops` updates (see
+    /// `AsLock::set_max_pending_ops`). Without a limit this always succeeds.
+    /// The other `update_tables` methods aren't limited, but their updates
+    /// count towards the limit.
+    ///
+    /// The updates held by a guard are replayed by the next `write`, after
+    /// waiting for any AsLockReadGuards on the standby table, so a stuck
+    /// reader doesn't grow them past the updates of a single guard. The limit
+    /// bounds that. When it is hit, drop the guard to publish its updates;
+    /// any stuck reader then holds up the next `write` rather than growing
+    /// the ops held in memory.
+    pub fn try_update_tables<'a, R>(
+        &'a mut self,
+        update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
+    ) -> Result<R, crate::PendingOpsFull> {
+        self.check_max_pending_ops()?;
+        Ok(self.update_tables(update))
+    }
+
+    /// Like `update_tables_closure`, but limited like `try_update_tables`.
+    pub fn try_update_tables_closure<R>(
+        &mut self,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> Result<R, crate::PendingOpsFull> {
+        self.check_max_pending_ops()?;
+        Ok(self.update_tables_closure(update))
+    }
+
+    fn check_max_pending_ops(&self) -> Result<(), crate::PendingOpsFull> {
+        if self.ops_to_replay.len() >= self.max_pending_ops {
+            return Err(crate::PendingOpsFull {
+                max_pending_ops: self.max_pending_ops,
+            });
+        }
+        Ok(())
+    }
+
+    /// Like `update_tables` but allows the user to pass a closure for
+    /// convenience. Only allows return values that own their data. See
+    /// `update_tables_closure_mut` for closures which mutate what they
+    /// capture.
+    ///
+    /// TODO: Consider allowing return values that have lifetimes, this should
+    /// likely be as safe as the explicit UpdateTables trait.
+    pub fn update_tables_closure<R>(
+        &mut self,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_closure_weighted(1, update)
+    }
+
+    /// Like `update_tables_closure`, but the update counts as `weight` towards
+    /// `publishing_cost_estimate`, instead of 1.
+    pub fn update_tables_closure_weighted<R>(
+        &mut self,
+        weight: usize,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_closure_impl(std::any::type_name_of_val(&update), weight, update)
+    }
+
+    /// Like `update_tables_closure`, but the update is counted under `label`
+    /// in the `op_stats`. Without the `op-stats` feature the label is ignored.
+    pub fn update_tables_closure_labeled<R>(
+        &mut self,
+        label: &'static str,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.update_tables_closure_impl(label, 1, update)
+    }
+
+    /// Like `update_tables_closure`, but `update` may mutate what it captures,
+    /// e.g. a counter or an iterator being consumed. It is cloned to update
+    /// the first table, and the original replays the update on the second, so
+    /// both start from the same captured state.
+    pub fn update_tables_closure_mut<R>(
+        &mut self,
+        mut update: impl FnMut(&mut T) -> R + Clone + 'static + Send,
+    ) -> R {
+        let label = std::any::type_name_of_val(&update);
+        let first = update.clone();
+        self.update_tables_impl(
+            label,
+            1,
+            None,
+            crate::ClosureUpdate::new(first, move |table: &mut T| {
+                update(table);
+            }),
+        )
+    }
+
+    fn update_tables_closure_impl<R>(
+        &mut self,
+        label: &'static str,
+        weight: usize,
+        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
+    ) -> R {
+        self.replay_cost += weight;
+        let res = {
+            let _applying = reentrancy::Applying::new(tables_id(self.tables));
+            update(self.guard.as_deref_mut().unwrap())
+        };
+
+        self.record_op(
+            label,
+            Box::new(move |table| {
+                update(table);
+            }),
+        );
+
+        res
+    }
+
+    /// Like `update_tables_closure` but `update` may borrow `state` instead of
+    /// capturing it, which lifts the 'static requirement off of `state`. The
+    /// standby table is updated with the borrowed `state`, and an owned copy
+    /// is recorded to replay the update on the other table.
+    pub fn update_tables_scoped<State, R>(
+        &mut self,
+        state: &State,
+        update: impl Fn(&mut T, &State) -> R + 'static + Sized + Send,
+    ) -> R
+    where
+        State: ToOwned + ?Sized,
+        State::Owned: 'static + Send,
+    {
+        let res = {
+            let _applying = reentrancy::Applying::new(tables_id(self.tables));
+            update(self.guard.as_deref_mut().unwrap(), state)
+        };
+
+        self.replay_cost += 1;
+        let label = std::any::type_name_of_val(&update);
+        let state = state.to_owned();
+        self.record_op(
+            label,
+            Box::new(move |table| {
+                update(table, std::borrow::Borrow::borrow(&state));
+            }),
+        );
+
+        res
+    }
+
+    /// The number of updates made through this guard, which will be replayed
+    /// on the other table by the next `write`.
+    pub fn num_pending_ops(&self) -> usize {
+        self.ops_to_replay.len()
+    }
+
+    /// Estimate of the cost of replaying the pending updates, which is paid by
+    /// the next `write`. This is the sum of the updates' weights, where updates
+    /// made without a weight count as 1. Can be used to decide when to drop
+    /// the guard to publish the updates.
+    pub fn publishing_cost_estimate(&self) -> usize {
+        self.replay_cost
+    }
+
+    /// Hold onto `value` and drop it only once this guard has published its
+    /// updates and released the write lock. Meant for values removed from the
+    /// table, such as `Arc`s shared with readers, so that their destructors
+    /// run at a deterministic point, outside of the swap.
+    ///
+    /// Note that this only covers the value removed from the standby table;
+    /// when the removal is replayed on the other table, that copy is dropped
+    /// during the replay.
+    pub fn defer_drop(&mut self, value: impl Send + 'static) {
+        self.deferred_drops.push(Box::new(value));
+    }
+}
+
+#[cfg(feature = "op-stats")]
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// See `AsLock::op_stats`. The updates made through this guard are
+    /// counted, but not yet replayed.
+    pub fn op_stats(&self) -> crate::op_stats::OpStats {
+        self.op_stats.snapshot()
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Record `op` to be replayed on the other table by the next
+    /// `sync_standby_table`.
+    fn record_op(&mut self, label: &'static str, op: Box<dyn FnOnce(&mut T) + Send>) {
+        if self.low_memory {
+            return;
+        }
+        self.ops_to_replay.push(op);
+        #[cfg(feature = "op-stats")]
+        self.op_stats.record(label);
+        #[cfg(not(feature = "op-stats"))]
+        let _ = label;
+    }
+
+    /// Write lock the standby table and bring it up to date, so that it
+    /// matches the active table. This may hang on `AsLockReadGuard`s which
+    /// exist from before the last swap.
+    fn sync_standby_table(&mut self) {
+        self.sync_standby_table_budgeted(usize::MAX);
+    }
+
+    /// Like `sync_standby_table`, but replays at most `max_ops` updates,
+    /// leaving the rest queued for the next call. Returns whether the standby
+    /// table is up to date, in which case the guard now holds it.
+    fn sync_standby_table_budgeted(&mut self, max_ops: usize) -> bool {
+        debug_assert!(self.guard.is_none());
+
+        if self.low_memory {
+            // There is no standby table, so update the active table in place,
+            // blocking readers like an RwLock.
+            let active_index = self.active_index.load(Ordering::SeqCst);
+            self.guard = Some(self.lock_table(active_index));
+            return true;
+        }
+
+        let standby_index = 1 - self.active_index.load(Ordering::SeqCst);
+        #[cfg(feature = "failpoints")]
+        fail::fail_point!("active_standby::await_readers");
+        let guard = self.lock_table(standby_index);
+        if self.is_unpublished() {
+            // The standby table is ahead of the active one.
+            self.guard = Some(guard);
+            return true;
+        }
+        self.replay(guard, max_ops)
+    }
+
+    /// Like `sync_standby_table`, but returns false instead of waiting for
+    /// AsLockReadGuards to leave the standby table.
+    fn try_sync_standby_table(&mut self) -> bool {
+        debug_assert!(self.guard.is_none());
+
+        let active_index = self.active_index.load(Ordering::SeqCst);
+        if self.low_memory {
+            self.guard = self.tables[active_index].try_write();
+            return self.guard.is_some();
+        }
+        match self.tables[1 - active_index].try_write() {
+            Some(guard) if self.is_unpublished() => {
+                self.guard = Some(guard);
+                true
+            }
+            Some(guard) => self.replay(guard, usize::MAX),
+            None => false,
+        }
+    }
+
+    /// Write lock `tables[index]`, recording how long it took if readers
+    /// held it.
+    fn lock_table(&self, index: usize) -> RwLockWriteGuard<'w, T> {
+        if let Some(guard) = self.tables[index].try_write() {
+            return guard;
+        }
+        let start = std::time::Instant::now();
+        let guard = self.tables[index].write();
+        self.wait_stats.record(start.elapsed());
+        guard
+    }
+
+    fn is_unpublished(&self) -> bool {
+        self.unpublished_cost.load(Ordering::Relaxed) != usize::MAX
+    }
+
+    /// Replay at most `max_ops` updates onto `guard`, the standby table.
+    /// Returns whether it is now up to date, in which case `self.guard`
+    /// holds it.
+    fn replay(&mut self, mut guard: RwLockWriteGuard<'w, T>, max_ops: usize) -> bool {
+        // Replay the ops on the standby table.
+        let _applying = reentrancy::Applying::new(tables_id(self.tables));
+        #[cfg(feature = "failpoints")]
+        let ops = self.ops_to_replay.drain(max_ops).inspect(|_| {
+            fail::fail_point!("active_standby::replay");
+        });
+        #[cfg(not(feature = "failpoints"))]
+        let ops = self.ops_to_replay.drain(max_ops);
+        #[cfg(feature = "op-stats")]
+        self.op_stats.replay::<_, OpLog<T>>(ops, &mut guard);
+        #[cfg(not(feature = "op-stats"))]
+        for op in ops.flatten() {
+            OpLog::apply(op, &mut guard);
+        }
+
+        if !self.ops_to_replay.is_empty() {
+            return false;
+        }
+        self.guard = Some(guard);
+        true
+    }
+
+    /// Release the standby table and swap it with the active table, publishing
+    /// all updates applied so far to readers.
+    fn swap_tables(&mut self) {
+        // Set the generation of the table being published while it is still
+        // locked.
+        let active_index = self.active_index.load(Ordering::SeqCst);
+        let published = if self.low_memory {
+            active_index
+        } else {
+            1 - active_index
+        };
+        let generation = self.publish_signal.num_publishes() + 1;
+        self.generations[published].store(generation, Ordering::SeqCst);
+        #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+        if let Some(seq_reads) = self.seq_reads {
+            seq_reads.store(self.guard.as_deref().unwrap(), generation);
+        }
+
+        // Swap the tables after releasing the RwLockAsLockWriteGuard to guarantee
+        // reads are never blocked.
+        self.guard = None;
+        self.unpublished_cost.store(usize::MAX, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        #[cfg(feature = "failpoints")]
+        fail::fail_point!("active_standby::before_swap");
+
+        if self.low_memory {
+            // The updates were made to the active table.
+            self.publish_signal.notify();
+            return;
+        }
+
+        // Swap the active and standby tables. This should never fail because
+        // there can only ever be 1 writer which spawns only 1 AsLockWriteGuard.
+        let res = self.active_index.compare_exchange(
+            active_index,
+            1 - active_index,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+        assert_eq!(res, Ok(active_index));
+        self.publish_signal.notify();
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Publish the updates made so far and replay them on the standby table,
+    /// as if this guard were dropped and a new one created.
+    fn resync(&mut self) {
+        self.swap_tables();
+        self.sync_standby_table();
+        self.replay_cost = 0;
+        self.op_keys.clear();
+    }
+}
+
+impl<'w, T: PartialEq> AsLockWriteGuard<'w, T> {
+    /// Like `assert_synced`, but returns whether the tables are equal instead
+    /// of panicking. Always true in low memory mode, where there is only a
+    /// single table.
+    ///
+    /// If this guard has no updates to replay, the tables are compared as they
+    /// are, without publishing. Otherwise they are published first, like
+    /// `assert_synced` does, except by a guard from `write_deferred`, whose
+    /// updates must stay unpublished, so the check is skipped and this
+    /// returns true.
+    pub fn tables_eq(&mut self) -> bool {
+        if self.low_memory {
+            return true;
+        }
+        if !self.ops_to_replay.is_empty() {
+            if self.deferred {
+                return true;
+            }
+            self.resync();
+        }
+        let active_table = self.tables[self.active_index.load(Ordering::SeqCst)].read();
+        *active_table == **self
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T>
+where
+    T: PartialEq + fmt::Debug,
+{
+    /// Test helper which panics if the tables have diverged. This publishes the
+    /// updates made so far, as if this guard were dropped and a new one
+    /// created, and then compares the active and standby tables.
+    ///
+    /// Like `write`, this waits for AsLockReadGuards to leave the standby
+    /// table, so it will deadlock if this thread holds an AsLockReadGuard.
+    pub fn assert_synced(&mut self) {
+        self.resync();
+        if self.low_memory {
+            // There is only a single table.
+            return;
+        }
+
+        let active_table = self.tables[self.active_index.load(Ordering::SeqCst)].read();
+        assert_eq!(
+            *active_table, **self,
+            "active and standby tables have diverged"
+        );
+    }
+}
+
+impl<'w, T> AsLockWriteGuard<'w, T> {
+    /// Publish the updates without releasing the write lock. The guard can't
+    /// be updated afterwards, and dropping it won't publish again.
+    /// Whether dropping the guard leaves its updates unpublished. See
+    /// `AsLock::write_deferred`.
+    #[cfg(feature = "optimistic")]
+    pub(crate) fn set_deferred(&mut self, deferred: bool) {
+        self.deferred = deferred;
+    }
+
+    pub(crate) fn publish(&mut self) {
+        // The guard is only missing if replaying ops panicked, in which case
+        // there is nothing to publish, or if already published.
+        if self.guard.is_none() {
+            return;
+        }
+        if self.deferred && !self.low_memory {
+            // Leave the updates on the standby table for a later publish.
+            self.guard = None;
+            if !self.ops_to_replay.is_empty() {
+                self.unpublished_cost
+                    .store(self.replay_cost, Ordering::Relaxed);
+            }
+            return;
+        }
+        self.swap_tables();
+    }
+}
+
+impl<'w, T> Drop for AsLockWriteGuard<'w, T> {
+    fn drop(&mut self) {
+        self.publish();
+
+        // Only after swapping the tables should we drop the Mutex to
+        // `ops_to_replay`, allowing a new AsLockWriteGuard.
+    }
+}
+
+impl<'w, T> std::ops::Deref for AsLockWriteGuard<'w, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.guard.as_deref().unwrap()
+    }
+}
+
+impl<'w, T: fmt::Debug> fmt::Debug for AsLockWriteGuard<'w, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::ops::Deref;
+        f.debug_struct("AsLockWriteGuard")
+            .field("num_ops_to_replay", &self.ops_to_replay.len())
+            .field("standby_table", self.deref())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    struct PushVec<T> {
+        value: T,
+    }
+    impl<'a, T> UpdateTables<'a, Vec<T>, ()> for PushVec<T>
+    where
+        T: Clone,
+    {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) {
+            table.push(self.value.clone());
+        }
+        fn apply_second(self, table: &mut Vec<T>) {
+            table.push(self.value); // Move the value instead of cloning.
+        }
+    }
+
+    struct PopVec {}
+    impl PopVec {
+        fn apply<T>(&mut self, table: &mut Vec<T>) -> Option<T> {
+            table.pop()
+        }
+    }
+    impl<'a, T> UpdateTables<'a, Vec<T>, Option<T>> for PopVec {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) -> Option<T> {
+            self.apply(table)
+        }
+        fn apply_second(mut self, table: &mut Vec<T>) {
+            self.apply(table);
+        }
+    }
+
+    /// This is an example of what not to do!
+    struct MutableRef {}
+    impl<'a, T> UpdateTables<'a, Vec<T>, &'a mut T> for MutableRef {
+        fn apply_first(&mut self, table: &'a mut Vec<T>) -> &'a mut T {
+            &mut table[0]
+        }
+        fn apply_second(self, table: &mut Vec<T>) {
+            let _ = &mut table[0];
+        }
+    }
+
+    #[test]
+    fn one_write_guard() {
+        // TODO: Have a multithreaded test for this.
+        let writer = AsLock::<Vec<i32>>::default();
+        let _wg = writer.write();
+        // let wg2 = writer.write();
+    }
+
+    #[test]
+    fn publish_update() {
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        assert_eq!(aslock.read().len(), 0);
+
+        {
+            let mut wg = aslock.write();
+            wg.update_tables(PushVec { value: 2 });
+            assert_eq!(wg.len(), 1);
+            {
+                // Perform check in another thread to avoid potential deadlock
+                // (calling both read and write on aslock at the same time).
+                let aslock = Arc::clone(&aslock);
+                assert!(thread::spawn(move || {
+                    assert_eq!(aslock.read().len(), 0);
+                })
+                .join()
+                .is_ok());
+            }
+        }
+
+        // When the write guard is dropped it publishes the changes to the readers.
+        assert_eq!(*aslock.read(), vec![2]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn new_parallel() {
+        let table = AsLock::new_parallel((0..1000).collect::<Vec<i32>>());
+        table.write().update_tables(PushVec { value: 1000 });
+        table.write().assert_synced();
+        assert_eq!(*table.read(), (0..=1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn changed() {
+        let table = Arc::new(AsLock::<Vec<i32>>::default());
+        let changed = table.changed();
+        let writer = Arc::clone(&table);
+        let handle = thread::spawn(move || {
+            writer.write().update_tables(PushVec { value: 2 });
+        });
+
+        // The future resolves once the update is visible to readers.
+        crate::primitives::changed::block_on(changed);
+        assert_eq!(*table.read(), vec![2]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn subscribe() {
+        let table = Arc::new(AsLock::<Vec<i32>>::default());
+        let mut listener = table.subscribe();
+        let writer = Arc::clone(&table);
+        let handle = thread::spawn(move || {
+            for value in 1..=3 {
+                writer.write().update_tables(PushVec { value });
+            }
+        });
+
+        // Each wait sees the update which was visible when it returned.
+        while table.read().len() < 3 {
+            let generation = listener.wait();
+            assert!(table.read().generation() >= generation);
+        }
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn changes() {
+        use futures_core::Stream;
+
+        let table = AsLock::<Vec<i32>>::default();
+        let mut changes = table.changes();
+        let waker = std::task::Waker::noop();
+        let mut cx = std::task::Context::from_waker(waker);
+        assert!(std::pin::Pin::new(&mut changes)
+            .poll_next(&mut cx)
+            .is_pending());
+
+        table.write().update_tables(PushVec { value: 1 });
+        table.write().update_tables(PushVec { value: 2 });
+        // Publishes between polls are seen as one.
+        let next = crate::primitives::changed::block_on(std::future::poll_fn(|cx| {
+            std::pin::Pin::new(&mut changes).poll_next(cx)
+        }));
+        assert_eq!(next, Some(table.current_generation()));
+        assert!(std::pin::Pin::new(&mut changes)
+            .poll_next(&mut cx)
+            .is_pending());
+    }
+
+    #[test]
+    fn on_swap() {
+        let table = AsLock::<Vec<i32>>::default();
+        let swaps = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        table.on_swap({
+            let swaps = std::sync::Arc::clone(&swaps);
+            move |old, new| swaps.lock().unwrap().push((old, new))
+        });
+
+        table.write().update_tables(PushVec { value: 2 });
+        {
+            let mut wg = table.write();
+            wg.update_tables(PushVec { value: 3 });
+            // Each publish made by the guard is reported.
+            wg.assert_synced();
+        }
+        assert_eq!(*swaps.lock().unwrap(), vec![(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(*table.read(), vec![2, 3]);
+    }
+
+    #[cfg(all(feature = "seqlock", not(feature = "safe-impl")))]
+    #[test]
+    fn seqlock_reads() {
+        let table = Arc::new(AsLock::new_seqlock([0_i32; 2]));
+        {
+            let mut wg = table.write();
+            wg.update_tables_closure(|table| table[0] = 1);
+            assert_eq!(*table.read(), [0, 0]);
+        }
+        let guard = table.read();
+        assert_eq!(*guard, [1, 0]);
+        assert_eq!(guard.generation(), 1);
+
+        // Reads never wait on the writer, even when it holds the only table.
+        table.set_low_memory_mode(true);
+        {
+            let mut wg = table.write();
+            wg.update_tables_closure(|table| table[1] = 2);
+            assert_eq!(*table.try_read().unwrap(), [1, 0]);
+        }
+        assert_eq!(*table.read(), [1, 2]);
+        table.set_low_memory_mode(false);
+
+        let reader = {
+            let table = Arc::clone(&table);
+            thread::spawn(move || {
+                let mut last = 1;
+                while last < 100 {
+                    let [a, b] = *table.read();
+                    // Copies are never torn.
+                    assert_eq!(b, a + 1);
+                    assert!(a >= last);
+                    last = a;
+                }
+            })
+        };
+        for _ in 0..99 {
+            table.write().update_tables_closure(|table| {
+                table[0] += 1;
+                table[1] += 1;
+            });
+        }
+        reader.join().unwrap();
+        assert_eq!(*table.read(), [100, 101]);
+    }
+
+    #[cfg(not(feature = "safe-impl"))]
+    #[test]
+    fn read_owned() {
+        struct Snapshot {
+            guard: AsLockOwnedReadGuard<Vec<i32>>,
+        }
+
+        let table = std::sync::Arc::new(AsLock::new(vec![1]));
+        let snapshot = Snapshot {
+            guard: AsLock::read_owned(&table),
+        };
+        table.write().update_tables(PushVec { value: 2 });
+        assert_eq!(*snapshot.guard, vec![1]);
+        assert_eq!(*table.read(), vec![1, 2]);
+
+        // The AsLock outlives the last Arc held by the caller.
+        let guard = AsLock::read_owned(&table);
+        drop(table);
+        assert_eq!(*guard, vec![1, 2]);
+    }
+
+    #[test]
+    fn inspect() {
+        use crate::inspect::Inspect;
+
+        let table = AsLock::<Vec<i32>>::default();
+        table.write().update_tables(PushVec { value: 2 });
+        assert_eq!(
+            table.inspect_sized(),
+            crate::inspect::Inspection {
+                type_name: "alloc::vec::Vec<i32>",
+                num_readers: None,
+                num_ops_to_replay: Some(1),
+                version: 1,
+                size: Some(1),
+            }
+        );
+
+        // Doesn't wait on the writer.
+        let wg = table.write();
+        assert_eq!(table.inspect().num_ops_to_replay, None);
+        drop(wg);
+    }
+
+    #[test]
+    fn update_tables_closure_mut() {
+        let table = AsLock::new(vec![]);
+        let mut wg = table.write();
+        // Without cloning, the second table would get [3, 4].
+        let mut next = 0;
+        wg.update_tables_closure_mut(move |vec| {
+            for _ in 0..2 {
+                next += 1;
+                vec.push(next);
+            }
+        });
+        let mut values = vec![5, 6].into_iter();
+        wg.update_tables_closure_mut(move |vec| vec.extend(values.by_ref()));
+        wg.assert_synced();
+        assert_eq!(*wg, vec![1, 2, 5, 6]);
+    }
+
+    #[test]
+    fn update_tables_closure() {
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        assert_eq!(aslock.read().len(), 0);
+
+        {
+            let mut wg = aslock.write();
+            wg.update_tables_closure(|vec| vec.push(2));
+            assert_eq!(wg.len(), 1);
+            {
+                // Perform check in another thread to avoid potential deadlock
+                // (calling both read and write on aslock at the same time).
+                let aslock = Arc::clone(&aslock);
+                assert!(thread::spawn(move || {
+                    assert_eq!(aslock.read().len(), 0);
+                })
+                .join()
+                .is_ok());
+            }
+        }
+
+        // When the write guard is dropped it publishes the changes to the readers.
+        assert_eq!(*aslock.read(), vec![2]);
+    }
+
+    #[cfg(feature = "op-stats")]
+    #[test]
+    fn op_stats() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        {
+            let mut wg = aslock.write();
+            wg.update_tables_labeled("push", PushVec { value: 2 });
+            wg.update_tables_closure_labeled("push", |vec| vec.push(3));
+            wg.update_tables_closure(|vec| vec.clear());
+            let stats = wg.op_stats();
+            assert_eq!(stats.ops["push"].count, 2);
+            assert_eq!(stats.total().replayed, 0);
+        }
+
+        // The updates are replayed by the next write.
+        drop(aslock.write());
+        let stats = aslock.op_stats();
+        assert_eq!(stats.ops.len(), 2);
+        assert_eq!(stats.ops["push"].replayed, 2);
+        assert_eq!(stats.total().count, 3);
+        assert_eq!(stats.total().replayed, 3);
+
+        // Unlabeled updates are counted under the name of their type.
+        let label = stats.ops.keys().find(|label| **label != "push").unwrap();
+        assert!(label.contains("op_stats::{{closure}}"), "{}", label);
+        assert!(stats.report().contains(label));
+    }
+
+    #[cfg(feature = "op-stats")]
+    #[test]
+    fn op_stats_low_memory_mode() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        aslock
+            .write()
+            .update_tables_closure_labeled("dropped", |vec| vec.push(1));
+        // Dropping the standby table drops the update's replay.
+        aslock.set_low_memory_mode(true);
+        aslock.set_low_memory_mode(false);
+
+        aslock
+            .write()
+            .update_tables_closure_labeled("replayed", |vec| vec.push(2));
+        drop(aslock.write());
+        let stats = aslock.op_stats();
+        assert_eq!(stats.ops["dropped"].replayed, 0);
+        assert_eq!(stats.ops["replayed"].replayed, 1);
+        assert_eq!(*aslock.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn update_tables_scoped() {
+        let aslock = AsLock::<Vec<String>>::default();
+        {
+            // Borrow a local which doesn't live for 'static.
+            let local = String::from("hello");
+            let mut wg = aslock.write();
+            let len = wg.update_tables_scoped(local.as_str(), |vec, s: &str| {
+                vec.push(s.to_string());
+                vec.len()
+            });
+            assert_eq!(len, 1);
+        }
+
+        assert_eq!(*aslock.read(), vec!["hello"]);
+        assert_eq!(*aslock.write(), vec!["hello"]);
+        assert_eq!(*aslock.read(), vec!["hello"]);
+    }
+
+    #[test]
+    fn pending_ops() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        {
+            let mut wg = aslock.write();
+            assert_eq!(wg.num_pending_ops(), 0);
+            assert_eq!(wg.publishing_cost_estimate(), 0);
+
+            wg.update_tables(PushVec { value: 2 });
+            wg.update_tables_closure(|vec| vec.push(3));
+            wg.update_tables_weighted(10, PushVec { value: 4 });
+            wg.update_tables_closure_weighted(5, |vec| vec.push(5));
+            assert_eq!(wg.num_pending_ops(), 4);
+            assert_eq!(wg.publishing_cost_estimate(), 17);
+        }
+
+        // The ops were replayed when creating the new guard.
+        let wg = aslock.write();
+        assert_eq!(*wg, vec![2, 3, 4, 5]);
+        assert_eq!(wg.num_pending_ops(), 0);
+        assert_eq!(wg.publishing_cost_estimate(), 0);
+    }
+
+    #[test]
+    fn from_identical_with() {
+        let options = crate::Options::default().max_pending_ops(Some(1));
+        let table = AsLock::from_identical_with(vec![1], vec![1], options);
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.try_update_tables_closure(|vec| vec.push(2)), Ok(()));
+            assert!(wg.try_update_tables_closure(|vec| vec.push(3)).is_err());
+        }
+        assert_eq!(*table.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn new_with_low_memory_mode() {
+        let options = crate::Options::default().low_memory_mode(true);
+        let table = AsLock::from_identical_with(vec![1], vec![], options);
+        assert!(table.is_low_memory_mode());
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(*table.read(), vec![1, 2]);
+
+        // Leaving low memory mode rebuilds the empty standby table.
+        table.set_low_memory_mode(false);
+        table.write().update_tables_closure(|vec| vec.push(3));
+        assert_eq!(*table.read(), vec![1, 2, 3]);
+        table.write().update_tables_closure(|vec| vec.push(4));
+        assert_eq!(*table.read(), vec![1, 2, 3, 4]);
+
+        assert!(!AsLock::new_with(vec![1], crate::Options::default()).is_low_memory_mode());
+    }
+
+    #[test]
+    fn from_fn() {
+        // Not Clone, so `new` isn't available.
+        #[derive(Debug, PartialEq)]
+        struct Conn(u32);
+
+        let mut built = 0;
+        let table = AsLock::from_fn(|| {
+            built += 1;
+            vec![Conn(1)]
+        });
+        assert_eq!(built, 2);
+
+        table.write().update_tables_closure(|vec| vec.push(Conn(2)));
+        assert_eq!(*table.read(), vec![Conn(1), Conn(2)]);
+        assert_eq!(*table.write(), vec![Conn(1), Conn(2)]);
+    }
+
+    #[test]
+    fn write_budgeted() {
+        let table = AsLock::new(vec![]);
+        {
+            let mut wg = table.write();
+            for i in 0..5 {
+                wg.update_tables_closure(move |vec| vec.push(i));
+            }
+        }
+        // Replays 2 of the 5 updates each time.
+        assert!(table.write_budgeted(2).is_none());
+        assert!(table.write_budgeted(2).is_none());
+        assert_eq!(*table.read(), vec![0, 1, 2, 3, 4]);
+        table
+            .write_budgeted(2)
+            .unwrap()
+            .update_tables_closure(|vec| vec.push(5));
+
+        table.write().update_tables_closure(|vec| vec.push(6));
+        assert!(table.write_budgeted(0).is_none());
+        // `write_full` finishes the replay regardless of the budget.
+        table.write_full().assert_synced();
+        assert_eq!(*table.read(), vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn write_timed() {
+        let table = AsLock::new(vec![]);
+        {
+            let mut wg = table.write();
+            for i in 0..3 {
+                wg.update_tables_closure(move |vec| {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    vec.push(i)
+                });
+            }
+        }
+        // Stops once the first update overruns the budget.
+        assert!(table.write_timed(std::time::Duration::ZERO).is_none());
+        assert!(table.write_budgeted(1).is_none());
+        let mut wg = table
+            .write_timed(std::time::Duration::from_secs(10))
+            .unwrap();
+        wg.assert_synced();
+        assert_eq!(*wg, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn read_racing_budgeted_write() {
+        let table = AsLock::new(vec![]);
+        // Loaded by a reader before the swap, so it is the standby table once
+        // locked.
+        let stale_index = table.active_index.load(Ordering::SeqCst);
+        {
+            let mut wg = table.write();
+            wg.update_tables_closure(|vec| vec.push(1));
+            wg.update_tables_closure(|vec| vec.push(2));
+        }
+        // Leaves the standby table half replayed.
+        assert!(table.write_budgeted(1).is_none());
+
+        let rg = table.lock_active(stale_index, |t| Some(t.read())).unwrap();
+        assert_eq!(*rg, vec![1, 2]);
+        assert_eq!(rg.generation(), table.current_generation());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn write_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let table = AsLock::new(vec![]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let rg = table.read();
+                sender.send(()).unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                drop(rg);
+            });
+            receiver.recv().unwrap();
+            // The reader is now left pointing at the standby table.
+            table.write().update_tables_closure(|vec| vec.push(1));
+
+            runtime.block_on(async {
+                let ticks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+                tokio::spawn({
+                    let ticks = Arc::clone(&ticks);
+                    async move {
+                        loop {
+                            ticks.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                });
+                let mut wg = table.write_async().await;
+                // Other tasks ran while waiting for the reader.
+                assert!(ticks.load(std::sync::atomic::Ordering::Relaxed) > 0);
+                assert_eq!(*wg, vec![1]);
+                wg.update_tables_closure(|vec| vec.push(2));
+            });
+        });
+        assert_eq!(*table.read(), vec![1, 2]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn write_async_backs_off() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let table = AsLock::new(vec![]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let rg = table.read();
+                sender.send(()).unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                drop(rg);
+            });
+            receiver.recv().unwrap();
+            // The reader is now left pointing at the standby table.
+            table.write().update_tables_closure(|vec| vec.push(1));
+
+            runtime.block_on(async {
+                let ticker = tokio::spawn(async {
+                    for _ in 0..5 {
+                        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+                    }
+                });
+                let mut polls = 0;
+                let mut write = std::pin::pin!(table.write_async());
+                let wg = std::future::poll_fn(|cx| {
+                    polls += 1;
+                    std::future::Future::poll(write.as_mut(), cx)
+                })
+                .await;
+                // Sleeps between tries, rather than spinning on the only
+                // worker thread, so the other task ran to completion.
+                assert!(polls < 20, "polled {} times", polls);
+                assert!(ticker.is_finished());
+                assert_eq!(*wg, vec![1]);
+            });
+        });
     }
-}
 
-impl<T: fmt::Debug> fmt::Debug for AsLock<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let num_ops_to_replay = self.ops_to_replay.lock().len();
-        f.debug_struct("AsLock")
-            .field("num_ops_to_replay", &num_ops_to_replay)
-            .field("standby_table", &*self.write())
-            .field("active_table", &*self.read())
-            .finish()
+    #[test]
+    fn into_inner() {
+        let table = AsLock::new(vec![1]);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(3));
+        assert_eq!(table.into_inner(), vec![1, 2, 3]);
+
+        let table = AsLock::new(vec![1]);
+        table.set_low_memory_mode(true);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(table.into_inner(), vec![1, 2]);
     }
-}
 
-impl<'w, T> AsLockWriteGuard<'w, T> {
-    /// Takes an update which will change the state of the underlying data. This
-    /// is done through the interface of UpdateTables.
-    ///
-    /// Users should never use the return value to directly mutate the tables,
-    /// since this will lead to them going out of sync.
-    ///
-    /// The update passed in must be valid for 'static because it will outlive
-    /// the AsLockWriteGuard taking the update, so we can't make any limitations on
-    /// it.
-    pub fn update_tables<'a, R>(
-        &'a mut self,
-        mut update: impl UpdateTables<'a, T, R> + 'static + Sized + Send,
-    ) -> R {
-        let res = update.apply_first(&mut self.guard);
+    #[test]
+    fn generation() {
+        let table = AsLock::new(vec![1]);
+        assert_eq!(table.read().generation(), 0);
+        assert_eq!(table.current_generation(), 0);
 
-        self.ops_to_replay.push(Box::new(move |table| {
-            update.apply_second(table);
-        }));
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(table.current_generation(), 1);
+        assert_eq!(table.read().generation(), 1);
 
-        res
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(3));
+        assert_eq!(table.read().generation(), 1);
+        assert!(table.publish());
+        assert_eq!(table.read().generation(), 2);
+
+        table.set_low_memory_mode(true);
+        table.write().update_tables_closure(|vec| vec.push(4));
+        let rg = table.read();
+        assert_eq!((rg.generation(), &*rg), (3, &vec![1, 2, 3, 4]));
     }
 
-    /// Like `update_tables` but allows the user to pass a closure for
-    /// convenience. Only allows return values that own their data.
-    ///
-    /// TODO: Consider allowing return values that have lifetimes, this should
-    /// likely be as safe as the explicit UpdateTables trait.
-    pub fn update_tables_closure<R>(
-        &mut self,
-        update: impl Fn(&mut T) -> R + 'static + Sized + Send,
-    ) -> R {
-        let res = update(&mut self.guard);
+    #[test]
+    fn snapshot() {
+        let table = AsLock::new(vec![1, 2]);
+        let snapshot = table.snapshot();
+        // Doesn't hold a read guard, so the writer isn't held up.
+        table.write().update_tables_closure(|vec| vec.push(3));
+        table.write().update_tables_closure(|vec| vec.push(4));
+        assert_eq!(snapshot, vec![1, 2]);
+        assert_eq!(table.snapshot(), vec![1, 2, 3, 4]);
+    }
 
-        self.ops_to_replay.push(Box::new(move |table| {
-            update(table);
-        }));
+    #[test]
+    fn stats() {
+        let table = AsLock::new(vec![]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let rg = table.read();
+                sender.send(()).unwrap();
+                thread::sleep(std::time::Duration::from_millis(20));
+                drop(rg);
+            });
+            receiver.recv().unwrap();
+            table.write().update_tables_closure(|vec| vec.push(1));
+            // Waits for the reader, which now points to the standby table.
+            table.write().update_tables_closure(|vec| vec.push(2));
+        });
 
-        res
+        let stats = table.stats();
+        assert_eq!(stats.num_readers, None);
+        assert_eq!(stats.num_ops_to_replay, Some(1));
+        assert_eq!(stats.num_publishes, 2);
+        assert_eq!(stats.num_waits, 1);
+        assert!(stats.max_wait >= std::time::Duration::from_millis(10));
+        assert_eq!(stats.total_wait, stats.max_wait);
+
+        let _wg = table.write();
+        assert_eq!(table.stats().num_ops_to_replay, None);
     }
-}
 
-impl<'w, T> Drop for AsLockWriteGuard<'w, T> {
-    fn drop(&mut self) {
-        // SAFETY: We must guarantee that all calls to AsLockWriteGuard::drop drop
-        // self.guard to unlock the table.
-        //
-        // SAFETY: We can guarantee that the guard is valid (and therefore safe
-        // to drop), because the value is only ever set on AsLockWriteGuard creation
-        // from a valid value and is never changed.
-        unsafe { ManuallyDrop::drop(&mut self.guard) };
+    #[test]
+    fn write_deferred() {
+        let table = AsLock::new(vec![]);
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(1));
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(2));
+        assert!(table.read().is_empty());
 
-        // Swap the tables after releasing the RwLockAsLockWriteGuard to guarantee
-        // reads are never blocked.
-        fence(Ordering::SeqCst);
+        assert!(table.publish());
+        assert!(!table.publish());
+        assert_eq!(*table.read(), vec![1, 2]);
 
-        let active_table = self.active_table.load(Ordering::SeqCst);
-        let standby_table = self.standby_table.load(Ordering::SeqCst);
-        assert_ne!(active_table, standby_table);
+        // A guard from `write` publishes the deferred updates too.
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(3));
+        table.write().update_tables_closure(|vec| vec.push(4));
+        assert_eq!(*table.read(), vec![1, 2, 3, 4]);
+        table.write().assert_synced();
 
-        // Swap the active and standby tables. These should never fail because
-        // there can only ever be 1 writer which spawns only 1 AsLockWriteGuard.
-        let res = self.active_table.compare_exchange(
-            active_table,
-            standby_table,
-            Ordering::SeqCst,
-            Ordering::SeqCst,
-        );
-        assert_eq!(res, Ok(active_table));
+        // Dropping the standby table publishes it first.
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(5));
+        table.set_low_memory_mode(true);
+        assert_eq!(*table.read(), vec![1, 2, 3, 4, 5]);
+    }
 
-        let res = self.standby_table.compare_exchange(
-            standby_table,
-            active_table,
-            Ordering::SeqCst,
-            Ordering::SeqCst,
-        );
-        assert_eq!(res, Ok(standby_table));
+    #[test]
+    fn read_racing_deferred_write() {
+        let table = AsLock::new(vec![]);
+        // Loaded by a reader before the swap, so it is the standby table once
+        // locked.
+        let stale_index = table.active_index.load(Ordering::SeqCst);
+        table.write().update_tables_closure(|vec| vec.push(1));
+        // Releases the standby table with an unpublished update.
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(2));
 
-        // Only after swapping the tables should we drop the Mutex to
-        // `ops_to_replay`, allowing a new AsLockWriteGuard.
+        let rg = table.lock_active(stale_index, |t| Some(t.read())).unwrap();
+        assert_eq!(*rg, vec![1]);
+        assert_eq!(rg.generation(), table.current_generation());
+        drop(rg);
+        // Later reads don't go back in time.
+        assert_eq!(*table.read(), vec![1]);
+        assert!(table.publish());
+        assert_eq!(*table.read(), vec![1, 2]);
     }
-}
 
-impl<'w, T> std::ops::Deref for AsLockWriteGuard<'w, T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &**self.guard
+    #[test]
+    fn try_write() {
+        let table = AsLock::new(vec![]);
+        let rg = table.read();
+        // Publishing leaves `rg` pointing to the standby table.
+        table.write().update_tables_closure(|vec| vec.push(1));
+        assert!(table.try_write().is_none());
+        drop(rg);
+
+        {
+            let mut wg = table.try_write().unwrap();
+            wg.update_tables_closure(|vec| vec.push(2));
+            // Another guard exists.
+            assert!(table.try_write().is_none());
+        }
+        assert_eq!(*table.read(), vec![1, 2]);
+
+        // In low memory mode, any reader blocks the writer.
+        table.set_low_memory_mode(true);
+        let rg = table.read();
+        assert!(table.try_write().is_none());
+        drop(rg);
+        assert!(table.try_write().is_some());
     }
-}
 
-impl<'w, T: fmt::Debug> fmt::Debug for AsLockWriteGuard<'w, T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use std::ops::Deref;
-        f.debug_struct("AsLockWriteGuard")
-            .field("num_ops_to_replay", &self.ops_to_replay.len())
-            .field("standby_table", self.deref())
-            .finish()
+    #[test]
+    fn tables_eq() {
+        let table = AsLock::new(vec![1]);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert!(table.tables_eq());
+
+        // An update which isn't deterministic diverges the tables.
+        let counter = std::sync::atomic::AtomicI32::new(0);
+        table.write().update_tables_closure(move |vec| {
+            vec.push(counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+        });
+        assert!(!table.tables_eq());
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::thread;
+    #[test]
+    fn tables_eq_doesnt_publish() {
+        let table = AsLock::new(vec![1]);
+        table.write().update_tables_closure(|vec| vec.push(2));
+        let generation = table.current_generation();
+        assert!(table.tables_eq());
+        assert_eq!(table.current_generation(), generation);
 
-    struct PushVec<T> {
-        value: T,
+        // Deferred updates stay unpublished.
+        table
+            .write_deferred()
+            .update_tables_closure(|vec| vec.push(3));
+        assert!(table.tables_eq());
+        assert_eq!(table.current_generation(), generation);
+        assert_eq!(*table.read(), vec![1, 2]);
+        assert!(table.publish());
+        assert_eq!(*table.read(), vec![1, 2, 3]);
+        assert!(table.tables_eq());
     }
-    impl<'a, T> UpdateTables<'a, Vec<T>, ()> for PushVec<T>
-    where
-        T: Clone,
-    {
-        fn apply_first(&mut self, table: &'a mut Vec<T>) {
-            table.push(self.value.clone());
+
+    #[cfg(feature = "failpoints")]
+    #[test]
+    fn failpoints() {
+        // Other tests may hit the failpoints concurrently, so only record the
+        // hits from this thread.
+        thread_local! {
+            static HITS: std::cell::RefCell<Vec<&'static str>> = Default::default();
         }
-        fn apply_second(self, table: &mut Vec<T>) {
-            table.push(self.value); // Move the value instead of cloning.
+        let scenario = fail::FailScenario::setup();
+        for name in [
+            "active_standby::before_swap",
+            "active_standby::replay",
+            "active_standby::await_readers",
+        ] {
+            fail::cfg_callback(name, move || HITS.with(|hits| hits.borrow_mut().push(name)))
+                .unwrap();
         }
-    }
 
-    struct PopVec {}
-    impl PopVec {
-        fn apply<T>(&mut self, table: &mut Vec<T>) -> Option<T> {
-            table.pop()
-        }
+        let table = AsLock::new(vec![]);
+        table.write().update_tables_closure(|vec| vec.push(1));
+        table.write().update_tables_closure(|vec| vec.push(2));
+        assert_eq!(
+            HITS.with(|hits| hits.take()),
+            [
+                "active_standby::await_readers",
+                "active_standby::before_swap",
+                "active_standby::await_readers",
+                "active_standby::replay",
+                "active_standby::before_swap",
+            ]
+        );
+        scenario.teardown();
     }
-    impl<'a, T> UpdateTables<'a, Vec<T>, Option<T>> for PopVec {
-        fn apply_first(&mut self, table: &'a mut Vec<T>) -> Option<T> {
-            self.apply(table)
-        }
-        fn apply_second(mut self, table: &mut Vec<T>) {
-            (&mut self).apply(table);
+
+    #[test]
+    fn update_tables_keyed() {
+        let table = AsLock::new(vec![0, 0]);
+        let set = |index: usize, value: i32| {
+            crate::ClosureOp::new(value, move |vec: &mut Vec<i32>, value| vec[index] = value)
+        };
+        {
+            let mut wg = table.write();
+            for value in 1..=3 {
+                wg.update_tables_keyed("first", set(0, value));
+                wg.update_tables_keyed("second", set(1, value * 10));
+            }
+            wg.update_tables(PushVec { value: 4 });
+            wg.update_tables_keyed("first", set(0, 5));
+            assert_eq!(wg.num_pending_ops(), 3);
+            assert_eq!(wg.publishing_cost_estimate(), 3);
+            wg.assert_synced();
+
+            // Updates which were already replayed aren't replaced.
+            wg.update_tables_keyed("first", set(0, 6));
+            assert_eq!(wg.num_pending_ops(), 1);
         }
+        let mut wg = table.write();
+        wg.assert_synced();
+        assert_eq!(*wg, vec![6, 30, 4]);
     }
 
-    /// This is an example of what not to do!
-    struct MutableRef {}
-    impl<'a, T> UpdateTables<'a, Vec<T>, &'a mut T> for MutableRef {
-        fn apply_first(&mut self, table: &'a mut Vec<T>) -> &'a mut T {
-            &mut table[0]
+    #[cfg(feature = "op-stats")]
+    #[test]
+    fn op_stats_update_tables_keyed() {
+        struct Set(i32);
+        impl<'a> UpdateTables<'a, Vec<i32>, ()> for Set {
+            fn apply_first(&mut self, table: &'a mut Vec<i32>) {
+                table[0] = self.0;
+            }
+            fn apply_second(self, table: &mut Vec<i32>) {
+                table[0] = self.0;
+            }
         }
-        fn apply_second(self, table: &mut Vec<T>) {
-            let _ = &mut table[0];
+
+        let table = AsLock::new(vec![0]);
+        {
+            let mut wg = table.write();
+            wg.update_tables_keyed("k", Set(1));
+            wg.update_tables_keyed("k", Set(2));
+            wg.update_tables_labeled("other", Set(3));
         }
-    }
+        drop(table.write());
 
-    #[test]
-    fn one_write_guard() {
-        // TODO: Have a multithreaded test for this.
-        let writer = AsLock::<Vec<i32>>::default();
-        let _wg = writer.write();
-        // let wg2 = writer.write();
+        // The replaced update is counted, but never replayed, and doesn't
+        // take the label of the updates after it.
+        let stats = table.op_stats();
+        let set = stats.ops[std::any::type_name::<Set>()];
+        assert_eq!((set.count, set.replayed), (2, 1));
+        assert_eq!(stats.ops["other"].replayed, 1);
+        assert_eq!(*table.read(), vec![3]);
     }
 
     #[test]
-    fn publish_update() {
-        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
-        assert_eq!(aslock.read().len(), 0);
+    fn try_update_tables() {
+        let table = AsLock::<Vec<i32>>::default();
+        {
+            let mut wg = table.write();
+            assert_eq!(wg.try_update_tables(PushVec { value: 1 }), Ok(()));
+        }
 
+        table.set_max_pending_ops(Some(2));
         {
-            let mut wg = aslock.write();
-            wg.update_tables(PushVec { value: 2 });
-            assert_eq!(wg.len(), 1);
-            {
-                // Perform check in another thread to avoid potential deadlock
-                // (calling both read and write on aslock at the same time).
-                let aslock = Arc::clone(&aslock);
-                assert!(thread::spawn(move || {
-                    assert_eq!(aslock.read().len(), 0);
-                })
-                .join()
-                .is_ok());
-            }
+            let mut wg = table.write();
+            assert_eq!(wg.try_update_tables(PushVec { value: 2 }), Ok(()));
+            wg.update_tables_closure(|vec| vec.push(3));
+            let full = Err(crate::PendingOpsFull { max_pending_ops: 2 });
+            assert_eq!(wg.try_update_tables(PushVec { value: 4 }), full);
+            assert_eq!(wg.try_update_tables_closure(|vec| vec.push(4)), full);
+            assert_eq!(wg.num_pending_ops(), 2);
         }
 
-        // When the write guard is dropped it publishes the changes to the readers.
-        assert_eq!(*aslock.read(), vec![2]);
+        // A new guard starts without pending ops.
+        assert_eq!(
+            table.write().try_update_tables_closure(|vec| vec.push(4)),
+            Ok(())
+        );
+        table.set_max_pending_ops(None);
+        assert_eq!(*table.write(), vec![1, 2, 3, 4]);
     }
 
     #[test]
-    fn update_tables_closure() {
-        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
-        assert_eq!(aslock.read().len(), 0);
+    fn defer_drop() {
+        struct OnDrop<F: FnMut()>(F);
+        impl<F: FnMut()> Drop for OnDrop<F> {
+            fn drop(&mut self) {
+                (self.0)()
+            }
+        }
 
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        let dropped = Arc::new(AtomicUsize::new(0));
         {
             let mut wg = aslock.write();
             wg.update_tables_closure(|vec| vec.push(2));
-            assert_eq!(wg.len(), 1);
-            {
-                // Perform check in another thread to avoid potential deadlock
-                // (calling both read and write on aslock at the same time).
+
+            wg.defer_drop({
                 let aslock = Arc::clone(&aslock);
-                assert!(thread::spawn(move || {
-                    assert_eq!(aslock.read().len(), 0);
+                let dropped = Arc::clone(&dropped);
+                OnDrop(move || {
+                    // The update is published, and the write lock released.
+                    assert_eq!(*aslock.read(), vec![2]);
+                    assert_eq!(*aslock.write(), vec![2]);
+                    dropped.fetch_add(1, Ordering::SeqCst);
                 })
-                .join()
-                .is_ok());
-            }
+            });
+            assert_eq!(dropped.load(Ordering::SeqCst), 0);
         }
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
 
-        // When the write guard is dropped it publishes the changes to the readers.
-        assert_eq!(*aslock.read(), vec![2]);
+    #[test]
+    fn same_table() {
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        let aslock2 = Arc::clone(&aslock);
+        assert!(AsLock::same_table(&aslock, &aslock2));
+        assert!(!AsLock::same_table(&aslock, &AsLock::<Vec<i32>>::default()));
+    }
+
+    #[test]
+    fn read_guard_is_sync() {
+        fn assert_sync<T: Sync>(_: &T) {}
+
+        let aslock = AsLock::new(vec![1, 2, 3, 4]);
+        let guard = aslock.read();
+        assert_sync(&guard);
+
+        let sums: Vec<i32> = thread::scope(|scope| {
+            let guard = &guard;
+            let handles: Vec<_> = (0..2)
+                .map(|i| scope.spawn(move || guard[i * 2..i * 2 + 2].iter().sum()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(sums, vec![3, 7]);
     }
 
     #[test]
@@ -432,6 +2530,154 @@ mod test {
         assert!(handler.join().is_ok());
     }
 
+    #[test]
+    fn mutable_ref() {
+        let aslock = AsLock::<Vec<i32>>::default();
+
+        {
+            // Show that without giving a mutable interface we can still mutate
+            // the underlying values in the table which will cause them to lose
+            // consistency.
+            let mut wg = aslock.write();
+            wg.update_tables(PushVec { value: 2 });
+            let mr = wg.update_tables(MutableRef {});
+            *mr = 10;
+        }
+
+        assert_eq!(*aslock.read(), vec![10]);
+
+        // This is bad and something clients must avoid. See comment on
+        // UpdateTables trait for why this cannot be enforced by the library.
+        assert_ne!(*aslock.read(), *aslock.write());
+    }
+
+    #[test]
+    fn assert_synced() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        let mut wg = aslock.write();
+        wg.update_tables(PushVec { value: 2 });
+        wg.assert_synced();
+        // The updates have been published, and the guard remains usable.
+        wg.update_tables(PushVec { value: 3 });
+        wg.assert_synced();
+        drop(wg);
+        assert_eq!(*aslock.read(), vec![2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "active and standby tables have diverged")]
+    fn assert_synced_diverged() {
+        let aslock = AsLock::<Vec<i32>>::default();
+        let mut wg = aslock.write();
+        wg.update_tables(PushVec { value: 2 });
+        *wg.update_tables(MutableRef {}) = 10;
+        wg.assert_synced();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(
+        expected = "AsLock::read called from within an update applied to the same AsLock"
+    )]
+    fn read_within_update() {
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        let inner = Arc::clone(&aslock);
+        aslock
+            .write()
+            .update_tables_closure(move |table| table.push(inner.read().len() as i32));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn write_within_replay() {
+        let aslock = Arc::new(AsLock::<Vec<i32>>::default());
+        let inner = Arc::clone(&aslock);
+        let first = std::sync::atomic::AtomicBool::new(true);
+        aslock.write().update_tables_closure(move |table| {
+            // Only reenter when the update is replayed.
+            if !first.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                drop(inner.write());
+            }
+            table.push(1);
+        });
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            drop(aslock.write());
+        }));
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        assert!(
+            message.starts_with("AsLock::write called from within"),
+            "{}",
+            message
+        );
+
+        // Other AsLocks are unaffected.
+        let other = AsLock::new(vec![1]);
+        other.write().update_tables_closure(|table| table.push(2));
+        assert_eq!(*other.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn try_read() {
+        let table = AsLock::new(vec![1]);
+        {
+            // Writing doesn't lock the active table.
+            let _wg = table.write();
+            assert_eq!(*table.try_read().unwrap(), vec![1]);
+        }
+
+        table.set_low_memory_mode(true);
+        let wg = table.write();
+        assert!(table.try_read().is_none());
+        assert!(table
+            .read_timeout(std::time::Duration::from_millis(1))
+            .is_none());
+        drop(wg);
+        let rg = table.read_timeout(std::time::Duration::from_secs(1));
+        assert_eq!(*rg.unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn low_memory_mode() {
+        let table = AsLock::new(vec![1]);
+        table.write().update_tables(PushVec { value: 2 });
+        table.set_low_memory_mode(true);
+        assert!(table.is_low_memory_mode());
+        {
+            let mut wg = table.write();
+            wg.update_tables(PushVec { value: 3 });
+            wg.update_tables_closure(|table| table.push(4));
+            assert_eq!(wg.num_pending_ops(), 0);
+        }
+        assert_eq!(*table.read(), vec![1, 2, 3, 4]);
+        assert_eq!(
+            format!("{:?}", table),
+            "AsLock { num_ops_to_replay: 0, active_table: [1, 2, 3, 4] }"
+        );
+        // The standby table was dropped.
+        assert!(table.tables.iter().any(|table| table.read().is_empty()));
+
+        table.set_low_memory_mode(false);
+        assert!(!table.is_low_memory_mode());
+        table.write().update_tables(PushVec { value: 5 });
+        assert_eq!(*table.read(), vec![1, 2, 3, 4, 5]);
+        table.write().assert_synced();
+    }
+
+    #[test]
+    fn read_racing_low_memory_reset() {
+        let table = AsLock::new(vec![1]);
+        // Loaded by a reader before the swap, so it is the standby table once
+        // locked.
+        let stale_index = table.active_index.load(Ordering::SeqCst);
+        table.write().update_tables(PushVec { value: 2 });
+        // Resets the standby table to an empty vec.
+        table.set_low_memory_mode(true);
+
+        let rg = table.lock_active(stale_index, |t| Some(t.read())).unwrap();
+        assert_eq!(*rg, vec![1, 2]);
+    }
+
     #[test]
     fn debug_str() {
         let aslock = AsLock::<Vec<i32>>::default();
@@ -451,9 +2697,19 @@ mod test {
             format!("{:?}", aslock),
             "AsLock { num_ops_to_replay: 1, standby_table: [2], active_table: [2] }"
         );
-        // The aliased sync lock shows up in this debug. What we mostly care
-        // about is that this says AsLockReadGuard and shows the underlying data. It's
-        // fine to update this if we ever change the underlying RwLock.
         assert_eq!(format!("{:?}", aslock.read()), "[2]");
     }
+
+    #[test]
+    fn read_guard_cloned() {
+        let aslock = AsLock::new(vec![1, 2]);
+        let rg = aslock.read();
+        assert_eq!(rg.cloned(), vec![1, 2]);
+        assert_eq!(rg.to_owned(), vec![1, 2]);
+
+        let table = AsLock::new(3);
+        let rg = table.read();
+        let cow: std::borrow::Cow<'_, i32> = (&rg).into();
+        assert!(matches!(cow, std::borrow::Cow::Borrowed(3)));
+    }
 }
@@ -0,0 +1,203 @@
+//! Storage for the lockless tables, and the pointers which Readers use to find
+//! the active one.
+//!
+//! By default the Writer owns the tables as `Box`es and Readers hold raw
+//! pointers to them, trusting the epoch protocol for safety. With the
+//! `safe-impl` feature the tables are instead reference counted, and Readers
+//! load them through `arc_swap`, so that this crate contains no `unsafe` code.
+//! This costs reference counting on every read.
+
+#[cfg(not(feature = "safe-impl"))]
+mod engine {
+    use crate::types::*;
+
+    /// A table owned by the Writer.
+    pub struct Table<T>(Box<T>);
+
+    /// The table which a Reader reads from.
+    pub struct TablePtr<T>(AtomicPtr<T>);
+
+    /// Borrow of a table held by an AsLockReadGuard.
+    pub type TableRef<'r, T> = &'r T;
+
+    /// Pointer to a table held by an AsLockOwnedReadGuard, which keeps the
+    /// Writer alive itself instead of borrowing it.
+    pub struct OwnedTableRef<T>(*const T);
+
+    // Shares the table like `TableRef`.
+    unsafe impl<T: Sync> Send for OwnedTableRef<T> {}
+    unsafe impl<T: Sync> Sync for OwnedTableRef<T> {}
+
+    /// Borrow the table held by an OwnedTableRef, as if it was loaded from
+    /// its TablePtr.
+    pub fn borrow_owned<T>(table: &OwnedTableRef<T>) -> TableRef<'_, T> {
+        table
+    }
+
+    impl<T> std::ops::Deref for OwnedTableRef<T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            // SAFETY: See `TablePtr::load`. The AsLockOwnedReadGuard keeps the
+            // Writer, and so the table, alive for as long as this exists.
+            unsafe { &*self.0 }
+        }
+    }
+
+    impl<T> Table<T> {
+        pub fn new(t: T) -> Table<T> {
+            Table(Box::new(t))
+        }
+
+        /// Only called once there are no Readers left pointing to the table.
+        pub fn into_inner(self) -> T {
+            *self.0
+        }
+
+        fn as_ptr(&self) -> *mut T {
+            &*self.0 as *const T as *mut T
+        }
+    }
+
+    impl<T> std::ops::Deref for Table<T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<T> std::ops::DerefMut for Table<T> {
+        fn deref_mut(&mut self) -> &mut T {
+            &mut self.0
+        }
+    }
+
+    impl<T> TablePtr<T> {
+        pub fn new(table: &Table<T>) -> TablePtr<T> {
+            TablePtr(AtomicPtr::new(table.as_ptr()))
+        }
+
+        pub fn load(&self) -> TableRef<'_, T> {
+            // SAFETY: Memory safety (valid pointer) is guaranteed by
+            // AsLockHandle/Writer, which enforce that the tables are created
+            // before any Reader exists and dropped only after all readers.
+            // Further the tables themselves are never moved in memory.
+            //
+            // SAFETY: Thread safety is what must be handled by us manually. The
+            // `epoch` counter by the Reader and `await_standby_table_free` by
+            // the Writer.
+            unsafe { &*self.0.load(Ordering::SeqCst) }
+        }
+
+        /// Like `load`, but not tied to the lifetime of the TablePtr. The
+        /// caller must keep the Writer alive while the OwnedTableRef exists.
+        pub fn load_owned(&self) -> OwnedTableRef<T> {
+            OwnedTableRef(self.0.load(Ordering::SeqCst))
+        }
+
+        /// A new pointer to the table this one points to.
+        pub fn duplicate(&self) -> TablePtr<T> {
+            TablePtr(AtomicPtr::new(self.0.load(Ordering::SeqCst)))
+        }
+
+        /// Point the Reader at `new` instead of `current`.
+        pub fn replace(&self, current: &Table<T>, new: &Table<T>) {
+            let res = self.0.compare_exchange(
+                current.as_ptr(),
+                new.as_ptr(),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+            assert_eq!(res, Ok(current.as_ptr()));
+        }
+    }
+}
+
+#[cfg(feature = "safe-impl")]
+mod engine {
+    use arc_swap::ArcSwap;
+    use std::sync::Arc;
+
+    /// A table owned by the Writer. Readers hold references to the active
+    /// table, but never to the standby table once they have left it.
+    pub struct Table<T>(Arc<T>);
+
+    /// The table which a Reader reads from.
+    pub struct TablePtr<T>(ArcSwap<T>);
+
+    /// Reference to a table held by an AsLockReadGuard.
+    pub type TableRef<'r, T> = arc_swap::Guard<Arc<T>>;
+
+    /// Reference to a table held by an AsLockOwnedReadGuard.
+    pub type OwnedTableRef<T> = Arc<T>;
+
+    /// Borrow the table held by an OwnedTableRef, as if it was loaded from
+    /// its TablePtr.
+    pub fn borrow_owned<T>(table: &OwnedTableRef<T>) -> TableRef<'_, T> {
+        arc_swap::Guard::from_inner(Arc::clone(table))
+    }
+
+    impl<T> Table<T> {
+        pub fn new(t: T) -> Table<T> {
+            Table(Arc::new(t))
+        }
+
+        /// Only called once there are no Readers left holding the table.
+        pub fn into_inner(self) -> T {
+            Arc::try_unwrap(self.0)
+                .ok()
+                .expect("table is shared after its Readers were dropped")
+        }
+    }
+
+    impl<T> std::ops::Deref for Table<T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<T> std::ops::DerefMut for Table<T> {
+        fn deref_mut(&mut self) -> &mut T {
+            // The Writer only mutates the standby table once Readers have
+            // released it according to their epochs, but a Reader updates its
+            // epoch just before dropping its reference.
+            while Arc::strong_count(&self.0) > 1 {
+                std::hint::spin_loop();
+            }
+            Arc::get_mut(&mut self.0).expect("standby table is shared")
+        }
+    }
+
+    impl<T> TablePtr<T> {
+        pub fn new(table: &Table<T>) -> TablePtr<T> {
+            TablePtr(ArcSwap::new(Arc::clone(&table.0)))
+        }
+
+        pub fn load(&self) -> TableRef<'_, T> {
+            self.0.load()
+        }
+
+        pub fn load_owned(&self) -> OwnedTableRef<T> {
+            self.0.load_full()
+        }
+
+        /// A new pointer to the table this one points to.
+        pub fn duplicate(&self) -> TablePtr<T> {
+            TablePtr(ArcSwap::new(self.0.load_full()))
+        }
+
+        /// Point the Reader at `new` instead of `current`.
+        pub fn replace(&self, current: &Table<T>, new: &Table<T>) {
+            let old = self.0.swap(Arc::clone(&new.0));
+            assert!(Arc::ptr_eq(&old, &current.0));
+        }
+    }
+}
+
+pub(crate) use engine::{borrow_owned, OwnedTableRef, Table, TablePtr, TableRef};
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Table<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        T::fmt(self, f)
+    }
+}
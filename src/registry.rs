@@ -0,0 +1,288 @@
+//! Process wide registry of active_standby tables.
+//!
+//! Tables opt in by calling `register`, or `register_sized` to also report
+//! their size, on the `AsLockHandle`/`AsLock`. The registry only holds weak
+//! references to the tables, so registering doesn't extend their lifetime;
+//! dropped tables are removed the next time the registry is listed.
+//!
+//! Inspecting a table never blocks on its writer. If a write guard is held
+//! when listing, the fields which require the writer's lock are `None`.
+//! Tables are formatted after releasing the registry, so a slow `Debug`
+//! doesn't hold up registering or listing from other threads.
+
+/// The state of a registered table at the time `tables` was called.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredTable {
+    /// Name given when registering the table.
+    pub name: String,
+    /// Name of the type of the underlying table.
+    pub type_name: &'static str,
+    /// Number of readers. Only tracked by the lockless flavor.
+    pub num_readers: Option<usize>,
+    /// Number of ops waiting to be replayed on the standby table.
+    pub num_ops_to_replay: Option<usize>,
+    /// Number of times the table has been published. See
+    /// `current_generation`.
+    pub version: u64,
+    /// When the table was last published, or `None` if it never has been.
+    pub last_publish: Option<std::time::SystemTime>,
+    /// Size of the active table, as reported by `Sizer`. Only filled in for
+    /// tables registered with `register_sized`.
+    pub size: Option<usize>,
+    /// Debug representation of the active table.
+    pub active_table: Option<String>,
+}
+
+/// What a registered table reports about itself.
+pub(crate) struct TableState {
+    pub num_readers: Option<usize>,
+    pub num_ops_to_replay: Option<usize>,
+    pub version: u64,
+    pub last_publish: Option<std::time::SystemTime>,
+    pub size: Option<usize>,
+    pub active_table: Option<String>,
+}
+
+/// Returns `None` once the table has been dropped. Otherwise returns a
+/// closure holding onto the table, which inspects it once the registry is
+/// unlocked, so that formatting the tables doesn't hold up other callers.
+type Upgrade = Box<dyn Fn() -> Option<Box<dyn FnOnce() -> TableState>> + Send + Sync>;
+
+struct Entry {
+    // Address of the table, to recognize it being registered again.
+    id: usize,
+    name: String,
+    type_name: &'static str,
+    upgrade: Upgrade,
+}
+
+// Not part of the active_standby synchronization protocol, so always use std
+// (even under loom).
+static REGISTRY: std::sync::Mutex<Vec<Entry>> = std::sync::Mutex::new(Vec::new());
+
+fn registry() -> std::sync::MutexGuard<'static, Vec<Entry>> {
+    // The registry's invariants can't be broken by a panic, so ignore poison.
+    REGISTRY
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Registering a table which is already registered replaces its entry, e.g.
+/// to rename it.
+pub(crate) fn register(
+    id: usize,
+    name: String,
+    type_name: &'static str,
+    upgrade: impl Fn() -> Option<Box<dyn FnOnce() -> TableState>> + Send + Sync + 'static,
+) {
+    let entry = Entry {
+        id,
+        name,
+        type_name,
+        upgrade: Box::new(upgrade),
+    };
+    let mut registry = registry();
+    match registry.iter_mut().find(|entry| entry.id == id) {
+        Some(registered) => *registered = entry,
+        None => registry.push(entry),
+    }
+}
+
+/// Register a sync table which is wrapped by another type, such as the
+/// `AsLock` types generated by `generate_sync_aslock`.
+pub fn register_sync<W, T>(name: impl Into<String>, table: &std::sync::Arc<W>)
+where
+    W: 'static + std::ops::Deref<Target = crate::sync::AsLock<T>> + Send + Sync,
+    T: 'static + std::fmt::Debug + Send + Sync,
+{
+    register_sync_with(name.into(), table, |table| table, |_| None)
+}
+
+/// Like `register_sync`, but also reports the size of the table.
+pub fn register_sync_sized<W, T>(name: impl Into<String>, table: &std::sync::Arc<W>)
+where
+    W: 'static + std::ops::Deref<Target = crate::sync::AsLock<T>> + Send + Sync,
+    T: 'static + std::fmt::Debug + crate::inspect::Sizer + Send + Sync,
+{
+    register_sync_with(
+        name.into(),
+        table,
+        |table| table,
+        |table| Some(table.size()),
+    )
+}
+
+/// Shared by `AsLock::register` and `register_sync`. `as_lock` gets the
+/// AsLock out of `table`, and `size` reports the size of the active table.
+pub(crate) fn register_sync_with<W, T>(
+    name: String,
+    table: &std::sync::Arc<W>,
+    as_lock: fn(&W) -> &crate::sync::AsLock<T>,
+    size: fn(&T) -> Option<usize>,
+) where
+    W: 'static + Send + Sync,
+    T: 'static + std::fmt::Debug + Send + Sync,
+{
+    // The address of the AsLock, so that registering a wrapper and the
+    // AsLock inside it are recognized as the same table.
+    let id = as_lock(table) as *const crate::sync::AsLock<T> as usize;
+    let table = std::sync::Arc::downgrade(table);
+    register(id, name, std::any::type_name::<T>(), move || {
+        let table = table.upgrade()?;
+        Some(Box::new(move || as_lock(&table).registry_state(size)))
+    });
+}
+
+/// List all live tables which have been registered.
+pub fn tables() -> Vec<RegisteredTable> {
+    let mut entries = vec![];
+    registry().retain(|entry| match (entry.upgrade)() {
+        None => false,
+        Some(inspect) => {
+            entries.push((entry.name.clone(), entry.type_name, inspect));
+            true
+        }
+    });
+
+    entries
+        .into_iter()
+        .map(|(name, type_name, inspect)| {
+            let state = inspect();
+            RegisteredTable {
+                name,
+                type_name,
+                num_readers: state.num_readers,
+                num_ops_to_replay: state.num_ops_to_replay,
+                version: state.version,
+                last_publish: state.last_publish,
+                size: state.size,
+                active_table: state.active_table,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    // The registry is global and tests run in parallel, so only look at the
+    // tables registered by the test itself.
+    fn find(name: &str) -> Option<RegisteredTable> {
+        tables().into_iter().find(|table| table.name == name)
+    }
+
+    #[test]
+    fn lockless_register() {
+        let table = crate::lockless::AsLockHandle::new(vec![1]);
+        table.register("lockless_register");
+        assert_eq!(find("lockless_register").unwrap().last_publish, None);
+        table.write().update_tables_closure(|vec| vec.push(2));
+
+        let registered = find("lockless_register").unwrap();
+        assert!(registered.last_publish.is_some());
+        assert_eq!(
+            registered,
+            RegisteredTable {
+                name: "lockless_register".to_string(),
+                type_name: "alloc::vec::Vec<i32>",
+                num_readers: Some(1),
+                num_ops_to_replay: Some(1),
+                version: 1,
+                last_publish: registered.last_publish,
+                size: None,
+                active_table: Some("[1, 2]".to_string()),
+            }
+        );
+
+        // Holding a write guard doesn't block inspecting the table.
+        {
+            let _wg = table.write();
+            let registered = find("lockless_register").unwrap();
+            assert_eq!(registered.version, 1);
+            assert_eq!(registered.num_ops_to_replay, None);
+            assert_eq!(registered.active_table, None);
+        }
+
+        drop(table);
+        assert_eq!(find("lockless_register"), None);
+    }
+
+    #[test]
+    fn sync_register() {
+        let table = Arc::new(crate::sync::AsLock::new(vec![1]));
+        table.register("sync_register");
+        table.write().update_tables_closure(|vec| vec.push(2));
+
+        let registered = find("sync_register").unwrap();
+        assert!(registered.last_publish.is_some());
+        assert_eq!(
+            registered,
+            RegisteredTable {
+                name: "sync_register".to_string(),
+                type_name: "alloc::vec::Vec<i32>",
+                num_readers: None,
+                num_ops_to_replay: Some(1),
+                version: 1,
+                last_publish: registered.last_publish,
+                size: None,
+                active_table: Some("[1, 2]".to_string()),
+            }
+        );
+
+        // In low memory mode the write guard holds the only table, which
+        // isn't waited on.
+        table.set_low_memory_mode(true);
+        {
+            let _wg = table.write();
+            let registered = find("sync_register").unwrap();
+            assert_eq!(registered.num_ops_to_replay, None);
+            assert_eq!(registered.active_table, None);
+        }
+
+        drop(table);
+        assert_eq!(find("sync_register"), None);
+    }
+
+    #[test]
+    fn register_sized() {
+        let lockless = crate::lockless::AsLockHandle::new(vec![1, 2]);
+        lockless.register_sized("register_sized_lockless");
+        assert_eq!(find("register_sized_lockless").unwrap().size, Some(2));
+
+        let sync = Arc::new(crate::sync::AsLock::new(vec![1, 2, 3]));
+        sync.register_sized("register_sized_sync");
+        assert_eq!(find("register_sized_sync").unwrap().size, Some(3));
+    }
+
+    #[test]
+    fn register_twice() {
+        let table = crate::lockless::AsLockHandle::new(vec![1]);
+        table.register("register_twice");
+        table.clone().register("register_twice_renamed");
+        assert_eq!(find("register_twice"), None);
+        assert!(find("register_twice_renamed").is_some());
+
+        let table = Arc::new(crate::sync::collections::AsVec::new(vec![1]));
+        register_sync("register_twice_sync", &table);
+        register_sync("register_twice_sync", &table);
+        let num_registered = tables()
+            .into_iter()
+            .filter(|table| table.name == "register_twice_sync")
+            .count();
+        assert_eq!(num_registered, 1);
+    }
+
+    #[test]
+    fn register_generated() {
+        let table = Arc::new(crate::sync::collections::AsVec::new(vec![1]));
+        register_sync("register_generated", &table);
+        assert_eq!(
+            find("register_generated").unwrap().active_table,
+            Some("[1]".to_string())
+        );
+        register_sync_sized("register_generated", &table);
+        assert_eq!(find("register_generated").unwrap().size, Some(1));
+    }
+}
@@ -0,0 +1,544 @@
+//! Background loop for keeping a table up to date with an external source,
+//! such as a config file.
+//!
+//! A `Reload` is built from a loader, which produces a new version of the
+//! table or an error, and is then spawned on a table. The loader is called
+//! once when the loop is spawned, every `period` if one is set, and whenever
+//! the loop is triggered. When the loader succeeds, its result replaces the
+//! table and is published, unless it's equal to the active table. Failures
+//! leave the table untouched and are recorded in the `ReloadStatus`.
+//!
+//! ```rust
+//! use active_standby::reload::Reload;
+//! use active_standby::sync::AsLock;
+//! use std::sync::atomic::{AtomicI32, Ordering};
+//! use std::sync::Arc;
+//!
+//! // Stand in for reading and parsing a config file.
+//! let source = Arc::new(AtomicI32::new(1));
+//! let loader = {
+//!     let source = Arc::clone(&source);
+//!     move || match source.load(Ordering::SeqCst) {
+//!         value if value >= 0 => Ok(value),
+//!         _ => Err("negative value"),
+//!     }
+//! };
+//!
+//! let table = Arc::new(AsLock::new(0));
+//! let reloader = Reload::new(loader).spawn_sync(Arc::clone(&table));
+//! assert_eq!(*table.read(), 1);
+//!
+//! source.store(-1, Ordering::SeqCst);
+//! let status = reloader.reload_and_wait();
+//! assert_eq!(status.last_error, Some("negative value"));
+//! assert_eq!(*table.read(), 1);
+//!
+//! source.store(2, Ordering::SeqCst);
+//! assert_eq!(reloader.reload_and_wait().published, 2);
+//! assert_eq!(*table.read(), 2);
+//! ```
+//!
+//! With the `notify` feature, `Reloader::watch_file` reloads the table
+//! whenever a file changes. Readers which don't own the `Reloader` can follow
+//! the outcome of the reloads through a `ReloadStatusHandle`.
+
+use crate::types::*;
+use std::fmt;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// Not part of the active_standby synchronization protocol, so always use std
+// (even under loom).
+type StatusCell<E> = std::sync::Arc<std::sync::Mutex<ReloadStatus<E>>>;
+
+enum Message {
+    // Reload, and signal the sender once the attempt is recorded.
+    Reload(Option<mpsc::SyncSender<()>>),
+    // Reported by a file watcher.
+    #[cfg_attr(not(feature = "notify"), allow(dead_code))]
+    WatchError(String),
+    Stop,
+}
+
+/// The outcome of the reloads run so far.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadStatus<E> {
+    /// Number of times the loader has been called.
+    pub attempts: u64,
+    /// Number of times the loader's result was published to the table.
+    pub published: u64,
+    /// The error returned by the most recent attempt, if it failed.
+    pub last_error: Option<E>,
+    /// The most recent error reported by a file watcher.
+    pub last_watch_error: Option<String>,
+}
+
+impl<E> Default for ReloadStatus<E> {
+    fn default() -> ReloadStatus<E> {
+        ReloadStatus {
+            attempts: 0,
+            published: 0,
+            last_error: None,
+            last_watch_error: None,
+        }
+    }
+}
+
+/// Configuration for a reload loop.
+pub struct Reload<L> {
+    loader: L,
+    period: Option<Duration>,
+}
+
+/// Handle to a running reload loop. Dropping it stops the loop.
+pub struct Reloader<E> {
+    sender: mpsc::Sender<Message>,
+    status: StatusCell<E>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Cloneable handle for triggering reloads, for instance from a file watcher.
+#[derive(Clone)]
+pub struct ReloadTrigger {
+    sender: mpsc::Sender<Message>,
+}
+
+/// Cloneable handle for following the status of a reload loop, which doesn't
+/// keep the loop running.
+pub struct ReloadStatusHandle<E> {
+    status: StatusCell<E>,
+}
+
+/// Reloads the table whenever the watched file changes, until dropped.
+/// Requires the `notify` feature.
+#[cfg(feature = "notify")]
+pub struct FileWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl<L> Reload<L> {
+    pub fn new<T, E>(loader: L) -> Reload<L>
+    where
+        L: FnMut() -> Result<T, E>,
+    {
+        Reload {
+            loader,
+            period: None,
+        }
+    }
+
+    /// Also reload every `period`, measured from the previous attempt.
+    /// Without a period, the table is only reloaded when triggered.
+    pub fn period(mut self, period: Duration) -> Reload<L> {
+        self.period = Some(period);
+        self
+    }
+
+    /// Spawn the loop, replacing the contents of a `sync::AsLock`. Returns
+    /// once the first load has been attempted.
+    pub fn spawn_sync<T, E>(self, table: Arc<crate::sync::AsLock<T>>) -> Reloader<E>
+    where
+        L: 'static + FnMut() -> Result<T, E> + Send,
+        T: 'static + Clone + PartialEq + Send + Sync,
+        E: 'static + Send,
+    {
+        self.spawn(move |value| {
+            if *table.read() == value {
+                return false;
+            }
+            table.write().update_tables(Replace { value });
+            true
+        })
+    }
+
+    /// Spawn the loop, replacing the contents of a `lockless::AsLockHandle`.
+    /// Returns once the first load has been attempted.
+    pub fn spawn_lockless<T, E>(self, table: crate::lockless::AsLockHandle<T>) -> Reloader<E>
+    where
+        L: 'static + FnMut() -> Result<T, E> + Send,
+        T: 'static + Clone + PartialEq + Send + Sync,
+        E: 'static + Send,
+    {
+        self.spawn(move |value| {
+            if *table.read() == value {
+                return false;
+            }
+            table.write().update_tables(Replace { value });
+            true
+        })
+    }
+
+    fn spawn<T, E>(self, mut publish: impl 'static + FnMut(T) -> bool + Send) -> Reloader<E>
+    where
+        L: 'static + FnMut() -> Result<T, E> + Send,
+        E: 'static + Send,
+    {
+        let Reload { mut loader, period } = self;
+        let (sender, receiver) = mpsc::channel();
+        let (loaded, wait_loaded) = mpsc::sync_channel(1);
+        let status = StatusCell::default();
+
+        let thread = {
+            let status = StatusCell::clone(&status);
+            let attempt_status = StatusCell::clone(&status);
+            let mut attempt = move || {
+                let result = loader().map(&mut publish);
+                let mut status = lock(&attempt_status);
+                status.attempts += 1;
+                match result {
+                    Ok(published) => {
+                        status.published += published as u64;
+                        status.last_error = None;
+                    }
+                    Err(e) => status.last_error = Some(e),
+                }
+            };
+
+            std::thread::spawn(move || {
+                attempt();
+                let _ = loaded.send(());
+                let mut next_attempt = period.map(|period| Instant::now() + period);
+                loop {
+                    let message = match next_attempt {
+                        None => receiver.recv().unwrap_or(Message::Stop),
+                        Some(deadline) => {
+                            match receiver
+                                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                            {
+                                Ok(message) => message,
+                                Err(mpsc::RecvTimeoutError::Timeout) => Message::Reload(None),
+                                Err(mpsc::RecvTimeoutError::Disconnected) => Message::Stop,
+                            }
+                        }
+                    };
+                    match message {
+                        Message::Stop => return,
+                        Message::WatchError(e) => lock(&status).last_watch_error = Some(e),
+                        Message::Reload(done) => {
+                            attempt();
+                            next_attempt = period.map(|period| Instant::now() + period);
+                            if let Some(done) = done {
+                                let _ = done.send(());
+                            }
+                        }
+                    }
+                }
+            })
+        };
+
+        // Fails only if the loader panicked.
+        let _ = wait_loaded.recv();
+        Reloader {
+            sender,
+            status,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl<E> Reloader<E> {
+    /// Ask the loop to reload, without waiting for it to do so.
+    pub fn reload(&self) {
+        let _ = self.sender.send(Message::Reload(None));
+    }
+
+    /// Reload, and return the status once the attempt is recorded.
+    pub fn reload_and_wait(&self) -> ReloadStatus<E>
+    where
+        E: Clone,
+    {
+        let (done, wait) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Reload(Some(done))).is_ok() {
+            // Fails only if the loader panicked.
+            let _ = wait.recv();
+        }
+        self.status()
+    }
+
+    pub fn status(&self) -> ReloadStatus<E>
+    where
+        E: Clone,
+    {
+        lock(&self.status).clone()
+    }
+
+    pub fn status_handle(&self) -> ReloadStatusHandle<E> {
+        ReloadStatusHandle {
+            status: StatusCell::clone(&self.status),
+        }
+    }
+
+    pub fn trigger(&self) -> ReloadTrigger {
+        ReloadTrigger {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Reload whenever the file at `path` is created or modified. Errors
+    /// reported by the watcher are recorded in `last_watch_error`. Requires
+    /// the `notify` feature.
+    #[cfg(feature = "notify")]
+    pub fn watch_file(&self, path: impl AsRef<std::path::Path>) -> notify::Result<FileWatcher> {
+        self.trigger().watch_file(path)
+    }
+
+    /// Stop the loop, waiting for an ongoing reload to finish.
+    pub fn stop(self) {}
+}
+
+impl<E> Drop for Reloader<E> {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            // Don't propagate a panic from the loader while dropping.
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for Reloader<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reloader")
+            .field("status", &*lock(&self.status))
+            .finish()
+    }
+}
+
+impl ReloadTrigger {
+    /// Ask the loop to reload. Returns false if the loop has stopped.
+    pub fn reload(&self) -> bool {
+        self.sender.send(Message::Reload(None)).is_ok()
+    }
+}
+
+#[cfg(feature = "notify")]
+impl ReloadTrigger {
+    /// See `Reloader::watch_file`.
+    pub fn watch_file(self, path: impl AsRef<std::path::Path>) -> notify::Result<FileWatcher> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        // Editors often save by replacing the file, which would end a watch on
+        // the file itself, so watch its directory instead.
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| notify::Error::generic("path doesn't name a file"))?
+            .to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let message = match event {
+                    Err(e) => Message::WatchError(e.to_string()),
+                    Ok(event) => {
+                        let changed =
+                            matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+                        if !changed
+                            || !event
+                                .paths
+                                .iter()
+                                .any(|p| p.file_name() == Some(&file_name))
+                        {
+                            return;
+                        }
+                        Message::Reload(None)
+                    }
+                };
+                let _ = self.sender.send(message);
+            })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(FileWatcher { _watcher: watcher })
+    }
+}
+
+impl fmt::Debug for ReloadTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadTrigger").finish_non_exhaustive()
+    }
+}
+
+impl<E> ReloadStatusHandle<E> {
+    pub fn status(&self) -> ReloadStatus<E>
+    where
+        E: Clone,
+    {
+        lock(&self.status).clone()
+    }
+}
+
+impl<E> Clone for ReloadStatusHandle<E> {
+    fn clone(&self) -> ReloadStatusHandle<E> {
+        ReloadStatusHandle {
+            status: StatusCell::clone(&self.status),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for ReloadStatusHandle<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadStatusHandle")
+            .field("status", &*lock(&self.status))
+            .finish()
+    }
+}
+
+#[cfg(feature = "notify")]
+impl fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileWatcher").finish_non_exhaustive()
+    }
+}
+
+fn lock<E>(status: &StatusCell<E>) -> std::sync::MutexGuard<'_, ReloadStatus<E>> {
+    // The status is always left consistent, so ignore poison.
+    status
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicI32;
+
+    fn source(value: i32) -> (Arc<AtomicI32>, impl FnMut() -> Result<i32, String>) {
+        let source = Arc::new(AtomicI32::new(value));
+        let loader = {
+            let source = Arc::clone(&source);
+            move || match source.load(Ordering::SeqCst) {
+                value if value >= 0 => Ok(value),
+                value => Err(format!("bad value {}", value)),
+            }
+        };
+        (source, loader)
+    }
+
+    #[test]
+    fn sync_reload() {
+        let (source, loader) = source(1);
+        let table = Arc::new(crate::sync::AsLock::new(0));
+        let reloader = Reload::new(loader).spawn_sync(Arc::clone(&table));
+        assert_eq!(*table.read(), 1);
+        assert_eq!(
+            reloader.status(),
+            ReloadStatus {
+                attempts: 1,
+                published: 1,
+                last_error: None,
+                last_watch_error: None,
+            }
+        );
+
+        // Unchanged results aren't published.
+        assert_eq!(reloader.reload_and_wait().published, 1);
+
+        source.store(-1, Ordering::SeqCst);
+        assert_eq!(
+            reloader.reload_and_wait(),
+            ReloadStatus {
+                attempts: 3,
+                published: 1,
+                last_error: Some("bad value -1".to_string()),
+                last_watch_error: None,
+            }
+        );
+        assert_eq!(*table.read(), 1);
+
+        // A successful reload clears the error.
+        source.store(2, Ordering::SeqCst);
+        assert_eq!(reloader.reload_and_wait().last_error, None);
+        assert_eq!(*table.read(), 2);
+    }
+
+    #[test]
+    fn lockless_period() {
+        let (source, loader) = source(1);
+        let table = crate::lockless::AsLockHandle::new(0);
+        let _reloader = Reload::new(loader)
+            .period(Duration::from_millis(1))
+            .spawn_lockless(table.clone());
+        assert_eq!(*table.read(), 1);
+
+        source.store(2, Ordering::SeqCst);
+        while *table.read() != 2 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn trigger() {
+        let (source, loader) = source(1);
+        let table = Arc::new(crate::sync::AsLock::new(0));
+        let reloader = Reload::new(loader).spawn_sync(Arc::clone(&table));
+        let trigger = reloader.trigger();
+
+        source.store(2, Ordering::SeqCst);
+        assert!(trigger.reload());
+        while *table.read() != 2 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        reloader.stop();
+        assert!(!trigger.reload());
+    }
+
+    #[test]
+    fn status_handle() {
+        let (source, loader) = source(1);
+        let table = Arc::new(crate::sync::AsLock::new(0));
+        let reloader = Reload::new(loader).spawn_sync(Arc::clone(&table));
+        let status = reloader.status_handle();
+
+        source.store(-1, Ordering::SeqCst);
+        reloader.reload_and_wait();
+        assert_eq!(status.status().last_error, Some("bad value -1".to_string()));
+
+        // The handle outlives the loop.
+        drop(reloader);
+        assert_eq!(status.status().attempts, 2);
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn watch_file() {
+        let dir = std::env::temp_dir().join(format!("active_standby_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("table.txt");
+        std::fs::write(&path, "1").unwrap();
+
+        let table = Arc::new(crate::sync::AsLock::new(0));
+        let loader = {
+            let path = path.clone();
+            move || {
+                let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+                contents.trim().parse::<i32>().map_err(|e| e.to_string())
+            }
+        };
+        let reloader = Reload::new(loader).spawn_sync(Arc::clone(&table));
+        let _watcher = reloader.watch_file(&path).unwrap();
+        assert_eq!(*table.read(), 1);
+
+        let wait_for = |done: &dyn Fn() -> bool| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !done() {
+                assert!(Instant::now() < deadline, "timed out waiting for reload");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        };
+
+        std::fs::write(&path, "2").unwrap();
+        wait_for(&|| *table.read() == 2);
+
+        // Bad contents leave the table untouched.
+        std::fs::write(&path, "two").unwrap();
+        wait_for(&|| reloader.status().last_error.is_some());
+        assert_eq!(*table.read(), 2);
+
+        drop(reloader);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
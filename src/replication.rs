@@ -0,0 +1,519 @@
+//! Shipping the updates of a table to a follower table, such as a warm standby
+//! in another process.
+//!
+//! The updates are user defined ops implementing `ReplicatedOp`, which encode
+//! themselves to bytes. A `Replicator` applies ops to the source table and
+//! writes them to a byte channel, and a `Follower` reads them from the other
+//! end and applies them to its own table. The transport is left to the user:
+//! anything which implements `std::io::Write` on the sending side, and which
+//! can hand the received bytes to `Follower::receive` on the other.
+//!
+//! ```rust
+//! use active_standby::replication::{Follower, ReplicatedOp, Replicator};
+//! use active_standby::sync::AsLock;
+//! use std::sync::Arc;
+//!
+//! struct Push(i32);
+//!
+//! impl ReplicatedOp<Vec<i32>> for Push {
+//!     type Error = std::array::TryFromSliceError;
+//!     fn encode(&self, buf: &mut Vec<u8>) {
+//!         buf.extend_from_slice(&self.0.to_le_bytes());
+//!     }
+//!     fn decode(bytes: &[u8]) -> Result<Push, Self::Error> {
+//!         Ok(Push(i32::from_le_bytes(bytes.try_into()?)))
+//!     }
+//!     fn apply(&self, table: &mut Vec<i32>) {
+//!         table.push(self.0);
+//!     }
+//! }
+//!
+//! let source = Arc::new(AsLock::new(vec![]));
+//! let follower_table = Arc::new(AsLock::new(vec![]));
+//! let mut replicator = Replicator::sync(Arc::clone(&source), vec![]);
+//! let mut follower = Follower::<_, Push>::sync(Arc::clone(&follower_table));
+//!
+//! replicator.replicate([Push(1), Push(2)]).unwrap();
+//! let bytes = std::mem::take(replicator.writer());
+//! assert_eq!(follower.receive(&bytes).unwrap(), 2);
+//! assert_eq!(*follower_table.read(), vec![1, 2]);
+//! assert_eq!(*source.read(), vec![1, 2]);
+//! ```
+//!
+//! # Wire format
+//!
+//! The stream is a sequence of frames, each made up of:
+//!
+//! 1. The sequence number of the op, as a little endian `u64`. The first op
+//!    sent by a `Replicator` is numbered 0, and each op after it is numbered
+//!    one higher than the last.
+//! 2. The length of the encoded op in bytes, as a little endian `u32`.
+//! 3. The op, as encoded by `ReplicatedOp::encode`.
+//!
+//! There is no header or padding between frames, so frames may be split
+//! across (or share) the chunks handed to `Follower::receive`. This format is
+//! stable, so a follower may be built from a different version of this crate,
+//! as long as the ops are encoded the same way.
+
+use crate::types::*;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+const HEADER_LEN: usize = 8 + 4;
+
+type ApplyOps<Op> = Box<dyn FnMut(Vec<Op>) + Send>;
+type InstallSnapshot<T> = Box<dyn FnMut(T) + Send>;
+
+/// An update which can be encoded, sent to another process, and applied to a
+/// table there.
+///
+/// `apply` is called once on each of the source's tables, and once on each of
+/// the follower's tables, so it must leave every table in the same state.
+pub trait ReplicatedOp<T>: Sized {
+    type Error;
+
+    /// Append the op to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decode an op from the bytes written by `encode`.
+    fn decode(bytes: &[u8]) -> Result<Self, Self::Error>;
+
+    fn apply(&self, table: &mut T);
+}
+
+struct ApplyOp<Op> {
+    op: Op,
+}
+
+impl<'a, T, Op> UpdateTables<'a, T, ()> for ApplyOp<Op>
+where
+    Op: ReplicatedOp<T>,
+{
+    fn apply_first(&mut self, table: &'a mut T) {
+        self.op.apply(table)
+    }
+    fn apply_second(self, table: &mut T) {
+        self.op.apply(table)
+    }
+}
+
+fn apply_sync<T, Op>(table: Arc<crate::sync::AsLock<T>>) -> ApplyOps<Op>
+where
+    T: 'static + Send + Sync,
+    Op: 'static + ReplicatedOp<T> + Send,
+{
+    Box::new(move |ops| {
+        let mut wg = table.write();
+        for op in ops {
+            wg.update_tables(ApplyOp { op });
+        }
+    })
+}
+
+fn apply_lockless<T, Op>(table: crate::lockless::AsLockHandle<T>) -> ApplyOps<Op>
+where
+    T: 'static + Send,
+    Op: 'static + ReplicatedOp<T> + Send,
+    crate::lockless::AsLockHandle<T>: Send,
+{
+    Box::new(move |ops| {
+        let mut wg = table.write();
+        for op in ops {
+            wg.update_tables(ApplyOp { op });
+        }
+    })
+}
+
+/// Applies ops to the source table and writes them to `W`.
+pub struct Replicator<T, Op, W> {
+    apply: ApplyOps<Op>,
+    writer: W,
+    next_seq: u64,
+    buf: Vec<u8>,
+    _table: PhantomData<fn(&mut T)>,
+}
+
+impl<T, Op, W> Replicator<T, Op, W>
+where
+    Op: 'static + ReplicatedOp<T> + Send,
+    W: io::Write,
+{
+    /// Replicate the updates made to a `sync::AsLock`.
+    pub fn sync(table: Arc<crate::sync::AsLock<T>>, writer: W) -> Replicator<T, Op, W>
+    where
+        T: 'static + Send + Sync,
+    {
+        Self::new(apply_sync(table), writer)
+    }
+
+    /// Replicate the updates made to a `lockless::AsLockHandle`.
+    pub fn lockless(table: crate::lockless::AsLockHandle<T>, writer: W) -> Replicator<T, Op, W>
+    where
+        T: 'static + Send,
+        crate::lockless::AsLockHandle<T>: Send,
+    {
+        Self::new(apply_lockless(table), writer)
+    }
+
+    fn new(apply: ApplyOps<Op>, writer: W) -> Replicator<T, Op, W> {
+        Replicator {
+            apply,
+            writer,
+            next_seq: 0,
+            buf: vec![],
+            _table: PhantomData,
+        }
+    }
+
+    /// Apply `ops` to the source table with a single write guard, then write
+    /// them to the writer.
+    ///
+    /// The ops are encoded before being applied. If writing fails, the source
+    /// table has already been updated, so the follower must be resynced.
+    pub fn replicate(&mut self, ops: impl IntoIterator<Item = Op>) -> io::Result<()> {
+        let ops: Vec<Op> = ops.into_iter().collect();
+        self.buf.clear();
+        for op in &ops {
+            self.buf.extend_from_slice(&self.next_seq.to_le_bytes());
+            let header_end = self.buf.len();
+            self.buf.extend_from_slice(&[0; 4]);
+            op.encode(&mut self.buf);
+            let len = (self.buf.len() - header_end - 4) as u32;
+            self.buf[header_end..header_end + 4].copy_from_slice(&len.to_le_bytes());
+            self.next_seq += 1;
+        }
+
+        (self.apply)(ops);
+        self.writer.write_all(&self.buf)?;
+        self.writer.flush()
+    }
+
+    /// Sequence number of the next op to be replicated.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+/// Error returned by `Follower::receive`. The frames received before the error
+/// have been applied, but the follower can't make progress after it, and
+/// must be resynced with `Follower::install_snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationError<E> {
+    /// A frame was received ahead of the next op expected, meaning that
+    /// frames were lost or the follower was connected to the wrong stream.
+    OutOfOrder { expected: u64, received: u64 },
+    /// `ReplicatedOp::decode` failed on the op with this sequence number.
+    Decode { seq: u64, error: E },
+}
+
+impl<E: fmt::Display> fmt::Display for ReplicationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplicationError::OutOfOrder { expected, received } => write!(
+                f,
+                "expected the op with sequence number {}, received {}",
+                expected, received
+            ),
+            ReplicationError::Decode { seq, error } => {
+                write!(f, "failed to decode op {}: {}", seq, error)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for ReplicationError<E> {}
+
+/// Applies the ops written by a `Replicator` to the follower table.
+///
+/// Ops which are older than the follower's table, because they were sent
+/// before the snapshot it was loaded from, are skipped.
+pub struct Follower<T, Op> {
+    apply: ApplyOps<Op>,
+    install: InstallSnapshot<T>,
+    next_seq: u64,
+
+    // Bytes of a frame which hasn't been fully received yet.
+    pending: Vec<u8>,
+
+    _table: PhantomData<fn(&mut T)>,
+}
+
+impl<T, Op> Follower<T, Op>
+where
+    Op: 'static + ReplicatedOp<T> + Send,
+{
+    /// Follow a source table, applying its ops to a `sync::AsLock`.
+    pub fn sync(table: Arc<crate::sync::AsLock<T>>) -> Follower<T, Op>
+    where
+        T: 'static + Clone + Send + Sync,
+    {
+        let install = {
+            let table = Arc::clone(&table);
+            Box::new(move |value| table.write().update_tables(Replace { value }))
+        };
+        Self::new(apply_sync(table), install)
+    }
+
+    /// Follow a source table, applying its ops to a `lockless::AsLockHandle`.
+    pub fn lockless(table: crate::lockless::AsLockHandle<T>) -> Follower<T, Op>
+    where
+        T: 'static + Clone + Send,
+        crate::lockless::AsLockHandle<T>: Send,
+    {
+        let install = {
+            let table = table.clone();
+            Box::new(move |value| table.write().update_tables(Replace { value }))
+        };
+        Self::new(apply_lockless(table), install)
+    }
+
+    fn new(apply: ApplyOps<Op>, install: InstallSnapshot<T>) -> Follower<T, Op> {
+        Follower {
+            apply,
+            install,
+            next_seq: 0,
+            pending: vec![],
+            _table: PhantomData,
+        }
+    }
+
+    /// Start following from `seq` instead of 0, for a follower table which
+    /// was loaded from a snapshot taken after the op before `seq`.
+    pub fn starting_at(mut self, seq: u64) -> Follower<T, Op> {
+        self.next_seq = seq;
+        self
+    }
+
+    /// Sequence number of the next op expected.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Replace both of the follower's tables with `snapshot`, published with a
+    /// single write guard, and expect the op numbered `seq` next. `snapshot`
+    /// must be a copy of the source's table taken after the op before `seq`
+    /// was applied to it, e.g. read alongside `Replicator::next_seq`.
+    ///
+    /// Ops which are already in flight are skipped if they are older than
+    /// `seq`, so this can be used to bootstrap a follower, or to recover one
+    /// after `receive` returned an error, or stalled on a corrupt frame. The
+    /// bytes buffered from an incomplete or failed frame are discarded, so
+    /// the next call to `receive` must start at a frame boundary, e.g. on a
+    /// new connection.
+    pub fn install_snapshot(&mut self, snapshot: T, seq: u64) {
+        (self.install)(snapshot);
+        self.next_seq = seq;
+        self.pending.clear();
+    }
+
+    /// Hand the follower the next chunk of bytes from the stream. All of the
+    /// frames completed by `bytes` are applied with a single write guard, and
+    /// the number of ops applied is returned.
+    pub fn receive(&mut self, bytes: &[u8]) -> Result<usize, ReplicationError<Op::Error>> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut ops = vec![];
+        let mut consumed = 0;
+        let mut result = Ok(());
+        while let Some(header) = self.pending.get(consumed..consumed + HEADER_LEN) {
+            let seq = u64::from_le_bytes(header[..8].try_into().unwrap());
+            let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+            let Some(op) = self
+                .pending
+                .get(consumed + HEADER_LEN..consumed + HEADER_LEN + len)
+            else {
+                break;
+            };
+            if seq < self.next_seq {
+                // Already included in the follower's table.
+                consumed += HEADER_LEN + len;
+                continue;
+            }
+            if seq > self.next_seq {
+                result = Err(ReplicationError::OutOfOrder {
+                    expected: self.next_seq,
+                    received: seq,
+                });
+                break;
+            }
+            match Op::decode(op) {
+                Ok(op) => ops.push(op),
+                Err(error) => {
+                    result = Err(ReplicationError::Decode { seq, error });
+                    break;
+                }
+            }
+            consumed += HEADER_LEN + len;
+            self.next_seq += 1;
+        }
+        self.pending.drain(..consumed);
+
+        let num_ops = ops.len();
+        if num_ops > 0 {
+            (self.apply)(ops);
+        }
+        result.map(|()| num_ops)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Push(u8);
+
+    impl ReplicatedOp<Vec<u8>> for Push {
+        type Error = String;
+        fn encode(&self, buf: &mut Vec<u8>) {
+            buf.push(self.0);
+        }
+        fn decode(bytes: &[u8]) -> Result<Push, String> {
+            match bytes {
+                [value] => Ok(Push(*value)),
+                _ => Err(format!("expected 1 byte, got {}", bytes.len())),
+            }
+        }
+        fn apply(&self, table: &mut Vec<u8>) {
+            table.push(self.0);
+        }
+    }
+
+    #[test]
+    fn wire_format() {
+        let source = Arc::new(crate::sync::AsLock::new(vec![]));
+        let mut replicator = Replicator::sync(source, vec![]);
+        replicator.replicate([Push(7)]).unwrap();
+        replicator.replicate([Push(8)]).unwrap();
+        assert_eq!(
+            *replicator.writer(),
+            vec![0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 7, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 8]
+        );
+        assert_eq!(replicator.next_seq(), 2);
+    }
+
+    #[test]
+    fn partial_frames() {
+        let source = crate::lockless::AsLockHandle::new(vec![]);
+        let follower_table = crate::lockless::AsLockHandle::new(Vec::<u8>::new());
+        let mut replicator = Replicator::lockless(source.clone(), vec![]);
+        let mut follower = Follower::<_, Push>::lockless(follower_table.clone());
+
+        replicator.replicate((0..3).map(Push)).unwrap();
+        let bytes = replicator.into_writer();
+        // Split in the middle of the second frame.
+        let (first, second) = bytes.split_at(HEADER_LEN + 1 + 4);
+        assert_eq!(follower.receive(first), Ok(1));
+        assert_eq!(*follower_table.read(), vec![0]);
+        assert_eq!(follower.receive(second), Ok(2));
+        assert_eq!(*follower_table.read(), vec![0, 1, 2]);
+        assert_eq!(*source.read(), vec![0, 1, 2]);
+        assert_eq!(follower.next_seq(), 3);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let source = Arc::new(crate::sync::AsLock::new(vec![]));
+        let follower_table = Arc::new(crate::sync::AsLock::new(Vec::<u8>::new()));
+        let mut replicator = Replicator::sync(source, vec![]);
+        let mut follower = Follower::<_, Push>::sync(Arc::clone(&follower_table)).starting_at(1);
+
+        // Ops from before the snapshot are skipped.
+        replicator.replicate([Push(0), Push(1)]).unwrap();
+        let bytes = std::mem::take(replicator.writer());
+        assert_eq!(follower.receive(&bytes), Ok(1));
+        assert_eq!(*follower_table.read(), vec![1]);
+
+        // Lose op 2.
+        replicator.replicate([Push(2)]).unwrap();
+        replicator.writer().clear();
+        replicator.replicate([Push(3)]).unwrap();
+        let bytes = std::mem::take(replicator.writer());
+        assert_eq!(
+            follower.receive(&bytes),
+            Err(ReplicationError::OutOfOrder {
+                expected: 2,
+                received: 3
+            })
+        );
+        assert_eq!(*follower_table.read(), vec![1]);
+    }
+
+    #[test]
+    fn install_snapshot() {
+        let source = crate::lockless::AsLockHandle::new(vec![]);
+        let follower_table = crate::lockless::AsLockHandle::new(Vec::<u8>::new());
+        let mut replicator = Replicator::lockless(source.clone(), vec![]);
+        let mut follower = Follower::<_, Push>::lockless(follower_table.clone());
+
+        replicator.replicate([Push(0), Push(1)]).unwrap();
+        // Lose the first ops, which are still in flight once the follower
+        // recovers from the snapshot.
+        let in_flight = std::mem::take(replicator.writer());
+        replicator.replicate([Push(2)]).unwrap();
+        let bytes = std::mem::take(replicator.writer());
+        assert!(follower.receive(&bytes).is_err());
+
+        follower.install_snapshot(source.read().clone(), replicator.next_seq());
+        assert_eq!(*follower_table.read(), vec![0, 1, 2]);
+        assert_eq!(*follower_table.write(), vec![0, 1, 2]);
+
+        // The frame which failed was discarded, and the ops in flight are
+        // skipped.
+        assert_eq!(follower.receive(&in_flight), Ok(0));
+        replicator.replicate([Push(3)]).unwrap();
+        assert_eq!(follower.receive(replicator.writer()), Ok(1));
+        assert_eq!(*follower_table.read(), vec![0, 1, 2, 3]);
+        assert_eq!(follower.next_seq(), 4);
+    }
+
+    #[test]
+    fn recover_from_corrupt_frame() {
+        let source = Arc::new(crate::sync::AsLock::new(vec![]));
+        let follower_table = Arc::new(crate::sync::AsLock::new(Vec::<u8>::new()));
+        let mut replicator = Replicator::sync(Arc::clone(&source), vec![]);
+        let mut follower = Follower::<_, Push>::sync(Arc::clone(&follower_table));
+
+        // A bogus length leaves the follower waiting for the rest of the
+        // frame, which never arrives.
+        replicator.replicate([Push(0)]).unwrap();
+        let mut corrupt = std::mem::take(replicator.writer());
+        corrupt[8..HEADER_LEN].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(follower.receive(&corrupt), Ok(0));
+        replicator.replicate([Push(1)]).unwrap();
+        let bytes = std::mem::take(replicator.writer());
+        assert_eq!(follower.receive(&bytes), Ok(0));
+
+        follower.install_snapshot(source.read().clone(), replicator.next_seq());
+        replicator.replicate([Push(2)]).unwrap();
+        assert_eq!(follower.receive(replicator.writer()), Ok(1));
+        assert_eq!(*follower_table.read(), vec![0, 1, 2]);
+        assert_eq!(follower.next_seq(), 3);
+    }
+
+    #[test]
+    fn decode_error() {
+        let follower_table = Arc::new(crate::sync::AsLock::new(Vec::<u8>::new()));
+        let mut follower = Follower::<_, Push>::sync(Arc::clone(&follower_table));
+        let mut bytes = vec![0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 7];
+        bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 8, 9]);
+        assert_eq!(
+            follower.receive(&bytes),
+            Err(ReplicationError::Decode {
+                seq: 1,
+                error: "expected 1 byte, got 2".to_string()
+            })
+        );
+        // The ops before the error are applied.
+        assert_eq!(*follower_table.read(), vec![7]);
+    }
+}
@@ -0,0 +1,281 @@
+//! Harness for estimating how long writers stall on readers.
+//!
+//! A `Simulation` runs a writer against a number of reader threads for a
+//! number of rounds. In each round, every reader is given a schedule - how
+//! long to wait before taking a read guard and how long to hold it - which is
+//! derived from the seed. The harness reports how long the writer's calls took,
+//! so that the worst interleavings can be found and then replayed using the
+//! seed of that run.
+//!
+//! The harness is agnostic to the flavor of the table. Readers are built by a
+//! factory so that each thread can hold its own `AsLockHandle`. Each read is
+//! passed a `Hold`, which should be waited on while holding the read guard:
+//!
+//! ```rust
+//! use active_standby::lockless::collections::AsVecHandle;
+//! use active_standby::simulate::Simulation;
+//!
+//! let table = AsVecHandle::<i32>::default();
+//! let report = Simulation::new(7).rounds(10).num_readers(2).run(
+//!     || {
+//!         let table = table.clone();
+//!         move |hold| {
+//!             let _guard = table.read();
+//!             hold.wait();
+//!         }
+//!     },
+//!     || table.write().push(1),
+//! );
+//! assert_eq!(report.write_stalls.len(), 10);
+//! assert_eq!(table.read().len(), 10);
+//! ```
+//!
+//! Schedules are deterministic given the seed, but the OS still decides how
+//! the threads are run, so reports are estimates and not exact.
+
+use crate::collections::Prng;
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+impl Prng {
+    /// Returns a value in `0..=max`.
+    pub(crate) fn up_to(&mut self, max: u32) -> u32 {
+        (self.next_u64() % (max as u64 + 1)) as u32
+    }
+}
+
+/// What a single reader does during a round. Waiting is done by yielding the
+/// thread, so the units are `std::thread::yield_now` calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderSchedule {
+    /// Yields before taking the read guard.
+    pub delay: u32,
+    /// Yields while holding the read guard.
+    pub hold: u32,
+}
+
+/// Passed to each read, and waited on while holding the read guard, to follow
+/// the reader's schedule.
+#[derive(Debug)]
+pub struct Hold {
+    yields: u32,
+}
+
+impl Hold {
+    pub fn wait(self) {
+        spin(self.yields);
+    }
+}
+
+/// Results of running a `Simulation`.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The seed the simulation was run with.
+    pub seed: u64,
+    /// The time the writer took in each round.
+    pub write_stalls: Vec<Duration>,
+    /// The round with the longest write stall.
+    pub worst_round: usize,
+    /// The reader schedules of `worst_round`.
+    pub worst_schedules: Vec<ReaderSchedule>,
+}
+
+impl Report {
+    pub fn max_write_stall(&self) -> Duration {
+        self.write_stalls[self.worst_round]
+    }
+
+    pub fn mean_write_stall(&self) -> Duration {
+        self.write_stalls.iter().sum::<Duration>() / self.write_stalls.len() as u32
+    }
+}
+
+/// Configuration for running readers against a writer under seeded schedules.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    seed: u64,
+    rounds: usize,
+    num_readers: usize,
+    max_delay: u32,
+    max_hold: u32,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Simulation {
+        Simulation {
+            seed,
+            rounds: 100,
+            num_readers: 4,
+            max_delay: 16,
+            max_hold: 64,
+        }
+    }
+
+    pub fn rounds(mut self, rounds: usize) -> Simulation {
+        assert!(rounds > 0, "a simulation requires at least 1 round");
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn num_readers(mut self, num_readers: usize) -> Simulation {
+        self.num_readers = num_readers;
+        self
+    }
+
+    /// Upper bound on `ReaderSchedule::delay`.
+    pub fn max_delay(mut self, max_delay: u32) -> Simulation {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Upper bound on `ReaderSchedule::hold`.
+    pub fn max_hold(mut self, max_hold: u32) -> Simulation {
+        self.max_hold = max_hold;
+        self
+    }
+
+    /// The schedules each reader follows in each round. `run` uses exactly
+    /// these schedules, so this can be used to inspect a run ahead of time.
+    pub fn schedules(&self) -> Vec<Vec<ReaderSchedule>> {
+        let mut prng = Prng::new(self.seed);
+        (0..self.rounds)
+            .map(|_| {
+                (0..self.num_readers)
+                    .map(|_| ReaderSchedule {
+                        delay: prng.up_to(self.max_delay),
+                        hold: prng.up_to(self.max_hold),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Run the simulation.
+    ///
+    /// `new_reader` is called once per reader thread, and returns the
+    /// function that thread uses to read the table. `write` is called once
+    /// per round, while the readers are following their schedules, and the
+    /// time it takes is that round's write stall.
+    pub fn run<F, R, W>(&self, new_reader: F, mut write: W) -> Report
+    where
+        F: Fn() -> R,
+        R: FnMut(Hold) + Send,
+        W: FnMut(),
+    {
+        let schedules = self.schedules();
+        // Readers and the writer line up at the start of each round.
+        let barrier = Barrier::new(self.num_readers + 1);
+        let mut write_stalls = Vec::with_capacity(self.rounds);
+
+        std::thread::scope(|scope| {
+            for reader_index in 0..self.num_readers {
+                let mut read = new_reader();
+                let barrier = &barrier;
+                let schedules = &schedules;
+                scope.spawn(move || {
+                    for round in schedules {
+                        let ReaderSchedule { delay, hold } = round[reader_index];
+                        barrier.wait();
+                        spin(delay);
+                        read(Hold { yields: hold });
+                    }
+                });
+            }
+
+            for _ in 0..self.rounds {
+                barrier.wait();
+                let start = Instant::now();
+                write();
+                write_stalls.push(start.elapsed());
+            }
+        });
+
+        let worst_round = (0..write_stalls.len())
+            .max_by_key(|&round| write_stalls[round])
+            .unwrap();
+        Report {
+            seed: self.seed,
+            worst_schedules: schedules[worst_round].clone(),
+            write_stalls,
+            worst_round,
+        }
+    }
+}
+
+fn spin(yields: u32) {
+    for _ in 0..yields {
+        std::thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn schedules_are_deterministic() {
+        let sim = Simulation::new(42).rounds(5).num_readers(3);
+        assert_eq!(sim.schedules(), sim.schedules());
+        assert_eq!(sim.schedules().len(), 5);
+        assert!(sim.schedules().iter().all(|round| round.len() == 3));
+        assert_ne!(
+            sim.schedules(),
+            Simulation::new(43).rounds(5).num_readers(3).schedules()
+        );
+    }
+
+    #[test]
+    fn schedules_are_bounded() {
+        let sim = Simulation::new(1).max_delay(2).max_hold(0);
+        for schedule in sim.schedules().into_iter().flatten() {
+            assert!(schedule.delay <= 2);
+            assert_eq!(schedule.hold, 0);
+        }
+    }
+
+    #[test]
+    fn lockless() {
+        let table = crate::lockless::AsLockHandle::<Vec<i32>>::default();
+        let report = Simulation::new(3).rounds(20).run(
+            || {
+                let table = table.clone();
+                move |hold: Hold| {
+                    let _guard = table.read();
+                    hold.wait();
+                }
+            },
+            || table.write().update_tables_closure(|vec| vec.push(1)),
+        );
+
+        assert_eq!(report.seed, 3);
+        assert_eq!(report.write_stalls.len(), 20);
+        assert_eq!(
+            report.max_write_stall(),
+            report.write_stalls[report.worst_round]
+        );
+        assert!(report.mean_write_stall() <= report.max_write_stall());
+        assert_eq!(
+            report.worst_schedules,
+            Simulation::new(3).rounds(20).schedules()[report.worst_round]
+        );
+        assert_eq!(table.read().len(), 20);
+    }
+
+    #[test]
+    fn sync() {
+        let table = Arc::new(crate::sync::AsLock::<Vec<i32>>::default());
+        let report = Simulation::new(3).rounds(20).num_readers(2).run(
+            || {
+                |hold: Hold| {
+                    let _guard = table.read();
+                    hold.wait();
+                }
+            },
+            || table.write().update_tables_closure(|vec| vec.push(1)),
+        );
+
+        assert_eq!(report.write_stalls.len(), 20);
+        assert_eq!(table.read().len(), 20);
+    }
+}
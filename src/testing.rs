@@ -0,0 +1,143 @@
+//! Harness for downstream tests which check when updates become visible to
+//! readers.
+//!
+//! `assert_publish_visible_after` runs readers on their own threads while an
+//! update is published, and checks that each of them only ever sees the table
+//! from before the update or after it, and that all of them see it after it
+//! once it has been published.
+//!
+//! ```rust
+//! use active_standby::lockless::collections::AsVecHandle;
+//! use active_standby::testing::assert_publish_visible_after;
+//!
+//! let table = AsVecHandle::new(vec![]);
+//! assert_publish_visible_after(
+//!     (0..4).map(|_| table.clone()),
+//!     || {
+//!         let mut wg = table.write();
+//!         wg.push(1);
+//!         wg.push(2);
+//!     },
+//!     |vec: &Vec<i32>| vec.is_empty(),
+//!     |vec: &Vec<i32>| vec == &[1, 2],
+//! );
+//! ```
+//!
+//! A sync `AsLock` is shared by reference, e.g.
+//! `(0..4).map(|_| &*table)`.
+
+use crate::types::Snapshot;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Barrier;
+
+/// Spawn a thread for each of `readers`, and have them read the table while
+/// `publish` updates it from the calling thread. Panics, on the calling
+/// thread, if a reader:
+/// - doesn't see the table in the `before` state before `publish` is called.
+/// - sees the table in neither the `before` nor the `after` state, meaning
+///   that the update was partially applied.
+/// - doesn't see the table in the `after` state once `publish` has returned.
+pub fn assert_publish_visible_after<T, S>(
+    readers: impl IntoIterator<Item = S>,
+    publish: impl FnOnce(),
+    before: impl Fn(&T) -> bool + Sync,
+    after: impl Fn(&T) -> bool + Sync,
+) where
+    T: ?Sized,
+    S: Snapshot<T> + Send,
+{
+    let readers: Vec<S> = readers.into_iter().collect();
+    let barrier = Barrier::new(readers.len() + 1);
+    let published = AtomicBool::new(false);
+    let (before, after) = (&before, &after);
+
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = readers
+            .into_iter()
+            .map(|reader| {
+                let (barrier, published) = (&barrier, &published);
+                scope.spawn(move || {
+                    assert!(
+                        reader.with(before),
+                        "reader didn't see the table in the `before` state"
+                    );
+                    barrier.wait();
+
+                    loop {
+                        // Loaded before reading, so that once it's set the
+                        // read is ordered after the publish.
+                        let is_published = published.load(Ordering::Acquire);
+                        let (is_before, is_after) = reader.with(|t| (before(t), after(t)));
+                        assert!(
+                            is_before || is_after,
+                            "reader saw a partially applied update"
+                        );
+                        if is_published {
+                            assert!(is_after, "reader didn't see the update once published");
+                            break;
+                        }
+                        std::thread::yield_now();
+                    }
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        publish();
+        published.store(true, Ordering::Release);
+
+        for thread in threads {
+            thread
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lockless() {
+        let table = crate::lockless::AsLockHandle::new(vec![]);
+        assert_publish_visible_after(
+            (0..4).map(|_| table.clone()),
+            || {
+                let mut wg = table.write();
+                wg.update_tables_closure(|vec| vec.push(1));
+                wg.update_tables_closure(|vec| vec.push(2));
+            },
+            |vec: &Vec<i32>| vec.is_empty(),
+            |vec: &Vec<i32>| vec == &[1, 2],
+        );
+    }
+
+    #[test]
+    fn sync() {
+        let table = crate::sync::AsLock::new(vec![]);
+        assert_publish_visible_after(
+            (0..4).map(|_| &table),
+            || {
+                let mut wg = table.write();
+                wg.update_tables_closure(|vec| vec.push(1));
+                wg.update_tables_closure(|vec| vec.push(2));
+            },
+            |vec: &Vec<i32>| vec.is_empty(),
+            |vec: &Vec<i32>| vec == &[1, 2],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "reader didn't see the update once published")]
+    fn not_visible() {
+        let table = crate::sync::AsLock::new(vec![]);
+        // Publishing nothing leaves the readers in the `before` state.
+        assert_publish_visible_after(
+            [&table],
+            || drop(table.write()),
+            |vec: &Vec<i32>| vec.is_empty(),
+            |vec: &Vec<i32>| !vec.is_empty(),
+        );
+    }
+}
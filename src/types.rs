@@ -1,8 +1,10 @@
 // Conditional compilation for using loom.
 #[cfg(loom)]
 pub(crate) use loom::hint::spin_loop;
+#[cfg(all(loom, not(feature = "safe-impl")))]
+pub(crate) use loom::sync::atomic::AtomicPtr;
 #[cfg(loom)]
-pub(crate) use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+pub(crate) use loom::sync::atomic::{AtomicUsize, Ordering};
 #[cfg(loom)]
 pub(crate) use loom::sync::Arc;
 #[cfg(loom)]
@@ -21,8 +23,10 @@ pub(crate) fn fence(ord: Ordering) {
 
 #[cfg(not(loom))]
 pub(crate) use std::hint::spin_loop;
+#[cfg(all(not(loom), not(feature = "safe-impl")))]
+pub(crate) use std::sync::atomic::AtomicPtr;
 #[cfg(not(loom))]
-pub(crate) use std::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+pub(crate) use std::sync::atomic::{fence, AtomicUsize, Ordering};
 #[cfg(not(loom))]
 pub(crate) use std::sync::Arc;
 
@@ -49,11 +53,25 @@ impl<T> Mutex<T> {
         return self.inner.lock();
     }
 
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        #[cfg(loom)]
+        return self.inner.try_lock().ok();
+        #[cfg(not(loom))]
+        return self.inner.try_lock();
+    }
+
     pub fn new(t: T) -> Mutex<T> {
         Mutex {
             inner: InnerMutex::new(t),
         }
     }
+
+    pub fn into_inner(self) -> T {
+        #[cfg(loom)]
+        return self.inner.into_inner().unwrap();
+        #[cfg(not(loom))]
+        return self.inner.into_inner();
+    }
 }
 
 // Wrap RwLock since loom and parking_lot have different APIs (loom poisons on
@@ -85,6 +103,24 @@ impl<T> RwLock<T> {
         return self.inner.read();
     }
 
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        #[cfg(loom)]
+        return self.inner.try_read().ok();
+        #[cfg(not(loom))]
+        return self.inner.try_read();
+    }
+
+    /// Loom has no timed locks, so under loom this is `try_read`.
+    pub fn try_read_for(&self, timeout: std::time::Duration) -> Option<RwLockReadGuard<'_, T>> {
+        #[cfg(loom)]
+        {
+            let _ = timeout;
+            return self.try_read();
+        }
+        #[cfg(not(loom))]
+        return self.inner.try_read_for(timeout);
+    }
+
     pub fn write(&self) -> RwLockWriteGuard<'_, T> {
         #[cfg(loom)]
         return self.inner.write().unwrap();
@@ -92,6 +128,20 @@ impl<T> RwLock<T> {
         return self.inner.write();
     }
 
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        #[cfg(loom)]
+        return self.inner.try_write().ok();
+        #[cfg(not(loom))]
+        return self.inner.try_write();
+    }
+
+    pub fn into_inner(self) -> T {
+        #[cfg(loom)]
+        return self.inner.into_inner().unwrap();
+        #[cfg(not(loom))]
+        return self.inner.into_inner();
+    }
+
     pub fn new(t: T) -> RwLock<T> {
         RwLock {
             inner: InnerRwLock::new(t),
@@ -136,13 +186,11 @@ impl<T> RwLock<T> {
 ///     opt[0].clone()
 /// }
 ///
-/// fn main() {
-///     let opt = vec![Arc::new(RefCell::new(3)), Arc::new(RefCell::new(5))];
-///     let opt_ref = ret_owned_value(&opt);
-///     *opt_ref.borrow_mut() += 1;
-///     println!("{:?}, {:?}", opt_ref, opt);
-///     // prints: "RefCell { value: 4 }, [RefCell { value: 4 }, RefCell { value: 5 }]"
-/// }
+/// let opt = vec![Arc::new(RefCell::new(3)), Arc::new(RefCell::new(5))];
+/// let opt_ref = ret_owned_value(&opt);
+/// *opt_ref.borrow_mut() += 1;
+/// println!("{:?}, {:?}", opt_ref, opt);
+/// // prints: "RefCell { value: 4 }, [RefCell { value: 4 }, RefCell { value: 5 }]"
 /// ```
 ///
 /// Therefore it is also highly recommended not to include types that allow for
@@ -165,3 +213,878 @@ pub trait UpdateTables<'a, T, R> {
     /// 'ops_to_replay' since each op would have a different type.
     fn apply_second(self, table: &mut T);
 }
+
+/// Adapter for writing an update as a closure which consumes owned state,
+/// without implementing `UpdateTables` by hand. The first table is updated
+/// with a clone of `state`, and `state` itself is moved into the update of the
+/// second table, the same way the collections handle the values passed to
+/// them.
+///
+/// Unlike `update_tables_closure`, `f` may be `FnMut`. It is still called once
+/// per table, so it must leave both tables in the same state.
+///
+/// ```rust
+/// use active_standby::sync::AsLock;
+/// use active_standby::ClosureOp;
+///
+/// let table = AsLock::new(vec![1]);
+/// let op = ClosureOp::new(vec![2, 3], |table: &mut Vec<i32>, mut values: Vec<i32>| {
+///     table.append(&mut values)
+/// });
+/// table.write().update_tables(op);
+/// assert_eq!(*table.read(), vec![1, 2, 3]);
+/// ```
+pub struct ClosureOp<S, F> {
+    state: S,
+    f: F,
+}
+
+impl<S, F> ClosureOp<S, F> {
+    pub fn new(state: S, f: F) -> ClosureOp<S, F> {
+        ClosureOp { state, f }
+    }
+}
+
+impl<'a, T, R, S, F> UpdateTables<'a, T, R> for ClosureOp<S, F>
+where
+    S: Clone,
+    F: FnMut(&mut T, S) -> R,
+{
+    fn apply_first(&mut self, table: &'a mut T) -> R {
+        (self.f)(table, self.state.clone())
+    }
+    fn apply_second(mut self, table: &mut T) {
+        // Move the state instead of cloning.
+        (self.f)(table, self.state);
+    }
+}
+
+/// Adapter for writing an update as a pair of closures, `first` for the first
+/// table and `second` for the second, so that the second may consume what the
+/// first had to clone. Both run exactly once, and must leave the tables in the
+/// same state. Only the return value of `first` is given to the caller.
+///
+/// `update_fn!` builds one from a single closure body.
+///
+/// ```rust
+/// use active_standby::sync::AsLock;
+/// use active_standby::ClosureUpdate;
+///
+/// let table = AsLock::new(vec![]);
+/// let value = String::from("a");
+/// let first_value = value.clone();
+/// let op = ClosureUpdate::new(
+///     move |table: &mut Vec<String>| table.push(first_value),
+///     move |table: &mut Vec<String>| table.push(value),
+/// );
+/// table.write().update_tables(op);
+/// assert_eq!(*table.read(), vec!["a"]);
+/// ```
+pub struct ClosureUpdate<F1, F2> {
+    first: Option<F1>,
+    second: F2,
+}
+
+impl<F1, F2> ClosureUpdate<F1, F2> {
+    pub fn new(first: F1, second: F2) -> ClosureUpdate<F1, F2> {
+        ClosureUpdate {
+            first: Some(first),
+            second,
+        }
+    }
+}
+
+impl<'a, T, R, F1, F2> UpdateTables<'a, T, R> for ClosureUpdate<F1, F2>
+where
+    F1: FnOnce(&mut T) -> R,
+    F2: FnOnce(&mut T),
+{
+    fn apply_first(&mut self, table: &'a mut T) -> R {
+        let first = self
+            .first
+            .take()
+            .expect("ClosureUpdate applied to the first table twice");
+        first(table)
+    }
+    fn apply_second(self, table: &mut T) {
+        (self.second)(table)
+    }
+}
+
+/// Replace the whole table with `value`. The sets also implement their
+/// element `replace` with it.
+pub(crate) struct Replace<T> {
+    pub(crate) value: T,
+}
+
+impl<'a, T> UpdateTables<'a, T, ()> for Replace<T>
+where
+    T: Clone,
+{
+    fn apply_first(&mut self, table: &'a mut T) {
+        *table = self.value.clone();
+    }
+    fn apply_second(self, table: &mut T) {
+        // Move the value instead of cloning.
+        *table = self.value;
+    }
+}
+
+/// Storage for the updates waiting to be replayed on the other table, in
+/// order. Removed ops, see `update_tables_keyed`, are left behind as `None`,
+/// so that removing one doesn't shift the others, which `OpKeys` and the
+/// op-stats labels refer to by position.
+///
+/// Lockless & sync store their ops through this, so that a more compact
+/// encoding, e.g. of typed ops into a byte buffer, can replace the default of
+/// boxed closures, `OpLog`.
+pub(crate) trait OpLogStorage<T> {
+    type Op;
+
+    /// Number of ops to replay, not counting the removed ones.
+    fn len(&self) -> usize;
+
+    /// Whether there is nothing left to take out of the log, including
+    /// removed ops.
+    fn is_empty(&self) -> bool;
+
+    /// Add `op` to the end of the log, returning its index.
+    fn push(&mut self, op: Self::Op) -> usize;
+
+    /// Remove the op at `index`, leaving `None` in its place. Returns whether
+    /// there was an op to remove.
+    fn remove(&mut self, index: usize) -> bool;
+
+    /// Take the first `max_ops` ops out of the log to be replayed, along with
+    /// the removed ones around them, which replaying should skip. Removed ops
+    /// don't count towards `max_ops`, and none are left at the front of the
+    /// log, so it is empty once all of its ops have been taken.
+    fn drain(&mut self, max_ops: usize) -> impl Iterator<Item = Option<Self::Op>> + '_;
+
+    fn clear(&mut self);
+
+    /// Replay an op taken out of the log on `table`.
+    fn apply(op: Self::Op, table: &mut T);
+}
+
+type ReplayOp<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+/// The default `OpLogStorage`, which stores each op as a boxed closure.
+pub(crate) struct OpLog<T> {
+    ops: std::collections::VecDeque<Option<ReplayOp<T>>>,
+
+    // Number of `None`s in `ops`.
+    num_removed: usize,
+}
+
+impl<T> OpLogStorage<T> for OpLog<T> {
+    type Op = ReplayOp<T>;
+
+    fn len(&self) -> usize {
+        self.ops.len() - self.num_removed
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn push(&mut self, op: ReplayOp<T>) -> usize {
+        self.ops.push_back(Some(op));
+        self.ops.len() - 1
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        let removed = self.ops.get_mut(index).and_then(Option::take).is_some();
+        self.num_removed += removed as usize;
+        removed
+    }
+
+    fn drain(&mut self, max_ops: usize) -> impl Iterator<Item = Option<ReplayOp<T>>> + '_ {
+        let mut num_ops = 0;
+        let mut num_removed = 0;
+        let end = self
+            .ops
+            .iter()
+            .position(|op| {
+                if op.is_none() {
+                    num_removed += 1;
+                    return false;
+                }
+                num_ops += 1;
+                num_ops > max_ops
+            })
+            .unwrap_or(self.ops.len());
+        self.num_removed -= num_removed;
+        self.ops.drain(..end)
+    }
+
+    fn clear(&mut self) {
+        self.ops.clear();
+        self.num_removed = 0;
+    }
+
+    fn apply(op: ReplayOp<T>, table: &mut T) {
+        op(table)
+    }
+}
+
+impl<T> Default for OpLog<T> {
+    fn default() -> OpLog<T> {
+        OpLog {
+            ops: std::collections::VecDeque::new(),
+            num_removed: 0,
+        }
+    }
+}
+
+/// Index into a write guard's ops to replay of the latest op recorded under
+/// each key, for `update_tables_keyed`. Shared by lockless & sync.
+#[derive(Default)]
+pub(crate) struct OpKeys(std::collections::HashMap<String, usize>);
+
+impl OpKeys {
+    /// Remove the op recorded under `key` from `ops`, if any. Returns whether
+    /// one was removed.
+    pub fn remove<T>(&mut self, key: &str, ops: &mut impl OpLogStorage<T>) -> bool {
+        match self.0.remove(key) {
+            Some(index) => ops.remove(index),
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, key: String, index: usize) {
+        self.0.insert(key, index);
+    }
+
+    /// Forget the keys, once the ops have been replayed.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Error returned by `try_update_tables` when the write guard already holds
+/// the maximum number of pending ops set by `set_max_pending_ops`. The update
+/// isn't applied to either table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingOpsFull {
+    pub max_pending_ops: usize,
+}
+
+impl std::fmt::Display for PendingOpsFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "write guard already holds the maximum of {} pending ops",
+            self.max_pending_ops
+        )
+    }
+}
+
+impl std::error::Error for PendingOpsFull {}
+
+/// Options for creating a table with `new_with` or `from_identical_with`,
+/// instead of setting them after creation. Options which don't apply to a
+/// flavor, or whose feature isn't enabled, are ignored.
+///
+/// Stats are chosen by enabling the `op-stats` and `reader-stats` features,
+/// and the locks never poison, so neither has an option.
+///
+/// New options may be added, so build it from `Options::default()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Options {
+    /// See `set_max_pending_ops`. Defaults to no limit.
+    pub max_pending_ops: Option<usize>,
+    /// Number of Readers to preallocate space for. Only used by the lockless
+    /// flavor. Defaults to 1024.
+    pub reader_capacity: usize,
+    /// See `set_leak_detect_timeout`. Only used by the lockless flavor, with
+    /// the `leak-detect` feature. Defaults to 5 seconds.
+    pub leak_detect_timeout: std::time::Duration,
+    /// Longest sleep between the tries of `write_async`, which backs off
+    /// exponentially from 100us. Only used by the sync flavor, with the
+    /// `tokio` feature. Defaults to 10ms.
+    pub max_write_backoff: std::time::Duration,
+    /// See `set_low_memory_mode`. Only used by the sync flavor. Since the
+    /// standby table is unused in low memory mode, `from_identical_with` may
+    /// be given an empty one, e.g. `T::default()`. Defaults to false.
+    pub low_memory_mode: bool,
+}
+
+impl Options {
+    pub fn max_pending_ops(mut self, max_pending_ops: Option<usize>) -> Options {
+        self.max_pending_ops = max_pending_ops;
+        self
+    }
+
+    pub fn reader_capacity(mut self, reader_capacity: usize) -> Options {
+        self.reader_capacity = reader_capacity;
+        self
+    }
+
+    pub fn leak_detect_timeout(mut self, timeout: std::time::Duration) -> Options {
+        self.leak_detect_timeout = timeout;
+        self
+    }
+
+    pub fn max_write_backoff(mut self, backoff: std::time::Duration) -> Options {
+        self.max_write_backoff = backoff;
+        self
+    }
+
+    pub fn low_memory_mode(mut self, enabled: bool) -> Options {
+        self.low_memory_mode = enabled;
+        self
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            max_pending_ops: None,
+            reader_capacity: 1024,
+            leak_detect_timeout: std::time::Duration::from_secs(5),
+            max_write_backoff: std::time::Duration::from_millis(10),
+            low_memory_mode: false,
+        }
+    }
+}
+
+/// Tables which can be created with space preallocated, so that both tables
+/// can be sized up front instead of growing while being filled.
+pub trait WithCapacity {
+    fn with_capacity(capacity: usize) -> Self;
+}
+
+impl<T> WithCapacity for Vec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity(capacity)
+    }
+}
+
+impl<T> WithCapacity for std::collections::VecDeque<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        std::collections::VecDeque::with_capacity(capacity)
+    }
+}
+
+impl WithCapacity for String {
+    fn with_capacity(capacity: usize) -> Self {
+        String::with_capacity(capacity)
+    }
+}
+
+impl<K, V, S: Default> WithCapacity for std::collections::HashMap<K, V, S> {
+    fn with_capacity(capacity: usize) -> Self {
+        std::collections::HashMap::with_capacity_and_hasher(capacity, S::default())
+    }
+}
+
+impl<T, S: Default> WithCapacity for std::collections::HashSet<T, S> {
+    fn with_capacity(capacity: usize) -> Self {
+        std::collections::HashSet::with_capacity_and_hasher(capacity, S::default())
+    }
+}
+
+/// Tables which can be cloned using multiple threads, so that `new_parallel`
+/// can build the second table of a large table faster than `new`.
+///
+/// With the `rayon` feature this is implemented for the std collections and
+/// `BitSet`, by cloning the elements on rayon's thread pool.
+///
+/// Note that a cloned `HashMap` or `HashSet` has the same hasher, but the
+/// elements are inserted in a different order, so the tables may iterate in a
+/// different order. Avoid updates whose effect depends on the iteration order,
+/// such as a `retain` which keeps the first N entries, on tables built this
+/// way.
+pub trait ParallelClone {
+    fn par_clone(&self) -> Self;
+}
+
+#[cfg(feature = "rayon")]
+mod parallel_clone {
+    use super::ParallelClone;
+    use rayon::prelude::*;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+    use std::hash::{BuildHasher, Hash};
+
+    impl<T: Clone + Send + Sync> ParallelClone for Vec<T> {
+        fn par_clone(&self) -> Self {
+            self.par_iter().cloned().collect()
+        }
+    }
+
+    impl<T: Clone + Send + Sync> ParallelClone for VecDeque<T> {
+        fn par_clone(&self) -> Self {
+            self.par_iter().cloned().collect()
+        }
+    }
+
+    impl<K, V, S> ParallelClone for HashMap<K, V, S>
+    where
+        K: Clone + Eq + Hash + Send + Sync,
+        V: Clone + Send + Sync,
+        S: Clone + BuildHasher + Send + Sync,
+    {
+        fn par_clone(&self) -> Self {
+            let mut map = HashMap::with_capacity_and_hasher(self.len(), self.hasher().clone());
+            map.par_extend(self.par_iter().map(|(k, v)| (k.clone(), v.clone())));
+            map
+        }
+    }
+
+    impl<T, S> ParallelClone for HashSet<T, S>
+    where
+        T: Clone + Eq + Hash + Send + Sync,
+        S: Clone + BuildHasher + Send + Sync,
+    {
+        fn par_clone(&self) -> Self {
+            let mut set = HashSet::with_capacity_and_hasher(self.len(), self.hasher().clone());
+            set.par_extend(self.par_iter().cloned());
+            set
+        }
+    }
+
+    impl<K, V> ParallelClone for BTreeMap<K, V>
+    where
+        K: Clone + Ord + Send + Sync,
+        V: Clone + Send + Sync,
+    {
+        fn par_clone(&self) -> Self {
+            self.par_iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        }
+    }
+
+    impl<T: Clone + Ord + Send + Sync> ParallelClone for BTreeSet<T> {
+        fn par_clone(&self) -> Self {
+            self.par_iter().cloned().collect()
+        }
+    }
+}
+
+/// Shared access to a `T` through a read guard. Implemented by the primitives
+/// & collections of both flavors, as well as by `std` and `parking_lot`
+/// `RwLock`s, so that code can be written against this trait while callers
+/// migrate from `Arc<RwLock<T>>` incrementally.
+///
+/// ```rust
+/// use active_standby::ReadInterface;
+/// use std::sync::{Arc, RwLock};
+///
+/// fn total(table: &impl ReadInterface<Vec<i32>>) -> i32 {
+///     table.read().iter().sum()
+/// }
+///
+/// assert_eq!(total(&Arc::new(RwLock::new(vec![1, 2]))), 3);
+/// assert_eq!(total(&active_standby::sync::AsLock::new(vec![1, 2])), 3);
+/// ```
+///
+/// Types like `std::sync::RwLock`, whose inherent `read` has a different
+/// signature, still resolve to the inherent method when called directly.
+///
+/// `ReadInterface` isn't object safe, since the guard type depends on the
+/// implementor. See `DynReadInterface` for an object safe version.
+pub trait ReadInterface<T: ?Sized> {
+    type Guard<'r>: std::ops::Deref<Target = T>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_>;
+}
+
+/// Object safe version of `ReadInterface`, which boxes the read guard.
+/// Implemented for every `ReadInterface`.
+pub trait DynReadInterface<T: ?Sized> {
+    fn read_dyn<'r>(&'r self) -> Box<dyn std::ops::Deref<Target = T> + 'r>
+    where
+        T: 'r;
+}
+
+impl<T: ?Sized, R: ReadInterface<T> + ?Sized> DynReadInterface<T> for R {
+    fn read_dyn<'r>(&'r self) -> Box<dyn std::ops::Deref<Target = T> + 'r>
+    where
+        T: 'r,
+    {
+        Box::new(self.read())
+    }
+}
+
+impl<T: ?Sized, R: ReadInterface<T> + ?Sized> ReadInterface<T> for &R {
+    type Guard<'r>
+        = R::Guard<'r>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        R::read(self)
+    }
+}
+
+impl<T: ?Sized, R: ReadInterface<T> + ?Sized> ReadInterface<T> for Box<R> {
+    type Guard<'r>
+        = R::Guard<'r>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        R::read(self)
+    }
+}
+
+impl<T: ?Sized, R: ReadInterface<T> + ?Sized> ReadInterface<T> for std::sync::Arc<R> {
+    type Guard<'r>
+        = R::Guard<'r>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        R::read(self)
+    }
+}
+
+/// Panics if the lock is poisoned, like `read().unwrap()`.
+impl<T: ?Sized> ReadInterface<T> for std::sync::RwLock<T> {
+    type Guard<'r>
+        = std::sync::RwLockReadGuard<'r, T>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        std::sync::RwLock::read(self).unwrap()
+    }
+}
+
+impl<T: ?Sized> ReadInterface<T> for parking_lot::RwLock<T> {
+    type Guard<'r>
+        = parking_lot::RwLockReadGuard<'r, T>
+    where
+        Self: 'r;
+
+    fn read(&self) -> Self::Guard<'_> {
+        parking_lot::RwLock::read(self)
+    }
+}
+
+/// Scoped access to a `T`, for libraries which only need to look at a value
+/// and would rather not name a guard type. Implemented by the primitives &
+/// collections of both flavors, by `Arc<T>`, and by `Arc<RwLock<T>>` for both
+/// `std` and `parking_lot` `RwLock`s. Unlike `ReadInterface`, the borrow of
+/// the table can't outlive `f`, so a plain `Arc<T>` is a valid implementor.
+///
+/// A sync `AsLock<T>` can be passed through the `Arc` which it is shared by,
+/// but the collections built on top of it are passed by reference (`&*table`).
+///
+/// ```rust
+/// use active_standby::Snapshot;
+/// use std::sync::{Arc, RwLock};
+///
+/// fn total(table: &impl Snapshot<Vec<i32>>) -> i32 {
+///     table.with(|table| table.iter().sum())
+/// }
+///
+/// assert_eq!(total(&Arc::new(vec![1, 2])), 3);
+/// assert_eq!(total(&Arc::new(RwLock::new(vec![1, 2]))), 3);
+/// assert_eq!(total(&active_standby::sync::AsLock::new(vec![1, 2])), 3);
+/// ```
+pub trait Snapshot<T: ?Sized> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R;
+}
+
+impl<T: ?Sized, S: Snapshot<T> + ?Sized> Snapshot<T> for &S {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        S::with(self, f)
+    }
+}
+
+impl<T: ?Sized> Snapshot<T> for std::sync::Arc<T> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(self)
+    }
+}
+
+/// Panics if the lock is poisoned, like `read().unwrap()`.
+impl<T: ?Sized> Snapshot<T> for std::sync::Arc<std::sync::RwLock<T>> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&std::sync::RwLock::read(self).unwrap())
+    }
+}
+
+impl<T: ?Sized> Snapshot<T> for std::sync::Arc<parking_lot::RwLock<T>> {
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&parking_lot::RwLock::read(self))
+    }
+}
+
+/// Aggregations computed under a single read guard, which only return owned
+/// results. This keeps the guard short lived, instead of holding onto it while
+/// aggregating in user code. Implemented for every `Snapshot` of a table
+/// which can be iterated by reference, such as the collections.
+///
+/// ```rust
+/// use active_standby::lockless::collections::AsHashMapHandle;
+/// use active_standby::Aggregate;
+///
+/// let table = AsHashMapHandle::new([("a", 1), ("b", 5)].into());
+/// assert_eq!(table.sum_of(|(_, v)| *v), 6);
+/// assert_eq!(table.count_where(|(_, v)| **v > 2), 1);
+/// assert_eq!(table.max_of(|(k, _)| *k), Some("b"));
+/// ```
+///
+/// Since `Arc<T>` is itself a `Snapshot<T>`, call these on a sync `AsLock`
+/// directly (`(*table).sum_of(...)`) rather than through its `Arc`.
+pub trait Aggregate<T: ?Sized>: Snapshot<T> {
+    /// Fold over the items of the active table.
+    fn fold_read<B>(
+        &self,
+        init: B,
+        f: impl for<'x> FnMut(B, <&'x T as IntoIterator>::Item) -> B,
+    ) -> B
+    where
+        for<'x> &'x T: IntoIterator,
+    {
+        self.with(|table| table.into_iter().fold(init, f))
+    }
+
+    /// The number of items for which `f` returns true.
+    fn count_where(
+        &self,
+        mut f: impl for<'x> FnMut(&<&'x T as IntoIterator>::Item) -> bool,
+    ) -> usize
+    where
+        for<'x> &'x T: IntoIterator,
+    {
+        self.with(|table| table.into_iter().filter(|item| f(item)).count())
+    }
+
+    /// The sum of `f` over the items.
+    fn sum_of<S>(&self, f: impl for<'x> FnMut(<&'x T as IntoIterator>::Item) -> S) -> S
+    where
+        for<'x> &'x T: IntoIterator,
+        S: std::iter::Sum,
+    {
+        self.with(|table| table.into_iter().map(f).sum())
+    }
+
+    /// The smallest value of `f` over the items, or `None` if there are none.
+    fn min_of<K>(&self, f: impl for<'x> FnMut(<&'x T as IntoIterator>::Item) -> K) -> Option<K>
+    where
+        for<'x> &'x T: IntoIterator,
+        K: Ord,
+    {
+        self.with(|table| table.into_iter().map(f).min())
+    }
+
+    /// The largest value of `f` over the items, or `None` if there are none.
+    fn max_of<K>(&self, f: impl for<'x> FnMut(<&'x T as IntoIterator>::Item) -> K) -> Option<K>
+    where
+        for<'x> &'x T: IntoIterator,
+        K: Ord,
+    {
+        self.with(|table| table.into_iter().map(f).max())
+    }
+}
+
+impl<T: ?Sized, S: Snapshot<T> + ?Sized> Aggregate<T> for S {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn total(table: &impl ReadInterface<Vec<i32>>) -> i32 {
+        table.read().iter().sum()
+    }
+
+    #[test]
+    fn read_interface() {
+        assert_eq!(total(&std::sync::RwLock::new(vec![1, 2])), 3);
+        assert_eq!(total(&parking_lot::RwLock::new(vec![1, 2])), 3);
+        assert_eq!(total(&crate::sync::AsLock::new(vec![1, 2])), 3);
+        assert_eq!(total(&crate::lockless::AsLockHandle::new(vec![1, 2])), 3);
+        assert_eq!(total(&crate::sync::collections::AsVec::new(vec![1, 2])), 3);
+        assert_eq!(
+            total(&crate::lockless::collections::AsVecHandle::new(vec![1, 2])),
+            3
+        );
+
+        // Blanket impls.
+        let table = std::sync::Arc::new(crate::sync::AsLock::new(vec![1, 2]));
+        assert_eq!(total(&table), 3);
+        assert_eq!(total(&&*table), 3);
+        assert_eq!(total(&Box::new(std::sync::RwLock::new(vec![1, 2]))), 3);
+    }
+
+    fn sum(table: &impl Snapshot<Vec<i32>>) -> i32 {
+        table.with(|table| table.iter().sum())
+    }
+
+    #[test]
+    fn snapshot() {
+        use std::sync::Arc;
+
+        assert_eq!(sum(&Arc::new(vec![1, 2])), 3);
+        assert_eq!(sum(&Arc::new(std::sync::RwLock::new(vec![1, 2]))), 3);
+        assert_eq!(sum(&Arc::new(parking_lot::RwLock::new(vec![1, 2]))), 3);
+        assert_eq!(sum(&crate::lockless::AsLockHandle::new(vec![1, 2])), 3);
+        assert_eq!(
+            sum(&crate::lockless::collections::AsVecHandle::new(vec![1, 2])),
+            3
+        );
+
+        let table = Arc::new(crate::sync::AsLock::new(vec![1, 2]));
+        assert_eq!(sum(&table), 3);
+        assert_eq!(sum(&&*table), 3);
+        let table = Arc::new(crate::sync::collections::AsVec::new(vec![1, 2]));
+        assert_eq!(sum(&&*table), 3);
+    }
+
+    #[test]
+    fn aggregate() {
+        let table = crate::lockless::collections::AsVecHandle::new(vec![3, 1, 2]);
+        assert_eq!(
+            table.fold_read(vec![], |mut acc, x| {
+                acc.push(*x * 2);
+                acc
+            }),
+            vec![6, 2, 4]
+        );
+        assert_eq!(table.count_where(|x| **x > 1), 2);
+        assert_eq!(table.sum_of(|x| *x), 6);
+        assert_eq!(table.min_of(|x| *x), Some(1));
+
+        let table = std::sync::Arc::new(crate::sync::collections::AsBTreeSet::default());
+        assert_eq!((*table).max_of(|x: &i32| *x), None);
+        table.write().insert(4);
+        assert_eq!((*table).max_of(|x| *x), Some(4));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_clone() {
+        use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+        let vec: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+        assert_eq!(vec.par_clone(), vec);
+        let deque: VecDeque<_> = vec.iter().cloned().collect();
+        assert_eq!(deque.par_clone(), deque);
+
+        let map: HashMap<_, _> = vec.iter().cloned().zip(0..).collect();
+        assert_eq!(map.par_clone(), map);
+        let set: HashSet<_> = vec.iter().cloned().collect();
+        assert_eq!(set.par_clone(), set);
+
+        let map: BTreeMap<_, _> = vec.iter().cloned().zip(0..).collect();
+        assert_eq!(map.par_clone(), map);
+        let set: BTreeSet<_> = vec.iter().cloned().collect();
+        assert_eq!(set.par_clone(), set);
+    }
+
+    #[test]
+    fn closure_op() {
+        let push_all = |table: &mut Vec<String>, values: Vec<String>| {
+            let len = table.len();
+            table.extend(values);
+            len
+        };
+
+        let table = crate::lockless::AsLockHandle::new(vec![]);
+        let values = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            table
+                .write()
+                .update_tables(ClosureOp::new(values.clone(), push_all)),
+            0
+        );
+        let mut wg = table.write();
+        assert_eq!(wg.update_tables(ClosureOp::new(values, push_all)), 2);
+        wg.assert_synced();
+        assert_eq!(*wg, vec!["a", "b", "a", "b"]);
+    }
+
+    #[test]
+    fn closure_op_fn_mut() {
+        let table = crate::sync::AsLock::new(0);
+        let mut calls = 0;
+        let mut wg = table.write();
+        wg.update_tables(ClosureOp::new(5, move |table: &mut i32, value| {
+            calls += 1;
+            *table += value;
+            calls
+        }));
+        wg.assert_synced();
+        assert_eq!(*wg, 5);
+    }
+
+    #[test]
+    fn closure_update() {
+        // Not Clone, so the first table gets its own and the second gets the
+        // caller's.
+        #[derive(Debug, PartialEq)]
+        struct Token(u32);
+
+        let table = crate::lockless::AsLockHandle::from_identical(vec![], vec![]);
+        let token = Token(1);
+        let mut wg = table.write();
+        let len = wg.update_tables(ClosureUpdate::new(
+            |table: &mut Vec<Token>| {
+                table.push(Token(1));
+                table.len()
+            },
+            move |table: &mut Vec<Token>| table.push(token),
+        ));
+        assert_eq!(len, 1);
+        drop(wg);
+        crate::assert_tables_eq!(table, vec![Token(1)]);
+    }
+
+    #[test]
+    fn update_fn() {
+        let table = crate::sync::AsLock::new(vec![]);
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut wg = table.write();
+        let len = wg.update_tables(crate::update_fn!([a, b] |table: &mut Vec<String>| {
+            table.extend([a, b]);
+            table.len()
+        }));
+        assert_eq!(len, 2);
+        wg.assert_synced();
+        assert_eq!(*wg, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn dyn_read_interface() {
+        let tables: Vec<Box<dyn DynReadInterface<Vec<i32>>>> = vec![
+            Box::new(std::sync::RwLock::new(vec![1])),
+            Box::new(crate::sync::AsLock::new(vec![2])),
+            Box::new(crate::lockless::AsLockHandle::new(vec![3])),
+        ];
+        let values: Vec<i32> = tables.iter().map(|table| table.read_dyn()[0]).collect();
+        assert_eq!(values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn op_log() {
+        let mut log = OpLog::<Vec<i32>>::default();
+        for i in 0..4 {
+            assert_eq!(log.push(Box::new(move |table| table.push(i))), i as usize);
+        }
+        assert!(log.remove(1));
+        assert!(!log.remove(1));
+        assert!(log.remove(2));
+        assert_eq!(log.len(), 2);
+
+        // The removed ops after the first are taken along with it.
+        let mut table = vec![];
+        for op in log.drain(1).flatten() {
+            OpLog::apply(op, &mut table);
+        }
+        assert_eq!(table, vec![0]);
+        assert_eq!(log.len(), 1);
+        for op in log.drain(usize::MAX).flatten() {
+            OpLog::apply(op, &mut table);
+        }
+        assert_eq!(table, vec![0, 3]);
+        assert!(log.is_empty());
+    }
+}
@@ -76,6 +76,49 @@ mod loom_tests {
         });
     }
 
+    #[test]
+    fn lockless_split_new_reader() {
+        // Readers created by the ReaderFactory race with the swap instead of
+        // being serialized with it by the writer's mutex.
+        loom::model(|| {
+            let (mut writer, readers) = active_standby::lockless::WriterHalf::from_identical(0, 0);
+
+            let writer_handle = thread::spawn(move || {
+                writer.write().update_tables(AddOne {});
+                writer.write().update_tables(AddOne {});
+            });
+
+            let reader = readers.new_reader();
+            let val = *reader.read();
+            assert!(val <= 2);
+
+            assert!(writer_handle.join().is_ok());
+            assert_eq!(*reader.read(), 2);
+            assert_eq!(*readers.new_reader().read(), 2);
+        });
+    }
+
+    #[test]
+    fn lockless_read_wait_free() {
+        loom::model(|| {
+            let table = AsLockHandle::<i32>::from_identical(0, 0);
+
+            let writer_handle = {
+                let table = table.clone();
+                thread::spawn(move || {
+                    table.write().update_tables(AddOne {});
+                    table.write().update_tables(AddOne {});
+                })
+            };
+
+            let val = *table.read_wait_free().unwrap();
+            assert!(val <= 2);
+
+            assert!(writer_handle.join().is_ok());
+            assert_eq!(*table.read_wait_free().unwrap(), 2);
+        });
+    }
+
     #[test]
     fn sync_single_thread() {
         loom::model(|| {
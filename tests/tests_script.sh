@@ -43,4 +43,9 @@ echo_and_run RUSTFLAGS=-Zsanitizer=address RUSTDOCFLAGS=-Zsanitizer=address \
 echo_and_run cargo clean
 echo_and_run cargo +nightly miri test --quiet
 
-echo_and_run cargo +nightly bench --quiet
\ No newline at end of file
+echo_and_run cargo +nightly bench --quiet
+
+# Soak the collections, and fuzz them against the std collections.
+echo_and_run cargo run --release --example stress -- --seconds 10
+echo_and_run \(cd fuzz \&\& cargo +nightly fuzz run vec -- -max_total_time=60\)
+echo_and_run \(cd fuzz \&\& cargo +nightly fuzz run hashmap -- -max_total_time=60\)
\ No newline at end of file